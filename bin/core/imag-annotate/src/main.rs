@@ -35,6 +35,8 @@
 extern crate clap;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_json;
 
 extern crate libimagentryannotation;
 extern crate libimagentryedit;
@@ -44,7 +46,6 @@ extern crate libimagstore;
 extern crate libimagutil;
 
 use std::io::Write;
-use std::path::PathBuf;
 
 use libimagentryannotation::annotateable::*;
 use libimagentryannotation::annotation_fetcher::*;
@@ -54,9 +55,10 @@ use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
 use libimagrt::runtime::Runtime;
+use libimagrt::output::Output;
 use libimagrt::setup::generate_runtime_setup;
 use libimagstore::store::FileLockEntry;
-use libimagstore::storeid::IntoStoreId;
+use libimagutil::warn_exit::warn_exit;
 
 mod ui;
 
@@ -71,8 +73,14 @@ fn main() {
         .subcommand_name()
         .map(|name| {
             match name {
-                "add"    => add(&rt),
-                "remove" => remove(&rt),
+                "add"    => {
+                    rt.reject_json_output("imag-annotate add").map_err_trace_exit_unwrap(1);
+                    add(&rt)
+                },
+                "remove" => {
+                    rt.reject_json_output("imag-annotate remove").map_err_trace_exit_unwrap(1);
+                    remove(&rt)
+                },
                 "list"   => list(&rt),
                 other    => {
                     debug!("Unknown command");
@@ -88,45 +96,63 @@ fn main() {
 fn add(rt: &Runtime) {
     let scmd            = rt.cli().subcommand_matches("add").unwrap(); // safed by main()
     let annotation_name = scmd.value_of("annotation_name").unwrap(); // safed by clap
-    let entry_name      = scmd
-        .value_of("entry")
-        .map(PathBuf::from)
-        .map(|pb| pb.into_storeid().map_err_trace_exit_unwrap(1))
-        .unwrap(); // safed by clap
-
-    let _ = rt.store()
-        .get(entry_name)
+    let ids             = rt
+        .ids_from_args(scmd, "entry")
         .map_err_trace_exit_unwrap(1)
-        .ok_or(AE::from("Entry does not exist".to_owned()))
-        .map_err_trace_exit_unwrap(1)
-        .annotate(rt.store(), annotation_name)
-        .map_err_trace_exit_unwrap(1)
-        .edit_content(&rt)
-        .map_err_trace_exit_unwrap(1);
+        .unwrap_or_else(|| warn_exit("No entry to annotate", 1));
+
+    // All entries are annotated with the same annotation entry, which is edited once afterwards
+    let mut annotation = None;
+    for id in ids {
+        // the annotation must not be borrowed while it is retrieved for the next entry
+        drop(annotation.take());
+
+        annotation = Some(rt.store()
+            .get(id)
+            .map_err_trace_exit_unwrap(1)
+            .ok_or(AE::from("Entry does not exist".to_owned()))
+            .map_err_trace_exit_unwrap(1)
+            .annotate(rt.store(), annotation_name)
+            .map_err_trace_exit_unwrap(1));
+    }
+
+    if let Some(mut annotation) = annotation {
+        let _ = annotation.edit_content(&rt).map_err_trace_exit_unwrap(1);
+        let _ = rt.report_touched(annotation.get_location()).map_err_trace_exit_unwrap(1);
+    }
 }
 
 fn remove(rt: &Runtime) {
     let scmd            = rt.cli().subcommand_matches("remove").unwrap(); // safed by main()
-    let entry_name      = scmd.value_of("entry").unwrap(); // safed by clap
     let annotation_name = scmd.value_of("annotation_name").unwrap(); // safed by clap
     let delete          = scmd.is_present("delete-annotation");
-
-    let mut entry = rt.store()
-        .get(PathBuf::from(entry_name).into_storeid().map_err_trace_exit_unwrap(1))
+    let ids             = rt
+        .ids_from_args(scmd, "entry")
         .map_err_trace_exit_unwrap(1)
-        .ok_or(AE::from("Entry does not exist".to_owned()))
-        .map_err_trace_exit_unwrap(1);
+        .unwrap_or_else(|| warn_exit("No entry to remove the annotation from", 1));
 
-    let annotation = entry
-        .denotate(rt.store(), annotation_name)
-        .map_err_trace_exit_unwrap(1);
+    let mut to_delete = None;
+    for id in ids {
+        let mut entry = rt.store()
+            .get(id)
+            .map_err_trace_exit_unwrap(1)
+            .ok_or(AE::from("Entry does not exist".to_owned()))
+            .map_err_trace_exit_unwrap(1);
+
+        let annotation = entry
+            .denotate(rt.store(), annotation_name)
+            .map_err_trace_exit_unwrap(1);
+
+        let _ = rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
 
-    if delete {
-        debug!("Deleting annotation object");
         if let Some(an) = annotation {
-            let loc = an.get_location().clone();
-            drop(an);
+            to_delete = Some(an.get_location().clone());
+        }
+    }
 
+    if delete {
+        debug!("Deleting annotation object");
+        if let Some(loc) = to_delete {
             let _ = rt
                 .store()
                 .delete(loc)
@@ -142,11 +168,12 @@ fn remove(rt: &Runtime) {
 fn list(rt: &Runtime) {
     let scmd        = rt.cli().subcommand_matches("list").unwrap(); // safed by clap
     let with_text   = scmd.is_present("list-with-text");
-    match scmd.value_of("entry").map(PathBuf::from) {
-        Some(pb) => {
+    let mut output  = rt.output();
+    match rt.ids_from_args(scmd, "entry").map_err_trace_exit_unwrap(1) {
+        Some(ids) => for id in ids {
             let _ = rt
                 .store()
-                .get(pb.into_storeid().map_err_trace_exit_unwrap(1))
+                .get(id)
                 .map_err_trace_exit_unwrap(1)
                 .ok_or(AE::from("Entry does not exist".to_owned()))
                 .map_err_trace_exit_unwrap(1)
@@ -154,10 +181,10 @@ fn list(rt: &Runtime) {
                 .map_err_trace_exit_unwrap(1)
                 .enumerate()
                 .map(|(i, a)| {
-                    list_annotation(&rt, i, a.map_err_trace_exit_unwrap(1), with_text)
+                    list_annotation(&mut output, i, a.map_err_trace_exit_unwrap(1), with_text)
                 })
                 .collect::<Vec<_>>();
-        }
+        },
 
         None => {
             // show them all
@@ -167,24 +194,34 @@ fn list(rt: &Runtime) {
                 .map_err_trace_exit_unwrap(1)
                 .enumerate()
                 .map(|(i, a)| {
-                    list_annotation(&rt, i, a.map_err_trace_exit_unwrap(1), with_text)
+                    list_annotation(&mut output, i, a.map_err_trace_exit_unwrap(1), with_text)
                 })
                 .collect::<Vec<_>>();
         }
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
-fn list_annotation<'a>(rt: &Runtime, i: usize, a: FileLockEntry<'a>, with_text: bool) {
-    let _ = if with_text {
-        writeln!(rt.stdout(),
-                 "--- {i: >5} | {id}\n{text}\n\n",
-                 i = i,
-                 id = a.get_location(),
-                 text = a.get_content())
+fn list_annotation<'a>(output: &mut Output, i: usize, a: FileLockEntry<'a>, with_text: bool) {
+    let id     = a.get_location().clone().without_base().to_str().map_err_trace_exit_unwrap(1);
+    let record = if with_text {
+        json!({ "index": i, "id": id, "text": a.get_content() })
     } else {
-        writeln!(rt.stdout(), "{: >5} | {}", i, a.get_location())
-    }
-    .to_exit_code()
-    .unwrap_or_exit();
+        json!({ "index": i, "id": id })
+    };
+
+    let _ = output
+        .emit(&record, |out| if with_text {
+            writeln!(out,
+                     "--- {i: >5} | {id}\n{text}\n\n",
+                     i = i,
+                     id = a.get_location(),
+                     text = a.get_content())
+        } else {
+            writeln!(out, "{: >5} | {}", i, a.get_location())
+        })
+        .to_exit_code()
+        .unwrap_or_exit();
 }
 
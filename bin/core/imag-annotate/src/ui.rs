@@ -29,7 +29,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .takes_value(true)
                          .required(true)
                          .multiple(false)
-                         .help("The entry to add the latitude/longitude to")
+                         .help("The entry to add the annotation to. A value of '-' reads store ids from stdin")
                          .value_name("ENTRY"))
                     .arg(Arg::with_name("annotation_name")
                          .index(2)
@@ -48,7 +48,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .takes_value(true)
                          .required(true)
                          .multiple(false)
-                         .help("The entry to remove the latitude/longitude from")
+                         .help("The entry to remove the annotation from. A value of '-' reads store ids from stdin")
                          .value_name("ENTRY"))
                     .arg(Arg::with_name("annotation_name")
                          .index(2)
@@ -74,7 +74,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .takes_value(true)
                          .required(false)
                          .multiple(false)
-                         .help("The entry to list annotations for (all annotations if not passed)")
+                         .help("The entry to list annotations for (read from stdin with --ids-from-stdin, all annotations otherwise)")
                          .value_name("ENTRY"))
                     .arg(Arg::with_name("list-with-text")
                          .long("text")
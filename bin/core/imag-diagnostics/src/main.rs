@@ -36,14 +36,17 @@ extern crate clap;
 extern crate toml;
 extern crate toml_query;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 
 #[macro_use] extern crate libimagrt;
 extern crate libimagerror;
 extern crate libimagentrylink;
+extern crate libimagrepair;
 extern crate libimagstore;
 
 use std::io::Write;
 
+use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
@@ -52,6 +55,7 @@ use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagstore::error::StoreError as Error;
 use libimagentrylink::internal::*;
+use libimagrepair::problem::find_problems;
 
 use toml::Value;
 use toml_query::read::TomlValueReadExt;
@@ -118,6 +122,10 @@ fn main() {
                                     "Print diagnostics about imag and the imag store",
                                     ui::build_ui);
 
+    if rt.cli().is_present("repair") {
+        return repair(&rt);
+    }
+
     let mut entries_counter = 0;
     let diags = rt.store()
         .entries()
@@ -190,6 +198,33 @@ fn main() {
 
     let n = diags.len();
 
+    if rt.output_format().is_json() {
+        let average = |sum: usize| if n == 0 { None } else { Some(sum / n) };
+        let max     = |max: &Option<(usize, StoreId)>| max.as_ref().map(|&(num, ref id)| {
+            let id = id.clone().without_base().to_str().map_err_trace_exit_unwrap(1);
+            json!({ "count": num, "id": id })
+        });
+
+        let record = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "entries": n,
+            "store_versions": version_counts,
+            "average_header_sections": average(sum_header_sections),
+            "average_content_bytes": average(sum_bytecount_content),
+            "average_overall_bytes": average(sum_overall_byte_size),
+            "largest_entry": max(&max_overall_byte_size),
+            "average_internal_links": average(num_internal_links),
+            "most_internal_links": max(&max_internal_links),
+            "verified": verified_count,
+            "unverified": unverified_count,
+        });
+
+        let mut output = rt.output();
+        let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+        let _ = output.finish().to_exit_code().unwrap_or_exit();
+        return;
+    }
+
     let mut out = rt.stdout();
 
     do_write!(out, "imag version {}", { env!("CARGO_PKG_VERSION") });
@@ -232,3 +267,50 @@ fn main() {
     }
 }
 
+fn repair(rt: &Runtime) {
+    let matches      = rt.cli();
+    let dry_run      = matches.is_present("dry-run");
+    let remove_refs  = matches.is_present("remove-dangling-refs");
+    let problems     = find_problems(rt.store()).map_err_trace_exit_unwrap(1);
+    let mut repaired = 0;
+    let mut kept     = 0;
+    let mut output   = rt.output();
+
+    for problem in problems.iter() {
+        let keep   = problem.is_lossy() && !remove_refs;
+        let action = if keep {
+            "none, pass --remove-dangling-refs to remove the ref"
+        } else {
+            problem.action()
+        };
+
+        let record = json!({ "problem": format!("{}", problem), "action": action });
+        let _ = output
+            .emit(&record, |out| writeln!(out, "{}: {}", problem, action))
+            .to_exit_code()
+            .unwrap_or_exit();
+
+        if keep {
+            kept += 1;
+        } else if !dry_run && problem.repair(rt.store()).map_err_trace().is_ok() {
+            repaired += 1;
+        }
+    }
+
+    let is_json = output.format().is_json();
+    let _       = output.finish().to_exit_code().unwrap_or_exit();
+
+    if !is_json {
+        let mut out = rt.stdout();
+
+        if dry_run {
+            do_write!(out, "{} inconsistencies found, run without --dry-run to repair them", problems.len());
+        } else {
+            do_write!(out, "{} inconsistencies found, {} repaired, {} kept", problems.len(), repaired, kept);
+        }
+    }
+
+    if !dry_run && repaired + kept < problems.len() {
+        ::std::process::exit(1)
+    }
+}
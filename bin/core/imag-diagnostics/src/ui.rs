@@ -17,9 +17,31 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::App;
+use clap::{Arg, App};
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
+        .arg(Arg::with_name("repair")
+             .long("repair")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .help("Find inconsistencies in the store (dangling links, orphaned external links and annotations, dangling refs) and repair them"))
+
+        .arg(Arg::with_name("dry-run")
+             .long("dry-run")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .requires("repair")
+             .help("Only print the inconsistencies and how they would be repaired"))
+
+        .arg(Arg::with_name("remove-dangling-refs")
+             .long("remove-dangling-refs")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .requires("repair")
+             .help("Remove refs to files which do not exist from their entries. Without this, they are only reported"))
 }
 
@@ -17,7 +17,7 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::{Arg, ArgGroup, App};
+use clap::{Arg, App};
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
@@ -26,18 +26,8 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .takes_value(true)
              .required(false)
              .multiple(true)
-             .help("The entry/entries to edit")
+             .help("The entry/entries to edit. Read from stdin with --ids-from-stdin")
              .value_name("ENTRY"))
-        .arg(Arg::with_name("entries-from-stdin")
-             .long("ids-from-stdin")
-             .short("I")
-             .takes_value(false)
-             .required(false)
-             .multiple(false)
-             .help("The entry/entries are piped in via stdin"))
-        .group(ArgGroup::with_name("input-method")
-               .args(&["entry", "entries-from-stdin"])
-               .required(true))
 
         .arg(Arg::with_name("edit-header")
              .long("header")
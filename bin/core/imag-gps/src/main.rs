@@ -35,6 +35,7 @@
 extern crate clap;
 #[macro_use]
 extern crate log;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagentrygps;
 #[macro_use] extern crate libimagrt;
@@ -44,7 +45,6 @@ extern crate libimagstore;
 
 use std::io::Write;
 use std::process::exit;
-use std::path::PathBuf;
 use std::str::FromStr;
 
 use libimagentrygps::error::GPSError as GE;
@@ -56,7 +56,9 @@ use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
-use libimagstore::storeid::IntoStoreId;
+use libimagstore::storeid::StoreId;
+
+use clap::ArgMatches;
 
 mod ui;
 
@@ -87,11 +89,7 @@ fn main() {
 
 fn add(rt: &Runtime) {
     let scmd = rt.cli().subcommand_matches("add").unwrap(); // safed by main()
-
-    let entry_name  = scmd.value_of("entry").unwrap(); // safed by clap
-    let sid         = PathBuf::from(entry_name)
-        .into_storeid()
-        .map_err_trace_exit_unwrap(1);
+    rt.reject_json_output("imag-gps add").map_err_trace_exit_unwrap(1);
 
     let c = {
         let parse = |value: &str| -> (i64, i64, i64) {
@@ -127,70 +125,106 @@ fn add(rt: &Runtime) {
         Coordinates::new(long, lati)
     };
 
-    rt.store()
-        .get(sid)
-        .map_err_trace_exit_unwrap(1)
-        .map(|mut entry| {
-            let _ = entry.set_coordinates(c).map_err_trace_exit_unwrap(1);
-        })
-        .unwrap_or_else(|| {
-            error!("No such entry: {}", entry_name);
-            exit(1)
-        });
+    for sid in entry_ids(rt, scmd) {
+        rt.store()
+            .get(sid.clone())
+            .map_err_trace_exit_unwrap(1)
+            .map(|mut entry| {
+                let _ = entry.set_coordinates(c.clone()).map_err_trace_exit_unwrap(1);
+                let _ = rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
+            })
+            .unwrap_or_else(|| {
+                error!("No such entry: {}", sid);
+                exit(1)
+            });
+    }
 }
 
 fn remove(rt: &Runtime) {
-    let scmd = rt.cli().subcommand_matches("remove").unwrap(); // safed by main()
+    let scmd       = rt.cli().subcommand_matches("remove").unwrap(); // safed by main()
+    let mut output = rt.output();
 
-    let entry_name  = scmd.value_of("entry").unwrap(); // safed by clap
-    let sid         = PathBuf::from(entry_name)
-        .into_storeid()
-        .map_err_trace_exit_unwrap(1);
+    if !scmd.is_present("print-removed") {
+        rt.reject_json_output("imag-gps remove without --print-removed").map_err_trace_exit_unwrap(1);
+    }
 
-    let removed_value = rt
-        .store()
-        .get(sid)
-        .map_err_trace_exit_unwrap(1)
-        .unwrap_or_else(|| { // if we have Ok(None)
-            error!("No such entry: {}", entry_name);
-            exit(1)
-        })
-        .remove_coordinates()
-        .map_err_trace_exit_unwrap(1) // The delete action failed
-        .unwrap_or_else(|| { // if we have Ok(None)
-            error!("Entry had no coordinates: {}", entry_name);
-            exit(1)
-        })
-        .map_err_trace_exit_unwrap(1); // The parsing of the deleted values failed
+    for sid in entry_ids(rt, scmd) {
+        let mut entry = rt
+            .store()
+            .get(sid.clone())
+            .map_err_trace_exit_unwrap(1)
+            .unwrap_or_else(|| { // if we have Ok(None)
+                error!("No such entry: {}", sid);
+                exit(1)
+            });
 
-    if scmd.is_present("print-removed") {
-        let _ = writeln!(rt.stdout(), "{}", removed_value).to_exit_code().unwrap_or_exit();
+        let removed_value = entry
+            .remove_coordinates()
+            .map_err_trace_exit_unwrap(1) // The delete action failed
+            .unwrap_or_else(|| { // if we have Ok(None)
+                error!("Entry had no coordinates: {}", sid);
+                exit(1)
+            })
+            .map_err_trace_exit_unwrap(1); // The parsing of the deleted values failed
+
+        if scmd.is_present("print-removed") {
+            let _ = output
+                .emit(&coordinates_record(&sid, &removed_value),
+                      |out| writeln!(out, "{}", removed_value))
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+
+        let _ = rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn get(rt: &Runtime) {
-    let scmd = rt.cli().subcommand_matches("get").unwrap(); // safed by main()
+    let scmd       = rt.cli().subcommand_matches("get").unwrap(); // safed by main()
+    let mut output = rt.output();
+
+    for sid in entry_ids(rt, scmd) {
+        let value = rt
+            .store()
+            .get(sid.clone())
+            .map_err_trace_exit_unwrap(1)
+            .unwrap_or_else(|| { // if we have Ok(None)
+                error!("No such entry: {}", sid);
+                exit(1)
+            })
+            .get_coordinates()
+            .map_err_trace_exit_unwrap(1) // The get action failed
+            .unwrap_or_else(|| { // if we have Ok(None)
+                error!("Entry has no coordinates: {}", sid);
+                exit(1)
+            });
+
+        let _ = output
+            .emit(&coordinates_record(&sid, &value), |out| writeln!(out, "{}", value))
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
+}
 
-    let entry_name  = scmd.value_of("entry").unwrap(); // safed by clap
-    let sid         = PathBuf::from(entry_name)
-        .into_storeid()
-        .map_err_trace_exit_unwrap(1);
+fn coordinates_record(id: &StoreId, coordinates: &Coordinates) -> ::serde_json::Value {
+    json!({
+        "id": format!("{}", id.local().display()),
+        "longitude": coordinates.longitude(),
+        "latitude": coordinates.latitude(),
+    })
+}
 
-    let value = rt
-        .store()
-        .get(sid)
+/// Get the ids of the entries to operate on, either from the commandline or from stdin
+fn entry_ids(rt: &Runtime, scmd: &ArgMatches) -> Vec<StoreId> {
+    rt.ids_from_args(scmd, "entry")
         .map_err_trace_exit_unwrap(1)
-        .unwrap_or_else(|| { // if we have Ok(None)
-            error!("No such entry: {}", entry_name);
+        .unwrap_or_else(|| {
+            error!("No entry passed, neither on the commandline nor via stdin");
             exit(1)
         })
-        .get_coordinates()
-        .map_err_trace_exit_unwrap(1) // The get action failed
-        .unwrap_or_else(|| { // if we have Ok(None)
-            error!("Entry has no coordinates: {}", entry_name);
-            exit(1)
-        });
-
-    let _       = writeln!(rt.stdout(), "{}", value).to_exit_code().unwrap_or_exit();
 }
 
@@ -41,10 +41,10 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                     .arg(Arg::with_name("entry")
                          .index(1)
                          .takes_value(true)
-                         .required(true)
-                         .multiple(false)
-                         .help("The entry to add the latitude/longitude to")
-                         .value_name("ENTRY"))
+                         .required(false)
+                         .multiple(true)
+                         .help("The entries to add the latitude/longitude to. Read from stdin with --ids-from-stdin")
+                         .value_name("ENTRIES"))
                     )
 
         .subcommand(SubCommand::with_name("remove")
@@ -59,10 +59,10 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                 .arg(Arg::with_name("entry")
                      .index(1)
                      .takes_value(true)
-                     .required(true)
-                     .multiple(false)
-                     .help("The entry to remove the latitude/longitude from")
-                     .value_name("ENTRY"))
+                     .required(false)
+                     .multiple(true)
+                     .help("The entries to remove the latitude/longitude from. Read from stdin with --ids-from-stdin")
+                     .value_name("ENTRIES"))
                 )
 
         .subcommand(SubCommand::with_name("get")
@@ -71,10 +71,10 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                 .arg(Arg::with_name("entry")
                      .index(1)
                      .takes_value(true)
-                     .required(true)
-                     .multiple(false)
-                     .help("The entry to get the latitude/longitude from")
-                     .value_name("ENTRY"))
+                     .required(false)
+                     .multiple(true)
+                     .help("The entries to get the latitude/longitude from. Read from stdin with --ids-from-stdin")
+                     .value_name("ENTRIES"))
                 .arg(Arg::with_name("format-json")
                      .long("json")
                      .takes_value(false)
@@ -0,0 +1,229 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use libimagstore::index::is_word_char;
+
+/// Extract the fragments of word characters every match of `pattern` must contain
+///
+/// This is used to narrow down the entries to grep through with the full-text index of the store.
+/// The extraction is conservative: only literal text outside of groups which is not affected by an
+/// optional quantifier is taken into account.
+///
+/// Returns `None` if no such fragments could be found, in which case every entry has to be
+/// searched.
+pub fn required_fragments(pattern: &str) -> Option<Vec<String>> {
+    let chars : Vec<char> = pattern.chars().collect();
+    let mut literals : Vec<String> = vec![];
+    let mut current  = String::new();
+    let mut depth    = 0;
+    let mut i        = 0;
+
+    macro_rules! finish_literal {
+        () => {
+            if !current.is_empty() {
+                literals.push(::std::mem::replace(&mut current, String::new()));
+            }
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+
+        match c {
+            '|' if depth == 0 => return None, // alternation, nothing is required
+            '(' => {
+                if chars.get(i) == Some(&'?') {
+                    // flag group. In verbose mode, '#' starts a comment, which we do not support
+                    let flags : String = chars[i..].iter().take_while(|c| **c != ')' && **c != ':').collect();
+                    if flags.contains('x') {
+                        return None;
+                    }
+                }
+                finish_literal!();
+                depth += 1;
+            },
+            ')' => {
+                finish_literal!();
+                depth = if depth > 0 { depth - 1 } else { 0 };
+            },
+            '[' => {
+                finish_literal!();
+                i = skip_class(&chars, i);
+            },
+            '*' | '?' => {
+                // the previous character is optional
+                current.pop();
+                finish_literal!();
+            },
+            '{' => {
+                // the previous character might be optional ("{0,n}")
+                current.pop();
+                finish_literal!();
+                while i < chars.len() && chars[i] != '}' {
+                    i += 1;
+                }
+                i += 1;
+            },
+            '+' | '.' | '^' | '$' => finish_literal!(),
+            '\\' => {
+                match chars.get(i).cloned() {
+                    Some(e) if e.is_alphanumeric() => {
+                        // character class or assertion like "\d" or "\b", maybe with arguments
+                        finish_literal!();
+                        i = skip_escape_arguments(&chars, i + 1, e);
+                    },
+                    Some(e) => {
+                        if depth == 0 {
+                            current.push(e);
+                        }
+                        i += 1;
+                    },
+                    None => return None,
+                }
+            },
+            c => if depth == 0 {
+                current.push(c);
+            },
+        }
+    }
+    finish_literal!();
+
+    let fragments : Vec<String> = literals
+        .iter()
+        .flat_map(|l| l.split(|c: char| !is_word_char(c)))
+        .filter(|f| !f.is_empty())
+        .map(String::from)
+        .collect();
+
+    if fragments.is_empty() {
+        None
+    } else {
+        Some(fragments)
+    }
+}
+
+/// Skip a character class, `i` points to the character after the opening bracket
+///
+/// Returns the index of the character after the closing bracket.
+fn skip_class(chars: &[char], mut i: usize) -> usize {
+    let mut depth = 1;
+
+    // a closing bracket at the start of the class is a literal
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+
+    while i < chars.len() && depth > 0 {
+        match chars[i] {
+            '\\' => i += 1,
+            '['  => depth += 1,
+            ']'  => depth -= 1,
+            _    => {},
+        }
+        i += 1;
+    }
+
+    i
+}
+
+/// Skip the arguments of an escape sequence like "\x41", "\u{1F600}" or "\pL"
+///
+/// `i` points to the character after the escaped character `e`. Returns the index of the first
+/// character after the escape sequence.
+fn skip_escape_arguments(chars: &[char], i: usize, e: char) -> usize {
+    let count = match e {
+        'x'       => 2,
+        'u'       => 4,
+        'U'       => 8,
+        'p' | 'P' => 1,
+        _         => return i,
+    };
+
+    if chars.get(i) == Some(&'{') {
+        let mut i = i;
+        while i < chars.len() && chars[i] != '}' {
+            i += 1;
+        }
+        i + 1
+    } else {
+        i + count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::required_fragments;
+
+    fn frags(s: &str) -> Option<Vec<String>> {
+        required_fragments(s)
+    }
+
+    fn some(v: &[&str]) -> Option<Vec<String>> {
+        Some(v.iter().map(|s| String::from(*s)).collect())
+    }
+
+    #[test]
+    fn test_plain_literal() {
+        assert_eq!(frags("hello"), some(&["hello"]));
+        assert_eq!(frags("hello world"), some(&["hello", "world"]));
+    }
+
+    #[test]
+    fn test_metacharacters_split() {
+        assert_eq!(frags("foo.*bar"), some(&["foo", "bar"]));
+        assert_eq!(frags("^foo$"), some(&["foo"]));
+        assert_eq!(frags("foo\\.bar"), some(&["foo", "bar"]));
+    }
+
+    #[test]
+    fn test_optional_characters_are_dropped() {
+        assert_eq!(frags("colou?r"), some(&["colo", "r"]));
+        assert_eq!(frags("ab*c"), some(&["a", "c"]));
+        assert_eq!(frags("ab{0,2}c"), some(&["a", "c"]));
+        assert_eq!(frags("ab+c"), some(&["ab", "c"]));
+    }
+
+    #[test]
+    fn test_groups_and_classes_are_ignored() {
+        assert_eq!(frags("foo(bar)?baz"), some(&["foo", "baz"]));
+        assert_eq!(frags("foo[a-z]+baz"), some(&["foo", "baz"]));
+        assert_eq!(frags("foo[]x]baz"), some(&["foo", "baz"]));
+        assert_eq!(frags("(?i)foo"), some(&["foo"]));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(frags("\\bfoo\\d+"), some(&["foo"]));
+        assert_eq!(frags("a\\x41b"), some(&["a", "b"]));
+        assert_eq!(frags("a\\p{Greek}b"), some(&["a", "b"]));
+    }
+
+    #[test]
+    fn test_nothing_required() {
+        assert_eq!(frags("foo|bar"), None);
+        assert_eq!(frags(".*"), None);
+        assert_eq!(frags("(?x)foo # comment"), None);
+        assert_eq!(frags("[abc]"), None);
+    }
+
+}
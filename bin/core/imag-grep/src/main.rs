@@ -33,25 +33,33 @@
 )]
 
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 extern crate clap;
 extern crate regex;
+extern crate filters;
 
 extern crate libimagstore;
 #[macro_use] extern crate libimagrt;
 extern crate libimagerror;
+extern crate libimagentryfilter;
 
 use std::io::Write;
 
 use regex::Regex;
+use filters::filter::Filter;
 
-use libimagrt::runtime::Runtime;
+use libimagrt::output::Output;
 use libimagrt::setup::generate_runtime_setup;
 use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+use libimagstore::iter::get::StoreIdGetIteratorExtension;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
+use libimagentryfilter::query::Query;
 
 mod ui;
+mod fragments;
 
 struct Options {
     files_with_matches: bool,
@@ -66,7 +74,7 @@ fn main() {
                                     ui::build_ui);
 
     let opts = Options {
-        files_with_matches    : rt.cli().is_present("files-with-matches"),
+        files_with_matches    : rt.cli().is_present("files-with-matches") || rt.prints_ids(),
         count                 : rt.cli().is_present("count"),
     };
 
@@ -82,19 +90,53 @@ fn main() {
             ::std::process::exit(1)
         });
 
-    let overall_count = rt
-        .store()
-        .entries()
-        .map_err_trace_exit_unwrap(1)
-        .into_get_iter()
+    let query = rt
+        .cli()
+        .value_of("filter")
+        .map(|q| Query::parse(q).map_err_trace_exit_unwrap(1));
+
+    if rt.cli().is_present("rebuild-index") {
+        let _ = rt.store().rebuild_index().map_err_trace_exit_unwrap(1);
+    }
+
+    let ids : Vec<StoreId> = match rt.ids_from_stdin().map_err_trace_exit_unwrap(1) {
+        Some(ids) => ids, // only search through the entries which were piped in
+        None      => {
+            let candidates = if rt.cli().is_present("no-index") {
+                None
+            } else {
+                rt.cli()
+                    .value_of("pattern")
+                    .and_then(fragments::required_fragments)
+                    .and_then(|fragments| {
+                        debug!("Looking up {:?} in index", fragments);
+                        rt.store().search_index(&fragments).map_err_trace_exit_unwrap(1)
+                    })
+            };
+
+            match candidates {
+                Some(candidates) => candidates,
+                None => rt.store().entries().map_err_trace_exit_unwrap(1),
+            }.without_store().collect()
+        },
+    };
+
+    let mut output = rt.output();
+    let overall_count = ids
+        .into_iter()
+        .into_get_iter(rt.store())
         .filter_map(|res| res.map_err_trace_exit_unwrap(1))
+        .filter(|entry| query.as_ref().map(|q| q.filter(entry)).unwrap_or(true))
         .filter(|entry| pattern.is_match(entry.get_content()))
-        .map(|entry| show(&rt, &entry, &pattern, &opts, &mut count))
+        .map(|entry| show(&mut output, &entry, &pattern, &opts, &mut count))
         .count();
 
     if opts.count {
-        let _ = writeln!(rt.stdout(), "{}", count).to_exit_code().unwrap_or_exit();
-    } else if !opts.files_with_matches {
+        let _ = output
+            .emit(&json!({ "count": count }), |out| writeln!(out, "{}", count))
+            .to_exit_code()
+            .unwrap_or_exit();
+    } else if !opts.files_with_matches && !output.format().is_json() {
         let _ = writeln!(rt.stdout(), "Processed {} files, {} matches, {} nonmatches",
                  overall_count,
                  count,
@@ -102,25 +144,41 @@ fn main() {
             .to_exit_code()
             .unwrap_or_exit();
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
-fn show(rt: &Runtime, e: &Entry, re: &Regex, opts: &Options, count: &mut usize) {
+fn show(output: &mut Output, e: &Entry, re: &Regex, opts: &Options, count: &mut usize) {
+    let id = e.get_location().clone().without_base().to_str().map_err_trace_exit_unwrap(1);
+
     if opts.files_with_matches {
-        let _ = writeln!(rt.stdout(), "{}", e.get_location()).to_exit_code().unwrap_or_exit();
+        let _ = output
+            .emit(&json!({ "id": id }), |out| writeln!(out, "{}", e.get_location()))
+            .to_exit_code()
+            .unwrap_or_exit();
     } else if opts.count {
         *count += 1;
     } else {
-        let _ = writeln!(rt.stdout(), "{}:", e.get_location()).to_exit_code().unwrap_or_exit();
-        for capture in re.captures_iter(e.get_content()) {
-            for mtch in capture.iter() {
-                if let Some(m) = mtch {
-                    let _ = writeln!(rt.stdout(), " '{}'", m.as_str()).to_exit_code().unwrap_or_exit();
+        let matches = re
+            .captures_iter(e.get_content())
+            .flat_map(|capture| {
+                capture
+                    .iter()
+                    .filter_map(|mtch| mtch.map(|m| String::from(m.as_str())))
+                    .collect::<Vec<String>>()
+            })
+            .collect::<Vec<String>>();
+
+        let _ = output
+            .emit(&json!({ "id": id, "matches": matches }), |out| {
+                writeln!(out, "{}:", e.get_location())?;
+                for m in matches.iter() {
+                    writeln!(out, " '{}'", m)?;
                 }
-            }
-        }
-
-        let _ = writeln!(rt.stdout(), "").to_exit_code().unwrap_or_exit();
+                writeln!(out, "")
+            })
+            .to_exit_code()
+            .unwrap_or_exit();
         *count += 1;
     }
 }
-
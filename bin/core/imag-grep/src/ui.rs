@@ -27,7 +27,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .takes_value(false)
              .required(false)
              .multiple(false)
-             .help("List files with matches"))
+             .help("List files with matches. Same as the global --print-ids flag"))
 
         .arg(Arg::with_name("count")
              .long("count")
@@ -37,6 +37,29 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .multiple(false)
              .help("Count matches"))
 
+        .arg(Arg::with_name("no-index")
+             .long("no-index")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .help("Do not use the full-text index of the store, but search all entries"))
+
+        .arg(Arg::with_name("rebuild-index")
+             .long("rebuild-index")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .help("Rebuild the full-text index before searching. Use this if entries were altered without imag"))
+
+        .arg(Arg::with_name("filter")
+             .long("filter")
+             .short("f")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .value_name("QUERY")
+             .help("Only search through entries matching this header query, for example 'todo.status == \"pending\" and tag:work'"))
+
         .arg(Arg::with_name("pattern")
              .index(1)
              .takes_value(false)
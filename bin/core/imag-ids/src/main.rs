@@ -34,20 +34,25 @@
 
 extern crate clap;
 extern crate filters;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagerror;
 extern crate libimagstore;
+extern crate libimagentryfilter;
+extern crate libimagrpc;
 #[macro_use] extern crate libimagrt;
 
 use std::io::Write;
 
 use filters::filter::Filter;
 
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_without_store;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
 use libimagstore::storeid::StoreId;
+use libimagentryfilter::query::Query;
+use libimagrpc::client::DaemonClient;
 
 mod ui;
 use ui::build_ui;
@@ -69,10 +74,16 @@ impl<'a, A> Filter<StoreId> for IsInCollectionsFilter<'a, A>
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-ids",
-                                    &version,
-                                    "print all ids",
-                                    build_ui);
+    let mut rt = generate_runtime_setup_without_store("imag-ids",
+                                                      &version,
+                                                      "print all ids",
+                                                      build_ui);
+
+    // The store is only opened if imagd does not serve it
+    let mut client = DaemonClient::from_runtime(&rt).map_err_trace_exit_unwrap(1);
+    if client.is_none() {
+        let _ = rt.open_store().map_err_trace_exit_unwrap(1);
+    }
 
     let print_storepath = rt.cli().is_present("print-storepath");
 
@@ -83,19 +94,53 @@ fn main() {
 
     let collection_filter = IsInCollectionsFilter(values, ::std::marker::PhantomData);
 
-    rt.store()
-        .entries()
-        .map_err_trace_exit_unwrap(1)
+    let query = rt
+        .cli()
+        .value_of("filter")
+        .map(|q| Query::parse(q).map_err_trace_exit_unwrap(1));
+
+    let mut output = rt.output();
+
+    let ids = match client {
+        Some(ref mut client) => client
+            .entries(None)
+            .map_err_trace_exit_unwrap(1)
+            .into_iter()
+            .map(|id| id.with_base(rt.store().path().clone()))
+            .collect::<Vec<_>>(),
+        None => rt.store().entries().map_err_trace_exit_unwrap(1).collect(),
+    };
+
+    ids.into_iter()
         .filter(|id| collection_filter.filter(id))
+        .filter(|id| match query {
+            None        => true,
+            Some(ref q) => match client {
+                Some(ref mut client) => client
+                    .get(id)
+                    .map_err_trace_exit_unwrap(1)
+                    .map(|entry| q.filter(&entry.into_entry().map_err_trace_exit_unwrap(1)))
+                    .unwrap_or(false),
+                None => rt
+                    .store()
+                    .get(id.clone())
+                    .map_err_trace_exit_unwrap(1)
+                    .map(|entry| q.filter(&entry))
+                    .unwrap_or(false),
+            },
+        })
         .map(|id| if print_storepath {
             id
         } else {
             id.without_base()
         })
         .for_each(|id| {
-            let _ = writeln!(rt.stdout(), "{}", id.to_str().map_err_trace_exit_unwrap(1))
+            let id = id.to_str().map_err_trace_exit_unwrap(1);
+            let _  = output.emit(&json!({ "id": id }), |out| writeln!(out, "{}", id))
                 .to_exit_code()
                 .unwrap_or_exit();
-        })
+        });
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
@@ -36,5 +36,14 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .multiple(true)
              .value_names(&["COLLECTION"])
              .help("Filter for ids which are only in these collections"))
+
+        .arg(Arg::with_name("filter")
+             .long("filter")
+             .short("f")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .value_name("QUERY")
+             .help("Only list entries matching this header query, for example 'todo.status == \"pending\" and tag:work'"))
 }
 
@@ -65,6 +65,15 @@ const GITIGNORE_STR : &'static str = r#"
 # configuration to this git repository.
 
 imagrc.toml
+
+# The full-text index of the store can be rebuilt from
+# the entries at any time, so we do not track it.
+
+store/.store/index
+
+# Lock files only exist while imag is running.
+
+store/.store/locks
 "#;
 
 fn main() {
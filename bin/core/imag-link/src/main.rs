@@ -36,6 +36,7 @@
 extern crate clap;
 extern crate url;
 #[macro_use] extern crate prettytable;
+#[macro_use] extern crate serde_json;
 #[cfg(test)] extern crate toml;
 #[cfg(test)] extern crate toml_query;
 #[cfg(test)] extern crate env_logger;
@@ -56,9 +57,13 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::process::exit;
 
+use clap::ArgMatches;
+
 use libimagentrylink::external::ExternalLinker;
+use libimagentrylink::graph::LinkGraph;
 use libimagentrylink::internal::InternalLinker;
 use libimagentrylink::internal::store_check::StoreLinkConsistentExt;
+use libimagentrylink::traversal::LinkTraversal;
 use libimagentrylink::error::LinkError as LE;
 use libimagerror::trace::{MapErrTrace, trace_error};
 use libimagerror::exit::ExitUnwrap;
@@ -101,10 +106,13 @@ fn main() {
         .subcommand_name()
         .map(|name| {
             match name {
-                "remove" => remove_linking(&rt),
-                "unlink" => unlink(&rt),
-                "list"   => list_linkings(&rt),
-                other    => {
+                "remove"    => remove_linking(&rt),
+                "unlink"    => unlink(&rt),
+                "list"      => list_linkings(&rt),
+                "graph"     => export_graph(&rt),
+                "path"      => show_path(&rt),
+                "neighbors" => list_neighbors(&rt),
+                other       => {
                     debug!("Unknown command");
                     let _ = rt.handle_unknown_subcommand("imag-link", other, rt.cli())
                         .map_err_trace_exit_unwrap(1)
@@ -114,8 +122,9 @@ fn main() {
             }
         })
         .or_else(|| {
-            if let (Some(from), Some(to)) = (rt.cli().value_of("from"), rt.cli().values_of("to")) {
-                Some(link_from_to(&rt, from, to))
+            if let (Some(from), true) = (rt.cli().value_of("from"), rt.cli().is_present("to")) {
+                let to = values_with_stdin_ids(&rt, rt.cli(), "to");
+                Some(link_from_to(&rt, from, to.iter().map(String::as_str)))
             } else {
                 warn_exit("No commandline call", 1)
             }
@@ -124,6 +133,20 @@ fn main() {
         .map_err_trace_exit_unwrap(1);
 }
 
+/// Get the values of the argument `name`, with a value of "-" replaced by the ids read from stdin
+fn values_with_stdin_ids(rt: &Runtime, matches: &ArgMatches, name: &str) -> Vec<String> {
+    let mut values = vec![];
+    for value in matches.values_of(name).into_iter().flat_map(|v| v) {
+        if value == "-" {
+            let ids = rt.read_ids_from_stdin().map_err_trace_exit_unwrap(1);
+            values.extend(ids.iter().map(|id| id.to_str()).map(|s| s.map_err_trace_exit_unwrap(1)));
+        } else {
+            values.push(String::from(value));
+        }
+    }
+    values
+}
+
 fn get_entry_by_name<'a>(rt: &'a Runtime, name: &str) -> Result<Option<FileLockEntry<'a>>, StoreError> {
     use libimagstore::storeid::StoreId;
 
@@ -146,6 +169,9 @@ fn link_from_to<'a, I>(rt: &'a Runtime, from: &'a str, to: I)
         },
     };
 
+    // all modified entries are written at once, so a failure cannot leave half a link behind
+    let mut transaction = rt.store().transaction();
+
     for entry in to {
         debug!("Handling 'to' entry: {:?}", entry);
         if PathBuf::from(entry).exists() {
@@ -176,13 +202,24 @@ fn link_from_to<'a, I>(rt: &'a Runtime, from: &'a str, to: I)
                     ::std::process::exit(1)
                 },
             };
-            let _ = from_entry
-                .add_internal_link(&mut to_entry)
-                .map_err_trace_exit_unwrap(1);
+            let _ = match rt.cli().value_of("type") {
+                Some(kind) => {
+                    let directed = rt.cli().is_present("directed");
+                    from_entry.add_internal_typed_link(&mut to_entry, String::from(kind), directed)
+                },
+                None => from_entry.add_internal_link(&mut to_entry),
+            }.map_err_trace_exit_unwrap(1);
+
+            transaction.update(to_entry);
         }
 
         info!("Ok: {} -> {}", from, entry);
     }
+
+    let from_id = from_entry.get_location().clone();
+    transaction.update(from_entry);
+    let _ = transaction.commit().map_err_trace_exit_unwrap(1);
+    let _ = rt.report_touched(&from_id).map_err_trace_exit_unwrap(1);
 }
 
 fn remove_linking(rt: &Runtime) {
@@ -210,57 +247,65 @@ fn remove_linking(rt: &Runtime) {
         Some(s) => s,
     };
 
-    rt.cli()
+    let mut transaction = rt.store().transaction();
+    let kind            = rt.cli()
         .subcommand_matches("remove")
         .unwrap()
-        .values_of("to")
-        .map(|values| {
-            for (entry, value) in values.map(|v| (get_entry_by_name(rt, v), v)) {
-                match entry {
-                    Err(e) => trace_error(&e),
-                    Ok(Some(mut to_entry)) => {
-                        let _ = to_entry
-                            .remove_internal_link(&mut from)
-                            .map_err_trace_exit_unwrap(1);
-                    },
-                    Ok(None) => {
-                        // looks like this is not an entry, but a filesystem URI and therefor an
-                        // external link...?
-                        if PathBuf::from(value).is_file() {
-                            let url = Url::parse(value).unwrap_or_else(|e| {
-                                error!("Error parsing URL: {:?}", e);
-                                ::std::process::exit(1);
-                            });
-                            from.remove_external_link(rt.store(), url).map_err_trace_exit_unwrap(1);
-                            info!("Ok: {}", value);
-                        } else {
-                            warn!("Entry not found: {:?}", value);
-                        }
-                    }
+        .value_of("type");
+
+    let values = values_with_stdin_ids(rt, rt.cli().subcommand_matches("remove").unwrap(), "to");
+
+    for (entry, value) in values.iter().map(|v| (get_entry_by_name(rt, v), v)) {
+        match entry {
+            Err(e) => trace_error(&e),
+            Ok(Some(mut to_entry)) => {
+                let _ = match kind {
+                    Some(kind) => to_entry.remove_internal_typed_link(&mut from, kind),
+                    None       => to_entry.remove_internal_link(&mut from),
+                }.map_err_trace_exit_unwrap(1);
+
+                transaction.update(to_entry);
+            },
+            Ok(None) => {
+                // looks like this is not an entry, but a filesystem URI and therefor an
+                // external link...?
+                if PathBuf::from(value).is_file() {
+                    let url = Url::parse(value).unwrap_or_else(|e| {
+                        error!("Error parsing URL: {:?}", e);
+                        ::std::process::exit(1);
+                    });
+                    from.remove_external_link(rt.store(), url).map_err_trace_exit_unwrap(1);
+                    info!("Ok: {}", value);
+                } else {
+                    warn!("Entry not found: {:?}", value);
                 }
             }
-        });
+        }
+    }
+
+    let from_id = from.get_location().clone();
+    transaction.update(from);
+    let _ = transaction.commit().map_err_trace_exit_unwrap(1);
+    let _ = rt.report_touched(&from_id).map_err_trace_exit_unwrap(1);
 }
 
 fn unlink(rt: &Runtime) {
     use libimagerror::iter::TraceIterator;
     use libimagstore::iter::get::StoreIdGetIteratorExtension;
 
+    let cmd = rt.cli().subcommand_matches("unlink").unwrap(); // checked in main()
+
     let _ = rt
-        .cli()
-        .subcommand_matches("unlink")
-        .unwrap() // checked in main()
-        .values_of("from")
-        .unwrap() // checked by clap
-        .map(PathBuf::from)
-        .collect::<Vec<PathBuf>>().into_iter() // for lifetime inference
-        .map(StoreId::new_baseless)
-        .unwrap_with(|e| { trace_error(&e); exit(1) })
+        .ids_from_args(cmd, "from")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| warn_exit("No entries to unlink", 1))
+        .into_iter()
         .into_get_iter(rt.store())
         .unwrap_with(|e| { trace_error(&e); exit(1) })
         .filter_map(|e| e)
-        .map(|mut entry| entry.unlink(rt.store()))
+        .map(|mut entry| entry.unlink(rt.store()).map(|_| entry))
         .unwrap_with(|e| { trace_error(&e); exit(1) })
+        .map(|entry| rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1))
         .collect::<Vec<_>>();
 }
 
@@ -271,26 +316,63 @@ fn list_linkings(rt: &Runtime) {
 
     let list_externals  = cmd.is_present("list-externals-too");
     let list_plain      = cmd.is_present("list-plain");
+    let kind            = cmd.value_of("type");
 
     let mut tab = ::prettytable::Table::new();
-    tab.set_titles(row!["#", "Link"]);
+    tab.set_titles(row!["#", "Link", "Type"]);
 
-    for entry in cmd.values_of("entries").unwrap() { // safed by clap
-        match rt.store().get(PathBuf::from(entry)) {
+    let mut output = rt.output();
+    let json       = output.format().is_json();
+
+    let ids = rt
+        .ids_from_args(cmd, "entries")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| warn_exit("No entries to list", 1));
+
+    for id in ids {
+        let entry_name = id.to_str().map_err_trace_exit_unwrap(1);
+        match rt.store().get(id) {
             Ok(Some(entry)) => {
-                for (i, link) in entry.get_internal_links().map_err_trace_exit_unwrap(1).enumerate() {
+                let links = match kind {
+                    Some(kind) => entry.get_internal_links_of_type(kind),
+                    None       => entry.get_internal_links(),
+                }.map_err_trace_exit_unwrap(1);
+
+                for (i, link) in links.enumerate() {
+                    let relation = match (link.kind(), link.direction()) {
+                        (Some(kind), Some(direction)) => format!("{} ({})", kind, direction.as_str()),
+                        (Some(kind), None)            => String::from(kind),
+                        (None, _)                     => String::new(),
+                    };
+
+                    let link_kind      = link.kind().map(String::from);
+                    let link_direction = link.direction().map(|d| d.as_str());
+
                     let link = link
                         .to_str()
                         .map_warn_err(|e| format!("Failed to convert StoreId to string: {:?}", e))
                         .ok();
 
                     if let Some(link) = link {
-                        if list_plain {
-                            let _ = writeln!(rt.stdout(), "{: <3}: {}", i, link)
-                                .to_exit_code()
-                                .unwrap_or_exit();
+                        if json {
+                            let record = json!({
+                                "entry": entry_name,
+                                "link": link,
+                                "external": false,
+                                "type": link_kind,
+                                "direction": link_direction,
+                            });
+                            let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+                        } else if list_plain {
+                            let _ = if relation.is_empty() {
+                                writeln!(rt.stdout(), "{: <3}: {}", i, link)
+                            } else {
+                                writeln!(rt.stdout(), "{: <3}: {} [{}]", i, link, relation)
+                            }
+                            .to_exit_code()
+                            .unwrap_or_exit();
                         } else {
-                            tab.add_row(row![i, link]);
+                            tab.add_row(row![i, link, relation]);
                         }
                     }
                 }
@@ -304,28 +386,117 @@ fn list_linkings(rt: &Runtime) {
                                 .map_err_trace_exit_unwrap(1)
                                 .into_string();
 
-                            if list_plain {
+                            if json {
+                                let record = json!({
+                                    "entry": entry_name,
+                                    "link": link,
+                                    "external": true,
+                                    "type": null,
+                                    "direction": null,
+                                });
+                                let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+                            } else if list_plain {
                                 let _ = writeln!(rt.stdout(), "{: <3}: {}", i, link)
                                     .to_exit_code()
                                     .unwrap_or_exit();
                             } else {
-                                tab.add_row(row![i, link]);
+                                tab.add_row(row![i, link, ""]);
                             }
                         })
                 }
             },
-            Ok(None)        => warn!("Not found: {}", entry),
+            Ok(None)        => warn!("Not found: {}", entry_name),
             Err(e)          => trace_error(&e),
         }
     }
 
-    if !list_plain {
+    if !list_plain && !json {
         let out      = rt.stdout();
         let mut lock = out.lock();
         tab.print(&mut lock)
             .to_exit_code()
             .unwrap_or_exit();
     }
+
+    output.finish().to_exit_code().unwrap_or_exit();
+}
+
+fn export_graph(rt: &Runtime) {
+    let cmd = rt.cli()
+        .subcommand_matches("graph")
+        .unwrap(); // safed by clap
+
+    let starts = rt
+        .ids_from_args(cmd, "entries")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| vec![]);
+
+    let depth = cmd.value_of("depth").map(parse_depth);
+
+    let collections = cmd
+        .values_of("collection")
+        .map(|v| v.map(String::from).collect::<Vec<_>>())
+        .unwrap_or_else(|| vec![]);
+
+    let graph = LinkGraph::walk(rt.store(), starts, depth, &collections).map_err_trace_exit_unwrap(1);
+    let out   = match cmd.value_of("format") {
+        Some("graphml") => graph.to_graphml(),
+        Some("json")    => format!("{}\n", graph.to_json()),
+        _               => graph.to_dot(),
+    };
+
+    let _ = write!(rt.stdout(), "{}", out)
+        .to_exit_code()
+        .unwrap_or_exit();
+}
+
+fn show_path(rt: &Runtime) {
+    let cmd  = rt.cli().subcommand_matches("path").unwrap(); // safed by clap
+    let from = cmd.value_of("from").unwrap(); // safed by clap
+    let to   = cmd.value_of("to").unwrap(); // safed by clap
+
+    let from_id = StoreId::new_baseless(PathBuf::from(from)).map_err_trace_exit_unwrap(1);
+    let to_id   = StoreId::new_baseless(PathBuf::from(to)).map_err_trace_exit_unwrap(1);
+
+    match rt.store().shortest_path(from_id, to_id).map_err_trace_exit_unwrap(1) {
+        Some(path) => {
+            let out      = rt.stdout();
+            let mut lock = out.lock();
+            for id in path {
+                let _ = writeln!(lock, "{}", id)
+                    .to_exit_code()
+                    .unwrap_or_exit();
+            }
+        },
+        None => {
+            warn!("{} and {} are not connected", from, to);
+            exit(1)
+        },
+    }
+}
+
+fn list_neighbors(rt: &Runtime) {
+    let cmd   = rt.cli().subcommand_matches("neighbors").unwrap(); // safed by clap
+    let entry = cmd.value_of("entry").unwrap(); // safed by clap
+    let depth = cmd.value_of("depth").map(parse_depth).unwrap_or(1);
+    let id    = StoreId::new_baseless(PathBuf::from(entry)).map_err_trace_exit_unwrap(1);
+
+    let mut output = rt.output();
+    for (id, distance) in rt.store().neighborhood(id, depth).map_err_trace_exit_unwrap(1) {
+        let record = json!({ "id": id.to_str().map_err_trace_exit_unwrap(1), "distance": distance });
+        let _ = output.emit(&record, |out| writeln!(out, "{: <3}: {}", distance, id))
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    output.finish().to_exit_code().unwrap_or_exit();
+}
+
+fn parse_depth(depth: &str) -> usize {
+    depth.parse::<usize>().unwrap_or_else(|_| {
+        error!("Depth is not a number: {}", depth);
+        exit(1)
+    })
 }
 
 #[cfg(test)]
@@ -594,4 +765,49 @@ mod tests {
         assert_eq!(*test_links2, links_toml_value(vec![]));
         assert_eq!(*test_links3, links_toml_value(vec![]));
     }
+
+    #[test]
+    fn test_typed_linking_and_removing_by_type() {
+        setup_logging();
+        let rt = generate_test_runtime(vec!["--type", "blocks", "--directed", "test1", "test2"])
+            .unwrap();
+
+        let test_id1 = create_test_default_entry(&rt, "test1").unwrap();
+        let test_id2 = create_test_default_entry(&rt, "test2").unwrap();
+
+        link_from_to(&rt, "test1", vec!["test2"].into_iter());
+
+        {
+            let test_entry1 = rt.store().get(test_id1.clone()).unwrap().unwrap();
+            let test_links1 = get_entry_links(&test_entry1).unwrap();
+            let test_entry2 = rt.store().get(test_id2.clone()).unwrap().unwrap();
+            let test_links2 = get_entry_links(&test_entry2).unwrap();
+
+            let link1 = test_links1.as_array().unwrap()[0].clone();
+            let link2 = test_links2.as_array().unwrap()[0].clone();
+            assert_eq!(link1.get("link"), Some(&Value::String(String::from("test2"))));
+            assert_eq!(link1.get("type"), Some(&Value::String(String::from("blocks"))));
+            assert_eq!(link1.get("direction"), Some(&Value::String(String::from("outgoing"))));
+            assert_eq!(link2.get("link"), Some(&Value::String(String::from("test1"))));
+            assert_eq!(link2.get("direction"), Some(&Value::String(String::from("incoming"))));
+        }
+
+        // Removing another relation type leaves the link alone
+        let rt = reset_test_runtime(vec!["remove", "--type", "relates-to", "test1", "test2"], rt)
+            .unwrap();
+        remove_linking(&rt);
+        {
+            let test_entry1 = rt.store().get(test_id1.clone()).unwrap().unwrap();
+            assert_eq!(get_entry_links(&test_entry1).unwrap().as_array().unwrap().len(), 1);
+        }
+
+        let rt = reset_test_runtime(vec!["remove", "--type", "blocks", "test1", "test2"], rt)
+            .unwrap();
+        remove_linking(&rt);
+
+        let test_entry1 = rt.store().get(test_id1).unwrap().unwrap();
+        let test_entry2 = rt.store().get(test_id2).unwrap().unwrap();
+        assert_eq!(*get_entry_links(&test_entry1).unwrap(), links_toml_value(vec![]));
+        assert_eq!(*get_entry_links(&test_entry2).unwrap(), links_toml_value(vec![]));
+    }
 }
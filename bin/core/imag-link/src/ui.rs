@@ -36,8 +36,16 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .takes_value(true)
                      .required(true)
                      .multiple(true)
-                     .help("Remove links to these entries")
+                     .help("Remove links to these entries. A value of '-' reads store ids from stdin")
                      .value_name("ENTRIES"))
+                .arg(Arg::with_name("type")
+                     .long("type")
+                     .short("t")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Only remove links of this relation type")
+                     .value_name("TYPE"))
                 )
         .subcommand(SubCommand::with_name("unlink")
                 .about("Remove all links from an entry")
@@ -45,9 +53,9 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                 .arg(Arg::with_name("from")
                      .index(1)
                      .takes_value(true)
-                     .required(true)
+                     .required(false)
                      .multiple(true)
-                     .help("Remove links from these entries")
+                     .help("Remove links from these entries. Read from stdin with --ids-from-stdin")
                      .value_name("ENTRY"))
                 )
 
@@ -58,8 +66,8 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .index(1)
                      .takes_value(true)
                      .multiple(true)
-                     .required(true)
-                     .help("List these entries, seperate by comma")
+                     .required(false)
+                     .help("List these entries, seperate by comma. Read from stdin with --ids-from-stdin")
                      .value_name("ENTRIES"))
 
                 .arg(Arg::with_name("list-externals-too")
@@ -74,6 +82,97 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .takes_value(false)
                      .required(false)
                      .help("List plain rather than in ASCII table"))
+
+                .arg(Arg::with_name("type")
+                     .long("type")
+                     .short("t")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Only list links of this relation type")
+                     .value_name("TYPE"))
+                )
+
+        .subcommand(SubCommand::with_name("graph")
+                .about("Export the link network as graph")
+                .version("0.1")
+                .arg(Arg::with_name("entries")
+                     .index(1)
+                     .takes_value(true)
+                     .multiple(true)
+                     .required(false)
+                     .help("Start walking the links at these entries. Read from stdin with --ids-from-stdin. Defaults to all entries in the store")
+                     .value_name("ENTRIES"))
+
+                .arg(Arg::with_name("depth")
+                     .long("depth")
+                     .short("d")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Follow at most this many links from the starting entries")
+                     .value_name("DEPTH"))
+
+                .arg(Arg::with_name("collection")
+                     .long("collection")
+                     .short("c")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(true)
+                     .number_of_values(1)
+                     .help("Only include entries of this collection (can be passed multiple times)")
+                     .value_name("COLLECTION"))
+
+                .arg(Arg::with_name("format")
+                     .long("format")
+                     .short("f")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .possible_values(&["dot", "graphml", "json"])
+                     .default_value("dot")
+                     .help("The format to export the graph in")
+                     .value_name("FORMAT"))
+                )
+
+        .subcommand(SubCommand::with_name("path")
+                .about("Show the shortest chain of links between two entries")
+                .version("0.1")
+                .arg(Arg::with_name("from")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("Start at this entry")
+                     .value_name("ENTRY"))
+                .arg(Arg::with_name("to")
+                     .index(2)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("End at this entry")
+                     .value_name("ENTRY"))
+                )
+
+        .subcommand(SubCommand::with_name("neighbors")
+                .about("List the entries within a number of links of an entry")
+                .version("0.1")
+                .arg(Arg::with_name("entry")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("List the neighbors of this entry")
+                     .value_name("ENTRY"))
+                .arg(Arg::with_name("depth")
+                     .long("depth")
+                     .short("d")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .default_value("1")
+                     .help("Follow at most this many links")
+                     .value_name("DEPTH"))
                 )
 
         .arg(Arg::with_name("check-consistency")
@@ -83,6 +182,24 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .required(false)
              .help("Check the link-consistency in the store (might be time-consuming)"))
 
+        .arg(Arg::with_name("type")
+             .long("type")
+             .short("t")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .requires("from")
+             .help("Link with this relation type (for example 'blocks' or 'parent-of')")
+             .value_name("TYPE"))
+
+        .arg(Arg::with_name("directed")
+             .long("directed")
+             .short("d")
+             .takes_value(false)
+             .required(false)
+             .requires("type")
+             .help("Make the typed link directed, from the 'from' entry to the 'to' entries"))
+
         .arg(Arg::with_name("from")
              .index(1)
              .takes_value(true)
@@ -97,7 +214,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .takes_value(true)
              .required(false)
              .multiple(true)
-             .help("Link to this entries")
+             .help("Link to this entries. A value of '-' reads store ids from stdin")
              .requires("from")
              .value_name("ENTRIES"))
 }
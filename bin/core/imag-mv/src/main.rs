@@ -39,6 +39,7 @@ extern crate clap;
 extern crate libimagstore;
 extern crate libimagerror;
 extern crate libimagentrylink;
+extern crate libimagentrymarkdown;
 
 use std::process::exit;
 
@@ -48,12 +49,11 @@ use ui::build_ui;
 use std::path::PathBuf;
 
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
 use libimagstore::storeid::StoreId;
-use libimagstore::store::Store;
-use libimagstore::store::FileLockEntry;
-use libimagentrylink::internal::InternalLinker;
-use libimagstore::iter::get::StoreIdGetIteratorExtension;
+use libimagentrylink::internal::move_links;
+use libimagentrymarkdown::rewrite::move_entry;
 
 fn main() {
     let version = make_imag_version!();
@@ -64,14 +64,6 @@ fn main() {
 
     debug!("mv");
 
-    let sourcename = rt
-        .cli()
-        .value_of("source")
-        .map(PathBuf::from)
-        .map(StoreId::new_baseless)
-        .unwrap() // unwrap safe by clap
-        .map_err_trace_exit_unwrap(1);
-
     let destname = rt
         .cli()
         .value_of("dest")
@@ -80,71 +72,57 @@ fn main() {
         .unwrap() // unwrap safe by clap
         .map_err_trace_exit_unwrap(1);
 
-    // remove links to entry, and re-add them later
-    let mut linked_entries = {
-        rt.store()
-            .get(sourcename.clone())
-            .map_err_trace_exit_unwrap(1)
-            .unwrap_or_else(|| {
-                error!("Funny things happened: Entry moved to destination did not fail, but entry does not exist");
-                exit(1)
-            })
-            .get_internal_links()
-            .map_err_trace_exit_unwrap(1)
-            .map(|link| link.get_store_id().clone())
-            .into_get_iter(rt.store())
-            .map(|e| {
-                e.map_err_trace_exit_unwrap(1)
-                .unwrap_or_else(|| {
-                    error!("Linked entry does not exist");
+    if rt.cli().value_of("source") == Some("-") {
+        // move all entries from stdin into the collection `destname`
+        for sourcename in rt.read_ids_from_stdin().map_err_trace_exit_unwrap(1) {
+            let dest = match sourcename.local().file_name() {
+                Some(name) => {
+                    let mut dest = destname.clone();
+                    dest.local_push(name);
+                    dest
+                },
+                None => {
+                    error!("Cannot move {}: Not a valid entry name", sourcename);
                     exit(1)
-                })
-            })
-            .collect::<Vec<_>>()
-    };
+                },
+            };
 
-    { // remove links to linked entries from source
-        let mut entry = rt
-            .store()
-            .get(sourcename.clone())
-            .map_err_trace_exit_unwrap(1)
-            .unwrap_or_else(|| {
-                error!("Source Entry does not exist");
-                exit(1)
-            });
-
-        for link in linked_entries.iter_mut() {
-            let _ = entry.remove_internal_link(link).map_err_trace_exit_unwrap(1);
+            mv(&rt, sourcename, dest);
         }
+    } else {
+        let sourcename = rt
+            .cli()
+            .value_of("source")
+            .map(PathBuf::from)
+            .map(StoreId::new_baseless)
+            .unwrap() // unwrap safe by clap
+            .map_err_trace_exit_unwrap(1);
+
+        mv(&rt, sourcename, destname);
     }
 
-    let _ = rt
-        .store()
-        .move_by_id(sourcename.clone(), destname.clone())
-        .map_err(|e| { // on error, re-add links
-            debug!("Re-adding links to source entry because moving failed");
-            relink(rt.store(), sourcename.clone(), &mut linked_entries);
-            e
-        })
-        .map_err_trace_exit_unwrap(1);
-
-    // re-add links to moved entry
-    relink(rt.store(), destname, &mut linked_entries);
-
     info!("Ok.");
 }
 
-fn relink<'a>(store: &'a Store, target: StoreId, linked_entries: &mut Vec<FileLockEntry<'a>>) {
-    let mut entry = store
-        .get(target)
-        .map_err_trace_exit_unwrap(1)
-        .unwrap_or_else(|| {
-            error!("Funny things happened: Entry moved to destination did not fail, but entry does not exist");
-            exit(1)
-        });
+fn mv(rt: &Runtime, sourcename: StoreId, destname: StoreId) {
+    if rt.cli().is_present("no-rewrite") {
+        let _ = rt
+            .store()
+            .move_by_id(sourcename.clone(), destname.clone())
+            .map_err_trace_exit_unwrap(1);
 
+        let _ = move_links(rt.store(), &sourcename, &destname).map_err_trace_exit_unwrap(1);
+    } else {
+        let report = move_entry(rt.store(), &sourcename, &destname).map_err_trace_exit_unwrap(1);
 
-    for mut link in linked_entries {
-        let _ = entry.add_internal_link(&mut link).map_err_trace_exit_unwrap(1);
+        for &(ref id, count) in report.rewritten() {
+            info!("Rewrote {} link(s) in {}", count, id);
+        }
+
+        for unrewritten in report.unrewritten() {
+            warn!("{}", unrewritten);
+        }
     }
+
+    let _ = rt.report_touched(&destname).map_err_trace_exit_unwrap(1);
 }
@@ -26,7 +26,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .takes_value(true)
              .required(true)
              .multiple(false)
-             .help("Source file")
+             .help("Source file. If '-', store ids are read from stdin and moved into the collection DEST")
              .value_name("ENTRY"))
 
         .arg(Arg::with_name("dest")
@@ -36,4 +36,11 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .multiple(false)
              .help("Destination name file")
              .value_name("DEST"))
+
+        .arg(Arg::with_name("no-rewrite")
+             .long("no-rewrite")
+             .takes_value(false)
+             .required(false)
+             .multiple(false)
+             .help("Do not rewrite the markdown links to the moved entry in the entries linked with it"))
 }
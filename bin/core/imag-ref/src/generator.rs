@@ -0,0 +1,133 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Selecting the hash generator for refs on the commandline
+
+use std::fmt::{Display, Formatter, Error as FmtError};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use libimagstore::store::Entry;
+use libimagentryref::error::RefError as RE;
+use libimagentryref::reference::Ref;
+use libimagentryref::refstore::UniqueRefPathGenerator;
+
+use ui::GENERATORS;
+
+/// Run `$body` with `$g` being the generator type named by `$name`
+#[macro_export]
+macro_rules! with_generator {
+    ($name:expr, $g:ident => $body:expr) => {
+        match $name {
+            "sha1"   => { type $g = ::libimagentryref::generators::sha1::Sha1;     $body },
+            "sha224" => { type $g = ::libimagentryref::generators::sha224::Sha224; $body },
+            "sha256" => { type $g = ::libimagentryref::generators::sha256::Sha256; $body },
+            "sha384" => { type $g = ::libimagentryref::generators::sha384::Sha384; $body },
+            "sha3"   => { type $g = ::libimagentryref::generators::sha3::Sha3;      $body },
+            _        => { type $g = ::libimagentryref::generators::sha512::Sha512; $body },
+        }
+    }
+}
+
+/// The hash generator selected with the "hash" and "partial" arguments
+///
+/// Refs which know the generator they were created with are checked with that one, refs which
+/// know the number of bytes their hash was calculated over are checked over that number of bytes.
+pub struct Generator<'a> {
+    name: &'a str,
+
+    /// Hash only the first N bytes of the file
+    bytes: Option<usize>,
+}
+
+impl<'a> Generator<'a> {
+
+    pub fn from_matches(matches: &'a ArgMatches) -> Generator<'a> {
+        Generator {
+            name: matches.value_of("hash").unwrap_or("sha512"),
+            bytes: matches
+                .value_of("partial")
+                .map(|n| n.parse::<usize>().unwrap()), // safe by clap validator
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn bytes(&self) -> Option<usize> {
+        self.bytes
+    }
+
+    pub fn status(&self, entry: &Entry) -> Result<RefStatus, RE> {
+        if entry.is_dangling()? {
+            return Ok(RefStatus::Dangling);
+        }
+
+        let name = match entry.get_generator()? {
+            Some(name) => name,
+            None       => self.name,
+        };
+
+        if !GENERATORS.contains(&name) {
+            return Err(RE::from(format!("Unknown hash '{}' in {}", name, entry.get_location())));
+        }
+
+        // refs which know how many bytes were hashed are checked over these bytes
+        let valid = match (entry.get_partial()?, self.bytes) {
+            (None, Some(_)) => hash_with(name, self.bytes, entry.get_path()?)? == entry.get_hash()?,
+            _               => with_generator!(name, G => entry.hash_valid::<G>())?,
+        };
+
+        if valid {
+            Ok(RefStatus::Ok)
+        } else {
+            Ok(RefStatus::HashMismatch)
+        }
+    }
+
+}
+
+fn hash_with<P: AsRef<Path>>(name: &str, bytes: Option<usize>, path: P) -> Result<String, RE> {
+    match bytes {
+        None    => with_generator!(name, G => G::unique_hash(path)),
+        Some(n) => with_generator!(name, G => G::hash_n_bytes(path, n)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefStatus {
+    Ok,
+    Dangling,
+    HashMismatch,
+}
+
+impl Display for RefStatus {
+
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        match *self {
+            RefStatus::Ok           => write!(fmt, "ok"),
+            RefStatus::Dangling     => write!(fmt, "dangling"),
+            RefStatus::HashMismatch => write!(fmt, "hash-mismatch"),
+        }
+    }
+
+}
+
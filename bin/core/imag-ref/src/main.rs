@@ -34,6 +34,7 @@
 
 #[macro_use] extern crate log;
 extern crate clap;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagstore;
 #[macro_use] extern crate libimagrt;
@@ -42,17 +43,31 @@ extern crate libimagerror;
 extern crate libimaginteraction;
 extern crate libimagutil;
 
+#[macro_use] mod generator;
 mod ui;
 use ui::build_ui;
 
+use std::io::Write;
 use std::path::PathBuf;
 use std::process::exit;
 
+use clap::ArgMatches;
+
 use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
-use libimagstore::storeid::IntoStoreId;
+use libimagstore::store::FileLockEntry;
+use libimagstore::storeid::StoreId;
+use libimagentryref::error::RefError as RE;
 use libimagentryref::reference::Ref;
+use libimagentryref::refstore::RefStore;
+use libimagentryref::relocate::relocate_dangling_refs;
+
+use generator::Generator;
+use generator::RefStatus;
+use ui::GENERATORS;
 
 fn main() {
     let version = make_imag_version!();
@@ -65,8 +80,12 @@ fn main() {
         .map(|name| {
             debug!("Call: {}", name);
             match name {
-                "deref"  => deref(&rt),
-                "remove" => remove(&rt),
+                "create"   => create(&rt),
+                "deref"    => deref(&rt),
+                "list"     => list(&rt),
+                "relocate" => relocate(&rt),
+                "remove"   => remove(&rt),
+                "verify"   => verify(&rt),
                 other => {
                     debug!("Unknown command");
                     let _ = rt.handle_unknown_subcommand("imag-ref", other, rt.cli())
@@ -80,29 +99,25 @@ fn main() {
 
 fn deref(rt: &Runtime) {
     let cmd  = rt.cli().subcommand_matches("deref").unwrap();
-    let id   = cmd.value_of("ID")
-        .map(String::from)
-        .map(PathBuf::from)
-        .unwrap() // saved by clap
-        .into_storeid()
-        .map_err_trace_exit_unwrap(1);
 
-    match rt.store().get(id.clone()).map_err_trace_exit_unwrap(1) {
-        Some(entry) => entry
-            .get_path()
-            .map_err_trace_exit_unwrap(1)
-            .to_str()
-            .ok_or_else(|| {
-                error!("Could not transform path into string!");
+    for id in arg_ids(rt, cmd) {
+        match rt.store().get(id.clone()).map_err_trace_exit_unwrap(1) {
+            Some(entry) => entry
+                .get_path()
+                .map_err_trace_exit_unwrap(1)
+                .to_str()
+                .ok_or_else(|| {
+                    error!("Could not transform path into string!");
+                    exit(1)
+                })
+                .map(|s| info!("{}", s))
+                .ok(), // safe here because we exited already in the error case
+            None => {
+                error!("No entry for id '{}' found", id);
                 exit(1)
-            })
-            .map(|s| info!("{}", s))
-            .ok(), // safe here because we exited already in the error case
-        None => {
-            error!("No entry for id '{}' found", id);
-            exit(1)
-        },
-    };
+            },
+        };
+    }
 }
 
 fn remove(rt: &Runtime) {
@@ -110,25 +125,224 @@ fn remove(rt: &Runtime) {
 
     let cmd  = rt.cli().subcommand_matches("remove").unwrap();
     let yes  = cmd.is_present("yes");
-    let id   = cmd.value_of("ID")
-        .map(String::from)
+
+    let from_stdin = match cmd.values_of("ID") {
+        Some(mut values) => values.any(|v| v == "-"),
+        None             => rt.reads_ids_from_stdin(),
+    };
+
+    if !yes && from_stdin {
+        // stdin is used for the ids, so we cannot ask
+        error!("Reading ids from stdin requires --yes");
+        exit(1)
+    }
+
+    for id in arg_ids(rt, cmd) {
+        match rt.store().get(id.clone()).map_err_trace_exit_unwrap(1) {
+            Some(mut entry) => {
+                if yes || ask_bool(&format!("Delete ref from entry '{}'", id), None) {
+                    let _ = entry.remove_ref().map_err_trace_exit_unwrap(1);
+                    let _ = rt.store().update(&mut entry).map_err_trace_exit_unwrap(1);
+                    let _ = rt.report_touched(entry.get_location()).map_err_trace_exit_unwrap(1);
+                } else {
+                    info!("Aborted");
+                }
+            },
+            None => {
+                error!("No entry for id '{}' found", id);
+                exit(1)
+            },
+        };
+    }
+}
+
+fn create(rt: &Runtime) {
+    let cmd       = rt.cli().subcommand_matches("create").unwrap();
+    let generator = Generator::from_matches(cmd);
+    let path      = cmd.value_of("path")
         .map(PathBuf::from)
         .unwrap() // saved by clap
-        .into_storeid()
+        .canonicalize()
+        .map_err(RE::from)
         .map_err_trace_exit_unwrap(1);
 
-    match rt.store().get(id.clone()).map_err_trace_exit_unwrap(1) {
-        Some(mut entry) => {
-            if yes || ask_bool(&format!("Delete ref from entry '{}'", id), None) {
-                let _ = entry.remove_ref().map_err_trace_exit_unwrap(1);
-            } else {
-                info!("Aborted");
-            }
-        },
-        None => {
-            error!("No entry for id '{}' found", id);
+    let mut entry = with_generator!(generator.name(), G => {
+        match generator.bytes() {
+            None    => rt.store().create_ref::<G, _>(&path),
+            Some(n) => G::hash_n_bytes(&path, n)
+                .and_then(|hash| rt.store().create_ref_with_hash::<G, _>(&path, hash)),
+        }
+    }).map_err_trace_exit_unwrap(1);
+
+    if let Some(n) = generator.bytes() {
+        let _ = entry.set_partial(n).map_err_trace_exit_unwrap(1);
+        let _ = rt.store().update(&mut entry).map_err_trace_exit_unwrap(1);
+    }
+
+    writeln!(rt.stdout(), "{}", entry.get_location())
+        .to_exit_code()
+        .unwrap_or_exit();
+}
+
+fn list(rt: &Runtime) {
+    let cmd       = rt.cli().subcommand_matches("list").unwrap();
+    let generator  = Generator::from_matches(cmd);
+    let mut output = rt.output();
+
+    for id in ref_ids(rt) {
+        let entry = match rt.store().get(id).map_err_trace_exit_unwrap(1) {
+            Some(entry) => entry,
+            None        => continue,
+        };
+
+        let status = generator.status(&entry).map_err_trace_exit_unwrap(1);
+        let path   = entry.get_path().map_err_trace_exit_unwrap(1);
+        let hash   = entry.get_hash().map_err_trace_exit_unwrap(1);
+        let record = json!({
+            "id": entry_id(&entry),
+            "path": path.to_string_lossy(),
+            "hash": hash,
+            "status": format!("{}", status),
+        });
+
+        output
+            .emit(&record, |out| {
+                writeln!(out, "{} | {} | {} | {}", entry.get_location(), path.display(), hash, status)
+            })
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    output.finish().to_exit_code().unwrap_or_exit();
+}
+
+fn verify(rt: &Runtime) {
+    let cmd       = rt.cli().subcommand_matches("verify").unwrap();
+    let generator = Generator::from_matches(cmd);
+    let mut problems = 0;
+    let mut output   = rt.output();
+
+    for id in ref_ids(rt) {
+        let entry = match rt.store().get(id).map_err_trace_exit_unwrap(1) {
+            Some(entry) => entry,
+            None        => continue,
+        };
+
+        let status = generator.status(&entry).map_err_trace_exit_unwrap(1);
+        if status != RefStatus::Ok {
+            problems += 1;
+            let record = json!({ "id": entry_id(&entry), "status": format!("{}", status) });
+            output.emit(&record, |out| writeln!(out, "{}: {}", entry.get_location(), status))
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+    }
+
+    output.finish().to_exit_code().unwrap_or_exit();
+
+    if problems != 0 {
+        warn!("{} reference(s) with problems", problems);
+        exit(1)
+    }
+
+    info!("All references ok");
+}
+
+/// The id of the entry, as used in the JSON output
+fn entry_id(entry: &FileLockEntry) -> String {
+    entry.get_location().clone().without_base().to_str().map_err_trace_exit_unwrap(1)
+}
+
+/// Get the ids passed as "ID" argument or via stdin
+fn arg_ids(rt: &Runtime, cmd: &ArgMatches) -> Vec<StoreId> {
+    rt.ids_from_args(cmd, "ID")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| {
+            error!("No ids passed, neither on the commandline nor via stdin");
             exit(1)
-        },
+        })
+}
+
+/// Get the ids of all entries in the "ref" collection which are refs
+///
+/// If ids are read from stdin (`--ids-from-stdin`), only these are considered.
+fn ref_ids(rt: &Runtime) -> Vec<StoreId> {
+    let ids = match rt.ids_from_stdin().map_err_trace_exit_unwrap(1) {
+        Some(ids) => ids,
+        None      => rt.store()
+            .entries()
+            .map_err_trace_exit_unwrap(1)
+            .without_store()
+            .collect(),
     };
+
+    ids.into_iter()
+        .filter(|id| id.is_in_collection(&["ref"]))
+        .filter(|id| {
+            rt.store()
+                .get(id.clone())
+                .map_err_trace_exit_unwrap(1)
+                .map(|entry| entry.is_ref().map_err_trace_exit_unwrap(1))
+                .unwrap_or(false)
+        })
+        .collect()
 }
 
+fn relocate(rt: &Runtime) {
+    let cmd   = rt.cli().subcommand_matches("relocate").unwrap();
+    let roots = cmd.values_of("roots").unwrap().map(PathBuf::from).collect::<Vec<_>>(); // saved by clap
+    let store = rt.store();
+
+    // Each ref can only be found again with the hash it was created with
+    let generators = match cmd.value_of("hash") {
+        Some(name) => vec![String::from(name)],
+        None       => {
+            let mut names = ref_ids(rt)
+                .into_iter()
+                .filter_map(|id| store.get(id).map_err_trace_exit_unwrap(1))
+                .map(|entry| {
+                    entry.get_generator()
+                        .map_err_trace_exit_unwrap(1)
+                        .map(String::from)
+                        .unwrap_or_else(|| String::from("sha512"))
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names
+        },
+    };
+
+    let mut relocated  = vec![];
+    let mut unresolved = vec![];
+    for name in generators.iter() {
+        if !GENERATORS.contains(&name.as_str()) {
+            warn!("Unknown hash '{}', skipping the references created with it", name);
+            continue;
+        }
+
+        let report = with_generator!(name.as_str(), G => {
+            relocate_dangling_refs::<G, _>(store, &roots)
+        }).map_err_trace_exit_unwrap(1);
+
+        relocated.extend(report.relocated().iter().cloned());
+        unresolved.extend(report.unresolved().iter().cloned());
+    }
+
+    // Refs which do not know their hash are tried with every hash
+    unresolved.retain(|&(ref id, _)| !relocated.iter().any(|&(ref rid, _, _)| rid == id));
+    unresolved.sort_by(|a, b| a.0.cmp(&b.0));
+    unresolved.dedup_by(|a, b| a.0 == b.0);
+
+    for &(ref id, ref old, ref new) in relocated.iter() {
+        writeln!(rt.stdout(), "{}: {} -> {}", id, old.display(), new.display())
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    for &(ref id, ref path) in unresolved.iter() {
+        warn!("Unresolved: {} ({})", id, path.display());
+    }
+
+    info!("Relocated {} reference(s), {} unresolved", relocated.len(), unresolved.len());
+}
@@ -19,6 +19,11 @@
 
 use clap::{Arg, App, SubCommand};
 
+use libimagutil::cli_validators::*;
+
+/// The names of the hash generators which can be selected on the commandline
+pub const GENERATORS : &[&str] = &["sha1", "sha224", "sha256", "sha384", "sha512", "sha3"];
+
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
         .subcommand(SubCommand::with_name("deref")
@@ -27,9 +32,10 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                     .arg(Arg::with_name("ID")
                          .index(1)
                          .takes_value(true)
-                         .required(true)
-                         .help("The id of the store entry to dereference")
-                         .value_name("ID"))
+                         .required(false)
+                         .multiple(true)
+                         .help("The ids of the store entries to dereference. Read from stdin with --ids-from-stdin")
+                         .value_name("IDS"))
                     )
 
         .subcommand(SubCommand::with_name("remove")
@@ -38,9 +44,9 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                 .arg(Arg::with_name("ID")
                      .index(1)
                      .takes_value(true)
-                     .required(true)
+                     .required(false)
                      .multiple(true)
-                     .help("Remove the reference from this store entry")
+                     .help("Remove the reference from these store entries. Read from stdin with --ids-from-stdin or '-', which requires --yes")
                      .value_name("ENTRIES"))
 
                 .arg(Arg::with_name("yes")
@@ -48,4 +54,70 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .short("y")
                      .help("Don't ask whether this really should be done"))
                 )
+
+        .subcommand(SubCommand::with_name("create")
+                .about("Create a reference to a file")
+                .version("0.1")
+                .arg(Arg::with_name("path")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .validator(is_file)
+                     .help("The file to create a reference to")
+                     .value_name("PATH"))
+                .arg(hash_arg("The hash to create the reference with (default: sha512)"))
+                .arg(partial_arg("Hash only the first N bytes of the file"))
+                )
+
+        .subcommand(SubCommand::with_name("list")
+                .about("List all references with their path, hash and status. With --ids-from-stdin, only the entries read from stdin are listed")
+                .version("0.1")
+                .arg(hash_arg("The hash of the references which do not know the hash they were created with (default: sha512)"))
+                .arg(partial_arg("Hash only the first N bytes of the files of the references which do not know how many bytes were hashed"))
+                )
+
+        .subcommand(SubCommand::with_name("verify")
+                .about("Check all references, exit with 1 if one of them is dangling or does not match its hash. With --ids-from-stdin, only the entries read from stdin are checked")
+                .version("0.1")
+                .arg(hash_arg("The hash of the references which do not know the hash they were created with (default: sha512)"))
+                .arg(partial_arg("Hash only the first N bytes of the files of the references which do not know how many bytes were hashed"))
+                )
+
+        .subcommand(SubCommand::with_name("relocate")
+                .about("Search for the files of dangling references and update the references")
+                .version("0.1")
+                .arg(Arg::with_name("roots")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(true)
+                     .help("Search these directories (recursively) for the moved files")
+                     .value_name("DIRECTORIES"))
+                .arg(hash_arg("Only relocate references created with this hash (default: all)"))
+                )
+}
+
+fn hash_arg<'a>(help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name("hash")
+        .long("hash")
+        .short("H")
+        .takes_value(true)
+        .required(false)
+        .multiple(false)
+        .possible_values(GENERATORS)
+        .help(help)
+        .value_name("HASH")
+}
+
+fn partial_arg<'a>(help: &'a str) -> Arg<'a, 'a> {
+    Arg::with_name("partial")
+        .long("partial")
+        .short("p")
+        .takes_value(true)
+        .required(false)
+        .multiple(false)
+        .validator(is_integer)
+        .help(help)
+        .value_name("N")
 }
@@ -41,6 +41,8 @@ use util::build_toml_header;
 type Result<T> = RResult<T, StoreError>;
 
 pub fn create(rt: &Runtime) {
+    rt.reject_json_output("imag-store create").map_err_trace_exit_unwrap(1);
+
     let scmd = rt.cli().subcommand_matches("create").unwrap();
     debug!("Found 'create' subcommand...");
 
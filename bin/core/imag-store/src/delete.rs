@@ -25,6 +25,8 @@ use libimagstore::storeid::StoreId;
 use libimagutil::warn_result::*;
 
 pub fn delete(rt: &Runtime) {
+    rt.reject_json_output("imag-store delete").map_err_trace_exit_unwrap(1);
+
     let scmd  = rt.cli().subcommand_matches("delete").unwrap();
     let id    = scmd.value_of("id").unwrap(); // safe by clap
     let path  = PathBuf::from(id);
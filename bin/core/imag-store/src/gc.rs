@@ -0,0 +1,76 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+use std::process::exit;
+
+use libimagrt::runtime::Runtime;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::io::ToExitCode;
+use libimagerror::exit::ExitUnwrap;
+use libimagrepair::problem::find_problems;
+
+/// Find inconsistencies in the store and repair them
+///
+/// With `--dry-run`, the inconsistencies and what would be done about them are only printed.
+/// Dangling refs are only removed with `--remove-dangling-refs`. Exits with 1 if not all other
+/// inconsistencies could be repaired.
+pub fn gc(rt: &Runtime) {
+    let matches      = rt.cli().subcommand_matches("gc").unwrap();
+    let dry_run      = matches.is_present("dry-run");
+    let remove_refs  = matches.is_present("remove-dangling-refs");
+    let problems     = find_problems(rt.store()).map_err_trace_exit_unwrap(1);
+    let mut repaired = 0;
+    let mut kept     = 0;
+    let mut output   = rt.output();
+
+    for problem in problems.iter() {
+        let keep   = problem.is_lossy() && !remove_refs;
+        let action = if keep {
+            "none, pass --remove-dangling-refs to remove the ref"
+        } else {
+            problem.action()
+        };
+
+        let record = json!({ "problem": format!("{}", problem), "action": action });
+        let _ = output
+            .emit(&record, |out| writeln!(out, "{}: {}", problem, action))
+            .to_exit_code()
+            .unwrap_or_exit();
+
+        if keep {
+            kept += 1;
+        } else if !dry_run && problem.repair(rt.store()).map_err_trace().is_ok() {
+            repaired += 1;
+        }
+    }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
+
+    if dry_run {
+        info!("{} inconsistencies found, run without --dry-run to repair them", problems.len());
+    } else {
+        info!("{} inconsistencies found, {} repaired, {} kept", problems.len(), repaired, kept);
+
+        if repaired + kept < problems.len() {
+            exit(1)
+        }
+    }
+}
+
@@ -0,0 +1,197 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::path::PathBuf;
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diff;
+
+use libimagrt::runtime::Runtime;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::io::ToExitCode;
+use libimagerror::exit::ExitUnwrap;
+use libimagstore::storeid::StoreId;
+use libimagutil::date::datetime_to_string;
+use libimagutil::warn_exit::warn_exit;
+
+pub fn history(rt: &Runtime) {
+    let scmd  = rt.cli().subcommand_matches("history").unwrap();
+    let id    = scmd.value_of("id").unwrap(); // safe by clap
+    let path  = PathBuf::from(id);
+    let store = Some(rt.store().path().clone());
+    let path  = StoreId::new(store, path).map_err_trace_exit_unwrap(1);
+    debug!("History of {:?}", path);
+
+    if !rt.store().history_enabled() {
+        warn!("The history is disabled, set 'store.history = true' in the configuration");
+    }
+
+    if scmd.is_present("restore") || scmd.is_present("diff") {
+        rt.reject_json_output("imag-store history").map_err_trace_exit_unwrap(1);
+    }
+
+    if let Some(rev) = scmd.value_of("restore") {
+        let rev = parse_revision(rev);
+        let _   = rt.store().restore(path, rev).map_err_trace_exit_unwrap(1);
+        info!("Ok: restored revision {}", rev);
+    } else if let Some(from) = scmd.value_of("diff") {
+        let from   = parse_revision(from);
+        let newest = rt
+            .store()
+            .history(path.clone())
+            .map_err_trace_exit_unwrap(1)
+            .last()
+            .map(|r| r.number());
+        let to     = diff_target(newest, from, scmd.value_of("to"))
+            .unwrap_or_else(|e| warn_exit(&e, 1));
+
+        print_diff(rt, path, from, to)
+    } else {
+        list_revisions(rt, path)
+    }
+}
+
+fn list_revisions(rt: &Runtime, path: StoreId) {
+    let mut output = rt.output();
+
+    for rev in rt.store().history(path).map_err_trace_exit_unwrap(1) {
+        let date = rev
+            .timestamp()
+            .map(|ts| datetime_to_string(&NaiveDateTime::from_timestamp(ts, 0)))
+            .unwrap_or_else(|| String::from("<unknown>"));
+
+        let record = json!({ "revision": rev.number(), "timestamp": rev.timestamp() });
+        let _ = output
+            .emit(&record, |out| writeln!(out, "{: >4} | {}", rev.number(), date))
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
+}
+
+/// Find the revision to diff revision `from` against, `None` meaning the current version
+///
+/// Without `to`, this is the revision after `from`, or the current version if `from` is the
+/// newest revision. A `to` which is neither "current" nor an existing revision is an error.
+fn diff_target(newest: Option<u64>, from: u64, to: Option<&str>) -> Result<Option<u64>, String> {
+    match to {
+        None => match from.checked_add(1) {
+            Some(next) if Some(next) <= newest => Ok(Some(next)),
+            _                                  => Ok(None),
+        },
+        Some("current") => Ok(None),
+        Some(to)        => {
+            let to = to.parse::<u64>().map_err(|_| format!("Not a revision number: {}", to))?;
+            if Some(to) <= newest {
+                Ok(Some(to))
+            } else {
+                Err(format!("No revision {}, the newest revision is {}", to,
+                            newest.map(|n| n.to_string()).unwrap_or_else(|| String::from("none"))))
+            }
+        },
+    }
+}
+
+/// Print the changes from revision `from` to revision `to`, `None` meaning the current version
+fn print_diff(rt: &Runtime, path: StoreId, from: u64, to: Option<u64>) {
+    let store = rt.store();
+    let get_revision = |rev| {
+        store
+            .get_revision(path.clone(), rev)
+            .map_err_trace_exit_unwrap(1)
+            .unwrap_or_else(|| warn_exit(&format!("No revision {} of {}", rev, path), 1))
+            .to_str()
+            .map_err_trace_exit_unwrap(1)
+    };
+
+    let old_text = get_revision(from);
+    let (new_name, new_text) = match to {
+        Some(to) => (format!("revision {}", to), get_revision(to)),
+        None     => {
+            let current = store
+                .get(path.clone())
+                .map_err_trace_exit_unwrap(1)
+                .map(|entry| entry.to_str().map_err_trace_exit_unwrap(1))
+                .unwrap_or_else(String::new);
+
+            (String::from("current"), current)
+        }
+    };
+
+    let mut out = rt.stdout();
+    let _ = writeln!(out, "--- {} revision {}", path, from)
+        .and_then(|_| writeln!(out, "+++ {} {}", path, new_name))
+        .to_exit_code()
+        .unwrap_or_exit();
+
+    for line in diff::lines(&old_text, &new_text) {
+        let _ = match line {
+            diff::Result::Left(l)    => writeln!(out, "-{}", l),
+            diff::Result::Both(l, _) => writeln!(out, " {}", l),
+            diff::Result::Right(r)   => writeln!(out, "+{}", r),
+        }
+        .to_exit_code()
+        .unwrap_or_exit();
+    }
+}
+
+fn parse_revision(s: &str) -> u64 {
+    s.parse::<u64>()
+        .unwrap_or_else(|_| warn_exit(&format!("Not a revision number: {}", s), 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::history;
+    use super::diff_target;
+
+    make_mock_app! {
+        app "imag-store";
+        modulename mock;
+        version env!("CARGO_PKG_VERSION");
+        with help "imag-store mocking app";
+    }
+    use self::mock::generate_test_runtime;
+
+    #[test]
+    fn test_history_of_unknown_entry() {
+        let rt = generate_test_runtime(vec!["history", "test_history_of_unknown_entry"]).unwrap();
+
+        // Listing the (empty) history must not create the entry
+        history(&rt);
+        assert!(rt.store().get(PathBuf::from("test_history_of_unknown_entry")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_diff_target() {
+        assert_eq!(diff_target(Some(3), 1, None), Ok(Some(2)));
+        assert_eq!(diff_target(Some(3), 3, None), Ok(None));
+        assert_eq!(diff_target(Some(3), 1, Some("current")), Ok(None));
+        assert_eq!(diff_target(Some(3), 1, Some("3")), Ok(Some(3)));
+        assert!(diff_target(Some(3), 1, Some("4")).is_err());
+        assert!(diff_target(None, 0, Some("0")).is_err());
+        assert!(diff_target(Some(3), 1, Some("foo")).is_err());
+        assert_eq!(diff_target(Some(3), ::std::u64::MAX, None), Ok(None));
+    }
+
+}
@@ -34,13 +34,19 @@
 
 extern crate clap;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 extern crate toml;
+extern crate chrono;
+extern crate diff;
+extern crate filters;
 #[cfg(test)] extern crate toml_query;
 #[macro_use] extern crate error_chain;
 
 #[macro_use] extern crate libimagrt;
 extern crate libimagstore;
 extern crate libimagerror;
+extern crate libimagentryfilter;
+extern crate libimagrepair;
 
 #[cfg(test)]
 #[macro_use]
@@ -55,7 +61,9 @@ use libimagerror::trace::MapErrTrace;
 mod create;
 mod delete;
 mod error;
+mod gc;
 mod get;
+mod history;
 mod retrieve;
 mod ui;
 mod update;
@@ -66,7 +74,9 @@ use std::ops::Deref;
 
 use create::create;
 use delete::delete;
+use gc::gc;
 use get::get;
+use history::history;
 use retrieve::retrieve;
 use ui::build_ui;
 use update::update;
@@ -86,7 +96,9 @@ fn main() {
         match command.deref() {
             "create"   => create(&rt),
             "delete"   => delete(&rt),
+            "gc"       => gc(&rt),
             "get"      => get(&rt),
+            "history"  => history(&rt),
             "retrieve" => retrieve(&rt),
             "update"   => update(&rt),
             "verify"   => verify(&rt),
@@ -21,6 +21,7 @@ use std::path::PathBuf;
 use std::io::Write;
 
 use clap::ArgMatches;
+use filters::filter::Filter;
 
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
@@ -28,7 +29,9 @@ use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
 use libimagerror::exit::ExitUnwrap;
+use libimagentryfilter::query::Query;
 use libimagutil::debug_result::*;
+use libimagutil::warn_exit::warn_exit;
 
 pub fn retrieve(rt: &Runtime) {
     rt.cli()
@@ -51,16 +54,24 @@ pub fn retrieve(rt: &Runtime) {
 }
 
 pub fn print_entry(rt: &Runtime, scmd: &ArgMatches, e: FileLockEntry) {
-    if do_print_raw(scmd) {
+    if let Some(fcmd) = scmd.subcommand_matches("filter-header") {
+        debug!("Filtering...");
+        if !filter_query(fcmd).map(|q| q.filter(&e)).unwrap_or(true) {
+            debug!("Entry does not match filter, not printing");
+            return;
+        }
+    }
+
+    if rt.output_format().is_json() {
+        debug!("Printing as JSON...");
+        let id     = e.get_location().clone().without_base().to_str().map_err_trace_exit_unwrap(1);
+        let record = json!({ "id": id, "header": e.get_header(), "content": e.get_content() });
+        let _ = rt.output().emit_record(&record).to_exit_code().unwrap_or_exit();
+    } else if do_print_raw(scmd) {
         debug!("Printing raw content...");
         let _ = writeln!(rt.stdout(), "{}", e.to_str().map_err_trace_exit_unwrap(1))
             .to_exit_code()
             .unwrap_or_exit();
-    } else if do_filter(scmd) {
-        debug!("Filtering...");
-        warn!("Filtering via header specs is currently now supported.");
-        warn!("Will fail now!");
-        unimplemented!()
     } else {
         debug!("Printing structured...");
         if do_print_header(scmd) {
@@ -104,7 +115,40 @@ fn do_print_raw(m: &ArgMatches) -> bool {
     m.is_present("raw")
 }
 
-fn do_filter(m: &ArgMatches) -> bool {
-    m.subcommand_matches("filter-header").is_some()
+/// Build the query from the arguments of the "filter-header" subcommand
+///
+/// `--where header.field=foo` and `--grep header.field=regex` are translated to the query
+/// language and combined with the query argument, if any.
+fn filter_query(m: &ArgMatches) -> Option<Query> {
+    let split = |spec: &str| -> (String, String) {
+        let mut parts = spec.splitn(2, '=');
+        let field     = parts.next().unwrap_or("").trim().to_string();
+        let value     = parts.next().unwrap_or_else(|| {
+            warn_exit(&format!("Expected 'header.field=value', got '{}'", spec), 1)
+        });
+
+        (field, value.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+
+    let mut parts = vec![];
+    if let Some(q) = m.value_of("query") {
+        parts.push(format!("({})", q));
+    }
+    if let Some(spec) = m.value_of("header-field-where") {
+        let (field, value) = split(spec);
+        parts.push(format!("{} == \"{}\"", field, value));
+    }
+    if let Some(spec) = m.value_of("header-field-grep") {
+        let (field, value) = split(spec);
+        parts.push(format!("{} ~ \"{}\"", field, value));
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+
+    let query = parts.join(" and ");
+    debug!("Query: {}", query);
+    Some(Query::parse(&query).map_err_trace_exit_unwrap(1))
 }
 
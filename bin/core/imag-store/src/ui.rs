@@ -91,8 +91,14 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .help("Print Entries as they are in the store"))
 
                    .subcommand(SubCommand::with_name("filter-header")
-                               .about("Retrieve Entries by filtering")
+                               .about("Print the entry only if its header matches the filter")
                                .version("0.1")
+                               .arg(Arg::with_name("query")
+                                    .index(1)
+                                    .takes_value(true)
+                                    .required(false)
+                                    .help("Header query, for example 'todo.status == \"pending\" and tag:work'")
+                                    .value_name("QUERY"))
                                .arg(Arg::with_name("header-field-where")
                                     .long("where")
                                     .short("w")
@@ -134,8 +140,14 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .help("Print Entries as they are in the store"))
 
                    .subcommand(SubCommand::with_name("filter-header")
-                               .about("Retrieve Entries by filtering")
+                               .about("Print the entry only if its header matches the filter")
                                .version("0.1")
+                               .arg(Arg::with_name("query")
+                                    .index(1)
+                                    .takes_value(true)
+                                    .required(false)
+                                    .help("Header query, for example 'todo.status == \"pending\" and tag:work'")
+                                    .value_name("QUERY"))
                                .arg(Arg::with_name("header-field-where")
                                     .long("where")
                                     .short("w")
@@ -186,6 +198,55 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .value_name("PATH"))
                    )
 
+       .subcommand(SubCommand::with_name("gc")
+                   .about("Find inconsistencies in the store (dangling links, orphaned external links and annotations, dangling refs) and repair them")
+                   .version("0.1")
+                   .arg(Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("Only print the inconsistencies and how they would be repaired"))
+                   .arg(Arg::with_name("remove-dangling-refs")
+                        .long("remove-dangling-refs")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("Remove refs to files which do not exist from their entries. Without this, they are only reported"))
+                   )
+
+       .subcommand(SubCommand::with_name("history")
+                   .about("Show the revision history of an entry (requires 'store.history' in the configuration)")
+                   .version("0.1")
+                   .arg(Arg::with_name("id")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("Show the history of the Store Entry with this path")
+                        .value_name("PATH"))
+                   .arg(Arg::with_name("diff")
+                        .long("diff")
+                        .short("d")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Show the changes from this revision to the next one (or the one passed with --to)")
+                        .value_name("REVISION"))
+                   .arg(Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("diff")
+                        .help("Show the changes up to this revision instead of the next one. 'current' for the current version")
+                        .value_name("REVISION"))
+                   .arg(Arg::with_name("restore")
+                        .long("restore")
+                        .takes_value(true)
+                        .required(false)
+                        .conflicts_with("diff")
+                        .help("Restore the entry to this revision")
+                        .value_name("REVISION"))
+                   )
+
        .subcommand(SubCommand::with_name("verify")
                    .about("Verify the store")
                    .version("0.1")
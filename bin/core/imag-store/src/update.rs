@@ -27,6 +27,8 @@ use libimagstore::storeid::StoreId;
 use util::build_toml_header;
 
 pub fn update(rt: &Runtime) {
+    rt.reject_json_output("imag-store update").map_err_trace_exit_unwrap(1);
+
     let scmd  = rt.cli().subcommand_matches("update").unwrap();
     let id    = scmd.value_of("id").unwrap(); // Safe by clap
     let path  = PathBuf::from(id);
@@ -35,17 +37,23 @@ pub fn update(rt: &Runtime) {
 
     let _ = rt.store()
         .retrieve(path)
-        .map(|mut locked_e| {
-            let e = locked_e.deref_mut();
-
-            scmd.value_of("content")
-                .map(|new_content| {
-                    *e.get_content_mut() = String::from(new_content);
-                    debug!("New content set");
-                });
-
-            *e.get_header_mut() = build_toml_header(scmd, e.get_header().clone());
-            debug!("New header set");
-        });
+        .and_then(|mut locked_e| {
+            {
+                let e = locked_e.deref_mut();
+
+                scmd.value_of("content")
+                    .map(|new_content| {
+                        *e.get_content_mut() = String::from(new_content);
+                        debug!("New content set");
+                    });
+
+                *e.get_header_mut() = build_toml_header(scmd, e.get_header().clone());
+                debug!("New header set");
+            }
+
+            // Write explicitely, so errors (like header schema violations) are reported
+            rt.store().update(&mut locked_e)
+        })
+        .map_err_trace_exit_unwrap(1);
 }
 
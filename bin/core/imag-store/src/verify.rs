@@ -21,36 +21,54 @@ use std::ops::Deref;
 
 use libimagrt::runtime::Runtime;
 use libimagutil::warn_exit::warn_exit;
+use libimagerror::trace::MapErrTrace;
 use libimagstore::store::Header;
 use libimagstore::store::StoreObject;
 
 /// Verify the store.
 ///
+/// Besides checking the headers for validity, the headers are checked against the header schemas
+/// of their collections. Each violation of a schema is reported.
+///
 /// This function is not intended to be called by normal programs but only by `imag-store`.
 pub fn verify(rt: &Runtime) {
     use libimagerror::trace::trace_error_dbg;
 
+    rt.reject_json_output("imag-store verify").map_err_trace_exit_unwrap(1);
+
     info!("Header | Content length | Path");
     info!("-------+----------------+-----");
     let result = rt
         .store()
         .walk("")
         .into_iter()
-        .all(|res| match res {
+        .map(|res| match res {
             StoreObject::Collection(_) => true,
             StoreObject::Id(id) => {
                 match rt.store().get(id.clone()) {
                     Ok(Some(fle)) => {
                         let p           = fle.get_location();
                         let content_len = fle.get_content().len();
-                        let header      = if fle.get_header().verify().is_ok() {
-                            "ok"
-                        } else {
+                        let violations  = match rt.store().check_schema(&fle) {
+                            Ok(violations) => violations,
+                            Err(e) => {
+                                trace_error_dbg(&e);
+                                return false;
+                            },
+                        };
+                        let header      = if fle.get_header().verify().is_err() {
                             "broken"
+                        } else if !violations.is_empty() {
+                            "schema"
+                        } else {
+                            "ok"
                         };
 
                         info!("{: >6} | {: >14} | {:?}", header, content_len, p.deref());
-                        true
+                        for violation in violations.iter() {
+                            warn!("{}: {}", p, violation);
+                        }
+                        violations.is_empty()
                     },
 
                     Ok(None) => {
@@ -64,7 +82,8 @@ pub fn verify(rt: &Runtime) {
                     },
                 }
             },
-        });
+        })
+        .fold(true, |all_ok, ok| all_ok && ok);
 
     if result {
         info!("Store seems to be fine");
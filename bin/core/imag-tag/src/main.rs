@@ -34,6 +34,7 @@
 
 extern crate clap;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 
 #[cfg(test)] extern crate toml;
 
@@ -41,6 +42,7 @@ extern crate libimagstore;
 #[macro_use] extern crate libimagrt;
 extern crate libimagentrytag;
 extern crate libimagerror;
+extern crate libimagrpc;
 
 #[cfg(test)]
 #[macro_use]
@@ -57,10 +59,11 @@ extern crate env_logger;
 
 use std::path::PathBuf;
 use std::io::Write;
-use std::io::Read;
 
 use libimagrt::runtime::Runtime;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_without_store;
+use libimagrt::output::Output;
+use libimagrt::output::OutputFormat;
 use libimagentrytag::tagable::Tagable;
 use libimagentrytag::tag::Tag;
 use libimagerror::trace::trace_error;
@@ -68,6 +71,7 @@ use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
 use libimagerror::exit::ExitUnwrap;
 use libimagstore::storeid::StoreId;
+use libimagrpc::client::DaemonClient;
 use libimagutil::warn_exit::warn_exit;
 
 use clap::ArgMatches;
@@ -78,55 +82,53 @@ use ui::build_ui;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-tag",
-                                    &version,
-                                    "Direct interface to the store. Use with great care!",
-                                    build_ui);
+    let mut rt = generate_runtime_setup_without_store("imag-tag",
+                                                      &version,
+                                                      "Direct interface to the store. Use with great care!",
+                                                      build_ui);
+
+    // The store is only opened if imagd does not serve it
+    let mut client = DaemonClient::from_runtime(&rt).map_err_trace_exit_unwrap(1);
+    if client.is_none() {
+        let _ = rt.open_store().map_err_trace_exit_unwrap(1);
+    }
 
     let ids : Vec<PathBuf> = rt
-        .cli()
-        .values_of("id")
-        .map(|vals| {
-            vals.map(PathBuf::from).collect()
-        }).unwrap_or_else(|| {
-            if !rt.cli().is_present("ids-from-stdin") {
-                error!("No ids");
-                ::std::process::exit(1)
-            }
-
-            let stdin = rt.stdin().unwrap_or_else(|| {
-                error!("Cannot get handle to stdin");
-                ::std::process::exit(1)
-            });
-
-            let mut buf = String::new();
-            let _ = stdin.lock().read_to_string(&mut buf).unwrap_or_else(|_| {
-                error!("Failed to read from stdin");
-                ::std::process::exit(1)
-            });
-
-            buf.lines().map(PathBuf::from).collect()
-        });
+        .ids_from_args(rt.cli(), "id")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| {
+            error!("No ids");
+            ::std::process::exit(1)
+        })
+        .into_iter()
+        .map(|id| id.local().clone())
+        .collect();
 
     rt.cli()
         .subcommand_name()
         .map(|name| match name {
             "list" => for id in ids {
-                list(id, &rt)
+                list(id, &rt, client.as_mut())
             },
             "remove" => for id in ids {
                 let id = PathBuf::from(id);
                 let add = None;
                 let rem = get_remove_tags(rt.cli());
                 debug!("id = {:?}, add = {:?}, rem = {:?}", id, add, rem);
-                alter(&rt, id, add, rem);
+                match client {
+                    Some(ref mut client) => alter_remote(client, &rt, id, add, rem),
+                    None                 => alter(&rt, id, add, rem),
+                }
             },
             "add" => for id in ids {
                 let id = PathBuf::from(id);
                 let add = get_add_tags(rt.cli());
                 let rem = None;
                 debug!("id = {:?}, add = {:?}, rem = {:?}", id, add, rem);
-                alter(&rt, id, add, rem);
+                match client {
+                    Some(ref mut client) => alter_remote(client, &rt, id, add, rem),
+                    None                 => alter(&rt, id, add, rem),
+                }
             },
             other => {
                 debug!("Unknown command");
@@ -172,6 +174,7 @@ fn alter(rt: &Runtime, id: PathBuf, add: Option<Vec<Tag>>, rem: Option<Vec<Tag>>
 
             debug!("Entry header now = {:?}", e.get_header());
 
+            let _ = rt.report_touched(e.get_location()).map_err_trace_exit_unwrap(1);
         },
 
         Ok(None) => {
@@ -185,14 +188,38 @@ fn alter(rt: &Runtime, id: PathBuf, add: Option<Vec<Tag>>, rem: Option<Vec<Tag>>
     }
 }
 
-fn list(id: PathBuf, rt: &Runtime) {
+/// Like `alter()`, but let imagd do the work
+fn alter_remote(client: &mut DaemonClient,
+                rt: &Runtime,
+                id: PathBuf,
+                add: Option<Vec<Tag>>,
+                rem: Option<Vec<Tag>>)
+{
     let path = StoreId::new(Some(rt.store().path().clone()), id).map_err_trace_exit_unwrap(1);
     debug!("path = {:?}", path);
 
-    let entry = match rt.store().get(path.clone()).map_err_trace_exit_unwrap(1) {
-        Some(e) => e,
-        None => warn_exit("No entry found.", 1),
-    };
+    let tags = client
+        .tag(&path, &add.unwrap_or_default(), &rem.unwrap_or_default())
+        .map_err_trace_exit_unwrap(1);
+
+    debug!("Tags now = {:?}", tags);
+    let _ = rt.report_touched(&path).map_err_trace_exit_unwrap(1);
+}
+
+fn list(id: PathBuf, rt: &Runtime, client: Option<&mut DaemonClient>) {
+    let path = StoreId::new(Some(rt.store().path().clone()), id).map_err_trace_exit_unwrap(1);
+    debug!("path = {:?}", path);
+
+    let tags = match client {
+        Some(client) => match client.get(&path).map_err_trace_exit_unwrap(1) {
+            Some(e) => e.into_entry().map_err_trace_exit_unwrap(1).get_tags(),
+            None => warn_exit("No entry found.", 1),
+        },
+        None => match rt.store().get(path.clone()).map_err_trace_exit_unwrap(1) {
+            Some(e) => e.get_tags(),
+            None => warn_exit("No entry found.", 1),
+        },
+    }.map_err_trace_exit_unwrap(1);
 
     let scmd = rt.cli().subcommand_matches("list").unwrap(); // safe, we checked in main()
 
@@ -206,32 +233,37 @@ fn list(id: PathBuf, rt: &Runtime) {
         comm_out = true;
     }
 
-    let tags = entry.get_tags().map_err_trace_exit_unwrap(1);
+    let mut output = if json_out && !rt.output_format().is_json() {
+        Output::new(OutputFormat::JsonLines, rt.stdout())
+    } else {
+        rt.output()
+    };
 
-    if json_out {
-        unimplemented!()
-    }
+    let record = json!({
+        "id": path.without_base().to_str().map_err_trace_exit_unwrap(1),
+        "tags": tags,
+    });
 
-    if line_out {
-        for tag in &tags {
-            let _ = writeln!(rt.stdout(), "{}", tag)
-                .to_exit_code()
-                .unwrap_or_exit();
+    let _ = output.emit(&record, |out| {
+        if line_out {
+            for tag in &tags {
+                writeln!(out, "{}", tag)?;
+            }
         }
-    }
 
-    if sepp_out {
-        let sepp = scmd.value_of("sep").unwrap(); // we checked before
-        let _ = writeln!(rt.stdout(), "{}", tags.join(sepp))
-            .to_exit_code()
-            .unwrap_or_exit();
-    }
+        if sepp_out {
+            let sepp = scmd.value_of("sep").unwrap(); // we checked before
+            writeln!(out, "{}", tags.join(sepp))?;
+        }
 
-    if comm_out {
-        let _ = writeln!(rt.stdout(), "{}", tags.join(", "))
-            .to_exit_code()
-            .unwrap_or_exit();
-    }
+        if comm_out {
+            writeln!(out, "{}", tags.join(", "))?;
+        }
+
+        Ok(())
+    }).to_exit_code().unwrap_or_exit();
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 /// Get the tags which should be added from the commandline
@@ -312,6 +344,17 @@ mod tests {
         let _ = ::env_logger::try_init();
     }
 
+    #[test]
+    fn test_ids_are_read_from_stdin_only_on_request() {
+        setup_logging();
+        let rt = generate_test_runtime(vec!["add", "foo"]).unwrap();
+        assert!(!rt.reads_ids_from_stdin());
+        assert!(rt.ids_from_args(rt.cli(), "id").unwrap().is_none());
+
+        let rt = generate_test_runtime(vec!["--ids-from-stdin", "add", "foo"]).unwrap();
+        assert!(rt.reads_ids_from_stdin());
+    }
+
     #[test]
     fn test_tag_add_adds_tag() {
         setup_logging();
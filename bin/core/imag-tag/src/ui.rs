@@ -28,15 +28,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                 .required(false)
                 .multiple(true)
                 .value_name("ID")
-                .help("Entry to use"))
-
-        .arg(Arg::with_name("ids-from-stdin")
-                .long("ids-from-stdin")
-                .short("I")
-                .takes_value(false)
-                .required(false)
-                .multiple(false)
-                .help("Read store ids to tag from stdin"))
+                .help("Entry to use. Read from stdin with --ids-from-stdin"))
 
         .subcommand(SubCommand::with_name("add")
                    .about("Add tags")
@@ -72,7 +64,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .short("j")
                         .takes_value(false)
                         .required(false)
-                        .help("List as JSON"))
+                        .help("List as JSON, same as the global --json flag"))
                    .arg(Arg::with_name("linewise")
                         .long("linewise")
                         .short("l")
@@ -100,7 +92,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                                 "commasep",
                                 "sep",
                           ])
-                          .required(true))
+                          .required(false))
                    )
 
 }
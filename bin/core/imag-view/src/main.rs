@@ -34,11 +34,14 @@
 
 extern crate clap;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
+extern crate filters;
 extern crate handlebars;
 extern crate tempfile;
 extern crate toml;
 extern crate toml_query;
 
+extern crate libimagentryfilter;
 extern crate libimagentryview;
 extern crate libimagerror;
 #[macro_use] extern crate libimagrt;
@@ -48,26 +51,24 @@ extern crate libimagutil;
 use std::str::FromStr;
 use std::collections::BTreeMap;
 use std::io::Write;
-use std::io::Read;
-use std::path::PathBuf;
 use std::process::Command;
 use std::process::exit;
 
 use handlebars::Handlebars;
+use filters::filter::Filter;
 use toml_query::read::TomlValueReadTypeExt;
 
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
 use libimagerror::str::ErrFromStr;
 use libimagerror::trace::MapErrTrace;
-use libimagerror::iter::TraceIterator;
 use libimagerror::io::ToExitCode;
 use libimagerror::exit::ExitUnwrap;
+use libimagentryfilter::query::Query;
 use libimagentryview::builtin::stdout::StdoutViewer;
 use libimagentryview::builtin::md::MarkdownViewer;
 use libimagentryview::viewer::Viewer;
 use libimagentryview::error::ViewError as VE;
-use libimagstore::storeid::IntoStoreId;
 use libimagstore::error::StoreError;
 use libimagstore::iter::get::StoreIdGetIteratorExtension;
 use libimagstore::store::FileLockEntry;
@@ -86,8 +87,14 @@ fn main() {
     let entry_ids    = entry_ids(&rt);
     let view_header  = rt.cli().is_present("view-header");
     let hide_content = rt.cli().is_present("not-view-content");
+    let query        = rt
+        .cli()
+        .value_of("filter")
+        .map(|q| Query::parse(q).map_err_trace_exit_unwrap(1));
 
     if rt.cli().is_present("in") {
+        rt.reject_json_output("imag-view --in").map_err_trace_exit_unwrap(1);
+
         let files = entry_ids
             .into_iter()
             .into_get_iter(rt.store())
@@ -97,6 +104,7 @@ fn main() {
                      .map_err(StoreError::from)
                      .map_err_trace_exit_unwrap(1)
             })
+            .filter(|entry| query.as_ref().map(|q| q.filter(entry)).unwrap_or(true))
             .map(|entry| create_tempfile_for(&entry, view_header, hide_content))
             .collect::<Vec<_>>();
 
@@ -182,7 +190,28 @@ fn main() {
                      .ok_or_else(|| String::from("Entry not found"))
                      .map_err(StoreError::from)
                      .map_err_trace_exit_unwrap(1)
-            });
+            })
+            .filter(|entry| query.as_ref().map(|q| q.filter(entry)).unwrap_or(true));
+
+        if rt.output_format().is_json() {
+            let mut output = rt.output();
+
+            for entry in iter {
+                let id         = entry.get_location().clone().without_base();
+                let mut record = json!({ "id": id.to_str().map_err_trace_exit_unwrap(1) });
+                if view_header {
+                    record["header"] = json!(entry.get_header());
+                }
+                if !hide_content {
+                    record["content"] = json!(entry.get_content());
+                }
+
+                let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+            }
+
+            let _ = output.finish().to_exit_code().unwrap_or_exit();
+            return;
+        }
 
         let out         = rt.stdout();
         let mut outlock = out.lock();
@@ -250,35 +279,12 @@ fn main() {
 }
 
 fn entry_ids(rt: &Runtime) -> Vec<StoreId> {
-    match rt.cli().values_of("id") {
-        Some(pathes) => pathes
-            .map(PathBuf::from)
-            .map(PathBuf::into_storeid)
-            .trace_unwrap_exit(1)
-            .collect(),
-
-        None => if rt.cli().is_present("entries-from-stdin") {
-            let stdin = rt.stdin().unwrap_or_else(|| {
-                error!("Cannot get handle to stdin");
-                ::std::process::exit(1)
-            });
-
-            let mut buf = String::new();
-            let _ = stdin.lock().read_to_string(&mut buf).unwrap_or_else(|_| {
-                error!("Failed to read from stdin");
-                ::std::process::exit(1)
-            });
-
-            buf.lines()
-                .map(PathBuf::from)
-                .map(PathBuf::into_storeid)
-                .trace_unwrap_exit(1)
-                .collect()
-        } else {
-            error!("Something weird happened. I was not able to find the path of the entries to edit");
+    rt.ids_from_args(rt.cli(), "id")
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| {
+            error!("No ids passed, neither on the commandline nor via stdin");
             ::std::process::exit(1)
-        }
-    }
+        })
 }
 
 fn create_tempfile_for<'a>(entry: &FileLockEntry<'a>, view_header: bool, hide_content: bool)
@@ -17,28 +17,26 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-use clap::{Arg, ArgGroup, App};
+use clap::{Arg, App};
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
-        .arg(Arg::with_name("entries-from-stdin")
-             .long("ids-from-stdin")
-             .short("I")
-             .required(false)
-             .multiple(true)
-             .help("The entry/entries are piped in via stdin"))
-
         .arg(Arg::with_name("id")
              .index(1)
              .takes_value(true)
              .required(false)
              .multiple(true)
-             .help("View these entries at this store path")
+             .help("View these entries at this store path. Read from stdin with --ids-from-stdin")
              .value_name("IDs"))
 
-        .group(ArgGroup::with_name("input-method")
-               .args(&["id", "entries-from-stdin"])
-               .required(true))
+        .arg(Arg::with_name("filter")
+             .long("filter")
+             .short("f")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .value_name("QUERY")
+             .help("Only view entries matching this header query, for example 'todo.status == \"pending\" and tag:work'"))
 
         .arg(Arg::with_name("autowrap")
             .long("autowrap")
@@ -409,5 +409,21 @@ fn forward_commandline_arguments(m: &ArgMatches, scmd: &mut Vec<String>) {
 
     push(None , Runtime::arg_logdest_name()                         , m , scmd);
 
+    if m.is_present(Runtime::arg_json_output_name()) {
+        scmd.insert(0, String::from("--json"));
+    }
+
+    if m.is_present(Runtime::arg_json_array_output_name()) {
+        scmd.insert(0, String::from("--json-array"));
+    }
+
+    if m.is_present(Runtime::arg_ids_from_stdin_name()) {
+        scmd.insert(0, String::from("--ids-from-stdin"));
+    }
+
+    if m.is_present(Runtime::arg_print_ids_name()) {
+        scmd.insert(0, String::from("--print-ids"));
+    }
+
 }
 
@@ -0,0 +1,126 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+extern crate clap;
+#[macro_use] extern crate log;
+
+extern crate libimagerror;
+extern crate libimagstore;
+extern crate libimagrpc;
+#[macro_use] extern crate libimagrt;
+
+use std::fs;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use libimagrt::setup::generate_runtime_setup;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::trace::trace_error;
+use libimagrpc::client::socket_path;
+use libimagrpc::error::RpcError;
+use libimagrpc::error::RpcErrorKind;
+use libimagrpc::server::Server;
+
+mod ui;
+use ui::build_ui;
+
+/// A connection is closed if the client does not send a request (or read the response) for this
+/// long, as the connections are served one after another and an idle client blocks all others
+const CONNECTION_TIMEOUT_SECS: u64 = 5;
+
+fn main() {
+    let version = make_imag_version!();
+    let rt = generate_runtime_setup("imagd",
+                                    &version,
+                                    "Serve the store over a unix domain socket",
+                                    build_ui);
+
+    let path = rt
+        .cli()
+        .value_of("socket")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| socket_path(&rt).map_err_trace_exit_unwrap(1));
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            error!("imagd is already running on {}", path.display());
+            ::std::process::exit(1)
+        }
+
+        debug!("Removing stale socket {}", path.display());
+        let _ = fs::remove_file(&path).map_err(RpcError::from).map_err_trace_exit_unwrap(1);
+    }
+
+    let listener = UnixListener::bind(&path).map_err(RpcError::from).map_err_trace_exit_unwrap(1);
+
+    // The socket gives full access to the store, so only the owner may connect
+    let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .map_err(RpcError::from)
+        .map_err_trace_exit_unwrap(1);
+
+    info!("Listening on {}", path.display());
+
+    // The store cannot be shared between threads, so connections are served one after another
+    let server  = Server::new(rt.store());
+    let timeout = Some(Duration::from_secs(CONNECTION_TIMEOUT_SECS));
+    for stream in listener.incoming() {
+        let result = stream
+            .and_then(|stream| {
+                let _ = stream.set_read_timeout(timeout)?;
+                let _ = stream.set_write_timeout(timeout)?;
+                stream.try_clone().map(|reader| (BufReader::new(reader), stream))
+            })
+            .map_err(RpcError::from)
+            .and_then(|(reader, writer)| server.serve(reader, writer));
+
+        match result {
+            Err(RpcError(RpcErrorKind::IO(ref e), _)) if is_timeout(e) => {
+                debug!("Closing idle connection");
+            },
+            Err(e) => trace_error(&e),
+            Ok(()) => {},
+        }
+    }
+}
+
+fn is_timeout(e: &::std::io::Error) -> bool {
+    e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut
+}
+
@@ -36,6 +36,7 @@ extern crate clap;
 #[macro_use] extern crate log;
 extern crate toml;
 extern crate toml_query;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagbookmark;
 #[macro_use] extern crate libimagrt;
@@ -44,11 +45,15 @@ extern crate libimagutil;
 
 use std::io::Write;
 use std::process::exit;
+use std::time::Duration;
 
 use toml_query::read::TomlValueReadTypeExt;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagbookmark::check::HttpLinkChecker;
+use libimagbookmark::check::check_all_links;
+use libimagbookmark::check::check_links;
 use libimagbookmark::collection::BookmarkCollection;
 use libimagbookmark::collection::BookmarkCollectionStore;
 use libimagbookmark::error::BookmarkError as BE;
@@ -74,6 +79,7 @@ fn main() {
             debug!("Call {}", name);
             match name {
                 "add"        => add(&rt),
+                "check"      => check(&rt),
                 "collection" => collection(&rt),
                 "list"       => list(&rt),
                 "remove"     => remove(&rt),
@@ -106,6 +112,55 @@ fn add(rt: &Runtime) {
     info!("Ready");
 }
 
+fn check(rt: &Runtime) {
+    let scmd    = rt.cli().subcommand_matches("check").unwrap();
+    let timeout = scmd
+        .value_of("timeout")
+        .map(|t| t.parse::<u64>().unwrap()) // safe by clap validator and default value
+        .unwrap();
+    let checker = HttpLinkChecker::new(Duration::from_secs(timeout));
+
+    let report = if scmd.is_present("all") {
+        check_all_links(rt.store(), &checker)
+    } else {
+        let coll = get_collection_name(rt, "check", "collection");
+
+        let collection = BookmarkCollectionStore::get(rt.store(), &coll)
+            .map_err_trace_exit_unwrap(1)
+            .ok_or(BE::from(format!("No BookmarkcollectionStore '{}' found", coll)))
+            .map_err_trace_exit_unwrap(1);
+
+        let ids = collection
+            .link_entries()
+            .map_err_trace_exit_unwrap(1)
+            .into_iter()
+            .map(|link| link.get_store_id().clone())
+            .collect::<Vec<_>>();
+
+        check_links(rt.store(), &checker, ids)
+    }.map_err_trace_exit_unwrap(1);
+
+    for result in report.checked().iter() {
+        if result.status().is_dead() {
+            writeln!(rt.stdout(), "{}: {}", result.url(), result.status())
+                .to_exit_code()
+                .unwrap_or_exit();
+        } else if let Some(to) = result.status().redirect() {
+            info!("{} moved to {}", result.url(), to);
+        }
+    }
+
+    for &(ref id, ref e) in report.failed().iter() {
+        error!("Could not check {}: {}", id, e);
+    }
+
+    info!("Checked {} link(s)", report.checked().len());
+
+    if !report.dead().is_empty() || !report.failed().is_empty() {
+        exit(1)
+    }
+}
+
 fn collection(rt: &Runtime) {
     let scmd = rt.cli().subcommand_matches("collection").unwrap();
 
@@ -139,13 +194,18 @@ fn list(rt: &Runtime) {
         .map_err_trace_exit_unwrap(1);
 
     let links   = collection.links(rt.store()).map_err_trace_exit_unwrap(1);
+    let mut output = rt.output();
     debug!("Listing...");
     for (i, link) in links.enumerate() {
         match link {
-            Ok(link) => writeln!(rt.stdout(), "{: >3}: {}", i, link).to_exit_code().unwrap_or_exit(),
+            Ok(link) => output
+                .emit(&json!({ "url": link.as_str() }), |out| writeln!(out, "{: >3}: {}", i, link))
+                .to_exit_code()
+                .unwrap_or_exit(),
             Err(e)   => trace_error(&e)
         }
     };
+    output.finish().to_exit_code().unwrap_or_exit();
     debug!("... ready with listing");
 
     info!("Ready");
@@ -101,6 +101,37 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .help("Filter links to contain these tags. When multiple tags are specified, all of them must be set for the link to match."))
                    )
 
+        .subcommand(SubCommand::with_name("check")
+                   .about("Check bookmarks for dead links")
+                   .version("0.1")
+                   .arg(Arg::with_name("collection")
+                        .long("collection")
+                        .short("c")
+                        .takes_value(true)
+                        .required(false)
+                        .multiple(false)
+                        .value_name("COLLECTION")
+                        .help("Check links from this collection, if not specified default from config will be used"))
+                   .arg(Arg::with_name("all")
+                        .long("all")
+                        .short("a")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .conflicts_with("collection")
+                        .help("Check all external links in the store, not only the ones of a collection"))
+                   .arg(Arg::with_name("timeout")
+                        .long("timeout")
+                        .short("t")
+                        .takes_value(true)
+                        .required(false)
+                        .multiple(false)
+                        .value_name("SECONDS")
+                        .default_value("10")
+                        .validator(is_integer)
+                        .help("Give up on a link after this many seconds"))
+                   )
+
         .subcommand(SubCommand::with_name("collection")
                    .about("Collection commands")
                    .version("0.1")
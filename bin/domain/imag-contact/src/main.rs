@@ -61,6 +61,8 @@ use walkdir::WalkDir;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::Output;
+use libimagrt::output::OutputFormat;
 use libimagerror::str::ErrFromStr;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
@@ -87,6 +89,8 @@ fn main() {
                                     "Contact management tool",
                                     build_ui);
 
+    let _ = rt.store().register_contact_schema().map_err_trace_exit_unwrap(1);
+
 
     rt.cli()
         .subcommand_name()
@@ -128,16 +132,19 @@ fn list(rt: &Runtime) {
         })
         .enumerate();
 
-    if scmd.is_present("json") {
-        let v : Vec<DeserVcard> = iterator.map(|tpl| tpl.1).collect();
+    // The --json flag of the subcommand prints an array, as it did before the global flag
+    let format = if scmd.is_present("json") && !rt.output_format().is_json() {
+        OutputFormat::JsonArray
+    } else {
+        rt.output_format()
+    };
 
-        match ::serde_json::to_string(&v) {
-            Ok(s) => writeln!(rt.stdout(), "{}", s).to_exit_code().unwrap_or_exit(),
-            Err(e) => {
-                error!("Error generating JSON: {:?}", e);
-                ::std::process::exit(1)
-            }
+    if format.is_json() {
+        let mut output = Output::new(format, rt.stdout());
+        for (_, deservcard) in iterator {
+            output.emit_record(&deservcard).to_exit_code().unwrap_or_exit();
         }
+        output.finish().to_exit_code().unwrap_or_exit();
     } else {
         iterator
             .map(|(i, deservcard)| {
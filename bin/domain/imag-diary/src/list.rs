@@ -48,10 +48,18 @@ pub fn list(rt: &Runtime) {
         [id.year() as u32, id.month(), id.day(), id.hour(), id.minute(), id.second()]
     });
 
+    let mut output = rt.output();
     for id in ids.into_iter().map(|id| id.into_storeid().map_err_trace_exit_unwrap(1)) {
-        writeln!(rt.stdout(), "{}", id)
+        let record = json!({
+            "id": id.to_str().map_err_trace_exit_unwrap(1),
+            "diary": diaryname,
+        });
+
+        output.emit(&record, |out| writeln!(out, "{}", id))
             .to_exit_code()
             .unwrap_or_exit();
     }
+
+    output.finish().to_exit_code().unwrap_or_exit();
 }
 
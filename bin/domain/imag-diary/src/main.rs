@@ -38,6 +38,7 @@ extern crate chrono;
 extern crate toml;
 extern crate toml_query;
 extern crate itertools;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagdiary;
 extern crate libimagentryedit;
@@ -54,6 +55,7 @@ use std::io::Write;
 use libimagrt::setup::generate_runtime_setup;
 use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
+use libimagdiary::diary::Diary;
 
 use itertools::Itertools;
 
@@ -76,6 +78,8 @@ fn main() {
                                     "Personal Diary/Diaries",
                                     ui::build_ui);
 
+    let _ = rt.store().register_diary_schema().map_err_trace_exit_unwrap(1);
+
     rt.cli()
         .subcommand_name()
         .map(|name| {
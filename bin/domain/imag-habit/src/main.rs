@@ -39,6 +39,7 @@ extern crate toml_query;
 extern crate kairos;
 extern crate chrono;
 extern crate prettytable;
+#[macro_use] extern crate serde_json;
 
 extern crate libimaghabit;
 extern crate libimagstore;
@@ -76,6 +77,7 @@ fn main() {
                                     "Habit tracking tool",
                                     ui::build_ui);
 
+    let _ = rt.store().register_habit_schemas().map_err_trace_exit_unwrap(1);
 
     let _ = rt
         .cli()
@@ -284,6 +286,7 @@ fn today(rt: &Runtime, future: bool) {
         .count() != 0;
 
     debug!("Any today relevant = {}", any_today_relevant);
+    let mut output = rt.output();
     if !any_today_relevant {
         let n = rt
             .cli()
@@ -311,6 +314,7 @@ fn today(rt: &Runtime, future: bool) {
 
                 if show_done || !is_done {
                     info!(" * {date}: {name}", date = date, name = name);
+                    let _ = output.emit_record(&habit_record(element)).to_exit_code().unwrap_or_exit();
                 }
             }
         }
@@ -359,12 +363,15 @@ fn today(rt: &Runtime, future: bool) {
             v.append(&mut list);
             table.add_row(v.iter().map(|s| Cell::new(s)).collect());
             empty = false;
+            let _ = output.emit_record(&habit_record(&e)).to_exit_code().unwrap_or_exit();
         }
 
-        if !empty {
+        if !empty && !output.format().is_json() {
             let _ = table.print(&mut rt.stdout()).to_exit_code().unwrap_or_exit();
         }
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn list(rt: &Runtime) {
@@ -388,8 +395,9 @@ fn list(rt: &Runtime) {
         .map(|s| Cell::new(s))
         .collect::<Vec<Cell>>();
 
-    let mut empty = true;
-    let mut table = Table::new();
+    let mut empty  = true;
+    let mut table  = Table::new();
+    let mut output = rt.output();
     table.set_titles(Row::new(header));
 
     let _ = rt
@@ -414,11 +422,14 @@ fn list(rt: &Runtime) {
             v.append(&mut list);
             table.add_row(v.iter().map(|s| Cell::new(s)).collect());
             empty = false;
+            let _ = output.emit_record(&habit_record(&e)).to_exit_code().unwrap_or_exit();
         });
 
-    if !empty {
+    if !empty && !output.format().is_json() {
         let _ = table.print(&mut rt.stdout()).to_exit_code().unwrap_or_exit();
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn show(rt: &Runtime) {
@@ -444,7 +455,8 @@ fn show(rt: &Runtime) {
         .map(|s| Cell::new(s))
         .collect::<Vec<Cell>>();
 
-    let mut table = Table::new();
+    let mut table  = Table::new();
+    let mut output = rt.output();
     table.set_titles(Row::new(header));
 
     let _ = rt
@@ -460,17 +472,20 @@ fn show(rt: &Runtime) {
             let recur    = habit.habit_recur_spec().map_err_trace_exit_unwrap(1);
             let comm     = habit.habit_comment().map_err_trace_exit_unwrap(1);
 
-            let _ = writeln!(rt.stdout(),
-                     "{i} - {name}\nBase      : {b},\nRecurrence: {r}\nComment   : {c}\n",
-                     i    = i,
-                     name = name,
-                     b    = basedate,
-                     r    = recur,
-                     c    = comm)
-                .to_exit_code()
-                .unwrap_or_exit();
-
-            let mut empty = true;
+            if !output.format().is_json() {
+                let _ = writeln!(rt.stdout(),
+                         "{i} - {name}\nBase      : {b},\nRecurrence: {r}\nComment   : {c}\n",
+                         i    = i,
+                         name = name,
+                         b    = basedate,
+                         r    = recur,
+                         c    = comm)
+                    .to_exit_code()
+                    .unwrap_or_exit();
+            }
+
+            let mut empty     = true;
+            let mut instances = vec![];
             let _ = habit
                 .linked_instances()
                 .map_err_trace_exit_unwrap(1)
@@ -481,17 +496,24 @@ fn show(rt: &Runtime) {
                 .enumerate()
                 .for_each(|(i, e)| {
                     let mut v = vec![format!("{}", i)];
-                    let mut instances = instance_lister_fn(&e);
-                    v.append(&mut instances);
+                    let mut instance = instance_lister_fn(&e);
+                    instances.push(json!({ "date": instance[0], "comment": instance[1] }));
+                    v.append(&mut instance);
                     table.add_row(v.iter().map(|s| Cell::new(s)).collect());
                     empty = false;
                 });
 
-            if !empty {
+            let mut record = habit_record(&habit);
+            record["instances"] = json!(instances);
+            let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+
+            if !empty && !output.format().is_json() {
                 let _ = table.print(&mut rt.stdout()).to_exit_code().unwrap_or_exit();
             }
         })
         .collect::<Vec<_>>();
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn done(rt: &Runtime) {
@@ -556,6 +578,24 @@ fn get_from_store<'a>(store: &'a Store, id: StoreId) -> Option<FileLockEntry<'a>
     }
 }
 
+/// The record of a habit in the JSON output
+fn habit_record(h: &FileLockEntry) -> serde_json::Value {
+    let next_due = h.next_instance_date().map_err_trace_exit_unwrap(1);
+    let done     = match next_due {
+        Some(ref date) => h.instance_exists_for_date(date).map_err_trace_exit_unwrap(1),
+        None           => false,
+    };
+
+    json!({
+        "name": h.habit_name().map_err_trace_exit_unwrap(1),
+        "basedate": h.habit_basedate().map_err_trace_exit_unwrap(1),
+        "recur": h.habit_recur_spec().map_err_trace_exit_unwrap(1),
+        "comment": h.habit_comment().map_err_trace_exit_unwrap(1),
+        "next_due": next_due.map(date_to_string_helper),
+        "done": done,
+    })
+}
+
 fn date_to_string_helper(d: chrono::NaiveDate) -> String {
     libimagutil::date::date_to_string(&d)
 }
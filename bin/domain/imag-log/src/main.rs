@@ -35,6 +35,7 @@
 extern crate clap;
 #[macro_use] extern crate is_match;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 extern crate toml;
 extern crate toml_query;
 extern crate itertools;
@@ -84,6 +85,8 @@ fn main() {
             },
         }
     } else {
+        rt.reject_json_output("imag-log").map_err_trace_exit_unwrap(1);
+
         let text       = get_log_text(&rt);
         let diary_name = rt.cli()
             .value_of("diaryname")
@@ -128,6 +131,8 @@ fn show(rt: &Runtime) {
         }
     };
 
+    let mut output = rt.output();
+
     for iter in iters {
         let _ = iter
             .into_get_iter(rt.store())
@@ -144,20 +149,39 @@ fn show(rt: &Runtime) {
             .sorted_by_key(|&(ref id, _)| id.clone())
             .into_iter()
             .map(|(id, entry)| {
-                writeln!(rt.stdout(),
-                        "{dname: >10} - {y: >4}-{m:0>2}-{d:0>2}T{H:0>2}:{M:0>2} - {text}",
-                         dname = id.diary_name(),
-                         y = id.year(),
-                         m = id.month(),
-                         d = id.day(),
-                         H = id.hour(),
-                         M = id.minute(),
-                         text = entry.get_content())
+                let datetime = format!("{y:0>4}-{m:0>2}-{d:0>2}T{H:0>2}:{M:0>2}:{S:0>2}",
+                                       y = id.year(),
+                                       m = id.month(),
+                                       d = id.day(),
+                                       H = id.hour(),
+                                       M = id.minute(),
+                                       S = id.second());
+                let record   = json!({
+                    "id": format!("{}", entry.get_location().local().display()),
+                    "diary": id.diary_name(),
+                    "datetime": datetime,
+                    "text": entry.get_content(),
+                });
+
+                output
+                    .emit(&record, |out| {
+                        writeln!(out,
+                                "{dname: >10} - {y: >4}-{m:0>2}-{d:0>2}T{H:0>2}:{M:0>2} - {text}",
+                                 dname = id.diary_name(),
+                                 y = id.year(),
+                                 m = id.month(),
+                                 d = id.day(),
+                                 H = id.hour(),
+                                 M = id.minute(),
+                                 text = entry.get_content())
+                    })
                     .to_exit_code()
             })
             .collect::<Result<Vec<()>, _>>()
             .unwrap_or_exit();
     }
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn get_diary_name(rt: &Runtime) -> String {
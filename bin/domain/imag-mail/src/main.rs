@@ -33,21 +33,30 @@
 )]
 
 extern crate clap;
+#[macro_use] extern crate serde_json;
 #[macro_use] extern crate log;
 
 #[macro_use] extern crate libimagrt;
+extern crate libimagstore;
 extern crate libimagmail;
 extern crate libimagerror;
 extern crate libimagutil;
 
 use std::io::Write;
+use std::path::PathBuf;
 
-use libimagerror::trace::{MapErrTrace, trace_error};
+use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
 use libimagmail::mail::Mail;
+use libimagmail::maildir::sync_maildir;
+use libimagmail::thread::Thread;
+use libimagmail::thread::link_threads;
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::output::Output;
+use libimagstore::storeid::IntoStoreId;
+use libimagstore::storeid::StoreId;
 use libimagutil::info_result::*;
 
 mod ui;
@@ -66,10 +75,13 @@ fn main() {
         .map(|name| {
             debug!("Call {}", name);
             match name {
-                "import-mail" => import_mail(&rt),
-                "list"        => list(&rt),
-                "mail-store"  => mail_store(&rt),
-                other         => {
+                "import-mail"  => import_mail(&rt),
+                "link-threads" => link_mail_threads(&rt),
+                "list"         => list(&rt),
+                "mail-store"   => mail_store(&rt),
+                "sync"         => sync(&rt),
+                "thread"       => thread(&rt),
+                other          => {
                     debug!("Unknown command");
                     let _ = rt.handle_unknown_subcommand("imag-mail", other, rt.cli())
                         .map_err_trace_exit_unwrap(1)
@@ -94,65 +106,142 @@ fn list(rt: &Runtime) {
     use libimagmail::error::ResultExt;
 
         // TODO: Implement lister type in libimagmail for this
-    fn list_mail(rt: &Runtime, m: Mail) {
-        let id = match m.get_message_id() {
-            Ok(Some(f)) => f,
-            Ok(None) => "<no id>".to_owned(),
-            Err(e) => {
-                trace_error(&e);
-                "<error>".to_owned()
-            },
-        };
-
-        let from = match m.get_from() {
-            Ok(Some(f)) => f,
-            Ok(None) => "<no from>".to_owned(),
-            Err(e) => {
-                trace_error(&e);
-                "<error>".to_owned()
-            },
-        };
-
-        let to = match m.get_to() {
-            Ok(Some(f)) => f,
-            Ok(None) => "<no to>".to_owned(),
-            Err(e) => {
-                trace_error(&e);
-                "<error>".to_owned()
-            },
-        };
-
-        let subject = match m.get_subject() {
-            Ok(Some(f)) => f,
-            Ok(None) => "<no subject>".to_owned(),
-            Err(e) => {
-                trace_error(&e);
-                "<error>".to_owned()
-            },
-        };
+    fn list_mail(output: &mut Output, id: StoreId, m: Mail) {
+        fn field(res: libimagmail::error::Result<Option<String>>) -> Option<String> {
+            res.map_err_trace().ok().and_then(|f| f)
+        }
+
+        let message_id = field(m.get_message_id());
+        let from       = field(m.get_from());
+        let to         = field(m.get_to());
+        let subject    = field(m.get_subject());
+
+        let record = json!({
+            "id": id.without_base().to_str().map_err_trace_exit_unwrap(1),
+            "message_id": message_id,
+            "from": from,
+            "to": to,
+            "subject": subject,
+        });
 
-        writeln!(rt.stdout(),
-                 "Mail: {id}\n\tFrom: {from}\n\tTo: {to}\n\t{subj}\n",
-                 from = from,
-                 id   = id,
-                 subj = subject,
-                 to   = to
-        ).to_exit_code().unwrap_or_exit()
+        output.emit(&record, |out| {
+            writeln!(out,
+                     "Mail: {id}\n\tFrom: {from}\n\tTo: {to}\n\t{subj}\n",
+                     from = from.as_ref().map(String::as_str).unwrap_or("<no from>"),
+                     id   = message_id.as_ref().map(String::as_str).unwrap_or("<no id>"),
+                     subj = subject.as_ref().map(String::as_str).unwrap_or("<no subject>"),
+                     to   = to.as_ref().map(String::as_str).unwrap_or("<no to>"))
+        }).to_exit_code().unwrap_or_exit()
     }
 
+    let mut output = rt.output();
+
     let _ = rt.store()
         .entries()
         .map_err_trace_exit_unwrap(1)
         .filter(|id| id.is_in_collection(&["mail"]))
         .filter_map(|id| {
             rt.store()
-                .get(id)
+                .get(id.clone())
                 .chain_err(|| MEK::RefHandlingError)
                 .map_err_trace_exit_unwrap(1)
-                .map(|fle| Mail::from_fle(fle).map_err_trace().ok())
+                .map(|fle| Mail::from_fle(fle).map_err_trace().ok().map(|m| (id, m)))
         })
         .filter_map(|e| e)
-        .for_each(|m| list_mail(&rt, m));
+        .for_each(|(id, m)| list_mail(&mut output, id, m));
+
+    output.finish().to_exit_code().unwrap_or_exit();
+}
+
+fn sync(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("sync").unwrap();
+
+    for maildir in scmd.values_of("maildirs").unwrap() { // enforced by clap
+        let report = sync_maildir(rt.store(), maildir).map_err_trace_exit_unwrap(1);
+
+        for id in report.imported() {
+            info!("Imported: {}", id);
+        }
+
+        for &(ref id, ref old, ref new) in report.moved() {
+            info!("Moved: {}: {} -> {}", id, old.display(), new.display());
+        }
+
+        for id in report.flags_changed() {
+            info!("Flags changed: {}", id);
+        }
+
+        for &(ref id, ref path, ref duplicate) in report.duplicates() {
+            warn!("Not imported: {} is a duplicate of {} ({})", duplicate.display(), path.display(), id);
+        }
+
+        for &(ref path, ref reason) in report.failed() {
+            warn!("Could not import {}: {}", path.display(), reason);
+        }
+
+        info!("{}: {} imported, {} moved, {} with changed flags, {} duplicates, {} failed",
+              maildir,
+              report.imported().len(),
+              report.moved().len(),
+              report.flags_changed().len(),
+              report.duplicates().len(),
+              report.failed().len());
+    }
+
+    link_mail_threads(rt);
+}
+
+fn link_mail_threads(rt: &Runtime) {
+    let linked = link_threads(rt.store()).map_err_trace_exit_unwrap(1);
+
+    for &(ref reply, ref parent) in linked.iter() {
+        debug!("Linked {} as reply to {}", reply, parent);
+    }
+
+    info!("Linked {} mail(s) with the mails they reply to", linked.len());
+}
+
+fn thread(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("thread").unwrap();
+    let id   = scmd.value_of("id")
+        .map(PathBuf::from)
+        .unwrap() // enforced by clap
+        .into_storeid()
+        .map_err_trace_exit_unwrap(1);
+
+    fn print_thread(rt: &Runtime, thread: &Thread, depth: usize) {
+        let (subject, from) = match rt.store().get(thread.id().clone()).map_err_trace_exit_unwrap(1) {
+            Some(fle) => {
+                let mail = Mail::from_fle(fle).map_err_trace_exit_unwrap(1);
+                let field = |f: libimagmail::error::Result<Option<String>>, default: &str| {
+                    f.map_err_trace().ok().and_then(|o| o).unwrap_or_else(|| default.to_owned())
+                };
+                (field(mail.get_subject(), "<no subject>"), field(mail.get_from(), "<no from>"))
+            },
+            None => ("<missing>".to_owned(), "<missing>".to_owned()),
+        };
+
+        writeln!(rt.stdout(),
+                 "{indent}{subj} ({from}) [{id}]",
+                 indent = "  ".repeat(depth),
+                 subj   = subject,
+                 from   = from,
+                 id     = thread.id()
+        ).to_exit_code().unwrap_or_exit();
+
+        for reply in thread.replies() {
+            print_thread(rt, reply, depth + 1);
+        }
+    }
+
+    let threads = Thread::of_entry(rt.store(), id).map_err_trace_exit_unwrap(1);
+    if threads.is_empty() {
+        info!("No mails linked with this entry");
+    }
+
+    for thread in threads.iter() {
+        print_thread(rt, thread, 0);
+    }
 }
 
 fn mail_store(rt: &Runtime) {
@@ -33,6 +33,35 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .value_name("PATH"))
                     )
 
+        .subcommand(SubCommand::with_name("sync")
+                    .about("Import new mails from Maildirs and update moved mails and their flags")
+                    .version("0.1")
+                    .arg(Arg::with_name("maildirs")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .multiple(true)
+                         .help("The Maildirs to sync, Maildir++ subfolders are synced as well")
+                         .value_name("MAILDIR"))
+                    )
+
+        .subcommand(SubCommand::with_name("link-threads")
+                    .about("Link all mails with the mails they reply to")
+                    .version("0.1")
+                    )
+
+        .subcommand(SubCommand::with_name("thread")
+                    .about("Print the conversation a mail is part of, or the conversations of the mails an entry is linked with")
+                    .version("0.1")
+                    .arg(Arg::with_name("id")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .multiple(false)
+                         .help("The mail or the entry linked with mails")
+                         .value_name("ID"))
+                    )
+
         .subcommand(SubCommand::with_name("list")
                     .about("List all stored references to mails")
                     .version("0.1")
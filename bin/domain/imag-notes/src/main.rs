@@ -35,6 +35,7 @@
 extern crate clap;
 #[macro_use] extern crate log;
 extern crate itertools;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagnotes;
 #[macro_use] extern crate libimagrt;
@@ -138,6 +139,8 @@ fn edit(rt: &Runtime) {
 fn list(rt: &Runtime) {
     use std::cmp::Ordering;
 
+    let mut output = rt.output();
+
     let _ = rt
         .store()
         .all_notes()
@@ -155,10 +158,15 @@ fn list(rt: &Runtime) {
         })
         .iter()
         .for_each(|note| {
-            let name = note.get_name().map_err_trace_exit_unwrap(1);
-            writeln!(rt.stdout(), "{}", name)
+            let name   = note.get_name().map_err_trace_exit_unwrap(1);
+            let id     = note.get_location().clone().without_base().to_str().map_err_trace_exit_unwrap(1);
+            let record = json!({ "id": id, "name": name });
+
+            output.emit(&record, |out| writeln!(out, "{}", name))
                 .to_exit_code()
                 .unwrap_or_exit()
         });
+
+    output.finish().to_exit_code().unwrap_or_exit();
 }
 
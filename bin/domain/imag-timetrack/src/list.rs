@@ -29,10 +29,14 @@ use clap::ArgMatches;
 use libimagerror::trace::trace_error;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
+use libimagerror::io::ToExitCode;
+use libimagerror::exit::ExitUnwrap;
 use libimagstore::store::FileLockEntry;
+use libimagstore::storeid::StoreId;
 use libimagtimetrack::error::TimeTrackError;
 use libimagtimetrack::timetrackingstore::TimeTrackStore;
 use libimagtimetrack::timetracking::TimeTracking;
+use libimagtimetrack::tag::TimeTrackingTag;
 use libimagtimetrack::error::Result;
 
 use libimagrt::runtime::Runtime;
@@ -117,7 +121,9 @@ pub fn list_impl(rt: &Runtime,
     let mut table = Table::new();
     table.set_titles(Row::new(["Tag", "Start", "End"].into_iter().map(|s| Cell::new(s)).collect()));
 
-    rt.store()
+    let mut output = rt.output();
+
+    let table = rt.store()
         .get_timetrackings()
         .map_err_trace_exit_unwrap(1)
         .trace_unwrap()
@@ -135,6 +141,11 @@ pub fn list_impl(rt: &Runtime,
                 let end   = e.get_end_datetime()?;
                 debug!(" -> end = {:?}", end);
 
+                let _ = output
+                    .emit_record(&timetracking_record(e.get_location(), &tag, start, end))
+                    .to_exit_code()
+                    .unwrap_or_exit();
+
                 let v = match (start, end) {
                     (None, _)          => vec![String::from(tag.as_str()), String::from(""), String::from("")],
                     (Some(s), None)    => {
@@ -162,7 +173,13 @@ pub fn list_impl(rt: &Runtime,
                 Ok(tab)
             })
         })
-        .map_err_trace_exit_unwrap(1)
+        .map_err_trace_exit_unwrap(1);
+
+    if output.format().is_json() {
+        return output.finish().to_exit_code().map(|_| 0).unwrap_or_else(|e| e.code());
+    }
+
+    table
         .print(&mut rt.stdout())
         .map_err(|_| TimeTrackError::from(String::from("Failed printing table")))
         .map(|_| 0)
@@ -170,3 +187,20 @@ pub fn list_impl(rt: &Runtime,
         .unwrap_or(1)
 }
 
+/// The record of a timetracking in the JSON output
+pub fn timetracking_record(id: &StoreId,
+                           tag: &TimeTrackingTag,
+                           start: Option<NaiveDateTime>,
+                           end: Option<NaiveDateTime>)
+    -> ::serde_json::Value
+{
+    let format = |dt: NaiveDateTime| dt.format("%Y-%m-%dT%H:%M:%S").to_string();
+
+    json!({
+        "id": id.clone().without_base().to_str().map_err_trace_exit_unwrap(1),
+        "tag": tag.as_str(),
+        "start": start.map(&format),
+        "end": end.map(&format),
+    })
+}
+
@@ -41,6 +41,7 @@ extern crate filters;
 extern crate itertools;
 extern crate prettytable;
 extern crate kairos;
+#[macro_use] extern crate serde_json;
 
 extern crate libimagerror;
 extern crate libimagstore;
@@ -72,6 +73,7 @@ use year::year;
 
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
+use libimagtimetrack::timetrackingstore::TimeTrackStore;
 
 fn main() {
     let version = make_imag_version!();
@@ -80,6 +82,8 @@ fn main() {
                                     "Time tracking module",
                                     build_ui);
 
+    let _ = rt.store().register_timetrack_schema().map_err_trace_exit_unwrap(1);
+
     let command = rt.cli().subcommand_name();
     let retval  = if let Some(command) = command {
         debug!("Call: {}", command);
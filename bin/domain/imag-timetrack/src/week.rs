@@ -36,6 +36,8 @@ use libimagtimetrack::iter::filter::*;
 
 use libimagrt::runtime::Runtime;
 
+use list::timetracking_record;
+
 pub fn week(rt: &Runtime) -> i32 {
     let cmd = rt.cli().subcommand().1.unwrap(); // checked in main
 
@@ -99,7 +101,9 @@ pub fn week(rt: &Runtime) -> i32 {
         tags_filter.and(start_time_filter).and(end_time_filter)
     };
 
-    rt.store()
+    let mut output = rt.output();
+
+    let result = rt.store()
         .get_timetrackings()
         .map_err_trace_exit_unwrap(1)
         .trace_unwrap()
@@ -116,18 +120,22 @@ pub fn week(rt: &Runtime) -> i32 {
             let end   = e.get_end_datetime()?;
             debug!(" -> end = {:?}", end);
 
-            Ok((tag, start, end))
+            Ok((e.get_location().clone(), tag, start, end))
         })
         .trace_unwrap_exit(1)
-        .map(|(tag, start, end)| {
-            match (start, end) {
-                (None, _)          => writeln!(rt.stdout(), "{} has no start time.", tag),
-                (Some(s), None)    => writeln!(rt.stdout(), "{} | {} - ...", tag, s),
-                (Some(s), Some(e)) => writeln!(rt.stdout(), "{} | {} - {}", tag, s, e),
-            }
+        .map(|(id, tag, start, end)| {
+            let record = timetracking_record(&id, &tag, start, end);
+            output.emit(&record, |out| match (start, end) {
+                (None, _)          => writeln!(out, "{} has no start time.", tag),
+                (Some(s), None)    => writeln!(out, "{} | {} - ...", tag, s),
+                (Some(s), Some(e)) => writeln!(out, "{} | {} - {}", tag, s, e),
+            })
             .to_exit_code()
         })
-        .collect::<Result<Vec<()>, _>>()
+        .collect::<Result<Vec<()>, _>>();
+
+    result
+        .and_then(|_| output.finish().to_exit_code())
         .map(|_| 0)
         .unwrap_or_else(|e| e.code())
 }
@@ -34,21 +34,37 @@
 
 extern crate clap;
 #[macro_use] extern crate log;
-extern crate toml;
-extern crate toml_query;
-#[macro_use] extern crate is_match;
+extern crate uuid;
+extern crate chrono;
+#[macro_use] extern crate serde_json;
 
 #[macro_use] extern crate libimagrt;
+extern crate libimagstore;
 extern crate libimagerror;
 extern crate libimagtodo;
+extern crate libimagutil;
 
 use std::process::{Command, Stdio};
 use std::io::stdin;
+use std::fs::File;
 use std::io::Write;
 
+use chrono::Local;
+use uuid::Uuid;
+
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagstore::store::FileLockEntry;
 use libimagtodo::taskstore::TaskStore;
+use libimagtodo::task::Task;
+use libimagtodo::task::Status;
+use libimagtodo::task::Priority;
+use libimagtodo::sync::sync_taskwarrior_export;
+use libimagtodo::error::TodoError as TE;
+use libimagtodo::error::TodoErrorKind as TEK;
+use libimagtodo::error::ResultExt;
+use libimagutil::date::date_from_string;
+use libimagutil::date::date_to_string;
 use libimagerror::trace::{MapErrTrace, trace_error};
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
@@ -60,12 +76,17 @@ fn main() {
     let version = make_imag_version!();
     let rt = generate_runtime_setup("imag-todo",
                                     &version,
-                                    "Interface with taskwarrior",
+                                    "Manage todos, also interfaces with taskwarrior",
                                     build_ui);
 
     match rt.cli().subcommand_name() {
         Some("tw-hook") => tw_hook(&rt),
-        Some("list") => list(&rt),
+        Some("list")    => list(&rt),
+        Some("create")  => create(&rt),
+        Some("edit")    => edit(&rt),
+        Some("done")    => done(&rt),
+        Some("delete")  => delete(&rt),
+        Some("sync")    => sync(&rt),
         Some(other) => {
             debug!("Unknown command");
             let _ = rt.handle_unknown_subcommand("imag-todo", other, rt.cli())
@@ -109,69 +130,315 @@ fn tw_hook(rt: &Runtime) {
 }
 
 fn list(rt: &Runtime) {
-    use toml_query::read::TomlValueReadTypeExt;
-
     let subcmd  = rt.cli().subcommand_matches("list").unwrap();
     let verbose = subcmd.is_present("verbose");
-
-    // Helper for toml_query::read::TomlValueReadExt::read() return value, which does only
-    // return Result<T> instead of Result<Option<T>>, which is a real inconvenience.
-    //
-    let no_identifier = |e: &::toml_query::error::Error| -> bool {
-        is_match!(e.kind(), &::toml_query::error::ErrorKind::IdentifierNotFoundInDocument(_))
+    let status  = subcmd.value_of("status").and_then(Status::from_str); // None means "all"
+    let due_before = if subcmd.is_present("overdue") {
+        Some(Local::now().naive_local().date().pred())
+    } else {
+        subcmd.value_of("due-before").map(|d| date_from_string(d).unwrap()) // safe by clap validator
     };
 
-    let res = rt.store().all_tasks() // get all tasks
-        .map(|iter| { // and if this succeeded
-            // filter out the ones were we can read the uuid
-            let uuids : Vec<_> = iter.filter_map(|storeid| {
-                match rt.store().retrieve(storeid) {
-                    Ok(fle) => {
-                        match fle.get_header().read_string("todo.uuid") {
-                            Ok(Some(ref u)) => Some(u.clone()),
-                            Ok(None) => {
-                                error!("Header missing field in {}", fle.get_location());
-                                None
-                            },
-                            Err(e) => {
-                                if !no_identifier(&e) {
-                                    trace_error(&e);
-                                }
-                                None
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        trace_error(&e);
-                        None
-                    },
-                }
+    let mut tasks = vec![];
+    for id in rt.store().all_tasks().map_err_trace_exit_unwrap(1) {
+        let fle = match rt.store().get(id) {
+            Ok(Some(fle)) => fle,
+            Ok(None)      => continue,
+            Err(e)        => {
+                trace_error(&e);
+                continue
+            },
+        };
+
+        let uuid = match fle.get_uuid() {
+            Ok(uuid) => uuid,
+            Err(e)   => {
+                error!("Cannot read uuid of {}", fle.get_location());
+                trace_error(&e);
+                continue
+            },
+        };
+
+        let task_status = fle.get_status().map_err_trace_exit_unwrap(1);
+        let due         = fle.get_due().map_err_trace_exit_unwrap(1);
+
+        if status.map(|s| s != task_status).unwrap_or(false) {
+            continue;
+        }
+
+        if let Some(ref before) = due_before {
+            if due.map(|d| d > *before).unwrap_or(true) {
+                continue;
+            }
+        }
+
+        tasks.push((due,
+                    fle.get_priority().map_err_trace_exit_unwrap(1),
+                    uuid,
+                    task_status,
+                    fle.get_description().map_err_trace_exit_unwrap(1),
+                    fle.get_tags().map_err_trace_exit_unwrap(1)));
+    }
+
+    // Tasks with a due date first, then by priority
+    tasks.sort_by_key(|&(due, priority, uuid, ..)| (due.is_none(), due, priority.is_none(), priority, uuid));
+
+    if rt.output_format().is_json() {
+        let mut output = rt.output();
+        for (due, priority, uuid, status, description, tags) in tasks {
+            let record = json!({
+                "uuid": format!("{}", uuid),
+                "status": status.as_str(),
+                "priority": priority.map(|p| p.as_str()),
+                "due": due.map(|d| date_to_string(&d)),
+                "description": description,
+                "tags": tags,
+            });
+
+            let _ = output.emit_record(&record).to_exit_code().unwrap_or_exit();
+        }
+
+        let _ = output.finish().to_exit_code().unwrap_or_exit();
+        return;
+    }
+
+    if verbose {
+        let uuids = tasks.iter().map(|t| format!("{}", t.2)).collect::<Vec<_>>();
+        let output = Command::new("task")
+            .stdin(Stdio::null())
+            .args(&uuids)
+            .spawn()
+            .unwrap_or_else(|e| {
+                error!("Failed to execute `task` on the commandline: {:?}. I'm dying now.", e);
+                ::std::process::exit(1)
             })
-            .collect();
-
-            // compose a `task` call with them, ...
-            let outstring = if verbose { // ... if verbose
-                let output = Command::new("task")
-                    .stdin(Stdio::null())
-                    .args(&uuids)
-                    .spawn()
-                    .unwrap_or_else(|e| {
-                        error!("Failed to execute `task` on the commandline: {:?}. I'm dying now.", e);
-                        ::std::process::exit(1)
-                    })
-                    .wait_with_output()
-                    .unwrap_or_else(|e| panic!("failed to unwrap output: {}", e));
-
-                String::from_utf8(output.stdout)
-                    .unwrap_or_else(|e| panic!("failed to execute: {}", e))
-            } else { // ... else just join them
-                uuids.join("\n")
-            };
-
-            // and then print that
-            let _ = writeln!(rt.stdout(), "{}", outstring).to_exit_code().unwrap_or_exit();
-        });
-
-    res.map_err_trace().ok();
+            .wait_with_output()
+            .unwrap_or_else(|e| panic!("failed to unwrap output: {}", e));
+
+        let outstring = String::from_utf8(output.stdout)
+            .unwrap_or_else(|e| panic!("failed to execute: {}", e));
+
+        let _ = writeln!(rt.stdout(), "{}", outstring).to_exit_code().unwrap_or_exit();
+        return;
+    }
+
+    for (due, priority, uuid, status, description, tags) in tasks {
+        let tags = tags.iter().map(|t| format!(" +{}", t)).collect::<String>();
+        let _ = writeln!(rt.stdout(),
+                         "{uuid} {status: <7} {prio: <6} {due: <10} {descr}{tags}",
+                         uuid   = uuid,
+                         status = status,
+                         prio   = priority.map(|p| String::from(p.as_str())).unwrap_or_default(),
+                         due    = due.map(|d| date_to_string(&d)).unwrap_or_default(),
+                         descr  = description.unwrap_or_default(),
+                         tags   = tags)
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
 }
 
+fn create(rt: &Runtime) {
+    let subcmd      = rt.cli().subcommand_matches("create").unwrap();
+    let description = subcmd.values_of("description")
+        .unwrap() // enforced by clap
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let depends = subcmd
+        .values_of("depends")
+        .map(|uuids| uuids.map(|u| task_uuid(rt, u)).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut task = rt.store().create_task(description).map_err_trace_exit_unwrap(1);
+
+    if let Some(priority) = subcmd.value_of("priority").and_then(Priority::from_str) {
+        let _ = task.set_priority(Some(priority)).map_err_trace_exit_unwrap(1);
+    }
+
+    if let Some(due) = subcmd.value_of("due") {
+        let due = date_from_string(due).unwrap(); // safe by clap validator
+        let _ = task.set_due(Some(due)).map_err_trace_exit_unwrap(1);
+    }
+
+    for tag in subcmd.values_of("tags").into_iter().flat_map(|tags| tags) {
+        let _ = task.add_tag(String::from(tag)).map_err_trace_exit_unwrap(1);
+    }
+
+    for uuid in depends {
+        let _ = task.add_dependency(uuid).map_err_trace_exit_unwrap(1);
+    }
+
+    let _    = rt.store().update(&mut task).map_err_trace_exit_unwrap(1);
+    let uuid = task.get_uuid().map_err_trace_exit_unwrap(1);
+    let _ = writeln!(rt.stdout(), "{}", uuid).to_exit_code().unwrap_or_exit();
+}
+
+fn edit(rt: &Runtime) {
+    let subcmd = rt.cli().subcommand_matches("edit").unwrap();
+    let uuid   = task_uuid(rt, subcmd.value_of("task").unwrap()); // enforced by clap
+
+    let uuids_of = |name| subcmd
+        .values_of(name)
+        .map(|uuids| uuids.map(|u| task_uuid(rt, u)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let add_depends    = uuids_of("add-depends");
+    let remove_depends = uuids_of("remove-depends");
+
+    let mut task = get_task(rt, uuid);
+
+    if let Some(description) = subcmd.value_of("description") {
+        let _ = task.set_description(String::from(description)).map_err_trace_exit_unwrap(1);
+    }
+
+    if let Some(priority) = subcmd.value_of("priority").and_then(Priority::from_str) {
+        let _ = task.set_priority(Some(priority)).map_err_trace_exit_unwrap(1);
+    } else if subcmd.is_present("no-priority") {
+        let _ = task.set_priority(None).map_err_trace_exit_unwrap(1);
+    }
+
+    if let Some(due) = subcmd.value_of("due") {
+        let due = date_from_string(due).unwrap(); // safe by clap validator
+        let _ = task.set_due(Some(due)).map_err_trace_exit_unwrap(1);
+    } else if subcmd.is_present("no-due") {
+        let _ = task.set_due(None).map_err_trace_exit_unwrap(1);
+    }
+
+    for tag in subcmd.values_of("add-tags").into_iter().flat_map(|tags| tags) {
+        let _ = task.add_tag(String::from(tag)).map_err_trace_exit_unwrap(1);
+    }
+
+    for tag in subcmd.values_of("remove-tags").into_iter().flat_map(|tags| tags) {
+        let _ = task.remove_tag(tag).map_err_trace_exit_unwrap(1);
+    }
+
+    for dependency in add_depends {
+        if dependency == uuid {
+            warn!("A task cannot depend on itself");
+            continue;
+        }
+        let _ = task.add_dependency(dependency).map_err_trace_exit_unwrap(1);
+    }
+
+    for dependency in remove_depends {
+        let _ = task.remove_dependency(&dependency).map_err_trace_exit_unwrap(1);
+    }
+
+    let _ = rt.store().update(&mut task).map_err_trace_exit_unwrap(1);
+}
+
+fn done(rt: &Runtime) {
+    let subcmd = rt.cli().subcommand_matches("done").unwrap();
+
+    for uuid in subcmd.values_of("task").unwrap() { // enforced by clap
+        let uuid     = task_uuid(rt, uuid);
+        let mut task = get_task(rt, uuid);
+
+        let open_dependencies = task.get_dependencies()
+            .map_err_trace_exit_unwrap(1)
+            .into_iter()
+            .filter(|dep| {
+                rt.store()
+                    .get_task(*dep)
+                    .map_err_trace_exit_unwrap(1)
+                    .map(|dep| dep.get_status().map_err_trace_exit_unwrap(1) == Status::Pending)
+                    .unwrap_or(false)
+            })
+            .count();
+
+        if open_dependencies != 0 {
+            warn!("Task {} depends on {} pending task(s)", uuid, open_dependencies);
+        }
+
+        let _ = task.set_status(Status::Done).map_err_trace_exit_unwrap(1);
+        let _ = rt.store().update(&mut task).map_err_trace_exit_unwrap(1);
+        info!("Done: {}", uuid);
+    }
+}
+
+fn delete(rt: &Runtime) {
+    let subcmd = rt.cli().subcommand_matches("delete").unwrap();
+
+    for uuid in subcmd.values_of("task").unwrap() { // enforced by clap
+        let uuid = task_uuid(rt, uuid);
+        let _    = rt.store().delete_task(uuid).map_err_trace_exit_unwrap(1);
+        info!("Deleted: {}", uuid);
+    }
+}
+
+fn sync(rt: &Runtime) {
+    let subcmd = rt.cli().subcommand_matches("sync").unwrap();
+
+    let report = match subcmd.value_of("export") {
+        Some(path) => {
+            let file = File::open(path).map_err(TE::from).map_err_trace_exit_unwrap(1);
+            sync_taskwarrior_export(rt.store(), file)
+        },
+        None       => {
+            let stdin = stdin();
+            let lock  = stdin.lock();
+            sync_taskwarrior_export(rt.store(), lock)
+        },
+    }.map_err_trace_exit_unwrap(1);
+
+    for uuid in report.imported() {
+        info!("Imported: {}", uuid);
+    }
+
+    for uuid in report.updated() {
+        info!("Updated: {}", uuid);
+    }
+
+    for uuid in report.locally_modified() {
+        info!("Modified in imag: {}", uuid);
+    }
+
+    for uuid in report.deleted() {
+        info!("Deleted: {}", uuid);
+    }
+
+    for uuid in report.missing() {
+        warn!("Not in the export: {}", uuid);
+    }
+
+    for conflict in report.conflicts() {
+        let _ = writeln!(rt.stdout(), "Conflict: {} ({})", conflict.uuid(), conflict.fields().join(", "))
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+
+    match subcmd.value_of("write-back") {
+        Some(path) => {
+            let file = File::create(path).map_err(TE::from).map_err_trace_exit_unwrap(1);
+            let _    = ::serde_json::to_writer_pretty(file, report.taskwarrior_updates())
+                .chain_err(|| TEK::ExportError)
+                .map_err_trace_exit_unwrap(1);
+        },
+        None       => if !report.locally_modified().is_empty() {
+            warn!("{} task(s) were modified in imag, pass --write-back to export them",
+                  report.locally_modified().len());
+        },
+    }
+
+    info!("{} imported, {} updated, {} unchanged, {} modified in imag, {} deleted, {} conflicts",
+          report.imported().len(),
+          report.updated().len(),
+          report.unchanged().len(),
+          report.locally_modified().len(),
+          report.deleted().len(),
+          report.conflicts().len());
+}
+
+/// Get the uuid of the task from the (prefix of the) uuid passed on the commandline
+fn task_uuid(rt: &Runtime, prefix: &str) -> Uuid {
+    rt.store().find_task_uuid(prefix).map_err_trace_exit_unwrap(1)
+}
+
+fn get_task<'a>(rt: &'a Runtime, uuid: Uuid) -> FileLockEntry<'a> {
+    rt.store()
+        .get_task(uuid)
+        .map_err_trace_exit_unwrap(1)
+        .unwrap_or_else(|| {
+            error!("No task {}", uuid);
+            ::std::process::exit(1)
+        })
+}
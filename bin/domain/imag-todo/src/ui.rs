@@ -19,6 +19,8 @@
 
 use clap::{Arg, App, ArgGroup, SubCommand};
 
+use libimagutil::date::date_from_string;
+
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
         .subcommand(SubCommand::with_name("tw-hook")
@@ -47,7 +49,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                     )
 
         .subcommand(SubCommand::with_name("list")
-                    .about("List all tasks")
+                    .about("List tasks")
                     .version("0.1")
 
                     .arg(Arg::with_name("verbose")
@@ -57,5 +59,212 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .required(false)
                          .help("Asks taskwarrior for all the details")
                         )
+
+                    .arg(Arg::with_name("status")
+                         .long("status")
+                         .short("s")
+                         .takes_value(true)
+                         .required(false)
+                         .possible_values(&["pending", "done", "all"])
+                         .default_value("pending")
+                         .value_name("STATUS")
+                         .help("List only tasks with this status")
+                        )
+
+                    .arg(Arg::with_name("due-before")
+                         .long("due-before")
+                         .short("b")
+                         .takes_value(true)
+                         .required(false)
+                         .validator(is_date)
+                         .value_name("DATE")
+                         .help("List only tasks which are due on or before this date (YYYY-MM-DD)")
+                        )
+
+                    .arg(Arg::with_name("overdue")
+                         .long("overdue")
+                         .short("o")
+                         .takes_value(false)
+                         .required(false)
+                         .conflicts_with("due-before")
+                         .help("List only tasks which are due before today")
+                        )
                    )
+
+        .subcommand(SubCommand::with_name("create")
+                    .about("Create a task")
+                    .version("0.1")
+
+                    .arg(Arg::with_name("description")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .multiple(true)
+                         .value_name("DESCRIPTION")
+                         .help("What there is to do")
+                        )
+
+                    .arg(priority_arg())
+                    .arg(due_arg())
+
+                    .arg(Arg::with_name("tags")
+                         .long("tags")
+                         .short("t")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("TAG")
+                         .help("Tag the task")
+                        )
+
+                    .arg(Arg::with_name("depends")
+                         .long("depends")
+                         .short("D")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("UUID")
+                         .help("The task depends on these tasks (a unique prefix of the UUID is sufficient)")
+                        )
+                   )
+
+        .subcommand(SubCommand::with_name("edit")
+                    .about("Change a task")
+                    .version("0.1")
+
+                    .arg(task_arg(false))
+
+                    .arg(Arg::with_name("description")
+                         .long("description")
+                         .short("m")
+                         .takes_value(true)
+                         .required(false)
+                         .value_name("DESCRIPTION")
+                         .help("Change the description")
+                        )
+
+                    .arg(priority_arg())
+                    .arg(Arg::with_name("no-priority")
+                         .long("no-priority")
+                         .takes_value(false)
+                         .required(false)
+                         .conflicts_with("priority")
+                         .help("Remove the priority")
+                        )
+
+                    .arg(due_arg())
+                    .arg(Arg::with_name("no-due")
+                         .long("no-due")
+                         .takes_value(false)
+                         .required(false)
+                         .conflicts_with("due")
+                         .help("Remove the due date")
+                        )
+
+                    .arg(Arg::with_name("add-tags")
+                         .long("add-tags")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("TAG")
+                         .help("Add these tags")
+                        )
+
+                    .arg(Arg::with_name("remove-tags")
+                         .long("remove-tags")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("TAG")
+                         .help("Remove these tags")
+                        )
+
+                    .arg(Arg::with_name("add-depends")
+                         .long("add-depends")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("UUID")
+                         .help("Add dependencies on these tasks")
+                        )
+
+                    .arg(Arg::with_name("remove-depends")
+                         .long("remove-depends")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(true)
+                         .value_name("UUID")
+                         .help("Remove the dependencies on these tasks")
+                        )
+                   )
+
+        .subcommand(SubCommand::with_name("done")
+                    .about("Mark tasks as done")
+                    .version("0.1")
+                    .arg(task_arg(true))
+                   )
+
+        .subcommand(SubCommand::with_name("delete")
+                    .about("Delete tasks")
+                    .version("0.1")
+                    .arg(task_arg(true))
+                   )
+
+        .subcommand(SubCommand::with_name("sync")
+                    .about("Synchronize with the output of 'task export'")
+                    .version("0.1")
+                    .arg(Arg::with_name("export")
+                         .index(1)
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(false)
+                         .value_name("FILE")
+                         .help("The file with the exported tasks. Read from stdin if not passed"))
+                    .arg(Arg::with_name("write-back")
+                         .long("write-back")
+                         .short("w")
+                         .takes_value(true)
+                         .required(false)
+                         .multiple(false)
+                         .value_name("FILE")
+                         .help("Write the tasks which were modified in imag to FILE, to be imported with 'task import FILE'"))
+                   )
+}
+
+fn task_arg<'a>(multiple: bool) -> Arg<'a, 'a> {
+    Arg::with_name("task")
+        .index(1)
+        .takes_value(true)
+        .required(true)
+        .multiple(multiple)
+        .value_name("UUID")
+        .help("The task (a unique prefix of the UUID is sufficient)")
+}
+
+fn priority_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("priority")
+        .long("priority")
+        .short("p")
+        .takes_value(true)
+        .required(false)
+        .possible_values(&["high", "medium", "low", "h", "m", "l", "H", "M", "L"])
+        .value_name("PRIORITY")
+        .help("The priority of the task")
+}
+
+fn due_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("due")
+        .long("due")
+        .short("d")
+        .takes_value(true)
+        .required(false)
+        .validator(is_date)
+        .value_name("DATE")
+        .help("The date the task is due (YYYY-MM-DD)")
+}
+
+fn is_date(s: String) -> Result<(), String> {
+    date_from_string(&s)
+        .map(|_| ())
+        .map_err(|e| format!("Not a date (YYYY-MM-DD): {}", e))
 }
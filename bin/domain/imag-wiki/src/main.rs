@@ -21,6 +21,7 @@ extern crate clap;
 extern crate regex;
 extern crate filters;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
 
 #[macro_use] extern crate libimagrt;
 extern crate libimagerror;
@@ -56,9 +57,15 @@ fn main() {
         Some("ids")         => ids(&rt, wiki_name),
         Some("idof")        => idof(&rt, wiki_name),
         Some("create")      => create(&rt, wiki_name),
-        Some("create-wiki") => create_wiki(&rt),
+        Some("create-wiki") => {
+            rt.reject_json_output("imag-wiki create-wiki").map_err_trace_exit_unwrap(1);
+            create_wiki(&rt)
+        },
         Some("show")        => show(&rt, wiki_name),
-        Some("delete")      => delete(&rt, wiki_name),
+        Some("delete")      => {
+            rt.reject_json_output("imag-wiki delete").map_err_trace_exit_unwrap(1);
+            delete(&rt, wiki_name)
+        },
         Some(other)         => {
             debug!("Unknown command");
             let _ = rt.handle_unknown_subcommand("imag-wiki", other, rt.cli())
@@ -78,8 +85,7 @@ fn ids(rt: &Runtime, wiki_name: &str) {
         String::from("")
     };
 
-    let out         = rt.stdout();
-    let mut outlock = out.lock();
+    let mut output = rt.output();
 
     rt.store()
         .get_wiki(wiki_name)
@@ -91,10 +97,14 @@ fn ids(rt: &Runtime, wiki_name: &str) {
         .all_ids()
         .map_err_trace_exit_unwrap(1)
         .for_each(|id| {
-            let _ = writeln!(outlock, "{}{}", prefix, id)
+            let _ = output
+                .emit(&json!({ "id": format!("{}", id.local().display()) }),
+                      |out| writeln!(out, "{}{}", prefix, id))
                 .to_exit_code()
                 .unwrap_or_exit();
         });
+
+    let _ = output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn idof(rt: &Runtime, wiki_name: &str) {
@@ -105,8 +115,7 @@ fn idof(rt: &Runtime, wiki_name: &str) {
         .map(String::from)
         .unwrap(); // safed by clap
 
-    let out      = rt.stdout();
-    let mut lock = out.lock();
+    let mut output = rt.output();
 
     let _ = rt.store()
         .get_wiki(wiki_name)
@@ -125,7 +134,11 @@ fn idof(rt: &Runtime, wiki_name: &str) {
                 String::from("")
             };
 
-            writeln!(lock, "{}{}", prefix, id).to_exit_code().unwrap_or_exit()
+            output
+                .emit(&json!({ "id": format!("{}", id.local().display()) }),
+                      |out| writeln!(out, "{}{}", prefix, id))
+                .to_exit_code()
+                .unwrap_or_exit()
         })
         .unwrap_or_else(|| {
             error!("Entry '{}' in wiki '{}' not found!", entryname, wiki_name);
@@ -140,6 +153,10 @@ fn create(rt: &Runtime, wiki_name: &str) {
     let scmd        = rt.cli().subcommand_matches("create").unwrap(); // safed by clap
     let name        = String::from(scmd.value_of("create-name").unwrap()); // safe by clap
 
+    if !scmd.is_present("create-printid") {
+        rt.reject_json_output("imag-wiki create without --print-id").map_err_trace_exit_unwrap(1);
+    }
+
     let wiki = rt
         .store()
         .get_wiki(&wiki_name)
@@ -169,11 +186,13 @@ fn create(rt: &Runtime, wiki_name: &str) {
         .map_err_trace_exit_unwrap(1);
 
     if scmd.is_present("create-printid") {
-        let out      = rt.stdout();
-        let mut lock = out.lock();
-        let id       = entry.get_location();
+        let id     = entry.get_location();
+        let record = json!({ "id": format!("{}", id.local().display()) });
 
-        writeln!(lock, "{}", id).to_exit_code().unwrap_or_exit()
+        rt.output()
+            .emit(&record, |out| writeln!(out, "{}", id))
+            .to_exit_code()
+            .unwrap_or_exit()
     }
 }
 
@@ -218,8 +237,7 @@ fn show(rt: &Runtime, wiki_name: &str) {
             ::std::process::exit(1)
         });
 
-    let out         = rt.stdout();
-    let mut outlock = out.lock();
+    let mut output = rt.output();
 
     for name in names {
         let entry = wiki
@@ -230,14 +248,21 @@ fn show(rt: &Runtime, wiki_name: &str) {
                 ::std::process::exit(1)
             });
 
-        writeln!(outlock, "{}", entry.get_location())
-                .to_exit_code()
-                .unwrap_or_exit();
+        let record = json!({
+            "id": format!("{}", entry.get_location().local().display()),
+            "content": entry.get_content(),
+        });
 
-        writeln!(outlock, "{}", entry.get_content())
-                .to_exit_code()
-                .unwrap_or_exit();
+        output
+            .emit(&record, |out| {
+                writeln!(out, "{}", entry.get_location())?;
+                writeln!(out, "{}", entry.get_content())
+            })
+            .to_exit_code()
+            .unwrap_or_exit();
     }
+
+    output.finish().to_exit_code().unwrap_or_exit();
 }
 
 fn delete(rt: &Runtime, wiki_name: &str) {
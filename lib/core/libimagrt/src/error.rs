@@ -22,8 +22,13 @@ error_chain! {
         RuntimeError, RuntimeErrorKind, ResultExt, Result;
     }
 
+    links {
+        StoreError(::libimagstore::error::StoreError, ::libimagstore::error::StoreErrorKind);
+    }
+
     foreign_links {
         IO(::std::io::Error);
+        JsonError(::serde_json::Error);
         TomlDeError(::toml::de::Error);
         TomlQueryError(::toml_query::error::Error);
         HandlebarsTemplateError(::handlebars::TemplateError);
@@ -40,6 +45,11 @@ error_chain! {
             display("IO Error")
         }
 
+        JsonOutputNotSupported(command: String) {
+            description("JSON output is not supported")
+            display("JSON output is not supported by '{}'", command)
+        }
+
         ProcessExitFailure {
             description("Process exited with failure")
             display("Process exited with failure")
@@ -50,6 +60,11 @@ error_chain! {
             display("IO Error: Could not open logfile")
         }
 
+        IOLogSocketConnectError(path: String) {
+            description("IO Error: Could not connect to log socket")
+            display("IO Error: Could not connect to log socket '{}'", path)
+        }
+
         ConfigTypeError(path: String, should_be_type: &'static str) {
             description("Error while reading the configuration: Type Error")
             display("Type Error: '{}' should be '{}'", path, should_be_type)
@@ -120,6 +135,11 @@ error_chain! {
             display("Configuration: Configuration Type not matching")
         }
 
+        StoreIdParseError(line: String) {
+            description("Could not parse store id")
+            display("Could not parse store id from '{}'", line)
+        }
+
     }
 }
 
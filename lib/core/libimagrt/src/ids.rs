@@ -0,0 +1,99 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Reading store ids which are piped between imag commands
+//!
+//! Each line of the input is one store id. Empty lines are ignored. A line may either contain the
+//! id relative to the store (what `imag-ids` prints), the absolute path of an entry inside the
+//! store or a JSON object with an "id" member (what `imag-ids --json` prints).
+
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use libimagstore::storeid::StoreId;
+
+use error::RuntimeErrorKind as REK;
+use error::Result;
+
+/// Read store ids from `input`, one per line
+///
+/// The returned ids have no base, so they can be passed to the `Store` directly.
+pub fn read_store_ids<R: BufRead>(store_path: &Path, input: R) -> Result<Vec<StoreId>> {
+    let mut ids = vec![];
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let path = if line.starts_with('{') {
+            match ::serde_json::from_str::<Value>(line)?.get("id") {
+                Some(&Value::String(ref id)) => PathBuf::from(id),
+                _ => return Err(REK::StoreIdParseError(String::from(line)).into()),
+            }
+        } else {
+            PathBuf::from(line)
+        };
+
+        let id = if path.is_absolute() {
+            StoreId::from_full_path(&store_path.to_path_buf(), path)?.without_base()
+        } else {
+            StoreId::new_baseless(path)?
+        };
+
+        debug!("Read id from input: {:?}", id);
+        ids.push(id);
+    }
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read(input: &str) -> Result<Vec<String>> {
+        read_store_ids(Path::new("/store"), input.as_bytes())
+            .map(|ids| ids.into_iter().map(|id| id.to_str().unwrap()).collect())
+    }
+
+    #[test]
+    fn test_plain_ids() {
+        assert_eq!(read("a/b\n\n  c \n").unwrap(), vec!["a/b", "c"]);
+    }
+
+    #[test]
+    fn test_absolute_pathes() {
+        assert_eq!(read("/store/a/b\n").unwrap(), vec!["a/b"]);
+        assert!(read("/elsewhere/a\n").is_err());
+    }
+
+    #[test]
+    fn test_json_records() {
+        assert_eq!(read("{\"id\":\"a/b\",\"tags\":[]}\n").unwrap(), vec!["a/b"]);
+        assert!(read("{\"tags\":[]}\n").is_err());
+    }
+
+}
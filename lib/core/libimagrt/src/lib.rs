@@ -48,6 +48,8 @@ extern crate toml;
 extern crate toml_query;
 #[macro_use] extern crate is_match;
 extern crate atty;
+extern crate serde;
+extern crate serde_json;
 
 extern crate libimagstore;
 extern crate libimagutil;
@@ -58,6 +60,8 @@ pub mod error;
 pub mod configuration;
 pub mod logger;
 pub mod io;
+pub mod ids;
+pub mod output;
 pub mod runtime;
 pub mod setup;
 pub mod spec;
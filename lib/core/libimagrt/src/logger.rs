@@ -20,6 +20,9 @@
 use std::io::Write;
 use std::io::stderr;
 use std::collections::BTreeMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::ops::Deref;
@@ -39,9 +42,18 @@ use handlebars::Handlebars;
 type ModuleName = String;
 type Result<T> = ::std::result::Result<T, RE>;
 
+/// The socket syslog listens on, if a "syslog" destination has no explicit path
+const DEFAULT_SYSLOG_SOCKET: &str = "/dev/log";
+
+/// The number of rotated files which are kept, if a file destination does not specify it
+const DEFAULT_ROTATION_KEEP: usize = 3;
+
+#[derive(Clone)]
 enum LogDestination {
     Stderr,
-    File(Arc<Mutex<::std::fs::File>>),
+    File(Arc<Mutex<LogFile>>),
+    #[cfg(unix)]
+    Socket(Arc<::std::os::unix::net::UnixDatagram>),
 }
 
 impl Default for LogDestination {
@@ -50,27 +62,132 @@ impl Default for LogDestination {
     }
 }
 
+impl LogDestination {
+
+    /// Check whether `self` and `other` are the same destination (the same file or socket)
+    fn is_same(&self, other: &LogDestination) -> bool {
+        match (self, other) {
+            (&LogDestination::Stderr, &LogDestination::Stderr)           => true,
+            (&LogDestination::File(ref a), &LogDestination::File(ref b)) => Arc::ptr_eq(a, b),
+            #[cfg(unix)]
+            (&LogDestination::Socket(ref a), &LogDestination::Socket(ref b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rotation {
+    /// Rotate the file before it grows larger than this many bytes
+    max_size: u64,
+
+    /// Keep this many rotated files (`<path>.1` being the most recent one)
+    keep: usize,
+}
+
+/// A log file, which is rotated when it exceeds a certain size
+///
+/// Several imag processes may write to (and rotate) the same file, so the size is always taken
+/// from the file at `path`, and the file is reopened if it was replaced in the meantime.
+struct LogFile {
+    path:     PathBuf,
+    file:     File,
+    rotation: Option<Rotation>,
+}
+
+impl LogFile {
+
+    fn open(path: PathBuf, rotation: Option<Rotation>) -> ::std::io::Result<LogFile> {
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        Ok(LogFile { path, file, rotation })
+    }
+
+    fn write_line(&mut self, line: &str) -> ::std::io::Result<()> {
+        let len  = line.len() as u64 + 1;
+        let size = self.current_size()?;
+
+        let rotate = match self.rotation {
+            Some(ref r) => size > 0 && size + len > r.max_size,
+            None        => false,
+        };
+
+        if rotate {
+            let _ = self.rotate()?;
+        }
+
+        write!(self.file, "{}\n", line)
+    }
+
+    /// Get the size of the file at `path`, reopening it if it is not the file we have open
+    /// (because it was rotated or removed by another process)
+    fn current_size(&mut self) -> ::std::io::Result<u64> {
+        match ::std::fs::metadata(&self.path) {
+            Ok(ref meta) if is_same_file(meta, &self.file.metadata()?) => Ok(meta.len()),
+            _ => {
+                self.file = OpenOptions::new().append(true).create(true).open(&self.path)?;
+                self.file.metadata().map(|meta| meta.len())
+            },
+        }
+    }
+
+    /// Move `<path>.N` to `<path>.N+1`, the current file to `<path>.1` and start a new file
+    fn rotate(&mut self) -> ::std::io::Result<()> {
+        use std::fs::{rename, remove_file};
+
+        let keep    = self.rotation.map(|r| r.keep).unwrap_or(0);
+        let path    = self.path.clone();
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+
+        if keep == 0 {
+            let _ = remove_file(&path)?;
+        } else {
+            for n in (1..keep).rev() {
+                if rotated(n).exists() {
+                    let _ = rename(rotated(n), rotated(n + 1))?;
+                }
+            }
+            let _ = rename(&path, rotated(1))?;
+        }
+
+        self.file = OpenOptions::new().append(true).create(true).open(&path)?;
+        Ok(())
+    }
+
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &::std::fs::Metadata, b: &::std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_: &::std::fs::Metadata, _: &::std::fs::Metadata) -> bool {
+    // Without inodes, a rotation by another process cannot be detected
+    true
+}
+
 struct ModuleSettings {
     enabled:        bool,
     level:          Option<Level>,
 
-    #[allow(unused)]
-    destinations:   Option<Vec<LogDestination>>,
+    /// Destinations for this module, in addition to the global ones
+    destinations:   Vec<LogDestination>,
 }
 
 /// Logger implementation for `log` crate.
+///
+/// Records are formatted with the per-level formats from `imag.logging.format` (or the ones from
+/// `imag.logging.modules.<module>.format`, if set) and written to the global destinations and the
+/// destinations of the module. Settings for a module `a::b` also apply to `a::b::c`, unless there
+/// are settings for `a::b::c`.
 pub struct ImagLogger {
     global_loglevel     : Level,
-
-    #[allow(unused)]
     global_destinations : Vec<LogDestination>,
-    // global_format_trace : ,
-    // global_format_debug : ,
-    // global_format_info  : ,
-    // global_format_warn  : ,
-    // global_format_error : ,
     module_settings     : BTreeMap<ModuleName, ModuleSettings>,
 
+    /// Templates are registered per level ("DEBUG") and per module and level ("libimagstore:DEBUG")
     handlebars: Handlebars,
 }
 
@@ -106,10 +223,17 @@ impl ImagLogger {
             handlebars.register_template_string("ERROR", fmt)?; // name must be uppercase
         }
 
+        for (module, level, fmt) in aggregate_module_formats(config)? {
+            handlebars.register_template_string(&format!("{}:{}", module, level), fmt)?;
+        }
+
+        // destinations which point to the same file share the file handle
+        let mut files = BTreeMap::new();
+
         Ok(ImagLogger {
             global_loglevel     : aggregate_global_loglevel(matches, config)?,
-            global_destinations : aggregate_global_destinations(matches, config)?,
-            module_settings     : aggregate_module_settings(matches, config)?,
+            global_destinations : aggregate_global_destinations(matches, config, &mut files)?,
+            module_settings     : aggregate_module_settings(matches, config, &mut files)?,
             handlebars          : handlebars,
         })
     }
@@ -118,16 +242,77 @@ impl ImagLogger {
         self.global_loglevel
     }
 
+    /// The most verbose level any record can be logged with, considering the module settings
+    pub fn max_loglevel(&self) -> Level {
+        self.module_settings
+            .values()
+            .filter(|s| s.enabled)
+            .filter_map(|s| s.level)
+            .fold(self.global_loglevel, ::std::cmp::max)
+    }
+
+    /// Get the settings of the module `target`, or of its closest parent module which has settings
+    fn module_settings_for<'a>(&'a self, target: &'a str) -> Option<(&'a str, &'a ModuleSettings)> {
+        let mut name = target;
+        loop {
+            if let Some(settings) = self.module_settings.get(name) {
+                return Some((name, settings));
+            }
+
+            match name.rfind("::") {
+                Some(i) => name = &name[..i],
+                None    => return None,
+            }
+        }
+    }
+
+    fn render(&self, module: Option<&str>, record: &Record) -> String {
+        let mut data = BTreeMap::new();
+
+        {
+            data.insert("level",        format!("{}", record.level()));
+            data.insert("module_path",  String::from(record.module_path().unwrap_or("<modulepath unknown>")));
+            data.insert("file",         String::from(record.file().unwrap_or("<file unknown>")));
+            data.insert("line",         format!("{}", record.line().unwrap_or(0)));
+            data.insert("target",       String::from(record.target()));
+            data.insert("message",      format!("{}", record.args()));
+        }
+
+        let template = module
+            .map(|m| format!("{}:{}", m, record.level()))
+            .and_then(|t| if self.handlebars.get_template(&t).is_some() { Some(t) } else { None })
+            .unwrap_or_else(|| format!("{}", record.level()));
+
+        self.handlebars
+            .render(&template, &data)
+            .unwrap_or_else(|e| format!("Failed rendering logging data: {:?}\n", e))
+    }
+
 }
 
 impl Log for ImagLogger {
 
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.global_loglevel
+        match self.module_settings_for(metadata.target()) {
+            Some((_, settings)) => {
+                settings.enabled && metadata.level() <= settings.level.unwrap_or(self.global_loglevel)
+            },
+            None => metadata.level() <= self.global_loglevel,
+        }
     }
 
     fn flush(&self) {
-        // nothing?
+        let all = self.module_settings
+            .values()
+            .flat_map(|s| s.destinations.iter())
+            .chain(self.global_destinations.iter());
+
+        for d in all {
+            if let &LogDestination::File(ref file) = d {
+                // if there is an error, we cannot do anything. So we ignore it here.
+                let _ = file.deref().lock().map(|mut f| f.file.flush());
+            }
+        }
     }
 
     fn log(&self, record: &Record) {
@@ -143,21 +328,12 @@ impl Log for ImagLogger {
             return;
         }
 
-        let mut data = BTreeMap::new();
-
-        {
-            data.insert("level",        format!("{}", record.level()));
-            data.insert("module_path",  String::from(record.module_path().unwrap_or("<modulepath unknown>")));
-            data.insert("file",         String::from(record.file().unwrap_or("<file unknown>")));
-            data.insert("line",         format!("{}", record.line().unwrap_or(0)));
-            data.insert("target",       String::from(record.target()));
-            data.insert("message",      format!("{}", record.args()));
+        if !self.enabled(record.metadata()) {
+            return;
         }
 
-        let logtext = self
-            .handlebars
-            .render(&format!("{}", record.level()), &data)
-            .unwrap_or_else(|e| format!("Failed rendering logging data: {:?}\n", e));
+        let module  = self.module_settings_for(record.target());
+        let logtext = self.render(module.map(|m| m.0), record);
 
         let log_to_destination = |d: &LogDestination| match d {
             &LogDestination::Stderr => {
@@ -168,51 +344,47 @@ impl Log for ImagLogger {
                 let _ = arc_mutex_logdest
                     .deref()
                     .lock()
-                    .map(|mut logdest| {
-                        write!(logdest, "{}\n", logtext)
-                    });
-            }
+                    .map(|mut logdest| logdest.write_line(&logtext));
+            },
+            #[cfg(unix)]
+            &LogDestination::Socket(ref socket) => {
+                let _ = socket.send(syslog_message(record).as_bytes());
+            },
         };
 
-        // hack to get the right target configuration.
-        // If there is no element here, we use the empty string which automatically drops through
-        // to the unwrap_or_else() case
-        let record_target = record
-            .target()
-            .split("::")
-            .next()
-            .unwrap_or("");
+        let destinations = self.global_destinations
+            .iter()
+            .chain(module.into_iter().flat_map(|(_, settings)| settings.destinations.iter()));
+        let mut logged_to : Vec<&LogDestination> = vec![];
 
-        self.module_settings
-            .get(record_target)
-            .map(|module_setting| {
-                let set = module_setting.enabled &&
-                    module_setting.level.unwrap_or(self.global_loglevel) >= record.level();
-
-                if set {
-                    module_setting.destinations.as_ref().map(|destinations| for d in destinations {
-                        // If there's an error, we cannot do anything, can we?
-                        let _ = log_to_destination(&d);
-                    });
-
-                    for d in self.global_destinations.iter() {
-                        // If there's an error, we cannot do anything, can we?
-                        let _ = log_to_destination(&d);
-                    }
-                }
-            })
-        .unwrap_or_else(|| {
-            if self.global_loglevel >= record.level() {
-                // Yes, we log
-                for d in self.global_destinations.iter() {
-                    // If there's an error, we cannot do anything, can we?
-                    let _ = log_to_destination(&d);
-                }
+        for d in destinations {
+            // A file may be configured as global and as module destination, but each record is
+            // written only once to it
+            if logged_to.iter().any(|other| other.is_same(d)) {
+                continue;
             }
-        });
+
+            // If there's an error, we cannot do anything, can we?
+            let _ = log_to_destination(d);
+            logged_to.push(d);
+        }
     }
 }
 
+/// Format a record as syslog message (RFC 3164 without timestamp and hostname, which the syslog
+/// daemon adds), with facility "user"
+fn syslog_message(record: &Record) -> String {
+    let severity = match record.level() {
+        Level::Error => 3,
+        Level::Warn  => 4,
+        Level::Info  => 6,
+        Level::Debug => 7,
+        Level::Trace => 7,
+    };
+
+    format!("<{}>imag: [{}] {}", 8 + severity, record.target(), record.args())
+}
+
 fn match_log_level_str(s: &str) -> Result<Level> {
     match s {
         "trace" => Ok(Level::Trace),
@@ -260,45 +432,109 @@ fn aggregate_global_loglevel(matches: &ArgMatches, config: Option<&Value>) -> Re
     }
 }
 
-fn translate_destination(raw: &str) -> Result<LogDestination> {
-    use std::fs::OpenOptions;
-
-    match raw {
-        "-" => Ok(LogDestination::Stderr),
-        other => {
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(other)
-                .map(Mutex::new)
-                .map(Arc::new)
-                .map(LogDestination::File)
-                .chain_err(|| EK::IOLogFileOpenError)
-        }
+/// Translate a destination specification
+///
+/// A destination is either "-" for stderr, "syslog" or "syslog:<socket path>" for a syslog
+/// socket, a path of a file or a table with the keys "path", "max_size" (in bytes) and
+/// optionally "keep" for a file which is rotated when it grows too large.
+///
+/// Files which were opened already are taken from `files`, so each file is opened only once.
+fn translate_destination(raw: &Value, files: &mut BTreeMap<PathBuf, LogDestination>)
+    -> Result<LogDestination>
+{
+    let type_error = || {
+        let path = "imag.logging.modules.<mod>.destinations".to_owned();
+        let ty   = "Array<String|Table>";
+        RE::from_kind(EK::ConfigTypeError(path, ty))
+    };
+
+    let (path, rotation) = match raw {
+        &Value::String(ref s) if s == "-" => return Ok(LogDestination::Stderr),
+        &Value::String(ref s) if s == "syslog" => {
+            // Not every system has a syslog daemon, which must not make every command fail
+            return Ok(open_socket(DEFAULT_SYSLOG_SOCKET).unwrap_or_else(|_| {
+                let _ = write!(stderr(), "Cannot connect to syslog at {}, logging to stderr\n",
+                               DEFAULT_SYSLOG_SOCKET);
+                LogDestination::Stderr
+            }));
+        },
+        &Value::String(ref s) if s.starts_with("syslog:") => return open_socket(&s["syslog:".len()..]),
+        &Value::String(ref s) => (PathBuf::from(s), None),
+        &Value::Table(_) => {
+            let path = raw.read_string("path")?.ok_or_else(&type_error)?;
+            let max_size = match raw.read("max_size")? {
+                Some(&Value::Integer(i)) if i > 0 => Some(i as u64),
+                Some(_)                           => return Err(type_error()),
+                None                              => None,
+            };
+            let keep = match raw.read("keep")? {
+                Some(&Value::Integer(i)) if i >= 0 => i as usize,
+                Some(_)                            => return Err(type_error()),
+                None                               => DEFAULT_ROTATION_KEEP,
+            };
+
+            (PathBuf::from(path), max_size.map(|max_size| Rotation { max_size, keep }))
+        },
+        _ => return Err(type_error()),
+    };
+
+    if let Some(dest) = files.get(&path) {
+        return Ok(dest.clone());
     }
+
+    let dest = LogFile::open(path.clone(), rotation)
+        .map(Mutex::new)
+        .map(Arc::new)
+        .map(LogDestination::File)
+        .chain_err(|| EK::IOLogFileOpenError)?;
+
+    files.insert(path, dest.clone());
+    Ok(dest)
 }
 
+#[cfg(unix)]
+fn open_socket(path: &str) -> Result<LogDestination> {
+    use std::os::unix::net::UnixDatagram;
 
-fn translate_destinations(raw: &Vec<Value>) -> Result<Vec<LogDestination>> {
+    UnixDatagram::unbound()
+        .and_then(|socket| socket.connect(path).map(|_| socket))
+        .map(Arc::new)
+        .map(LogDestination::Socket)
+        .chain_err(|| EK::IOLogSocketConnectError(String::from(path)))
+}
+
+#[cfg(not(unix))]
+fn open_socket(path: &str) -> Result<LogDestination> {
+    Err(RE::from_kind(EK::IOLogSocketConnectError(String::from(path))))
+}
+
+fn translate_destinations(raw: &Vec<Value>, files: &mut BTreeMap<PathBuf, LogDestination>)
+    -> Result<Vec<LogDestination>>
+{
     raw.iter()
         .fold(Ok(vec![]), |acc, val| {
             acc.and_then(|mut v| {
-                let dest = val.as_str()
-                    .ok_or_else(|| {
-                        let path = "imag.logging.modules.<mod>.destinations".to_owned();
-                        let ty   = "Array<String>";
-                        RE::from_kind(EK::ConfigTypeError(path, ty))
-                    })
-                    .and_then(translate_destination)?;
-                v.push(dest);
+                v.push(translate_destination(val, files)?);
                 Ok(v)
             })
         })
 }
 
-fn aggregate_global_destinations(matches: &ArgMatches, config: Option<&Value>)
+fn aggregate_global_destinations(matches: &ArgMatches,
+                                 config: Option<&Value>,
+                                 files: &mut BTreeMap<PathBuf, LogDestination>)
     -> Result<Vec<LogDestination>>
 {
+    if let Some(values) = matches.value_of(Runtime::arg_logdest_name()) {
+        // parse logdest specification from commandline, which overrides the configuration
+        return values.split(",")
+            .fold(Ok(vec![]), move |acc, dest| {
+                acc.and_then(|mut v| {
+                    v.push(translate_destination(&Value::String(String::from(dest)), files)?);
+                    Ok(v)
+                })
+            });
+    }
 
     match config {
         Some(cfg) => cfg
@@ -310,22 +546,8 @@ fn aggregate_global_destinations(matches: &ArgMatches, config: Option<&Value>)
                 let ty   = "Array";
                 RE::from_kind(EK::ConfigTypeError(path, ty))
             })
-            .and_then(translate_destinations),
-        None => {
-            if let Some(values) = matches.value_of(Runtime::arg_logdest_name()) {
-                // parse logdest specification from commandline
-
-                values.split(",")
-                    .fold(Ok(vec![]), move |acc, dest| {
-                        acc.and_then(|mut v| {
-                            v.push(translate_destination(dest)?);
-                            Ok(v)
-                        })
-                    })
-            } else {
-                Ok(vec![ LogDestination::default() ])
-            }
-        }
+            .and_then(|raw| translate_destinations(raw, files)),
+        None => Ok(vec![ LogDestination::default() ]),
     }
 }
 
@@ -377,7 +599,31 @@ fn aggregate_global_format_error(config: Option<&Value>)
                             config)
 }
 
-fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
+/// Get the formats from `imag.logging.modules.<module>.format` as (module, LEVEL, format)
+fn aggregate_module_formats(config: Option<&Value>) -> Result<Vec<(String, &'static str, String)>> {
+    let modules = match config.map(|cfg| cfg.read("imag.logging.modules")) {
+        Some(Ok(Some(&Value::Table(ref t)))) => t,
+        Some(Err(e)) => return Err(e).map_err(From::from),
+        _ => return Ok(vec![]), // wrong types are reported by aggregate_module_settings()
+    };
+
+    let mut formats = vec![];
+    for (module_name, v) in modules {
+        for &(key, level) in &[("trace", "TRACE"), ("debug", "DEBUG"), ("info", "INFO"),
+                               ("warn", "WARN"), ("error", "ERROR")]
+        {
+            if let Some(fmt) = v.read_string(&format!("format.{}", key))? {
+                formats.push((module_name.clone(), level, fmt));
+            }
+        }
+    }
+
+    Ok(formats)
+}
+
+fn aggregate_module_settings(_matches: &ArgMatches,
+                             config: Option<&Value>,
+                             files: &mut BTreeMap<PathBuf, LogDestination>)
     -> Result<BTreeMap<ModuleName, ModuleSettings>>
 {
     // Helper macro to return the error from Some(Err(_)) and map everything else to an
@@ -390,7 +636,7 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
                 None         => None,
             }
         }
-    };
+    }
 
     match config {
         Some(cfg) => match cfg.read("imag.logging.modules") {
@@ -408,7 +654,7 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
                                         let ty = "Array";
                                         RE::from_kind(EK::ConfigTypeError(path, ty))
                                     })
-                                    .and_then(translate_destinations)
+                                    .and_then(|raw| translate_destinations(raw, files))
                             })
                     };
 
@@ -416,18 +662,20 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
                         v.read_string("level")?.map(|s| match_log_level_str(&s))
                     };
 
-                    let enabled = v.read("enabled")?
-                        .map(|v| v.as_bool().unwrap_or(false))
-                        .ok_or_else(|| {
+                    let enabled = match v.read("enabled")? {
+                        Some(&Value::Boolean(b)) => b,
+                        Some(_) => {
                             let path = "imag.logging.modules.<mod>.enabled".to_owned();
                             let ty = "Boolean";
-                            RE::from_kind(EK::ConfigTypeError(path, ty))
-                        })?;
+                            return Err(RE::from_kind(EK::ConfigTypeError(path, ty)))
+                        },
+                        None => true,
+                    };
 
                     let module_settings = ModuleSettings {
                         enabled: enabled,
                         level: level,
-                        destinations: destinations,
+                        destinations: destinations.unwrap_or_else(Vec::new),
                     };
 
                     // We don't care whether there was a value, we override it.
@@ -457,3 +705,190 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
     }
 }
 
+#[cfg(test)]
+mod test {
+    use std::fs::read_dir;
+    use std::fs::create_dir_all;
+    use std::fs::remove_dir_all;
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::*;
+
+    /// A fresh temporary directory for the test `name`
+    fn tempdir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!("imag-logger-test-{}", name));
+        let _   = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        let mut s = String::new();
+        if let Ok(mut f) = File::open(path) {
+            f.read_to_string(&mut s).unwrap();
+        }
+        s
+    }
+
+    fn logger(config: &str, args: Vec<&str>) -> ImagLogger {
+        let config = format!(r#"
+            [imag.logging.format]
+            trace = "{{{{level}}}} {{{{target}}}} {{{{message}}}}"
+            debug = "{{{{level}}}} {{{{target}}}} {{{{message}}}}"
+            info  = "{{{{level}}}} {{{{target}}}} {{{{message}}}}"
+            warn  = "{{{{level}}}} {{{{target}}}} {{{{message}}}}"
+            error = "{{{{level}}}} {{{{target}}}} {{{{message}}}}"
+
+            {}
+        "#, config);
+        let config  = ::toml::de::from_str::<Value>(&config).unwrap();
+        let matches = Runtime::get_default_cli_builder("test", "0", "test")
+            .get_matches_from(args);
+
+        ImagLogger::new(&matches, Some(&config)).unwrap()
+    }
+
+    fn log(logger: &ImagLogger, level: Level, target: &str, message: &str) {
+        logger.log(&Record::builder()
+                   .level(level)
+                   .target(target)
+                   .module_path(Some(target))
+                   .args(format_args!("{}", message))
+                   .build());
+    }
+
+    #[test]
+    fn test_module_destinations_and_levels() {
+        let dir = tempdir("modules");
+        let logger = logger(&format!(r#"
+            [imag.logging]
+            level = "info"
+            destinations = [ "{dir}/global.log" ]
+
+            [imag.logging.modules.libfoo]
+            level = "debug"
+            destinations = [ "{dir}/foo.log" ]
+
+            [imag.logging.modules."libfoo::quiet"]
+            level = "error"
+
+            [imag.logging.modules.libbar]
+            enabled = false
+            destinations = [ "{dir}/bar.log" ]
+
+            [imag.logging.modules.libbaz]
+            destinations = [ "{dir}/global.log" ]
+
+            [imag.logging.modules.libbaz.format]
+            info = "baz: {{{{message}}}}"
+        "#, dir = dir.display()), vec!["test"]);
+
+        assert_eq!(logger.max_loglevel(), Level::Debug);
+
+        log(&logger, Level::Debug, "libfoo::store", "foo debug");
+        log(&logger, Level::Warn,  "libfoo::quiet::x", "quiet warn");
+        log(&logger, Level::Error, "libfoo::quiet", "quiet error");
+        log(&logger, Level::Error, "libbar", "bar error");
+        log(&logger, Level::Info,  "libother", "other info");
+        log(&logger, Level::Debug, "libother", "other debug");
+        log(&logger, Level::Info,  "libbaz::x", "baz info");
+        logger.flush();
+
+        assert_eq!(read(&dir.join("global.log")),
+                   "DEBUG libfoo::store foo debug\n\
+                    ERROR libfoo::quiet quiet error\n\
+                    INFO libother other info\n\
+                    baz: baz info\n");
+        // "libfoo::quiet" has its own settings, without the destinations of "libfoo"
+        assert_eq!(read(&dir.join("foo.log")), "DEBUG libfoo::store foo debug\n");
+        assert_eq!(read(&dir.join("bar.log")), "");
+    }
+
+    #[test]
+    fn test_commandline_overrides_destinations() {
+        let dir  = tempdir("commandline");
+        let dest = format!("{}/cli.log", dir.display());
+        let logger = logger(&format!(r#"
+            [imag.logging]
+            level = "info"
+            destinations = [ "{dir}/config.log" ]
+        "#, dir = dir.display()), vec!["test", "--logging-destinations", &dest]);
+
+        log(&logger, Level::Info, "libfoo", "hello");
+        logger.flush();
+
+        assert_eq!(read(&dir.join("cli.log")), "INFO libfoo hello\n");
+        assert_eq!(read(&dir.join("config.log")), "");
+    }
+
+    #[test]
+    fn test_file_rotation() {
+        let dir = tempdir("rotation");
+        let logger = logger(&format!(r#"
+            [imag.logging]
+            level = "info"
+            destinations = [ {{ path = "{dir}/rot.log", max_size = 50, keep = 2 }} ]
+        "#, dir = dir.display()), vec!["test"]);
+
+        for i in 0..7 {
+            log(&logger, Level::Info, "libfoo", &format!("message {}", i)); // 22 bytes each
+        }
+        logger.flush();
+
+        assert_eq!(read(&dir.join("rot.log")),   "INFO libfoo message 6\n");
+        assert_eq!(read(&dir.join("rot.log.1")), "INFO libfoo message 4\nINFO libfoo message 5\n");
+        assert_eq!(read(&dir.join("rot.log.2")), "INFO libfoo message 2\nINFO libfoo message 3\n");
+        assert_eq!(read_dir(&dir).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_file_rotation_by_several_processes() {
+        let dir    = tempdir("rotation-processes");
+        let config = format!(r#"
+            [imag.logging]
+            level = "info"
+            destinations = [ {{ path = "{dir}/rot.log", max_size = 50, keep = 2 }} ]
+        "#, dir = dir.display());
+        let loggers = [logger(&config, vec!["test"]), logger(&config, vec!["test"])];
+
+        for i in 0..7 {
+            log(&loggers[i % 2], Level::Info, "libfoo", &format!("message {}", i));
+        }
+        loggers[0].flush();
+        loggers[1].flush();
+
+        assert_eq!(read(&dir.join("rot.log")),   "INFO libfoo message 6\n");
+        assert_eq!(read(&dir.join("rot.log.1")), "INFO libfoo message 4\nINFO libfoo message 5\n");
+        assert_eq!(read(&dir.join("rot.log.2")), "INFO libfoo message 2\nINFO libfoo message 3\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_syslog_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir    = tempdir("syslog");
+        let path   = dir.join("log.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let logger = logger(&format!(r#"
+            [imag.logging]
+            level = "info"
+            destinations = []
+
+            [imag.logging.modules.libfoo]
+            destinations = [ "syslog:{}" ]
+        "#, path.display()), vec!["test"]);
+
+        log(&logger, Level::Warn, "libfoo", "to syslog");
+        log(&logger, Level::Warn, "libbar", "not to syslog");
+        log(&logger, Level::Error, "libfoo", "again");
+
+        let mut buf = [0; 128];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(::std::str::from_utf8(&buf[..n]).unwrap(), "<12>imag: [libfoo] to syslog");
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(::std::str::from_utf8(&buf[..n]).unwrap(), "<11>imag: [libfoo] again");
+    }
+
+}
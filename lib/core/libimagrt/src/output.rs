@@ -0,0 +1,222 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Structured output of the imag binaries
+//!
+//! Binaries emit their results as records through `Runtime::output()`. By default, the text
+//! representation of each record is printed. If `--json` is passed, each record is printed as one
+//! line of JSON ("JSON lines"), with `--json-array` all records are printed as one JSON array.
+//!
+//! ```ignore
+//! let mut output = rt.output();
+//! for id in ids {
+//!     output.emit(&json!({ "id": format!("{}", id) }), |out| writeln!(out, "{}", id))
+//!         .to_exit_code()
+//!         .unwrap_or_exit();
+//! }
+//! output.finish().to_exit_code().unwrap_or_exit();
+//! ```
+
+use std::io::Result as IoResult;
+use std::io::Write;
+
+use serde::Serialize;
+use serde_json;
+
+use io::OutputProxy;
+
+/// The format in which records are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The (human readable) text representation
+    Text,
+
+    /// One JSON object per line
+    JsonLines,
+
+    /// One JSON array containing all records
+    JsonArray,
+}
+
+impl OutputFormat {
+
+    pub fn is_json(&self) -> bool {
+        *self != OutputFormat::Text
+    }
+
+}
+
+/// Writer for the records of a command
+///
+/// In the `JsonArray` format, the array is closed by `Output::finish()` or when the `Output` is
+/// dropped.
+#[derive(Debug)]
+pub struct Output<W: Write = OutputProxy> {
+    format: OutputFormat,
+    out: W,
+    records: usize,
+    finished: bool,
+}
+
+impl<W: Write> Output<W> {
+
+    pub fn new(format: OutputFormat, out: W) -> Output<W> {
+        Output {
+            format: format,
+            out: out,
+            records: 0,
+            finished: false,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Emit one record
+    ///
+    /// `text` is called to print the text representation of the record if the output format is
+    /// `OutputFormat::Text`, otherwise `record` is serialized.
+    pub fn emit<R, F>(&mut self, record: &R, text: F) -> IoResult<()>
+        where R: Serialize,
+              F: FnOnce(&mut W) -> IoResult<()>
+    {
+        match self.format {
+            OutputFormat::Text      => text(&mut self.out)?,
+            OutputFormat::JsonLines => {
+                serde_json::to_writer(&mut self.out, record)?;
+                writeln!(self.out)?;
+            },
+            OutputFormat::JsonArray => {
+                if self.records == 0 {
+                    write!(self.out, "[")?;
+                } else {
+                    write!(self.out, ",")?;
+                }
+                serde_json::to_writer(&mut self.out, record)?;
+            },
+        }
+
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Emit one record in the JSON formats only
+    ///
+    /// For commands which print the text representation of all records in one piece (for example
+    /// as a table), this does nothing if the output format is `OutputFormat::Text`.
+    pub fn emit_record<R: Serialize>(&mut self, record: &R) -> IoResult<()> {
+        if self.format.is_json() {
+            self.emit(record, |_| Ok(()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Finish the output, closing the JSON array if necessary
+    pub fn finish(mut self) -> IoResult<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> IoResult<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        if self.format == OutputFormat::JsonArray {
+            if self.records == 0 {
+                write!(self.out, "[")?;
+            }
+            writeln!(self.out, "]")?;
+        }
+
+        self.out.flush()
+    }
+
+}
+
+impl<W: Write> Drop for Output<W> {
+
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn records(format: OutputFormat, n: usize) -> String {
+        let mut buf = vec![];
+        {
+            let mut out = Output::new(format, &mut buf);
+            for i in 0..n {
+                out.emit(&vec![i], |w| writeln!(w, "record {}", i)).unwrap();
+            }
+            out.finish().unwrap();
+        }
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(records(OutputFormat::Text, 2), "record 0\nrecord 1\n");
+    }
+
+    #[test]
+    fn test_json_lines() {
+        assert_eq!(records(OutputFormat::JsonLines, 0), "");
+        assert_eq!(records(OutputFormat::JsonLines, 2), "[0]\n[1]\n");
+    }
+
+    #[test]
+    fn test_json_array() {
+        assert_eq!(records(OutputFormat::JsonArray, 0), "[]\n");
+        assert_eq!(records(OutputFormat::JsonArray, 2), "[[0],[1]]\n");
+    }
+
+    #[test]
+    fn test_emit_record() {
+        let mut buf = vec![];
+        {
+            let mut out = Output::new(OutputFormat::Text, &mut buf);
+            out.emit_record(&1).unwrap();
+        }
+        assert!(buf.is_empty());
+
+        {
+            let mut out = Output::new(OutputFormat::JsonLines, &mut buf);
+            out.emit_record(&1).unwrap();
+        }
+        assert_eq!(String::from_utf8(buf).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn test_json_array_closed_on_drop() {
+        let mut buf = vec![];
+        {
+            let mut out = Output::new(OutputFormat::JsonArray, &mut buf);
+            out.emit(&1, |_| Ok(())).unwrap();
+        }
+        assert_eq!(String::from_utf8(buf).unwrap(), "[1]\n");
+    }
+
+}
@@ -22,6 +22,7 @@ use std::process::Command;
 use std::env;
 use std::process::exit;
 use std::io::Stdin;
+use std::io::Write;
 
 pub use clap::App;
 use clap::AppSettings;
@@ -36,9 +37,12 @@ use error::RuntimeErrorKind;
 use error::ResultExt;
 use logger::ImagLogger;
 use io::OutputProxy;
+use output::Output;
+use output::OutputFormat;
 
 use libimagerror::trace::*;
 use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
 use libimagstore::file_abstraction::InMemoryFileAbstraction;
 use libimagutil::debug_result::DebugResult;
 use spec::CliSpec;
@@ -63,6 +67,23 @@ impl<'a> Runtime<'a> {
     /// The cli_app object should be initially build with the ::get_default_cli_builder() function.
     pub fn new<C>(cli_app: C) -> Result<Runtime<'a>, RuntimeError>
         where C: Clone + CliSpec<'a> + InternalConfiguration
+    {
+        Runtime::new_opening_store(cli_app, true)
+    }
+
+    /// Like `Runtime::new()`, but without opening the store
+    ///
+    /// The runtime gets an empty in-memory store at the store path instead, until
+    /// `Runtime::open_store()` is called. This is for modules which talk to `imagd` if it is
+    /// running and only need the store otherwise.
+    pub fn new_without_store<C>(cli_app: C) -> Result<Runtime<'a>, RuntimeError>
+        where C: Clone + CliSpec<'a> + InternalConfiguration
+    {
+        Runtime::new_opening_store(cli_app, false)
+    }
+
+    fn new_opening_store<C>(cli_app: C, open_store: bool) -> Result<Runtime<'a>, RuntimeError>
+        where C: Clone + CliSpec<'a> + InternalConfiguration
     {
         use libimagerror::trace::trace_error;
 
@@ -97,7 +118,7 @@ impl<'a> Runtime<'a> {
             }
         };
 
-        Runtime::_new(cli_app, matches, config)
+        Runtime::_new(cli_app, matches, config, open_store)
     }
 
     /// Builds the Runtime object using the given `config`.
@@ -106,10 +127,10 @@ impl<'a> Runtime<'a> {
         where C: Clone + CliSpec<'a> + InternalConfiguration
     {
         let matches = cli_app.clone().matches();
-        Runtime::_new(cli_app, matches, config)
+        Runtime::_new(cli_app, matches, config, true)
     }
 
-    fn _new<C>(cli_app: C, matches: ArgMatches<'a>, config: Option<Value>)
+    fn _new<C>(cli_app: C, matches: ArgMatches<'a>, config: Option<Value>, open_store: bool)
                -> Result<Runtime<'a>, RuntimeError>
     where C: Clone + CliSpec<'a> + InternalConfiguration
     {
@@ -129,7 +150,8 @@ impl<'a> Runtime<'a> {
         debug!("RTP path    = {:?}", rtp);
         debug!("Store path  = {:?}", storepath);
 
-        let store_result = if cli_app.use_inmemory_fs() {
+        // The in-memory store only exists in this process, so it is not locked against others
+        let store_result = if cli_app.use_inmemory_fs() || !open_store {
             Store::new_with_backend(storepath,
                                     &config,
                                     Box::new(InMemoryFileAbstraction::default()))
@@ -157,6 +179,9 @@ impl<'a> Runtime<'a> {
     ///   * -c <file> | --config <file> for alternative configuration file
     ///   * -r <path> | --rtp <path> for alternative runtimepath
     ///   * --store <path> for alternative store path
+    ///   * --json | --json-array for JSON output
+    ///   * -I | --ids-from-stdin for reading store ids from stdin
+    ///   * --print-ids for printing the ids of touched entries
     /// Each has the appropriate help text included.
     ///
     /// The `appname` shall be "imag-<command>".
@@ -230,6 +255,32 @@ impl<'a> Runtime<'a> {
                 .takes_value(true)
                 .value_name("LOGDESTS"))
 
+            .arg(Arg::with_name(Runtime::arg_json_output_name())
+                .long("json")
+                .help("Print the output as JSON, one record per line")
+                .required(false)
+                .takes_value(false))
+
+            .arg(Arg::with_name(Runtime::arg_json_array_output_name())
+                .long("json-array")
+                .help("Print the output as one JSON array")
+                .required(false)
+                .takes_value(false)
+                .conflicts_with(Runtime::arg_json_output_name()))
+
+            .arg(Arg::with_name(Runtime::arg_ids_from_stdin_name())
+                .long("ids-from-stdin")
+                .short("I")
+                .help("Read the store ids to operate on from stdin, one per line, if no ids are passed on the commandline")
+                .required(false)
+                .takes_value(false))
+
+            .arg(Arg::with_name(Runtime::arg_print_ids_name())
+                .long("print-ids")
+                .help("Print the ids of the entries which were created or altered, one per line, so they can be piped into another imag command")
+                .required(false)
+                .takes_value(false))
+
     }
 
     /// Get the argument names of the Runtime which are available
@@ -243,6 +294,10 @@ impl<'a> Runtime<'a> {
             Runtime::arg_runtimepath_name(),
             Runtime::arg_storepath_name(),
             Runtime::arg_editor_name(),
+            Runtime::arg_json_output_name(),
+            Runtime::arg_json_array_output_name(),
+            Runtime::arg_ids_from_stdin_name(),
+            Runtime::arg_print_ids_name(),
         ]
     }
 
@@ -297,6 +352,15 @@ impl<'a> Runtime<'a> {
         self.store
     }
 
+    /// Open the store, for a runtime which was built with `Runtime::new_without_store()`
+    pub fn open_store(&mut self) -> Result<(), RuntimeError> {
+        let storepath = self.store.path().clone();
+
+        self.store = Store::new(storepath, &self.configuration)
+            .chain_err(|| RuntimeErrorKind::Instantiate)?;
+        Ok(())
+    }
+
     /// Re-set the Store object within
     ///
     /// # Warning
@@ -309,6 +373,26 @@ impl<'a> Runtime<'a> {
         self
     }
 
+    /// Get the argument name for JSON lines output
+    pub fn arg_json_output_name() -> &'static str {
+        "json-output"
+    }
+
+    /// Get the argument name for JSON array output
+    pub fn arg_json_array_output_name() -> &'static str {
+        "json-array-output"
+    }
+
+    /// Get the argument name for reading store ids from stdin
+    pub fn arg_ids_from_stdin_name() -> &'static str {
+        "ids-from-stdin"
+    }
+
+    /// Get the argument name for printing the ids of touched entries
+    pub fn arg_print_ids_name() -> &'static str {
+        "print-ids"
+    }
+
     /// Get the argument name for the logging destination
     pub fn arg_logdest_name() -> &'static str {
         "logging-destinations"
@@ -328,7 +412,7 @@ impl<'a> Runtime<'a> {
                 .map_err_trace()
                 .unwrap_or_else(|_| exit(1));
 
-            set_max_level(logger.global_loglevel().to_level_filter());
+            set_max_level(logger.max_loglevel().to_level_filter());
 
             debug!("Init logger with {}", logger.global_loglevel());
 
@@ -401,6 +485,34 @@ impl<'a> Runtime<'a> {
         OutputProxy::Out(::std::io::stdout())
     }
 
+    /// Get the output format requested on the commandline
+    pub fn output_format(&self) -> OutputFormat {
+        if self.cli_matches.is_present(Runtime::arg_json_array_output_name()) {
+            OutputFormat::JsonArray
+        } else if self.cli_matches.is_present(Runtime::arg_json_output_name()) {
+            OutputFormat::JsonLines
+        } else {
+            OutputFormat::Text
+        }
+    }
+
+    /// Get an `Output` for emitting records to stdout in the requested format
+    pub fn output(&self) -> Output {
+        Output::new(self.output_format(), self.stdout())
+    }
+
+    /// Fail if JSON output was requested
+    ///
+    /// Commands which do not print records call this, so `--json` is not silently ignored.
+    /// `command` is the name of the command, for the error message.
+    pub fn reject_json_output(&self, command: &str) -> Result<(), RuntimeError> {
+        if self.output_format().is_json() {
+            Err(RuntimeError::from_kind(RuntimeErrorKind::JsonOutputNotSupported(String::from(command))))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn stderr(&self) -> OutputProxy {
         OutputProxy::Err(::std::io::stderr())
     }
@@ -409,6 +521,77 @@ impl<'a> Runtime<'a> {
         Some(::std::io::stdin())
     }
 
+    /// Check whether store ids should be read from stdin
+    ///
+    /// This is only the case if it was requested with `--ids-from-stdin`. Stdin is never read
+    /// just because it is not a terminal, as it is not one in cron jobs or over ssh either.
+    pub fn reads_ids_from_stdin(&self) -> bool {
+        self.cli_matches.is_present(Runtime::arg_ids_from_stdin_name())
+    }
+
+    /// Read store ids from stdin, one per line
+    ///
+    /// See the `ids` module for the accepted formats. This reads stdin regardless of
+    /// `--ids-from-stdin`.
+    pub fn read_ids_from_stdin(&self) -> Result<Vec<StoreId>, RuntimeError> {
+        let stdin = self.stdin().ok_or_else(|| RuntimeError::from_kind(RuntimeErrorKind::IOError))?;
+        ::ids::read_store_ids(self.store.path(), stdin.lock())
+    }
+
+    /// Read store ids from stdin, if they should be read from there
+    ///
+    /// Returns `None` if ids are not read from stdin (see `Runtime::reads_ids_from_stdin()`) or if
+    /// stdin did not contain any id.
+    pub fn ids_from_stdin(&self) -> Result<Option<Vec<StoreId>>, RuntimeError> {
+        if !self.reads_ids_from_stdin() {
+            return Ok(None);
+        }
+
+        self.read_ids_from_stdin()
+            .map(|ids| if ids.is_empty() { None } else { Some(ids) })
+    }
+
+    /// Get the store ids passed as values of the argument `name` in `matches`
+    ///
+    /// If the argument is not present, the ids are read from stdin if `--ids-from-stdin` was passed
+    /// (see `Runtime::ids_from_stdin()`). A value of "-" is replaced by the ids from stdin.
+    ///
+    /// Returns `None` if there are neither ids on the commandline nor on stdin.
+    pub fn ids_from_args<'b>(&self, matches: &ArgMatches<'b>, name: &str)
+        -> Result<Option<Vec<StoreId>>, RuntimeError>
+    {
+        match matches.values_of(name) {
+            None         => self.ids_from_stdin(),
+            Some(values) => {
+                let mut ids = vec![];
+                for value in values {
+                    if value == "-" {
+                        ids.extend(self.read_ids_from_stdin()?);
+                    } else {
+                        ids.push(StoreId::new_baseless(PathBuf::from(value))?);
+                    }
+                }
+                Ok(Some(ids))
+            },
+        }
+    }
+
+    /// Check whether the ids of touched entries should be printed (`--print-ids`)
+    pub fn prints_ids(&self) -> bool {
+        self.cli_matches.is_present(Runtime::arg_print_ids_name())
+    }
+
+    /// Report that the entry with the id `id` was created or altered
+    ///
+    /// If `--print-ids` was passed, the id is printed to stdout, so it can be piped into the next
+    /// imag command. Otherwise, this does nothing.
+    pub fn report_touched(&self, id: &StoreId) -> Result<(), RuntimeError> {
+        if self.prints_ids() {
+            writeln!(self.stdout(), "{}", id.local().display())?;
+        }
+        Ok(())
+    }
+
     /// Helper for handling subcommands which are not available.
     ///
     /// # Example
@@ -44,3 +44,24 @@ pub fn generate_runtime_setup<'a, B>(name: Name, version: Version<'a>, about: Ab
             exit(1);
         })
 }
+
+/// Like `generate_runtime_setup()`, but the store is not opened (see
+/// `Runtime::new_without_store()`)
+pub fn generate_runtime_setup_without_store<'a, B>(name: Name,
+                                                    version: Version<'a>,
+                                                    about: About,
+                                                    builder: B)
+    -> Runtime<'a>
+    where B: FnOnce(App<'a, 'a>) -> App<'a, 'a>
+{
+    use std::process::exit;
+    use libimagerror::trace::trace_error_dbg;
+
+    Runtime::new_without_store(builder(Runtime::get_default_cli_builder(name, version, about)))
+        .unwrap_or_else(|e| {
+            println!("Could not set up Runtime");
+            println!("{:?}", e);
+            trace_error_dbg(&e);
+            exit(1);
+        })
+}
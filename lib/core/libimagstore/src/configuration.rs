@@ -17,11 +17,16 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::time::Duration;
+
 use toml::Value;
 
 use store::Result;
 use error::StoreError as SE;
 use error::StoreErrorKind as SEK;
+use schema::HeaderSchema;
+use hook::HookPosition;
+use hook::ExternalCommandHook;
 
 /// Checks whether the store configuration has a key "implicit-create" which maps to a boolean
 /// value. If that key is present, the boolean is returned, otherwise false is returned.
@@ -37,8 +42,142 @@ pub fn config_implicit_store_create_allowed(config: &Option<Value>) -> Result<bo
     }
 }
 
+/// Checks whether the store configuration has a key "index" which maps to a boolean value. If
+/// that key is present, the boolean is returned, otherwise false is returned.
+///
+/// The full-text index is disabled by default, as maintaining it costs time on each write.
+pub fn config_index_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    if let Some(ref t) = *config {
+        t.read_bool("store.index").map(|o| o.unwrap_or(false)).map_err(SE::from)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Checks whether the store configuration has a key "history" which maps to a boolean value. If
+/// that key is present, the boolean is returned, otherwise false is returned.
+pub fn config_history_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    if let Some(ref t) = *config {
+        t.read_bool("store.history").map(|o| o.unwrap_or(false)).map_err(SE::from)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Get the maximum number of revisions kept per entry from the key "history-limit" of the store
+/// configuration.
+///
+/// Returns `None` (no limit) if the key is not present or zero.
+pub fn config_history_limit(config: &Option<Value>) -> Result<Option<usize>> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.history-limit";
+
+    if let Some(ref t) = *config {
+        match t.read_int(key)? {
+            None | Some(0)  => Ok(None),
+            Some(n) if n > 0 => Ok(Some(n as usize)),
+            Some(_)         => Err(SE::from_kind(SEK::ConfigTypeError(key, "positive integer"))),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get the name of the backend from the key "backend" of the store configuration, if present
+pub fn config_backend(config: &Option<Value>) -> Result<Option<String>> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    if let Some(ref t) = *config {
+        t.read_string("store.backend").map_err(SE::from)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Get how long to wait for a lock held by another process from the key "lock-timeout" (in
+/// seconds) of the store configuration.
+///
+/// Defaults to 10 seconds if the key is not present.
+pub fn config_lock_timeout(config: &Option<Value>) -> Result<Duration> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key     = "store.lock-timeout";
+    let default = Duration::from_secs(10);
+
+    if let Some(ref t) = *config {
+        match t.read_int(key)? {
+            None             => Ok(default),
+            Some(n) if n >= 0 => Ok(Duration::from_secs(n as u64)),
+            Some(_)          => Err(SE::from_kind(SEK::ConfigTypeError(key, "non-negative integer"))),
+        }
+    } else {
+        Ok(default)
+    }
+}
+
+/// Get the header schemas declared in the "schema" table of the store configuration
+///
+/// Each key of the table is a collection, each value a schema as described in the `schema` module.
+pub fn config_schemas(config: &Option<Value>) -> Result<Vec<HeaderSchema>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key = "store.schema";
+
+    if let Some(ref t) = *config {
+        match t.read(key)? {
+            None => Ok(vec![]),
+            Some(&Value::Table(ref schemas)) => schemas
+                .iter()
+                .map(|(collection, schema)| HeaderSchema::from_toml(collection.clone(), schema))
+                .collect(),
+            Some(_) => Err(SE::from_kind(SEK::ConfigTypeError(key, "table"))),
+        }
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// Get the external command hooks from the "hooks" table of the store configuration
+///
+/// Each key of the table is a hook position (for example "post-update"), each value an array of
+/// hooks as described in `ExternalCommandHook::from_toml()`.
+pub fn config_hooks(config: &Option<Value>) -> Result<Vec<(HookPosition, ExternalCommandHook)>> {
+    use toml_query::read::TomlValueReadExt;
+
+    let key   = "store.hooks";
+    let error = || SE::from_kind(SEK::ConfigTypeError(key, "table of hook arrays"));
+
+    if let Some(ref t) = *config {
+        match t.read(key)? {
+            None => Ok(vec![]),
+            Some(&Value::Table(ref positions)) => {
+                let mut hooks = vec![];
+                for (position, list) in positions {
+                    let position = HookPosition::from_str(position).ok_or_else(&error)?;
+                    let list     = list.as_array().ok_or_else(&error)?;
+
+                    for hook in list {
+                        hooks.push((position, ExternalCommandHook::from_toml(hook)?));
+                    }
+                }
+                Ok(hooks)
+            },
+            Some(_) => Err(error()),
+        }
+    } else {
+        Ok(vec![])
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use toml::de::from_str as toml_from_str;
     use configuration::*;
 
@@ -73,5 +212,116 @@ mod tests {
         assert!(config_implicit_store_create_allowed(&Some(config)).unwrap());
     }
 
-}
+    #[test]
+    fn test_index_enabled_toml_empty() {
+        let config = toml_from_str("").unwrap();
+        assert!(!config_index_enabled(&Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_index_enabled_toml_true() {
+        let config = toml_from_str(r#"
+        [store]
+            index = true
+        "#).unwrap();
+
+        assert!(config_index_enabled(&Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_history_toml_empty() {
+        let config = Some(toml_from_str("").unwrap());
+        assert!(!config_history_enabled(&config).unwrap());
+        assert_eq!(config_history_limit(&config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_history_toml_limit() {
+        let config = Some(toml_from_str(r#"
+        [store]
+            history = true
+            history-limit = 5
+        "#).unwrap());
+
+        assert!(config_history_enabled(&config).unwrap());
+        assert_eq!(config_history_limit(&config).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(config_backend(&Some(toml_from_str("").unwrap())).unwrap(), None);
+
+        let config = toml_from_str(r#"
+        [store]
+            backend = "sqlite"
+        "#).unwrap();
+        assert_eq!(config_backend(&Some(config)).unwrap(), Some(String::from("sqlite")));
+    }
+
+    #[test]
+    fn test_history_toml_negative_limit() {
+        let config = Some(toml_from_str(r#"
+        [store]
+            history-limit = -1
+        "#).unwrap());
+
+        assert!(config_history_limit(&config).is_err());
+    }
 
+    #[test]
+    fn test_schemas() {
+        assert!(config_schemas(&Some(toml_from_str("").unwrap())).unwrap().is_empty());
+
+        let config = toml_from_str(r#"
+        [store.schema."habit/template"]
+            strict = true
+        [store.schema."habit/template".fields]
+            "habit.template.name" = { type = "string", required = true }
+        "#).unwrap();
+
+        let schemas = config_schemas(&Some(config)).unwrap();
+        assert_eq!(schemas.len(), 1);
+        assert_eq!(schemas[0].collection(), "habit/template");
+        assert!(schemas[0].is_strict());
+    }
+
+    #[test]
+    fn test_hooks() {
+        assert!(config_hooks(&Some(toml_from_str("").unwrap())).unwrap().is_empty());
+
+        let config = toml_from_str(r#"
+        [store.hooks]
+            post-update = [ { command = "a" }, { command = "b", args = [ "x" ] } ]
+            pre-delete  = [ { command = "c" } ]
+        "#).unwrap();
+
+        let hooks = config_hooks(&Some(config)).unwrap();
+        assert_eq!(hooks.len(), 3);
+        assert_eq!(hooks[0].0, HookPosition::PostUpdate);
+        assert_eq!(hooks[2], (HookPosition::PreDelete, ExternalCommandHook::new("c", vec![])));
+
+        let config = toml_from_str(r#"
+        [store.hooks]
+            after-update = [ { command = "a" } ]
+        "#).unwrap();
+        assert!(config_hooks(&Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        assert_eq!(config_lock_timeout(&None).unwrap(), Duration::from_secs(10));
+
+        let config = toml_from_str(r#"
+        [store]
+            lock-timeout = 0
+        "#).unwrap();
+        assert_eq!(config_lock_timeout(&Some(config)).unwrap(), Duration::from_secs(0));
+
+        let config = toml_from_str(r#"
+        [store]
+            lock-timeout = -1
+        "#).unwrap();
+        assert!(config_lock_timeout(&Some(config)).is_err());
+    }
+
+}
@@ -227,6 +227,131 @@ error_chain! {
             display("Error when calling move({:?} -> {:?})", old, new)
         }
 
+        IndexSerializationError {
+            description("Error while serializing the index")
+            display("Error while serializing the index")
+        }
+
+        IndexDeserializationError {
+            description("Error while deserializing the index")
+            display("Error while deserializing the index")
+        }
+
+        JournalSerializationError {
+            description("Error while serializing the transaction journal")
+            display("Error while serializing the transaction journal")
+        }
+
+        JournalDeserializationError {
+            description("Error while deserializing the transaction journal")
+            display("Error while deserializing the transaction journal")
+        }
+
+        JournalReplayError {
+            description("Error while replaying the transaction journal")
+            display("Error while replaying the transaction journal")
+        }
+
+        TransactionCommitError {
+            description("Error while committing a transaction")
+            display("Error while committing a transaction")
+        }
+
+        TransactionRollbackError {
+            description("Error while rolling back a transaction")
+            display("Error while rolling back a transaction")
+        }
+
+        BackendError {
+            description("Error in the store backend")
+            display("Error in the store backend")
+        }
+
+        UnknownBackend(name: String) {
+            description("Unknown store backend")
+            display("Unknown store backend: '{}'", name)
+        }
+
+        HistoryError {
+            description("Error while handling the revision history of an entry")
+            display("Error while handling the revision history of an entry")
+        }
+
+        RevisionNotFound(sid: StoreId, rev: u64) {
+            description("Revision not found")
+            display("Revision {} of {} not found", rev, sid)
+        }
+
+        SchemaParseError(collection: String) {
+            description("Invalid header schema")
+            display("Invalid header schema for collection '{}'", collection)
+        }
+
+        SchemaError {
+            description("Error while handling header schemas")
+            display("Error while handling header schemas")
+        }
+
+        HeaderSchemaViolation(sid: StoreId, violations: String) {
+            description("Header violates the schema of the collection")
+            display("Header of {} violates the schema of its collection: {}", sid, violations)
+        }
+
+        HookError(name: String) {
+            description("Hook failed")
+            display("Hook '{}' failed", name)
+        }
+
+        HookAborted(position: &'static str) {
+            description("Operation aborted by hook")
+            display("Operation aborted by {} hook", position)
+        }
+
+        LockFileError(path: PathBuf) {
+            description("Error while handling lock file")
+            display("Error while handling lock file {:?}", path)
+        }
+
+        EntryLocked(sid: StoreId, holder: String) {
+            description("Entry is locked by another process")
+            display("Entry {} is locked by another process ({})", sid, holder)
+        }
+
+        EntryChanged(sid: StoreId) {
+            description("Entry was changed by another process")
+            display("Entry {} was changed by another process since it was read", sid)
+        }
+
+        StoreLocked(holder: String) {
+            description("Store is locked by another process")
+            display("Store is locked by another process ({})", holder)
+        }
+
+        InvalidVersion(version: String) {
+            description("Invalid version")
+            display("Invalid version: '{}'", version)
+        }
+
+        MigrationError(sid: StoreId, migration: String) {
+            description("Migration failed")
+            display("Migration '{}' failed for {}", migration, sid)
+        }
+
+        MigrationNotApplicable(sid: StoreId, migration: String, version: String) {
+            description("Migration cannot be applied")
+            display("Migration '{}' cannot be applied to {}, which was written by version {}", migration, sid, version)
+        }
+
+        NoMigrations {
+            description("No migrations are registered")
+            display("No migrations are registered")
+        }
+
+        MigrationBackupError(sid: StoreId) {
+            description("Could not back up entry before migrating it")
+            display("Could not back up {} before migrating it", sid)
+        }
+
         // Parser-related errors
 
         MissingMainSection  {
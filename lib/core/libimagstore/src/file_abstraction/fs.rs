@@ -20,6 +20,7 @@
 use std::fs::{File, OpenOptions, create_dir_all, remove_file, copy, rename};
 use std::io::{Seek, SeekFrom, Read};
 use std::path::{Path, PathBuf};
+use std::collections::HashMap;
 
 use error::{StoreError as SE, StoreErrorKind as SEK};
 use error::ResultExt;
@@ -27,7 +28,9 @@ use error::ResultExt;
 use super::FileAbstraction;
 use super::FileAbstractionInstance;
 use super::Drain;
+use schema::SCHEMA_COLLECTION;
 use store::Entry;
+use store::SQLITE_DATABASE;
 use storeid::StoreId;
 use file_abstraction::iter::PathIterator;
 
@@ -100,6 +103,13 @@ impl FileAbstractionInstance for FSFileAbstractionInstance {
         }
         unreachable!();
     }
+
+    fn sync(&mut self) -> Result<(), SE> {
+        match *self {
+            FSFileAbstractionInstance::File(ref f, _) => f.sync_all().chain_err(|| SEK::FileNotWritten),
+            FSFileAbstractionInstance::Absent(_)      => Ok(()),
+        }
+    }
 }
 
 /// `FSFileAbstraction` state type
@@ -151,9 +161,26 @@ impl FileAbstraction for FSFileAbstraction {
         Box::new(FSFileAbstractionInstance::Absent(p))
     }
 
-    /// We return nothing from the FS here.
-    fn drain(&self) -> Result<Drain, SE> {
-        Ok(Drain::empty())
+    /// Read all entries below `basepath` from the filesystem
+    ///
+    /// Files which are not entries are skipped: the internal data of the store except for the
+    /// header schemas, hidden files and directories (like `.git`) and the SQLite database.
+    fn drain(&self, basepath: PathBuf) -> Result<Drain, SE> {
+        let mut entries = HashMap::new();
+
+        for path in self.pathes_recursively(basepath.clone())? {
+            if !path.is_file() || !is_entry_path(&basepath, &path) {
+                debug!("Not draining {:?}", path);
+                continue;
+            }
+
+            debug!("Draining {:?}", path);
+            let id    = StoreId::from_full_path(&basepath, path.clone())?;
+            let entry = self.new_instance(path.clone()).get_file_content(id)?;
+            let _     = entries.insert(path, entry);
+        }
+
+        Ok(Drain::new(entries))
     }
 
     /// FileAbstraction::fill implementation that consumes the Drain and writes everything to the
@@ -189,6 +216,26 @@ fn open_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<File> {
     OpenOptions::new().write(true).read(true).open(p)
 }
 
+/// Whether `path` below `basepath` can be an entry of the store
+fn is_entry_path(basepath: &Path, path: &Path) -> bool {
+    let local = match path.strip_prefix(basepath) {
+        Ok(local) => local,
+        Err(_)    => return false,
+    };
+
+    let hidden = local
+        .components()
+        .any(|c| c.as_os_str().to_str().map(|s| s.starts_with('.')).unwrap_or(true));
+
+    // The database and the files SQLite writes next to it, like "store.sqlite-journal"
+    let database = local
+        .to_str()
+        .map(|s| s.starts_with(SQLITE_DATABASE))
+        .unwrap_or(true);
+
+    (!hidden || local.starts_with(SCHEMA_COLLECTION)) && !database
+}
+
 fn create_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<File> {
     if let Some(parent) = p.as_ref().parent() {
         debug!("Implicitely creating directory: {:?}", parent);
@@ -196,6 +243,6 @@ fn create_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<File> {
             return Err(e);
         }
     }
-    OpenOptions::new().write(true).read(true).create(true).open(p)
+    OpenOptions::new().write(true).read(true).create(true).truncate(true).open(p)
 }
 
@@ -61,7 +61,7 @@ impl FileAbstractionInstance for InMemoryFileAbstractionInstance {
     /**
      * Get the mutable file behind a InMemoryFileAbstraction object
      */
-    fn get_file_content(&mut self, _: StoreId) -> Result<Entry, SE> {
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
         debug!("Getting lazy file: {:?}", self);
 
         self.fs_abstraction
@@ -70,7 +70,14 @@ impl FileAbstractionInstance for InMemoryFileAbstractionInstance {
             .and_then(|mut mtx| {
                 mtx.get_mut()
                     .get(&self.absent_path)
-                    .cloned()
+                    .map(|stored| {
+                        // The entry might have been renamed or copied, so it gets the id it is
+                        // requested with rather than the one it was written with
+                        let mut entry = Entry::new(id);
+                        *entry.get_header_mut()  = stored.get_header().clone();
+                        *entry.get_content_mut() = stored.get_content().clone();
+                        entry
+                    })
                     .ok_or_else(|| SE::from_kind(SEK::FileNotFound))
             })
     }
@@ -136,7 +143,7 @@ impl FileAbstraction for InMemoryFileAbstraction {
         let mut mtx = self.backend().lock().expect("Locking Mutex failed");
         let backend = mtx.get_mut();
 
-        let a = backend.get(from).cloned().ok_or_else(|| SE::from_kind(SEK::FileNotFound))?;
+        let a = backend.remove(from).ok_or_else(|| SE::from_kind(SEK::FileNotFound))?;
         backend.insert(to.clone(), a);
         debug!("Renaming: {:?} -> {:?} worked", from, to);
         Ok(())
@@ -164,7 +171,7 @@ impl FileAbstraction for InMemoryFileAbstraction {
         Box::new(InMemoryFileAbstractionInstance::new(self.backend().clone(), p))
     }
 
-    fn drain(&self) -> Result<Drain, SE> {
+    fn drain(&self, _basepath: PathBuf) -> Result<Drain, SE> {
         self.backend_cloned().map(Drain::new)
     }
 
@@ -17,22 +17,28 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::path::Path;
 use std::path::PathBuf;
 use std::fmt::Debug;
 use std::collections::HashMap;
 
 use error::StoreError as SE;
+use schema::SCHEMA_COLLECTION;
 use store::Entry;
+use store::INTERNAL_COLLECTION;
 use storeid::StoreId;
 
 mod fs;
 mod inmemory;
 mod iter;
+#[cfg(feature = "sqlite")] mod sqlite;
 
 pub use self::fs::FSFileAbstraction;
 pub use self::fs::FSFileAbstractionInstance;
 pub use self::inmemory::InMemoryFileAbstraction;
 pub use self::inmemory::InMemoryFileAbstractionInstance;
+#[cfg(feature = "sqlite")] pub use self::sqlite::SqliteFileAbstraction;
+#[cfg(feature = "sqlite")] pub use self::sqlite::SqliteFileAbstractionInstance;
 use self::iter::PathIterator;
 
 /// An abstraction trait over filesystem actions
@@ -47,7 +53,8 @@ pub trait FileAbstraction : Debug {
 
     fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance>;
 
-    fn drain(&self) -> Result<Drain, SE>;
+    /// Get all entries below `basepath` from the backend
+    fn drain(&self, basepath: PathBuf) -> Result<Drain, SE>;
     fn fill<'a>(&'a mut self, d: Drain) -> Result<(), SE>;
 
     fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE>;
@@ -62,6 +69,22 @@ pub trait FileAbstractionInstance : Debug {
     /// Entry type itself must be constructed with the id.
     fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE>;
     fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE>;
+
+    /// Make sure the written content is persisted, even if the system crashes
+    ///
+    /// Backends which do not buffer writes do nothing here.
+    fn sync(&mut self) -> Result<(), SE> {
+        Ok(())
+    }
+}
+
+/// Whether the file at `local` (relative to the store path) is moved along when the store is
+/// migrated to another backend
+///
+/// These are the entries and, of the internal data of the store, the header schemas. The
+/// full-text index is rebuilt when it is used next, the history and the journal are dropped.
+pub(crate) fn is_migrated(local: &Path) -> bool {
+    !local.starts_with(INTERNAL_COLLECTION) || local.starts_with(SCHEMA_COLLECTION)
 }
 
 pub struct Drain(HashMap<PathBuf, Entry>);
@@ -166,5 +189,27 @@ baz
         assert_eq!(bah.get_content(), "Hello World\nbaz\n\n");
     }
 
+    #[test]
+    fn fs_file_overwritten_by_new_instance_is_truncated() {
+        use tempdir::TempDir;
+        use super::FileAbstraction;
+        use super::fs::FSFileAbstraction;
+
+        let dir  = TempDir::new("imag-fs-abstraction").unwrap();
+        let fs   = FSFileAbstraction::default();
+        let path = dir.path().join("test1");
+        let loca = StoreId::new_baseless(PathBuf::from("test1")).unwrap();
+
+        let mut file = Entry::new(loca.clone());
+        *file.get_content_mut() = String::from("A rather long content");
+        fs.new_instance(path.clone()).write_file_content(&file).unwrap();
+
+        *file.get_content_mut() = String::from("Short");
+        fs.new_instance(path.clone()).write_file_content(&file).unwrap();
+
+        let bah = fs.new_instance(path).get_file_content(loca).unwrap();
+        assert_eq!(bah.get_content(), "Short");
+    }
+
 }
 
@@ -0,0 +1,433 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A backend which keeps all entries in a single SQLite database
+//!
+//! The entries are stored in one table, keyed by their path relative to the store path. This way,
+//! the database file can be moved around and used for a store at another location.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+use rusqlite::Error as SqliteError;
+use rusqlite::Row;
+
+use error::{StoreError as SE, StoreErrorKind as SEK};
+use error::ResultExt;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use super::is_migrated;
+use store::Entry;
+use storeid::StoreId;
+use file_abstraction::iter::PathIterator;
+
+type Backend = Arc<Mutex<Connection>>;
+
+const SCHEMA : &'static str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        path    TEXT PRIMARY KEY NOT NULL,
+        content TEXT NOT NULL
+    );
+";
+
+/// An instance of an entry in the SQLite backend
+#[derive(Debug)]
+pub struct SqliteFileAbstractionInstance {
+    connection: Backend,
+    base: PathBuf,
+    path: PathBuf,
+}
+
+impl FileAbstractionInstance for SqliteFileAbstractionInstance {
+
+    fn get_file_content(&mut self, id: StoreId) -> Result<Entry, SE> {
+        debug!("Getting from database: {:?}", self.path);
+        let key  = key(&self.base, &self.path)?;
+        let conn = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        let content : String = conn
+            .query_row("SELECT content FROM entries WHERE path = ?1", &[&key], |row| row.get(0))
+            .map_err(|e| match e {
+                SqliteError::QueryReturnedNoRows => SE::from_kind(SEK::FileNotFound),
+                e => SE::with_chain(e, SEK::BackendError),
+            })?;
+
+        Entry::from_str(id, &content)
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<(), SE> {
+        debug!("Writing to database: {:?}", self.path);
+        let key     = key(&self.base, &self.path)?;
+        let content = buf.to_str()?;
+        let conn    = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        conn.execute("INSERT OR REPLACE INTO entries (path, content) VALUES (?1, ?2)",
+                     &[&key, &content])
+            .map(|_| ())
+            .chain_err(|| SEK::BackendError)
+            .chain_err(|| SEK::FileNotWritten)
+    }
+
+}
+
+/// A `FileAbstraction` which keeps all entries in a SQLite database
+///
+/// The paths passed to the functions of this backend must be inside of `base`, which is the path
+/// of the store.
+#[derive(Debug)]
+pub struct SqliteFileAbstraction {
+    connection: Backend,
+    base: PathBuf,
+}
+
+impl SqliteFileAbstraction {
+
+    /// Open (or create) the database at `database` for the store at `base`
+    pub fn new<P: AsRef<Path>>(database: P, base: PathBuf) -> Result<SqliteFileAbstraction, SE> {
+        debug!("Opening database {:?}", database.as_ref());
+        Connection::open(database)
+            .chain_err(|| SEK::BackendError)
+            .and_then(|conn| SqliteFileAbstraction::with_connection(conn, base))
+    }
+
+    /// Create a backend with a database which only lives in memory
+    pub fn in_memory(base: PathBuf) -> Result<SqliteFileAbstraction, SE> {
+        Connection::open_in_memory()
+            .chain_err(|| SEK::BackendError)
+            .and_then(|conn| SqliteFileAbstraction::with_connection(conn, base))
+    }
+
+    fn with_connection(conn: Connection, base: PathBuf) -> Result<SqliteFileAbstraction, SE> {
+        let _ = conn.execute_batch(SCHEMA).chain_err(|| SEK::BackendError)?;
+
+        Ok(SqliteFileAbstraction {
+            connection: Arc::new(Mutex::new(conn)),
+            base,
+        })
+    }
+
+    /// Execute a statement which is expected to alter exactly one row
+    ///
+    /// Fails with `FileNotFound` if no row was altered.
+    fn execute_on_one(&self, sql: &str, from: &PathBuf, to: Option<&PathBuf>) -> Result<(), SE> {
+        let from = key(&self.base, from)?;
+        let conn = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        let altered = match to {
+            Some(to) => conn.execute(sql, &[&from, &key(&self.base, to)?]),
+            None     => conn.execute(sql, &[&from]),
+        }.chain_err(|| SEK::BackendError)?;
+
+        if altered == 0 {
+            Err(SE::from_kind(SEK::FileNotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Select `columns` of all rows below `basepath`, ordered by their path
+    ///
+    /// The keys below a "directory" are selected by their range instead of their prefix, so that
+    /// SQLite can use the index of the primary key.
+    fn select_below<T, F>(&self, columns: &str, basepath: &PathBuf, f: F) -> Result<Vec<T>, SE>
+        where F: FnMut(&Row) -> T
+    {
+        let key  = key(&self.base, basepath)?;
+        let conn = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        match dir_range(&key) {
+            Some((from, to)) => {
+                let sql = format!("SELECT {} FROM entries WHERE path >= ?1 AND path < ?2 ORDER BY path",
+                                  columns);
+                let mut stmt = conn.prepare(&sql).chain_err(|| SEK::BackendError)?;
+                let rows     = stmt.query_map(&[&from, &to], f).chain_err(|| SEK::BackendError)?;
+                rows.map(|r| r.chain_err(|| SEK::BackendError)).collect()
+            },
+            None => {
+                let sql      = format!("SELECT {} FROM entries ORDER BY path", columns);
+                let mut stmt = conn.prepare(&sql).chain_err(|| SEK::BackendError)?;
+                let rows     = stmt.query_map(&[], f).chain_err(|| SEK::BackendError)?;
+                rows.map(|r| r.chain_err(|| SEK::BackendError)).collect()
+            },
+        }
+    }
+
+}
+
+impl FileAbstraction for SqliteFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<(), SE> {
+        debug!("Removing from database: {:?}", path);
+        self.execute_on_one("DELETE FROM entries WHERE path = ?1", path, None)
+            .chain_err(|| SEK::FileNotRemoved)
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        debug!("Copying in database: {:?} -> {:?}", from, to);
+        self.execute_on_one("INSERT OR REPLACE INTO entries (path, content)
+                             SELECT ?2, content FROM entries WHERE path = ?1", from, Some(to))
+            .chain_err(|| SEK::FileNotCopied)
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<(), SE> {
+        debug!("Renaming in database: {:?} -> {:?}", from, to);
+        if !self.is_file(from)? {
+            return Err(SE::from_kind(SEK::FileNotFound)).chain_err(|| SEK::FileNotRenamed);
+        }
+
+        if from != to && self.is_file(to)? {
+            let _ = self.remove_file(to).chain_err(|| SEK::FileNotRenamed)?;
+        }
+
+        self.execute_on_one("UPDATE entries SET path = ?2 WHERE path = ?1", from, Some(to))
+            .chain_err(|| SEK::FileNotRenamed)
+    }
+
+    fn create_dir_all(&self, _: &PathBuf) -> Result<(), SE> {
+        Ok(())
+    }
+
+    /// Check whether there is an entry at `path` or an entry below `path`, which makes `path` a
+    /// "directory"
+    fn exists(&self, path: &PathBuf) -> Result<bool, SE> {
+        let key = key(&self.base, path)?;
+        if key.is_empty() {
+            return Ok(true); // the store itself
+        }
+
+        let (from, to) = dir_range(&key).unwrap(); // the key is not empty
+        let conn       = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM entries
+                                      WHERE path = ?1 OR (path >= ?2 AND path < ?3))",
+                       &[&key, &from, &to],
+                       |row| row.get(0))
+            .chain_err(|| SEK::BackendError)
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool, SE> {
+        let key  = key(&self.base, path)?;
+        let conn = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM entries WHERE path = ?1)",
+                       &[&key],
+                       |row| row.get(0))
+            .chain_err(|| SEK::BackendError)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(SqliteFileAbstractionInstance {
+            connection: self.connection.clone(),
+            base: self.base.clone(),
+            path: p,
+        })
+    }
+
+    /// Read all entries below `basepath` from the database
+    ///
+    /// Of the internal data of the store, only the header schemas are read.
+    fn drain(&self, basepath: PathBuf) -> Result<Drain, SE> {
+        let mut entries = HashMap::new();
+        let rows        = self.select_below("path, content", &basepath, |row| {
+            (row.get::<_, String>(0), row.get::<_, String>(1))
+        })?;
+
+        for (key, content) in rows {
+            if !is_migrated(Path::new(&key)) {
+                debug!("Not draining {:?}", key);
+                continue;
+            }
+
+            let path  = self.base.join(key);
+            let id    = StoreId::from_full_path(&self.base, path.clone())?;
+            let entry = Entry::from_str(id, &content)?;
+            let _     = entries.insert(path, entry);
+        }
+
+        Ok(Drain::new(entries))
+    }
+
+    fn fill<'a>(&'a mut self, mut d: Drain) -> Result<(), SE> {
+        debug!("Draining into : {:?}", self);
+        let mut conn = self.connection.lock().map_err(|_| SE::from_kind(SEK::LockError))?;
+        let tx       = conn.transaction().chain_err(|| SEK::BackendError)?;
+
+        for (path, element) in d.iter() {
+            debug!("Drain into {:?}: {:?}", self.base, path);
+            let key     = key(&self.base, &path)?;
+            let content = element.to_str()?;
+
+            let _ = tx
+                .execute("INSERT OR REPLACE INTO entries (path, content) VALUES (?1, ?2)",
+                         &[&key, &content])
+                .chain_err(|| SEK::BackendError)?;
+        }
+
+        tx.commit().chain_err(|| SEK::BackendError)
+    }
+
+    fn pathes_recursively(&self, basepath: PathBuf) -> Result<PathIterator, SE> {
+        let pathes : Vec<PathBuf> = self
+            .select_below("path", &basepath, |row| row.get::<_, String>(0))?
+            .into_iter()
+            .map(|key| self.base.join(key))
+            .collect();
+
+        Ok(PathIterator::new(Box::new(pathes.into_iter())))
+    }
+
+}
+
+/// Get the key of `path` in the database, which is the path relative to `base`
+fn key(base: &PathBuf, path: &PathBuf) -> Result<String, SE> {
+    path.strip_prefix(base)
+        .chain_err(|| SEK::StoreIdBuildFromFullPathError)?
+        .to_str()
+        .map(String::from)
+        .ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))
+}
+
+/// Get the range of the keys below the "directory" `key`, `None` if `key` is the store itself
+///
+/// All keys below "a" start with "a/", so they sort at or after "a/" and before "a0", as '0'
+/// directly follows '/'.
+fn dir_range(key: &str) -> Option<(String, String)> {
+    if key.is_empty() {
+        None
+    } else {
+        Some((format!("{}/", key), format!("{}0", key)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use file_abstraction::FileAbstraction;
+    use store::Entry;
+    use storeid::StoreId;
+
+    fn backend() -> SqliteFileAbstraction {
+        SqliteFileAbstraction::in_memory(PathBuf::from("/store")).unwrap()
+    }
+
+    fn write(backend: &SqliteFileAbstraction, id: &str, content: &str) {
+        let id = StoreId::new(Some(PathBuf::from("/store")), PathBuf::from(id)).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(content);
+        backend.new_instance(id.into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+    }
+
+    fn read(backend: &SqliteFileAbstraction, id: &str) -> Option<String> {
+        let id = StoreId::new(Some(PathBuf::from("/store")), PathBuf::from(id)).unwrap();
+        backend.new_instance(id.clone().into_pathbuf().unwrap())
+            .get_file_content(id)
+            .ok()
+            .map(|e| e.get_content().clone())
+    }
+
+    fn pb(s: &str) -> PathBuf {
+        PathBuf::from(s)
+    }
+
+    #[test]
+    fn test_write_read_overwrite() {
+        let backend = backend();
+        write(&backend, "a/b", "Hello World");
+        assert_eq!(read(&backend, "a/b"), Some(String::from("Hello World")));
+
+        write(&backend, "a/b", "Bye");
+        assert_eq!(read(&backend, "a/b"), Some(String::from("Bye")));
+        assert_eq!(read(&backend, "a/c"), None);
+    }
+
+    #[test]
+    fn test_exists_and_is_file() {
+        let backend = backend();
+        write(&backend, "a/b", "");
+
+        assert!(backend.exists(&pb("/store/a/b")).unwrap());
+        assert!(backend.exists(&pb("/store/a")).unwrap());
+        assert!(backend.exists(&pb("/store")).unwrap());
+        assert!(!backend.exists(&pb("/store/a/bc")).unwrap());
+        assert!(!backend.exists(&pb("/store/ab")).unwrap());
+
+        assert!(backend.is_file(&pb("/store/a/b")).unwrap());
+        assert!(!backend.is_file(&pb("/store/a")).unwrap());
+    }
+
+    #[test]
+    fn test_remove_copy_rename() {
+        let backend = backend();
+        write(&backend, "a", "content");
+
+        assert!(backend.copy(&pb("/store/a"), &pb("/store/b")).is_ok());
+        assert_eq!(read(&backend, "b"), Some(String::from("content")));
+
+        assert!(backend.rename(&pb("/store/b"), &pb("/store/c")).is_ok());
+        assert_eq!(read(&backend, "b"), None);
+        assert_eq!(read(&backend, "c"), Some(String::from("content")));
+
+        assert!(backend.remove_file(&pb("/store/a")).is_ok());
+        assert!(!backend.exists(&pb("/store/a")).unwrap());
+
+        assert!(backend.remove_file(&pb("/store/a")).is_err());
+        assert!(backend.rename(&pb("/store/a"), &pb("/store/d")).is_err());
+        assert!(backend.copy(&pb("/store/a"), &pb("/store/d")).is_err());
+    }
+
+    #[test]
+    fn test_pathes_recursively() {
+        let backend = backend();
+        write(&backend, "a/b", "");
+        write(&backend, "a/c", "");
+        write(&backend, "ab", "");
+
+        let all : Vec<PathBuf> = backend.pathes_recursively(pb("/store")).unwrap().collect();
+        assert_eq!(all, vec![pb("/store/a/b"), pb("/store/a/c"), pb("/store/ab")]);
+
+        let a : Vec<PathBuf> = backend.pathes_recursively(pb("/store/a")).unwrap().collect();
+        assert_eq!(a, vec![pb("/store/a/b"), pb("/store/a/c")]);
+    }
+
+    #[test]
+    fn test_drain_fill() {
+        let backend = backend();
+        write(&backend, "a", "one");
+        write(&backend, "b/c", "two");
+        write(&backend, ".store/index", "index");
+        write(&backend, ".store/schema/a", "schema");
+
+        let mut other = SqliteFileAbstraction::in_memory(PathBuf::from("/store")).unwrap();
+        assert!(other.fill(backend.drain(pb("/store")).unwrap()).is_ok());
+
+        assert_eq!(read(&other, "a"), Some(String::from("one")));
+        assert_eq!(read(&other, "b/c"), Some(String::from("two")));
+        assert_eq!(read(&other, ".store/index"), None);
+        assert_eq!(read(&other, ".store/schema/a"), Some(String::from("schema")));
+    }
+
+}
@@ -0,0 +1,165 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Revision history of entries
+//!
+//! If the history is enabled, the store keeps the prior versions of an entry each time the entry
+//! is overwritten or deleted. Each revision is stored as an entry in the internal collection of
+//! the store, at `.store/history/<id>/<number>`, where the content of the revision entry is the
+//! complete prior version (header and content) of the entry.
+//!
+//! Revisions are numbered per entry, starting at 1. Higher numbers are newer revisions.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadTypeExt;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The collection in which the revisions of entries are kept
+pub const HISTORY_COLLECTION : &'static str = ".store/history";
+
+/// Header key under which the time a revision was recorded is stored in the revision entry
+const TIMESTAMP_KEY : &'static str = "revision.timestamp";
+
+/// A revision of an entry
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Revision {
+    number: u64,
+    timestamp: Option<i64>,
+}
+
+impl Revision {
+
+    /// The number of the revision
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// The time (in seconds since the unix epoch) at which this revision was replaced by a newer
+    /// version of the entry, if known
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+}
+
+/// Get the id (with base) of the directory in which the revisions of `id` are kept
+pub(crate) fn history_dir(base: &PathBuf, id: &StoreId) -> Result<StoreId> {
+    let mut local = PathBuf::from(HISTORY_COLLECTION);
+    local.push(id.local());
+    StoreId::new(Some(base.clone()), local)
+}
+
+/// Get the id (with base) of the entry in which revision `number` of `id` is kept
+pub(crate) fn revision_id(base: &PathBuf, id: &StoreId, number: u64) -> Result<StoreId> {
+    let mut local = PathBuf::from(HISTORY_COLLECTION);
+    local.push(id.local());
+    local.push(format!("{}", number));
+    StoreId::new(Some(base.clone()), local)
+}
+
+/// Get the revision number from the path of a revision entry, if the path is directly in `dir`
+pub(crate) fn revision_number(dir: &PathBuf, path: &PathBuf) -> Option<u64> {
+    if path.parent() != Some(dir) {
+        return None;
+    }
+
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse::<u64>().ok())
+}
+
+/// Build the entry which keeps `old` as revision `rid`
+pub(crate) fn revision_entry(rid: StoreId, old: &Entry) -> Result<Entry> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let mut entry = Entry::new(rid);
+    let _ = entry
+        .get_header_mut()
+        .insert(TIMESTAMP_KEY, Value::Integer(timestamp))
+        .chain_err(|| SEK::HistoryError)?;
+    *entry.get_content_mut() = old.to_str()?;
+    Ok(entry)
+}
+
+/// Get the `Revision` object describing the revision entry `rev`
+pub(crate) fn revision_from_entry(number: u64, rev: &Entry) -> Result<Revision> {
+    let timestamp = rev
+        .get_header()
+        .read_int(TIMESTAMP_KEY)
+        .chain_err(|| SEK::HistoryError)?;
+
+    Ok(Revision { number, timestamp })
+}
+
+/// Get the version of the entry `id` which is kept in the revision entry `rev`
+pub(crate) fn entry_from_revision(id: StoreId, rev: &Entry) -> Result<Entry> {
+    Entry::from_str(id, rev.get_content()).chain_err(|| SEK::HistoryError)
+}
+
+/// Helper to build an error for a non-existing revision
+pub(crate) fn revision_not_found(id: &StoreId, number: u64) -> SE {
+    SE::from_kind(SEK::RevisionNotFound(id.clone(), number))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_revision_number() {
+        let dir = PathBuf::from("/s/.store/history/a");
+
+        assert_eq!(revision_number(&dir, &PathBuf::from("/s/.store/history/a/3")), Some(3));
+        assert_eq!(revision_number(&dir, &PathBuf::from("/s/.store/history/a/b/3")), None);
+        assert_eq!(revision_number(&dir, &PathBuf::from("/s/.store/history/a/x")), None);
+        assert_eq!(revision_number(&dir, &PathBuf::from("/s/.store/history/b/3")), None);
+    }
+
+    #[test]
+    fn test_revision_entry_roundtrip() {
+        let base = PathBuf::from("/s");
+        let id   = StoreId::new(Some(base.clone()), PathBuf::from("a")).unwrap();
+
+        let mut old = Entry::new(id.clone());
+        *old.get_content_mut() = String::from("old content");
+
+        let rid = revision_id(&base, &id, 1).unwrap();
+        let rev = revision_entry(rid, &old).unwrap();
+
+        assert!(revision_from_entry(1, &rev).unwrap().timestamp().is_some());
+        assert_eq!(entry_from_revision(id, &rev).unwrap(), old);
+    }
+
+}
@@ -0,0 +1,392 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Hooks which are executed when entries are created, retrieved, updated, deleted or moved
+//!
+//! A `Hook` is registered for a `HookPosition` with `Store::register_hook()`. Hooks for a "pre"
+//! position are executed before the store operation and can abort it by returning an error. Hooks
+//! for a "post" position are executed after the operation succeeded, their errors are only
+//! reported, as the operation cannot be undone anymore.
+//!
+//! The data a hook receives depends on its position:
+//!
+//! * `pre-create`, `pre-retrieve`, `pre-delete`, `post-delete`: only the id of the entry
+//!   (`HookData::Id`), as there is no entry (yet or anymore) at this point.
+//! * `post-create`: the freshly created entry, which has the default header and no content. The
+//!   content is passed to the update hooks when the entry is written.
+//! * `post-retrieve`: the entry as it was read from the store.
+//! * `pre-update`, `post-update`: the entry as it is written. These hooks are only executed if the
+//!   entry was mutably borrowed since it was retrieved or last written, not for every entry which
+//!   is dropped.
+//! * `pre-move`, `post-move`: the old and the new id (`HookData::Move`).
+//!
+//! The `ExternalCommandHook` executes a command and passes the entry as JSON object on stdin.
+//! Its stdout is forwarded to the log, so it does not end up in the output of the imag command.
+//! These hooks can be configured in the `[store.hooks]` section of the configuration file:
+//!
+//! ```toml
+//! [store.hooks]
+//! post-update = [ { command = "/path/to/script", args = [ "--foo" ] } ]
+//! ```
+
+use std::fmt::Debug;
+use std::io::ErrorKind as IoErrorKind;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+use serde_json;
+use toml::Value;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The point in a store operation at which a hook is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPosition {
+    PreCreate,
+    PostCreate,
+    PreRetrieve,
+    PostRetrieve,
+    PreUpdate,
+    PostUpdate,
+    PreDelete,
+    PostDelete,
+    PreMove,
+    PostMove,
+}
+
+impl HookPosition {
+
+    pub fn from_str(s: &str) -> Option<HookPosition> {
+        match s {
+            "pre-create"    => Some(HookPosition::PreCreate),
+            "post-create"   => Some(HookPosition::PostCreate),
+            "pre-retrieve"  => Some(HookPosition::PreRetrieve),
+            "post-retrieve" => Some(HookPosition::PostRetrieve),
+            "pre-update"    => Some(HookPosition::PreUpdate),
+            "post-update"   => Some(HookPosition::PostUpdate),
+            "pre-delete"    => Some(HookPosition::PreDelete),
+            "post-delete"   => Some(HookPosition::PostDelete),
+            "pre-move"      => Some(HookPosition::PreMove),
+            "post-move"     => Some(HookPosition::PostMove),
+            _               => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            HookPosition::PreCreate    => "pre-create",
+            HookPosition::PostCreate   => "post-create",
+            HookPosition::PreRetrieve  => "pre-retrieve",
+            HookPosition::PostRetrieve => "post-retrieve",
+            HookPosition::PreUpdate    => "pre-update",
+            HookPosition::PostUpdate   => "post-update",
+            HookPosition::PreDelete    => "pre-delete",
+            HookPosition::PostDelete   => "post-delete",
+            HookPosition::PreMove      => "pre-move",
+            HookPosition::PostMove     => "post-move",
+        }
+    }
+
+    /// Whether hooks at this position are executed before the operation and can abort it
+    pub fn is_pre(&self) -> bool {
+        match *self {
+            HookPosition::PreCreate   |
+            HookPosition::PreRetrieve |
+            HookPosition::PreUpdate   |
+            HookPosition::PreDelete   |
+            HookPosition::PreMove     => true,
+            _                         => false,
+        }
+    }
+
+}
+
+/// The data a hook gets to see
+#[derive(Debug)]
+pub enum HookData<'a> {
+    /// The id of the entry, for operations where the entry is not (yet) available
+    Id(&'a StoreId),
+
+    /// The entry itself
+    Entry(&'a Entry),
+
+    /// The ids of a moved entry
+    Move {
+        old: &'a StoreId,
+        new: &'a StoreId,
+    },
+}
+
+impl<'a> HookData<'a> {
+
+    /// The id of the entry the hook is executed for, the old id if the entry is moved
+    pub fn id(&self) -> &StoreId {
+        match *self {
+            HookData::Id(id)             => id,
+            HookData::Entry(entry)       => entry.get_location(),
+            HookData::Move { old, .. }   => old,
+        }
+    }
+
+    /// Get the JSON object which is passed to external hooks
+    ///
+    /// The object contains the keys "position", "id", and, depending on the operation, "entry"
+    /// (with "header" and "content") or "new_id".
+    pub fn to_json(&self, position: HookPosition) -> Result<serde_json::Value> {
+        use serde_json::Value as JV;
+        use serde_json::Map;
+
+        let id_to_json = |id: &StoreId| -> Result<JV> {
+            id.local().to_str().map(|s| JV::String(String::from(s))).ok_or_else(|| {
+                SE::from_kind(SEK::StoreIdHandlingError)
+            })
+        };
+
+        let mut map = Map::new();
+        let _ = map.insert(String::from("position"), JV::String(String::from(position.as_str())));
+        let _ = map.insert(String::from("id"), id_to_json(self.id())?);
+
+        match *self {
+            HookData::Id(_) => {},
+            HookData::Entry(entry) => {
+                let header = serde_json::to_value(entry.get_header()).chain_err(|| SEK::EncodingError)?;
+
+                let mut e = Map::new();
+                let _ = e.insert(String::from("header"), header);
+                let _ = e.insert(String::from("content"), JV::String(entry.get_content().clone()));
+                let _ = map.insert(String::from("entry"), JV::Object(e));
+            },
+            HookData::Move { new, .. } => {
+                let _ = map.insert(String::from("new_id"), id_to_json(new)?);
+            },
+        }
+
+        Ok(JV::Object(map))
+    }
+
+}
+
+/// A hook which is executed by the store
+pub trait Hook : Debug + Send + Sync {
+
+    /// The name of the hook, used in messages
+    fn name(&self) -> &str;
+
+    /// Execute the hook
+    ///
+    /// If the hook is executed before an operation, returning an error aborts the operation.
+    fn execute(&self, position: HookPosition, data: &HookData) -> Result<()>;
+
+}
+
+/// A hook which executes an external command
+///
+/// The command gets the `HookData` as JSON object on stdin (see `HookData::to_json()`), the
+/// hook fails if the command exits with a non-zero exit code. What the command prints on stdout is
+/// logged, the command does not write to the stdout of imag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalCommandHook {
+    command: String,
+    args: Vec<String>,
+    working_dir: Option<PathBuf>,
+}
+
+impl ExternalCommandHook {
+
+    pub fn new<S: Into<String>>(command: S, args: Vec<String>) -> ExternalCommandHook {
+        ExternalCommandHook {
+            command: command.into(),
+            args: args,
+            working_dir: None,
+        }
+    }
+
+    /// Execute the command in `dir` instead of the current working directory
+    pub fn with_working_dir(mut self, dir: PathBuf) -> ExternalCommandHook {
+        self.working_dir = Some(dir);
+        self
+    }
+
+    /// Read a hook from the configuration, a table with the keys "command" and "args" (optional)
+    pub fn from_toml(v: &Value) -> Result<ExternalCommandHook> {
+        let error   = || SE::from_kind(SEK::ConfigTypeError("store.hooks", "table with command"));
+        let command = v.get("command").and_then(Value::as_str).ok_or_else(&error)?;
+        let args    = match v.get("args") {
+            None => vec![],
+            Some(&Value::Array(ref args)) => args
+                .iter()
+                .map(|a| a.as_str().map(String::from).ok_or_else(&error))
+                .collect::<Result<Vec<String>>>()?,
+            Some(_) => return Err(error()),
+        };
+
+        Ok(ExternalCommandHook::new(command, args))
+    }
+
+}
+
+impl Hook for ExternalCommandHook {
+
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn execute(&self, position: HookPosition, data: &HookData) -> Result<()> {
+        let error = || SEK::HookError(self.command.clone());
+        let json  = data.to_json(position)?;
+
+        debug!("Executing hook {} {:?} for {}", self.command, self.args, data.id());
+        let mut command = Command::new(&self.command);
+        let _ = command
+            .args(&self.args)
+            .env("IMAG_HOOK_POSITION", position.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+
+        if let Some(ref dir) = self.working_dir {
+            let _ = command.current_dir(dir);
+        }
+
+        let mut input = serde_json::to_vec(&json).chain_err(&error)?;
+        input.push(b'\n');
+
+        let mut child = command.spawn().chain_err(&error)?;
+        let mut stdin = child.stdin.take().ok_or_else(|| SE::from_kind(error()))?;
+
+        // The input is written while the output is read, so that neither the command nor we block
+        // on a full pipe. The handle is dropped when the thread ends, so the command sees EOF on
+        // stdin. A command which exits without reading its input closes the pipe, which is fine.
+        let writer = thread::spawn(move || match stdin.write_all(&input) {
+            Err(ref e) if e.kind() == IoErrorKind::BrokenPipe => Ok(()),
+            other => other,
+        });
+
+        let output = child.wait_with_output().chain_err(&error)?;
+        let _      = writer
+            .join()
+            .map_err(|_| SE::from_kind(error()))?
+            .chain_err(&error)?;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            info!("{}: {}", self.command, line);
+        }
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            debug!("Hook {} exited with {}", self.command, output.status);
+            Err(SE::from_kind(error()))
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::de::from_str as toml_from_str;
+
+    use super::*;
+    use storeid::StoreId;
+
+    fn entry() -> Entry {
+        let id = StoreId::new(Some(PathBuf::from("/s")), PathBuf::from("a/b")).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from("content");
+        entry
+    }
+
+    #[test]
+    fn test_position_roundtrip() {
+        for pos in &[HookPosition::PreCreate, HookPosition::PostMove] {
+            assert_eq!(HookPosition::from_str(pos.as_str()), Some(*pos));
+        }
+        assert!(HookPosition::PreUpdate.is_pre());
+        assert!(!HookPosition::PostUpdate.is_pre());
+    }
+
+    #[test]
+    fn test_data_to_json() {
+        let entry = entry();
+        let json  = HookData::Entry(&entry).to_json(HookPosition::PostUpdate).unwrap();
+
+        assert_eq!(json["position"], "post-update");
+        assert_eq!(json["id"], "a/b");
+        assert_eq!(json["entry"]["content"], "content");
+        assert_eq!(json["entry"]["header"]["imag"]["version"], env!("CARGO_PKG_VERSION"));
+
+        let new  = StoreId::new(Some(PathBuf::from("/s")), PathBuf::from("c")).unwrap();
+        let old  = entry.get_location();
+        let json = HookData::Move { old, new: &new }.to_json(HookPosition::PreMove).unwrap();
+        assert_eq!(json["new_id"], "c");
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let v = toml_from_str(r#"
+        command = "notify"
+        args = [ "-v" ]
+        "#).unwrap();
+
+        let hook = ExternalCommandHook::from_toml(&v).unwrap();
+        assert_eq!(hook, ExternalCommandHook::new("notify", vec![String::from("-v")]));
+
+        assert!(ExternalCommandHook::from_toml(&toml_from_str("args = []").unwrap()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_command_exit_code() {
+        let entry = entry();
+        let data  = HookData::Entry(&entry);
+        let sh    = |script: &str| {
+            ExternalCommandHook::new("sh", vec![String::from("-c"), String::from(script)])
+        };
+
+        // The command gets the entry on stdin
+        let hook = sh(r#"grep -q '"content":"content"'"#);
+        assert!(hook.execute(HookPosition::PreUpdate, &data).is_ok());
+
+        // The output of the command is read, the command does not block on it
+        assert!(sh("cat; seq 1 100000").execute(HookPosition::PostUpdate, &data).is_ok());
+
+        // The command may write its output before reading all of its input
+        let mut big = Entry::new(entry.get_location().clone());
+        *big.get_content_mut() = ::std::iter::repeat('x').take(1 << 20).collect();
+        let big_data = HookData::Entry(&big);
+        assert!(sh("cat").execute(HookPosition::PostUpdate, &big_data).is_ok());
+
+        // The command does not have to read its input
+        assert!(sh("exit 0").execute(HookPosition::PostUpdate, &big_data).is_ok());
+
+        assert!(sh("exit 1").execute(HookPosition::PreUpdate, &data).is_err());
+        assert!(ExternalCommandHook::new("/nonexistent", vec![])
+                .execute(HookPosition::PreUpdate, &data)
+                .is_err());
+    }
+
+}
@@ -0,0 +1,395 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A full-text index over the content of the entries in the store
+//!
+//! The index maps each entry to the set of (lowercased) words in its content and holds the
+//! inverse mapping from word to entries in memory. Only the forward mapping is persisted, the
+//! inverse mapping is rebuilt when the index is loaded.
+//!
+//! The index is not a replacement for actually matching the content of an entry. It is only a
+//! tool to narrow down the set of entries which _might_ match a query: Every entry which
+//! contains a string consisting of word characters contains a word which has this string as
+//! substring.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use serde_json;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Result;
+use storeid::StoreId;
+
+/// The format version of the persisted index
+///
+/// If the format of the index changes, this number is increased and indexes of other versions
+/// are discarded and rebuilt.
+const INDEX_FORMAT_VERSION : u64 = 1;
+
+/// Split a text into the set of lowercased words in it.
+///
+/// A word is a maximal sequence of alphanumeric characters or underscores, which is what the
+/// `\w` character class of a regex matches.
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !is_word_char(c))
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Check whether a character is a word character as understood by the index
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The in-memory representation of the full-text index
+#[derive(Debug, Default)]
+pub struct Index {
+    /// Local part of the StoreId -> words in the content of the entry
+    documents: BTreeMap<String, BTreeSet<String>>,
+
+    /// Word -> Local part of the StoreIds of entries which contain the word
+    postings: BTreeMap<String, BTreeSet<String>>,
+
+    /// Local part of the StoreId -> new words, `None` if removed, for the entries altered since
+    /// the index was loaded or written
+    changes: BTreeMap<String, Option<BTreeSet<String>>>,
+}
+
+impl Index {
+
+    pub fn new() -> Index {
+        Index::default()
+    }
+
+    /// Load an index from its serialized form
+    ///
+    /// Returns `None` if the serialized index is of another format version and therefor has to be
+    /// rebuilt.
+    pub fn from_json(s: &str) -> Result<Option<Index>> {
+        let (version, documents) : (u64, BTreeMap<String, BTreeSet<String>>) =
+            serde_json::from_str(s).chain_err(|| SEK::IndexDeserializationError)?;
+
+        if version != INDEX_FORMAT_VERSION {
+            debug!("Index format version {} does not match {}", version, INDEX_FORMAT_VERSION);
+            return Ok(None);
+        }
+
+        let mut index = Index::new();
+        for (id, words) in documents {
+            index.insert_words(id, words);
+        }
+        index.set_clean();
+
+        Ok(Some(index))
+    }
+
+    /// Serialize the index
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&(INDEX_FORMAT_VERSION, &self.documents))
+            .chain_err(|| SEK::IndexSerializationError)
+    }
+
+    /// Whether the index was altered since it was loaded or written
+    pub fn is_dirty(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    pub fn set_clean(&mut self) {
+        self.changes.clear();
+    }
+
+    /// Replace this index by `base` with the changes made to this index applied on top
+    ///
+    /// `base` is the index as it was written by another process since this index was loaded, so
+    /// that writing this index does not drop the changes of the other process.
+    pub fn rebase(&mut self, mut base: Index) {
+        for (key, words) in self.changes.iter() {
+            let _ = base.remove_key(key);
+            if let Some(ref words) = *words {
+                base.insert_words(key.clone(), words.clone());
+            }
+        }
+
+        base.changes = ::std::mem::replace(&mut self.changes, BTreeMap::new());
+        *self = base;
+    }
+
+    /// The number of entries in the index
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Check whether an entry is in the index
+    pub fn contains(&self, id: &StoreId) -> bool {
+        local_key(id).map(|k| self.documents.contains_key(&k)).unwrap_or(false)
+    }
+
+    /// Insert (or replace) the content of the entry `id` in the index
+    pub fn insert(&mut self, id: &StoreId, content: &str) -> Result<()> {
+        let key = local_key(id)?;
+        let words = tokenize(content);
+
+        if self.documents.get(&key).map(|old| *old == words).unwrap_or(false) {
+            trace!("Index for {} unchanged", key);
+            return Ok(());
+        }
+
+        self.remove_key(&key);
+        self.insert_words(key, words);
+        Ok(())
+    }
+
+    /// Remove the entry `id` from the index
+    pub fn remove(&mut self, id: &StoreId) -> Result<()> {
+        let key = local_key(id)?;
+        self.remove_key(&key);
+        Ok(())
+    }
+
+    /// Move the indexed content of the entry `old` to `new`
+    pub fn rename(&mut self, old: &StoreId, new: &StoreId) -> Result<()> {
+        let old = local_key(old)?;
+        let new = local_key(new)?;
+
+        if let Some(words) = self.remove_key(&old) {
+            self.remove_key(&new);
+            self.insert_words(new, words);
+        }
+
+        Ok(())
+    }
+
+    /// Copy the indexed content of the entry `from` to `to`
+    pub fn copy(&mut self, from: &StoreId, to: &StoreId) -> Result<()> {
+        let from = local_key(from)?;
+        let to   = local_key(to)?;
+
+        if let Some(words) = self.documents.get(&from).cloned() {
+            self.remove_key(&to);
+            self.insert_words(to, words);
+        }
+
+        Ok(())
+    }
+
+    /// Find all entries which possibly contain all of the `fragments`
+    ///
+    /// Each fragment must consist of word characters only (see `is_word_char()`), fragments which
+    /// do not are ignored. The lookup is case-insensitive.
+    ///
+    /// Returns the StoreIds (without base) of all entries which contain, for each fragment, a word
+    /// which contains the fragment.
+    pub fn candidates<S: AsRef<str>>(&self, fragments: &[S]) -> Result<Vec<StoreId>> {
+        let mut result : Option<BTreeSet<&String>> = None;
+
+        for fragment in fragments.iter().map(AsRef::as_ref) {
+            if fragment.is_empty() || !fragment.chars().all(is_word_char) {
+                debug!("Ignoring fragment '{}' in index lookup", fragment);
+                continue;
+            }
+
+            let fragment = fragment.to_lowercase();
+            let matching : BTreeSet<&String> = self.postings
+                .iter()
+                .filter(|&(word, _)| word.contains(&fragment[..]))
+                .flat_map(|(_, ids)| ids.iter())
+                .collect();
+
+            result = Some(match result {
+                None       => matching,
+                Some(prev) => prev.intersection(&matching).cloned().collect(),
+            });
+        }
+
+        result
+            .map(|ids| ids.into_iter().collect::<Vec<_>>())
+            .unwrap_or_else(|| self.documents.keys().collect())
+            .into_iter()
+            .map(|key| StoreId::new_baseless(PathBuf::from(key)))
+            .collect()
+    }
+
+    fn insert_words(&mut self, key: String, words: BTreeSet<String>) {
+        for word in words.iter() {
+            let _ = self.postings
+                .entry(word.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(key.clone());
+        }
+
+        let _ = self.changes.insert(key.clone(), Some(words.clone()));
+        self.documents.insert(key, words);
+    }
+
+    fn remove_key(&mut self, key: &String) -> Option<BTreeSet<String>> {
+        // Recorded even if the entry is not in this index, it may be in the one of another process
+        let _     = self.changes.insert(key.clone(), None);
+        let words = self.documents.remove(key)?;
+
+        for word in words.iter() {
+            let now_empty = match self.postings.get_mut(word) {
+                Some(ids) => {
+                    let _ = ids.remove(key);
+                    ids.is_empty()
+                },
+                None => false,
+            };
+
+            if now_empty {
+                let _ = self.postings.remove(word);
+            }
+        }
+
+        Some(words)
+    }
+
+}
+
+fn local_key(id: &StoreId) -> Result<String> {
+    id.local()
+        .to_str()
+        .map(String::from)
+        .ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use storeid::StoreId;
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    fn candidates(index: &Index, fragments: &[&str]) -> Vec<String> {
+        index.candidates(fragments)
+            .unwrap()
+            .into_iter()
+            .map(|id| id.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let words : Vec<String> = tokenize("Hello, World! hello_world 42").into_iter().collect();
+        assert_eq!(words, vec!["42", "hello", "hello_world", "world"]);
+    }
+
+    #[test]
+    fn test_candidates_substring() {
+        let mut index = Index::new();
+        index.insert(&id("a"), "The quick brown fox").unwrap();
+        index.insert(&id("b"), "jumps over the lazy dog").unwrap();
+
+        assert_eq!(candidates(&index, &["uick"]), vec!["a"]);
+        assert_eq!(candidates(&index, &["THE"]), vec!["a", "b"]);
+        assert_eq!(candidates(&index, &["the", "dog"]), vec!["b"]);
+        assert!(candidates(&index, &["cat"]).is_empty());
+    }
+
+    #[test]
+    fn test_candidates_without_fragments_yields_all() {
+        let mut index = Index::new();
+        index.insert(&id("a"), "foo").unwrap();
+        index.insert(&id("b"), "bar").unwrap();
+
+        assert_eq!(candidates(&index, &[]), vec!["a", "b"]);
+        assert_eq!(candidates(&index, &["foo bar"]), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_reinsert_removes_old_words() {
+        let mut index = Index::new();
+        index.insert(&id("a"), "foo").unwrap();
+        index.insert(&id("a"), "bar").unwrap();
+
+        assert!(candidates(&index, &["foo"]).is_empty());
+        assert_eq!(candidates(&index, &["bar"]), vec!["a"]);
+    }
+
+    #[test]
+    fn test_remove_and_rename() {
+        let mut index = Index::new();
+        index.insert(&id("a"), "foo").unwrap();
+        index.insert(&id("b"), "foo").unwrap();
+
+        index.remove(&id("a")).unwrap();
+        assert_eq!(candidates(&index, &["foo"]), vec!["b"]);
+
+        index.rename(&id("b"), &id("c")).unwrap();
+        assert_eq!(candidates(&index, &["foo"]), vec!["c"]);
+        assert!(!index.contains(&id("b")));
+
+        index.copy(&id("c"), &id("d")).unwrap();
+        assert_eq!(candidates(&index, &["foo"]), vec!["c", "d"]);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut index = Index::new();
+        index.insert(&id("a/b"), "foo bar").unwrap();
+        index.insert(&id("c"), "bar baz").unwrap();
+
+        let json = index.to_json().unwrap();
+        let index = Index::from_json(&json).unwrap().unwrap();
+
+        assert!(!index.is_dirty());
+        assert_eq!(index.len(), 2);
+        assert_eq!(candidates(&index, &["bar"]), vec!["a/b", "c"]);
+        assert_eq!(candidates(&index, &["baz"]), vec!["c"]);
+    }
+
+    #[test]
+    fn test_rebase() {
+        let mut base = Index::new();
+        base.insert(&id("a"), "foo").unwrap();
+        base.insert(&id("b"), "foo").unwrap();
+        let json = base.to_json().unwrap();
+
+        let mut index = Index::from_json(&json).unwrap().unwrap();
+        index.insert(&id("a"), "bar").unwrap();
+        index.remove(&id("c")).unwrap();
+
+        // Another process changed the index in the meantime
+        base.insert(&id("c"), "foo").unwrap();
+        base.insert(&id("d"), "foo").unwrap();
+
+        index.rebase(Index::from_json(&base.to_json().unwrap()).unwrap().unwrap());
+        assert!(index.is_dirty());
+        assert_eq!(candidates(&index, &["foo"]), vec!["b", "d"]);
+        assert_eq!(candidates(&index, &["bar"]), vec!["a"]);
+    }
+
+    #[test]
+    fn test_json_other_version_is_discarded() {
+        assert!(Index::from_json("[0, {}]").unwrap().is_none());
+    }
+
+}
@@ -17,7 +17,7 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
-#![recursion_limit="256"]
+#![recursion_limit="512"]
 
 #![deny(
     dead_code,
@@ -46,6 +46,9 @@ extern crate walkdir;
 extern crate serde_json;
 #[macro_use] extern crate error_chain;
 extern crate toml_query;
+extern crate chrono;
+extern crate fs2;
+#[cfg(feature = "sqlite")] extern crate rusqlite;
 
 extern crate libimagerror;
 extern crate libimagutil;
@@ -56,6 +59,13 @@ pub mod storeid;
 pub mod error;
 pub mod iter;
 pub mod store;
+pub mod index;
+pub mod transaction;
+pub mod history;
+pub mod schema;
+pub mod hook;
+pub mod lock;
+pub mod migration;
 mod configuration;
 pub mod file_abstraction;
 
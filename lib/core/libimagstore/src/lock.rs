@@ -0,0 +1,295 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Locking the store and its entries against other processes
+//!
+//! While an entry is borrowed with `Store::create()` or `Store::retrieve()` (while a
+//! `FileLockEntry` for it exists), the store holds a lock file for it in `.store/locks/entries` in
+//! the store directory. Entries borrowed with `Store::get()` are only locked while they are
+//! written. Operations which change the
+//! structure of the store (deleting and moving entries, committing transactions) additionally hold
+//! the store lock `.store/locks/store`.
+//!
+//! A process which wants a lock which is held by another process waits until the lock is released
+//! or the configured timeout ("lock-timeout" in the store configuration) expired. In the latter
+//! case, it fails with `StoreErrorKind::EntryLocked` or `StoreErrorKind::StoreLocked`.
+//!
+//! Lock files are locked with the locking facility of the operating system (`flock()` on unix)
+//! while they are held, so a lock is released by the operating system if the process holding it
+//! dies. A lock file which exists but is not locked is stale and simply taken over.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::fs::create_dir_all;
+use std::fs::remove_file;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use fs2::FileExt;
+use fs2::lock_contended_error;
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadTypeExt;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The directory inside the store the lock files are kept in
+pub const LOCK_DIRECTORY : &'static str = ".store/locks";
+
+/// How long to wait between two attempts to get a lock which is held by another process
+const RETRY_INTERVAL_MS : u64 = 20;
+
+/// The locks of a store
+#[derive(Debug)]
+pub struct Locks {
+    store_location: PathBuf,
+    timeout: Duration,
+}
+
+impl Locks {
+
+    /// Locks for the store in `store_location`, waiting up to `timeout` for locks held by other
+    /// processes
+    pub fn new(store_location: PathBuf, timeout: Duration) -> Locks {
+        Locks {
+            store_location: store_location,
+            timeout: timeout,
+        }
+    }
+
+    /// Lock the entry `id`
+    pub fn lock_entry(&self, id: &StoreId) -> Result<LockFile> {
+        let local = id.local().to_str().ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))?;
+        let name  = format!("{}/entries/{}", LOCK_DIRECTORY, escape(local));
+        self.lock(name, |holder| SEK::EntryLocked(id.clone(), holder))
+    }
+
+    /// Lock the store for an operation which changes its structure
+    pub fn lock_store(&self) -> Result<LockFile> {
+        self.lock(format!("{}/store", LOCK_DIRECTORY), SEK::StoreLocked)
+    }
+
+    fn lock<F>(&self, name: String, locked: F) -> Result<LockFile>
+        where F: FnOnce(String) -> SEK
+    {
+        let id = StoreId::new(Some(self.store_location.clone()), PathBuf::from(name))?;
+        LockFile::acquire(id, self.timeout, locked)
+    }
+
+}
+
+/// A held lock, released when dropped
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl LockFile {
+
+    fn acquire<F>(id: StoreId, timeout: Duration, locked: F) -> Result<LockFile>
+        where F: FnOnce(String) -> SEK
+    {
+        let path  = id.clone().into_pathbuf()?;
+        let error = || SEK::LockFileError(path.clone());
+        let start = Instant::now();
+
+        if let Some(dir) = path.parent() {
+            let _ = create_dir_all(dir).chain_err(&error)?;
+        }
+
+        loop {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(&path)
+                .chain_err(&error)?;
+
+            match file.try_lock_exclusive() {
+                Ok(()) => {},
+                Err(ref e) if e.kind() == lock_contended_error().kind() => {
+                    if start.elapsed() >= timeout {
+                        return Err(SE::from_kind(locked(holder(&mut file, id))));
+                    }
+
+                    sleep(Duration::from_millis(RETRY_INTERVAL_MS));
+                    continue;
+                },
+                Err(e) => return Err(e).chain_err(&error),
+            }
+
+            // The previous holder removes the file before releasing the lock, so we might have
+            // locked a file which is not in the lock directory anymore
+            if !is_same_file(&file, &path).chain_err(&error)? {
+                continue;
+            }
+
+            if file.metadata().chain_err(&error)?.len() > 0 {
+                info!("Taking over stale lock of {}: {}", holder(&mut file, id.clone()), path.display());
+            }
+
+            let mut entry = Entry::new(id);
+            let _ = entry
+                .get_header_mut()
+                .insert("lock.pid", Value::Integer(process::id() as i64))?;
+            let content = entry.to_str()?;
+
+            let _ = file.set_len(0)
+                .and_then(|_| file.seek(SeekFrom::Start(0)))
+                .and_then(|_| file.write_all(content.as_bytes()))
+                .and_then(|_| file.flush())
+                .chain_err(&error)?;
+
+            debug!("Acquired lock {}", path.display());
+            return Ok(LockFile { path, file });
+        }
+    }
+
+}
+
+impl Drop for LockFile {
+
+    fn drop(&mut self) {
+        // Remove the file while we still hold the lock, so nobody else gets it in between
+        if let Err(e) = remove_file(&self.path) {
+            debug!("Could not remove lock {}: {:?}", self.path.display(), e);
+        }
+
+        if let Err(e) = self.file.unlock() {
+            debug!("Could not release lock {}: {:?}", self.path.display(), e);
+        } else {
+            debug!("Released lock {}", self.path.display());
+        }
+    }
+
+}
+
+/// Get a description of the process holding the lock from the lock file
+fn holder(file: &mut File, id: StoreId) -> String {
+    let mut buf = String::new();
+    let _ = file.seek(SeekFrom::Start(0)).and_then(|_| file.read_to_string(&mut buf));
+
+    Entry::from_str(id, &buf)
+        .ok()
+        .and_then(|entry| entry.get_header().read_int("lock.pid").ok().and_then(|pid| pid))
+        .map(|pid| format!("pid {}", pid))
+        .unwrap_or_else(|| String::from("unknown process"))
+}
+
+/// Escape a store id so it can be used as a file name
+fn escape(id: &str) -> String {
+    id.replace('%', "%25").replace('/', "%2F")
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> ::std::io::Result<bool> {
+    use std::io::ErrorKind;
+    use std::fs::metadata;
+    use std::os::unix::fs::MetadataExt;
+
+    let held = file.metadata()?;
+    match metadata(path) {
+        Ok(m) => Ok(m.dev() == held.dev() && m.ino() == held.ino()),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Files which are open cannot be removed on other platforms
+#[cfg(not(unix))]
+fn is_same_file(_: &File, _: &Path) -> ::std::io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn id(base: &Path, s: &str) -> StoreId {
+        StoreId::new(Some(PathBuf::from(base)), PathBuf::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a/b%c"), "a%2Fb%25c");
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir   = TempDir::new("imag-lock").unwrap();
+        let locks = Locks::new(PathBuf::from(dir.path()), Duration::from_millis(50));
+        let other = Locks::new(PathBuf::from(dir.path()), Duration::from_millis(50));
+        let entry = id(dir.path(), "a/b");
+
+        let lock = locks.lock_entry(&entry).unwrap();
+        match other.lock_entry(&entry) {
+            Err(e) => match *e.kind() {
+                SEK::EntryLocked(ref locked, ref holder) => {
+                    assert_eq!(locked, &entry);
+                    assert_eq!(holder, &format!("pid {}", process::id()));
+                },
+                ref kind => panic!("Unexpected error: {:?}", kind),
+            },
+            Ok(_) => panic!("Entry locked twice"),
+        }
+
+        // Other entries can still be locked
+        assert!(other.lock_entry(&id(dir.path(), "a/c")).is_ok());
+
+        let path = lock.path.clone();
+        drop(lock);
+        assert!(!path.exists());
+        assert!(other.lock_entry(&entry).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let dir   = TempDir::new("imag-lock").unwrap();
+        let locks = Locks::new(PathBuf::from(dir.path()), Duration::from_millis(0));
+
+        // A lock file left behind by a process which died while holding the store lock
+        let path = dir.path().join(LOCK_DIRECTORY).join("store");
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(&path).unwrap().write_all(b"---\n[lock]\npid = 1\n---\n").unwrap();
+
+        let lock = locks.lock_store().unwrap();
+        assert!(path.exists());
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+}
@@ -0,0 +1,402 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migrating entries written by older versions of imag
+//!
+//! Each entry carries the version of imag which wrote it in its header ("imag.version"). If a
+//! library changes the header layout of its entries, it registers a `Migration` with
+//! `Store::register_migration()`, which upgrades an entry from the layout of one version to the
+//! layout of a later version. A migration can be restricted to the entries of a collection.
+//!
+//! `Store::migrate()` applies the registered migrations to all entries which were written by an
+//! older version. A migration is applied to an entry if the entry is in the collection of the
+//! migration (if any) and the version of the entry is at least the `from` version and older than
+//! the `to` version of the migration. The version of a migrated entry is set to the highest `to`
+//! version of the applied migrations, entries which do not need any migration are not touched.
+//!
+//! Migrations are applied in the order of their `to` versions, so an entry can be upgraded over
+//! several versions at once. Several migrations with the same `to` version (for example from
+//! different libraries) are applied in the order they were registered. If an entry is older than
+//! the `from` version of a migration it needs, there is no way to upgrade it and migrating the
+//! entry fails. Entries which fail are reported, the other entries are migrated anyways.
+//!
+//! Before an entry is changed, it is backed up in the internal collection of the store, at
+//! `.store/migration-backup/<timestamp>/<id>`.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::path::PathBuf;
+
+use semver::Version;
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadTypeExt;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The collection in which the backups of migrated entries are kept
+pub const BACKUP_COLLECTION : &'static str = ".store/migration-backup";
+
+/// The function which upgrades an entry
+pub type MigrationFn = fn(&mut Entry) -> Result<()>;
+
+/// A step which upgrades entries from the header layout of one version to the layout of a later
+/// version
+#[derive(Clone)]
+pub struct Migration {
+    name: String,
+    collection: Option<String>,
+    from: Version,
+    to: Version,
+    migrate: MigrationFn,
+}
+
+impl Migration {
+
+    /// A migration called `name`, which upgrades entries written by versions `from` (inclusive)
+    /// up to `to` (exclusive) with `migrate`
+    ///
+    /// Fails if `from` or `to` is not a valid version or `from` is not older than `to`.
+    pub fn new<S>(name: S, from: &str, to: &str, migrate: MigrationFn) -> Result<Migration>
+        where S: Into<String>
+    {
+        let from = parse_version(from)?;
+        let to   = parse_version(to)?;
+
+        if from >= to {
+            return Err(SE::from_kind(SEK::InvalidVersion(format!("{} -> {}", from, to))));
+        }
+
+        Ok(Migration { name: name.into(), collection: None, from, to, migrate })
+    }
+
+    /// Only apply the migration to the entries in `collection`
+    pub fn for_collection<S: Into<String>>(mut self, collection: S) -> Migration {
+        self.collection = Some(collection.into());
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn collection(&self) -> Option<&String> {
+        self.collection.as_ref()
+    }
+
+    pub fn from(&self) -> &Version {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Version {
+        &self.to
+    }
+
+    /// Whether the migration is concerned with the entry `id`
+    pub fn concerns(&self, id: &StoreId) -> bool {
+        self.collection
+            .as_ref()
+            .map(|c| {
+                let components = c.split('/').filter(|c| !c.is_empty()).collect::<Vec<_>>();
+                id.is_in_collection(&components)
+            })
+            .unwrap_or(true)
+    }
+
+    /// Whether an entry written by `version` needs this migration
+    pub fn applies_to(&self, version: &Version) -> bool {
+        self.from <= *version && *version < self.to
+    }
+
+}
+
+impl Debug for Migration {
+
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "Migration '{}' ({} -> {})", self.name, self.from, self.to)
+    }
+
+}
+
+/// What `Store::migrate()` did to an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigratedEntry {
+    id: StoreId,
+    from: Version,
+    to: Version,
+    applied: Vec<String>,
+    backup: StoreId,
+}
+
+impl MigratedEntry {
+
+    /// The id of the entry
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    /// The version the entry was written by
+    pub fn from(&self) -> &Version {
+        &self.from
+    }
+
+    /// The version the entry was upgraded to, the highest `to` version of the applied migrations
+    pub fn to(&self) -> &Version {
+        &self.to
+    }
+
+    /// The names of the migrations which were applied to the entry, in order
+    pub fn applied(&self) -> &Vec<String> {
+        &self.applied
+    }
+
+    /// The id of the backup of the entry
+    pub fn backup(&self) -> &StoreId {
+        &self.backup
+    }
+
+}
+
+/// The outcome of `Store::migrate()`
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub(crate) migrated: Vec<MigratedEntry>,
+    pub(crate) failed: Vec<(StoreId, SE)>,
+}
+
+impl MigrationReport {
+
+    /// The entries which were upgraded
+    pub fn migrated(&self) -> &Vec<MigratedEntry> {
+        &self.migrated
+    }
+
+    /// The entries which could not be upgraded, with the reason
+    pub fn failed(&self) -> &Vec<(StoreId, SE)> {
+        &self.failed
+    }
+
+}
+
+/// The version of imag which writes entries
+pub fn current_version() -> Version {
+    // The version of this crate is always a valid version
+    Version::parse(env!("CARGO_PKG_VERSION")).unwrap()
+}
+
+/// Get the version `entry` was written by
+pub fn entry_version(entry: &Entry) -> Result<Version> {
+    entry.get_header()
+        .read_string("imag.version")?
+        .ok_or_else(|| SE::from_kind(SEK::MissingVersionInfo))
+        .and_then(|v| parse_version(&v))
+}
+
+fn parse_version(v: &str) -> Result<Version> {
+    Version::parse(v).chain_err(|| SEK::InvalidVersion(String::from(v)))
+}
+
+/// Upgrade `entry` with the `migrations` it needs
+///
+/// `migrations` must be sorted by their `to` version. Returns the version the entry was written
+/// by, the version it was upgraded to and the names of the applied migrations, or `None` if no
+/// migration applies to the entry. In that case, the entry is not changed.
+pub(crate) fn migrate_entry(migrations: &[Migration], entry: &mut Entry)
+    -> Result<Option<(Version, Version, Vec<String>)>>
+{
+    let from = entry_version(entry)?;
+    if from >= current_version() {
+        return Ok(None);
+    }
+
+    let mut version : Version         = from.clone();
+    let mut reached : Option<Version> = None;
+    let mut applied                   = vec![];
+
+    let id = entry.get_location().clone();
+    for migration in migrations.iter().filter(|m| m.concerns(&id)) {
+        // All migrations to the same version are checked against the version of the entry before
+        // any of them was applied
+        if reached.as_ref().map(|r| r != migration.to()).unwrap_or(false) {
+            version = reached.take().unwrap();
+        }
+
+        if version < *migration.from() {
+            let version = format!("{}", version);
+            let name    = migration.name().clone();
+            return Err(SE::from_kind(SEK::MigrationNotApplicable(id, name, version)));
+        }
+
+        if migration.applies_to(&version) {
+            debug!("Applying {:?} to {}", migration, id);
+            let _ = (migration.migrate)(entry)
+                .chain_err(|| SEK::MigrationError(id.clone(), migration.name().clone()))?;
+
+            applied.push(migration.name().clone());
+            reached = Some(migration.to().clone());
+        }
+    }
+
+    if applied.is_empty() {
+        return Ok(None);
+    }
+
+    // The migrations are sorted, so the last one which was applied has the highest version
+    let to = reached.unwrap_or(version);
+
+    let _ = entry
+        .get_header_mut()
+        .insert("imag.version", Value::String(format!("{}", to)))?;
+
+    Ok(Some((from, to, applied)))
+}
+
+/// Get the id (with base) of the backup of `id` made by the migration run started at `timestamp`
+pub(crate) fn backup_id(base: &PathBuf, timestamp: i64, id: &StoreId) -> Result<StoreId> {
+    let mut local = PathBuf::from(BACKUP_COLLECTION);
+    local.push(format!("{}", timestamp));
+    local.push(id.local());
+    StoreId::new(Some(base.clone()), local)
+}
+
+pub(crate) fn migrated_entry(id: StoreId,
+                             from: Version,
+                             to: Version,
+                             applied: Vec<String>,
+                             backup: StoreId)
+    -> MigratedEntry
+{
+    MigratedEntry { id, from, to, applied, backup }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml_query::delete::TomlValueDeleteExt;
+
+    use super::*;
+
+    fn entry(version: &str) -> Entry {
+        let id = StoreId::new(Some(PathBuf::from("/s")), PathBuf::from("a")).unwrap();
+        let mut entry = Entry::new(id);
+        let _ = entry.get_header_mut().insert("imag.version", Value::String(String::from(version)));
+        let _ = entry.get_header_mut().insert("a.old", Value::Integer(1));
+        entry
+    }
+
+    fn rename(entry: &mut Entry) -> Result<()> {
+        let hdr = entry.get_header_mut();
+        if let Some(v) = hdr.delete("a.old")? {
+            let _ = hdr.insert("a.new", v)?;
+        }
+        Ok(())
+    }
+
+    fn count(entry: &mut Entry) -> Result<()> {
+        let n = entry.get_header().read_int("a.count")?.unwrap_or(0);
+        let _ = entry.get_header_mut().insert("a.count", Value::Integer(n + 1))?;
+        Ok(())
+    }
+
+    fn fail(_: &mut Entry) -> Result<()> {
+        Err(SE::from_kind(SEK::HeaderInconsistency))
+    }
+
+    #[test]
+    fn test_invalid_migrations() {
+        assert!(Migration::new("m", "0.2.0", "0.1.0", count).is_err());
+        assert!(Migration::new("m", "0.1.0", "0.1.0", count).is_err());
+        assert!(Migration::new("m", "0.1", "0.2.0", count).is_err());
+    }
+
+    #[test]
+    fn test_migrate_entry() {
+        let migrations = vec![
+            Migration::new("rename", "0.1.0", "0.2.0", rename).unwrap(),
+            Migration::new("count-1", "0.2.0", "0.3.0", count).unwrap(),
+            Migration::new("count-2", "0.2.0", "0.3.0", count).unwrap(),
+        ];
+
+        let mut e = entry("0.1.5");
+        let (from, to, applied) = migrate_entry(&migrations, &mut e).unwrap().unwrap();
+        assert_eq!(from, Version::parse("0.1.5").unwrap());
+        assert_eq!(to, Version::parse("0.3.0").unwrap());
+        assert_eq!(applied, vec!["rename", "count-1", "count-2"]);
+        assert_eq!(e.get_header().read_int("a.new").unwrap(), Some(1));
+        assert_eq!(e.get_header().read_int("a.count").unwrap(), Some(2));
+
+        // The entry gets the version of the last migration, not the current version
+        assert_eq!(entry_version(&e).unwrap(), to);
+
+        // Entries which already have the layout of a version only get the later migrations
+        let mut e = entry("0.2.0");
+        let (_, _, applied) = migrate_entry(&migrations, &mut e).unwrap().unwrap();
+        assert_eq!(applied, vec!["count-1", "count-2"]);
+        assert_eq!(e.get_header().read_int("a.old").unwrap(), Some(1));
+
+        // Entries which need no migration are not touched
+        let mut e = entry("0.3.0");
+        assert!(migrate_entry(&migrations, &mut e).unwrap().is_none());
+        assert_eq!(entry_version(&e).unwrap(), Version::parse("0.3.0").unwrap());
+
+        // There is no migration for entries written before 0.1.0
+        let mut e = entry("0.0.1");
+        match migrate_entry(&migrations, &mut e).unwrap_err().kind() {
+            &SEK::MigrationNotApplicable(_, ref name, ref version) => {
+                assert_eq!(name, "rename");
+                assert_eq!(version, "0.0.1");
+            },
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+
+        // Entries written by the current version are not touched
+        let mut e = entry(env!("CARGO_PKG_VERSION"));
+        assert!(migrate_entry(&migrations, &mut e).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migration_for_collection() {
+        let migrations = vec![
+            Migration::new("rename", "0.1.0", "0.2.0", rename).unwrap().for_collection("b"),
+        ];
+
+        // The entry is not in collection "b", so it does not need the migration
+        let mut e = entry("0.0.1");
+        assert!(migrate_entry(&migrations, &mut e).unwrap().is_none());
+        assert_eq!(e.get_header().read_int("a.old").unwrap(), Some(1));
+        assert_eq!(entry_version(&e).unwrap(), Version::parse("0.0.1").unwrap());
+    }
+
+    #[test]
+    fn test_failing_migration() {
+        let migrations = vec![Migration::new("fail", "0.1.0", "0.2.0", fail).unwrap()];
+        let mut e = entry("0.1.0");
+
+        match migrate_entry(&migrations, &mut e).unwrap_err().kind() {
+            &SEK::MigrationError(_, ref name) => assert_eq!(name, "fail"),
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+}
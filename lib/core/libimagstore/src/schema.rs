@@ -0,0 +1,536 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Header schemas for the entries of a collection
+//!
+//! A `HeaderSchema` declares which header fields the entries of a collection (for example
+//! "habit/template") have and of which type these fields are. Schemas are either declared in the
+//! `[store.schema]` section of the configuration file or registered by the libraries which manage
+//! the collection, see `Store::register_schema()`.
+//!
+//! In the configuration file, a schema looks like this:
+//!
+//! ```toml
+//! [store.schema."habit/template"]
+//! strict = true
+//!
+//! [store.schema."habit/template".fields]
+//! "habit.template.name"     = { type = "string", required = true }
+//! "habit.template.until"    = { type = "date" }
+//! ```
+//!
+//! If a schema is `strict`, the tables which contain declared fields must not contain any other
+//! fields. This catches typos in field names.
+//!
+//! Registered schemas are persisted in the internal collection of the store, at
+//! `.store/schema/<collection>`, so that they are also enforced if the registering library is not
+//! used. A schema from the configuration file replaces the registered schema of the same
+//! collection.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error as FmtError;
+use std::path::PathBuf;
+use std::result::Result as RResult;
+
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::Result;
+use storeid::StoreId;
+
+/// The collection in which the registered schemas are kept
+pub const SCHEMA_COLLECTION : &'static str = ".store/schema";
+
+/// Header key under which a schema is stored in its schema entry
+const SCHEMA_KEY : &'static str = "schema";
+
+/// The format of date strings in headers
+pub const DATE_FORMAT : &'static str = "%Y-%m-%d";
+
+/// The format of datetime strings in headers
+pub const DATETIME_FORMAT : &'static str = "%Y-%m-%dT%H:%M:%S";
+
+/// The type of a header field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    Table,
+
+    /// A date, either a TOML datetime or a string in `DATE_FORMAT`
+    Date,
+
+    /// A date and time, either a TOML datetime or a string in `DATETIME_FORMAT`
+    Datetime,
+}
+
+impl FieldType {
+
+    pub fn from_str(s: &str) -> Option<FieldType> {
+        match s {
+            "string"   => Some(FieldType::String),
+            "integer"  => Some(FieldType::Integer),
+            "float"    => Some(FieldType::Float),
+            "boolean"  => Some(FieldType::Boolean),
+            "array"    => Some(FieldType::Array),
+            "table"    => Some(FieldType::Table),
+            "date"     => Some(FieldType::Date),
+            "datetime" => Some(FieldType::Datetime),
+            _          => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FieldType::String   => "string",
+            FieldType::Integer  => "integer",
+            FieldType::Float    => "float",
+            FieldType::Boolean  => "boolean",
+            FieldType::Array    => "array",
+            FieldType::Table    => "table",
+            FieldType::Date     => "date",
+            FieldType::Datetime => "datetime",
+        }
+    }
+
+    /// Check whether `v` is of this type
+    pub fn matches(&self, v: &Value) -> bool {
+        match (*self, v) {
+            (FieldType::String,   &Value::String(_))   => true,
+            (FieldType::Integer,  &Value::Integer(_))  => true,
+            (FieldType::Float,    &Value::Float(_))    => true,
+            (FieldType::Boolean,  &Value::Boolean(_))  => true,
+            (FieldType::Array,    &Value::Array(_))    => true,
+            (FieldType::Table,    &Value::Table(_))    => true,
+            (FieldType::Date,     &Value::Datetime(_)) => true,
+            (FieldType::Datetime, &Value::Datetime(_)) => true,
+            (FieldType::Date,     &Value::String(ref s)) => {
+                NaiveDate::parse_from_str(s, DATE_FORMAT).is_ok()
+            },
+            (FieldType::Datetime, &Value::String(ref s)) => {
+                NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).is_ok()
+            },
+            _ => false,
+        }
+    }
+
+}
+
+/// The declaration of one header field in a `HeaderSchema`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    kind: FieldType,
+    required: bool,
+}
+
+impl FieldSchema {
+
+    pub fn kind(&self) -> FieldType {
+        self.kind
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+}
+
+/// A violation of a `HeaderSchema` by a header
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaViolation {
+    /// A required field is missing
+    Missing(String),
+
+    /// A field has the wrong type
+    WrongType {
+        field: String,
+        expected: FieldType,
+        found: &'static str,
+    },
+
+    /// A field which is not declared in a strict schema
+    Unknown(String),
+}
+
+impl Display for SchemaViolation {
+
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FmtError> {
+        match *self {
+            SchemaViolation::Missing(ref field) => {
+                write!(fmt, "Required field '{}' is missing", field)
+            },
+            SchemaViolation::WrongType { ref field, ref expected, ref found } => {
+                write!(fmt, "Field '{}' should be {}, but is {}", field, expected.as_str(), found)
+            },
+            SchemaViolation::Unknown(ref field) => {
+                write!(fmt, "Field '{}' is not known", field)
+            },
+        }
+    }
+
+}
+
+/// The schema of the headers of the entries in a collection
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderSchema {
+    collection: String,
+    strict: bool,
+    fields: BTreeMap<String, FieldSchema>,
+}
+
+impl HeaderSchema {
+
+    /// Create an empty schema for the collection `collection`, for example "habit/template"
+    pub fn new<S: Into<String>>(collection: S) -> HeaderSchema {
+        HeaderSchema {
+            collection: collection.into(),
+            strict: false,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Set whether fields which are not declared are forbidden in the tables of declared fields
+    pub fn strict(mut self, strict: bool) -> HeaderSchema {
+        self.strict = strict;
+        self
+    }
+
+    /// Declare a field which must be present
+    pub fn required<S: Into<String>>(self, field: S, kind: FieldType) -> HeaderSchema {
+        self.field(field, kind, true)
+    }
+
+    /// Declare a field which may be present
+    pub fn optional<S: Into<String>>(self, field: S, kind: FieldType) -> HeaderSchema {
+        self.field(field, kind, false)
+    }
+
+    fn field<S: Into<String>>(mut self, field: S, kind: FieldType, required: bool) -> HeaderSchema {
+        let _ = self.fields.insert(field.into(), FieldSchema { kind, required });
+        self
+    }
+
+    pub fn collection(&self) -> &String {
+        &self.collection
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, FieldSchema> {
+        &self.fields
+    }
+
+    /// Check whether the schema applies to the entry `id`
+    pub fn applies_to(&self, id: &StoreId) -> bool {
+        let components : Vec<&str> = self.collection.split('/').filter(|c| !c.is_empty()).collect();
+        id.is_in_collection(&components)
+    }
+
+    /// Check `header` against this schema
+    pub fn validate(&self, header: &Value) -> Vec<SchemaViolation> {
+        let mut violations = vec![];
+
+        for (field, schema) in self.fields.iter() {
+            match header.read(field) {
+                Ok(Some(v)) => if !schema.kind.matches(v) {
+                    violations.push(SchemaViolation::WrongType {
+                        field: field.clone(),
+                        expected: schema.kind,
+                        found: v.type_str(),
+                    });
+                },
+                Ok(None) | Err(_) => if schema.required {
+                    violations.push(SchemaViolation::Missing(field.clone()));
+                },
+            }
+        }
+
+        if self.strict {
+            for table in self.tables() {
+                if let Ok(Some(&Value::Table(ref t))) = header.read(&table) {
+                    for key in t.keys() {
+                        let field = format!("{}.{}", table, key);
+                        if !self.fields.contains_key(&field) && !self.is_table(&field) {
+                            violations.push(SchemaViolation::Unknown(field));
+                        }
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+
+    /// The tables which contain declared fields
+    fn tables(&self) -> BTreeSet<String> {
+        let mut tables = BTreeSet::new();
+
+        for field in self.fields.keys() {
+            let mut parts : Vec<&str> = field.split('.').collect();
+            while parts.len() > 1 {
+                let _ = parts.pop();
+                let _ = tables.insert(parts.join("."));
+            }
+        }
+
+        tables
+    }
+
+    fn is_table(&self, field: &str) -> bool {
+        let prefix = format!("{}.", field);
+        self.fields.keys().any(|f| f.starts_with(&prefix))
+    }
+
+    /// Read a schema for `collection` from its TOML representation
+    ///
+    /// See the module documentation for the format.
+    pub fn from_toml<S: Into<String>>(collection: S, v: &Value) -> Result<HeaderSchema> {
+        let collection = collection.into();
+        let error      = || SE::from_kind(SEK::SchemaParseError(collection.clone()));
+
+        let strict = match v.get("strict") {
+            None                        => false,
+            Some(&Value::Boolean(b))    => b,
+            Some(_)                     => return Err(error()),
+        };
+
+        let mut schema = HeaderSchema::new(collection.clone()).strict(strict);
+
+        match v.get("fields") {
+            None => {},
+            Some(&Value::Table(ref fields)) => for (name, field) in fields {
+                let kind = field
+                    .get("type")
+                    .and_then(Value::as_str)
+                    .and_then(FieldType::from_str)
+                    .ok_or_else(&error)?;
+
+                let required = match field.get("required") {
+                    None                     => false,
+                    Some(&Value::Boolean(b)) => b,
+                    Some(_)                  => return Err(error()),
+                };
+
+                schema = schema.field(name.clone(), kind, required);
+            },
+            Some(_) => return Err(error()),
+        }
+
+        Ok(schema)
+    }
+
+    /// Get the TOML representation of the schema, as read by `HeaderSchema::from_toml()`
+    pub fn to_toml(&self) -> Value {
+        let fields = self.fields
+            .iter()
+            .map(|(name, field)| {
+                let mut t = BTreeMap::new();
+                let _ = t.insert(String::from("type"), Value::String(String::from(field.kind.as_str())));
+                let _ = t.insert(String::from("required"), Value::Boolean(field.required));
+                (name.clone(), Value::Table(t))
+            })
+            .collect();
+
+        let mut t = BTreeMap::new();
+        let _ = t.insert(String::from("strict"), Value::Boolean(self.strict));
+        let _ = t.insert(String::from("fields"), Value::Table(fields));
+        Value::Table(t)
+    }
+
+}
+
+/// Get the id (with base) of the directory in which the registered schemas are kept
+pub(crate) fn schema_dir(base: &PathBuf) -> Result<StoreId> {
+    StoreId::new(Some(base.clone()), PathBuf::from(SCHEMA_COLLECTION))
+}
+
+/// Get the id (with base) of the entry in which the schema of `collection` is kept
+pub(crate) fn schema_id(base: &PathBuf, collection: &str) -> Result<StoreId> {
+    let mut local = PathBuf::from(SCHEMA_COLLECTION);
+    local.push(collection);
+    StoreId::new(Some(base.clone()), local)
+}
+
+/// Get the collection from the path of a schema entry in `dir`
+pub(crate) fn schema_collection(dir: &PathBuf, path: &PathBuf) -> Option<String> {
+    path.strip_prefix(dir)
+        .ok()
+        .and_then(|p| p.to_str())
+        .map(|p| p.replace('\\', "/"))
+        .filter(|p| !p.is_empty())
+}
+
+/// Build the entry which keeps `schema`
+pub(crate) fn schema_entry(id: StoreId, schema: &HeaderSchema) -> Result<Entry> {
+    use toml_query::insert::TomlValueInsertExt;
+
+    let mut entry = Entry::new(id);
+    let _ = entry
+        .get_header_mut()
+        .insert(SCHEMA_KEY, schema.to_toml())
+        .chain_err(|| SEK::SchemaError)?;
+    Ok(entry)
+}
+
+/// Get the schema of `collection` which is kept in `entry`
+pub(crate) fn schema_from_entry(collection: String, entry: &Entry) -> Result<HeaderSchema> {
+    match entry.get_header().read(SCHEMA_KEY).chain_err(|| SEK::SchemaError)? {
+        Some(v) => HeaderSchema::from_toml(collection, v),
+        None    => Err(SE::from_kind(SEK::SchemaParseError(collection))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use toml::de::from_str as toml_from_str;
+    use toml::Value;
+
+    use super::*;
+    use storeid::StoreId;
+
+    fn schema() -> HeaderSchema {
+        HeaderSchema::new("todo")
+            .strict(true)
+            .required("todo.uuid", FieldType::String)
+            .optional("todo.due", FieldType::Datetime)
+            .optional("todo.meta.priority", FieldType::Integer)
+    }
+
+    fn header(s: &str) -> Value {
+        toml_from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let s = HeaderSchema::new("habit/template");
+        let id = |p| StoreId::new_baseless(PathBuf::from(p)).unwrap();
+
+        assert!(s.applies_to(&id("habit/template/foo")));
+        assert!(!s.applies_to(&id("habit/instance/foo")));
+        assert!(!s.applies_to(&id("habitat/template/foo")));
+    }
+
+    #[test]
+    fn test_valid_header() {
+        let h = header(r#"
+        [todo]
+        uuid = "1234"
+        due  = "2018-01-01T12:00:00"
+        [todo.meta]
+        priority = 1
+        [imag]
+        version = "0.8.0"
+        "#);
+
+        assert!(schema().validate(&h).is_empty());
+    }
+
+    #[test]
+    fn test_missing_and_wrong_type() {
+        let h = header(r#"
+        [todo]
+        due = "tomorrow"
+        "#);
+
+        let violations = schema().validate(&h);
+        assert_eq!(violations, vec![
+            SchemaViolation::WrongType {
+                field: String::from("todo.due"),
+                expected: FieldType::Datetime,
+                found: "string",
+            },
+            SchemaViolation::Missing(String::from("todo.uuid")),
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_field_in_strict_schema() {
+        let h = header(r#"
+        [todo]
+        uuid = "1234"
+        uid  = "1234"
+        [todo.meta]
+        prio = 1
+        "#);
+
+        assert_eq!(schema().validate(&h), vec![
+            SchemaViolation::Unknown(String::from("todo.uid")),
+            SchemaViolation::Unknown(String::from("todo.meta.prio")),
+        ]);
+
+        assert!(schema().strict(false).validate(&h).is_empty());
+    }
+
+    #[test]
+    fn test_toml_roundtrip() {
+        let s = schema();
+        assert_eq!(HeaderSchema::from_toml("todo", &s.to_toml()).unwrap(), s);
+    }
+
+    #[test]
+    fn test_schema_entry_roundtrip() {
+        let base = PathBuf::from("/s");
+        let dir  = schema_dir(&base).unwrap().into_pathbuf().unwrap();
+        let id   = schema_id(&base, "habit/template").unwrap();
+        let pb   = id.clone().into_pathbuf().unwrap();
+
+        assert_eq!(schema_collection(&dir, &pb), Some(String::from("habit/template")));
+
+        let entry = schema_entry(id, &schema()).unwrap();
+        assert_eq!(schema_from_entry(String::from("todo"), &entry).unwrap(), schema());
+    }
+
+    #[test]
+    fn test_from_toml_config() {
+        let config = header(r#"
+        strict = true
+        [fields]
+        "todo.uuid" = { type = "string", required = true }
+        "todo.due"  = { type = "datetime" }
+        "#);
+
+        let s = HeaderSchema::from_toml("todo", &config).unwrap();
+        assert!(s.is_strict());
+        assert!(s.fields()["todo.uuid"].is_required());
+        assert_eq!(s.fields()["todo.due"].kind(), FieldType::Datetime);
+
+        let broken = header(r#"
+        [fields]
+        "todo.uuid" = { type = "uuid" }
+        "#);
+        assert!(HeaderSchema::from_toml("todo", &broken).is_err());
+    }
+
+}
@@ -24,6 +24,8 @@ use std::path::PathBuf;
 use std::result::Result as RResult;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::sync::Mutex;
+use std::sync::Weak;
 use std::io::Read;
 use std::ops::Deref;
 use std::ops::DerefMut;
@@ -34,6 +36,7 @@ use std::fmt::Error as FMTError;
 use toml::Value;
 use walkdir::WalkDir;
 use walkdir::Iter as WalkDirIter;
+use walkdir::WalkDirIterator;
 use toml_query::read::TomlValueReadExt;
 use toml_query::read::TomlValueReadTypeExt;
 
@@ -41,11 +44,31 @@ use error::{StoreError as SE, StoreErrorKind as SEK};
 use error::ResultExt;
 use storeid::{IntoStoreId, StoreId, StoreIdIteratorWithStore};
 use file_abstraction::FileAbstractionInstance;
+use index::Index;
+use history;
+use history::Revision;
+use schema;
+use schema::HeaderSchema;
+use schema::SchemaViolation;
+use hook::Hook;
+use hook::HookData;
+use hook::HookPosition;
+use lock::LockFile;
+use lock::Locks;
+use migration;
+use migration::MigratedEntry;
+use migration::MigrationReport;
+use migration::Migration;
+use transaction::Journal;
+use transaction::Transaction;
+use transaction::entry_after;
 
 // We re-export the following things so tests can use them
 pub use file_abstraction::FileAbstraction;
 pub use file_abstraction::FSFileAbstraction;
 pub use file_abstraction::InMemoryFileAbstraction;
+#[cfg(feature = "sqlite")]
+pub use file_abstraction::SqliteFileAbstraction;
 
 use libimagerror::trace::trace_error;
 use libimagutil::debug_result::*;
@@ -53,6 +76,20 @@ use libimagutil::debug_result::*;
 /// The Result Type returned by any interaction with the store that could fail
 pub type Result<T> = RResult<T, SE>;
 
+/// The collection in the store where the store keeps its internal data
+///
+/// Entries in this collection are not listed by `Store::entries()` or `Store::walk()`.
+pub const INTERNAL_COLLECTION : &'static str = ".store";
+
+/// The id of the entry the full-text index is persisted in
+const INDEX_ENTRY : &'static str = ".store/index";
+
+/// The id of the entry the journal of a transaction is written to while it is committed
+const JOURNAL_ENTRY : &'static str = ".store/journal";
+
+/// The name of the database file inside the store directory, if the SQLite backend is used
+pub const SQLITE_DATABASE : &'static str = "store.sqlite";
+
 
 #[derive(Debug, PartialEq)]
 enum StoreEntryStatus {
@@ -67,6 +104,13 @@ struct StoreEntry {
     id: StoreId,
     file: Box<FileAbstractionInstance>,
     status: StoreEntryStatus,
+
+    /// The lock which keeps other processes from borrowing the entry while it is borrowed
+    lock: Option<LockFile>,
+
+    /// The entry as it was read, if it was borrowed with `Store::get()` and is therefore not
+    /// locked while it is borrowed
+    read: Option<String>,
 }
 
 pub enum StoreObject {
@@ -108,6 +152,12 @@ impl Iterator for Walk {
             debug!("[Walk] Processing next item: {:?}", something);
             match something {
                 Ok(next) => if next.file_type().is_dir() {
+                    if next.file_name() == INTERNAL_COLLECTION {
+                        debug!("Skipping internal collection...");
+                        self.dirwalker.skip_current_dir();
+                        continue;
+                    }
+
                     debug!("Found directory...");
                     return Some(StoreObject::Collection(next.path().to_path_buf()))
                 } else /* if next.file_type().is_file() */ {
@@ -140,17 +190,12 @@ impl StoreEntry {
     fn new(id: StoreId, backend: &Box<FileAbstraction>) -> Result<StoreEntry> {
         let pb = id.clone().into_pathbuf()?;
 
-        #[cfg(feature = "fs-lock")]
-        {
-            open_file(pb.clone())
-                .and_then(|f| f.lock_exclusive())
-                .chain_err(|| SEK::IoError)?;
-        }
-
         Ok(StoreEntry {
             id,
             file: backend.new_instance(pb),
             status: StoreEntryStatus::Present,
+            lock: None,
+            read: None,
         })
     }
 
@@ -162,18 +207,23 @@ impl StoreEntry {
 
     fn get_entry(&mut self) -> Result<Entry> {
         if !self.is_borrowed() {
-            self.file
-                .get_file_content(self.id.clone())
-                .or_else(|err| if is_match!(err.kind(), &SEK::FileNotFound) {
-                    Ok(Entry::new(self.id.clone()))
-                } else {
-                    Err(err)
-                })
+            self.read_entry()
         } else {
             Err(SE::from_kind(SEK::EntryAlreadyBorrowed(self.id.clone())))
         }
     }
 
+    /// Read the entry from the backend, regardless whether it is borrowed
+    fn read_entry(&mut self) -> Result<Entry> {
+        self.file
+            .get_file_content(self.id.clone())
+            .or_else(|err| if is_match!(err.kind(), &SEK::FileNotFound) {
+                Ok(Entry::new(self.id.clone()))
+            } else {
+                Err(err)
+            })
+    }
+
     fn write_entry(&mut self, entry: &Entry) -> Result<()> {
         if self.is_borrowed() {
             assert_eq!(self.id, entry.location);
@@ -186,17 +236,6 @@ impl StoreEntry {
     }
 }
 
-#[cfg(feature = "fs-lock")]
-impl Drop for StoreEntry {
-
-    fn drop(self) {
-        self.get_entry()
-            .and_then(|entry| open_file(entry.get_location().clone()))
-            .and_then(|f| f.unlock())
-    }
-
-}
-
 
 /// The Store itself, through this object one can interact with IMAG's entries
 pub struct Store {
@@ -205,7 +244,7 @@ pub struct Store {
     ///
     /// Internal Path->File cache map
     ///
-    /// Caches the files, so they remain flock()ed
+    /// Caches the files and the locks of the borrowed entries
     ///
     /// Could be optimized for a threadsafe HashMap
     ///
@@ -215,6 +254,40 @@ pub struct Store {
     ///
     /// This provides the filesystem-operation functions (or pretends to)
     backend: Box<FileAbstraction>,
+
+    /// Whether the full-text index is maintained
+    index_enabled: bool,
+
+    /// The full-text index
+    ///
+    /// Loaded lazily when it is used the first time. `None` if it was not loaded yet or if there
+    /// is no index in the store yet.
+    index: Mutex<Option<Index>>,
+
+    /// Whether prior versions of entries are kept
+    history_enabled: bool,
+
+    /// The maximum number of revisions kept per entry, `None` if unlimited
+    history_limit: Option<usize>,
+
+    /// The header schemas from the configuration, by collection
+    configured_schemas: BTreeMap<String, HeaderSchema>,
+
+    /// The header schemas registered by libraries, by collection
+    registered_schemas: RwLock<BTreeMap<String, HeaderSchema>>,
+
+    /// The hooks which are executed on store operations, in order of registration
+    hooks: RwLock<Vec<(HookPosition, Arc<Hook>)>>,
+
+    /// The migrations registered by libraries, in order of registration
+    migrations: RwLock<Vec<Migration>>,
+
+    /// The locks against other processes, `None` if the store is not locked (for example because
+    /// it does not live on the filesystem)
+    locks: Option<Locks>,
+
+    /// The store lock, if it is currently held by this process
+    store_lock: Mutex<Option<Weak<LockFile>>>,
 }
 
 impl Store {
@@ -230,47 +303,88 @@ impl Store {
     ///
     /// If the path exists and is a file, the operation is aborted as well, an error is returned.
     ///
+    /// The store and its entries are locked against other processes, see the `lock` module.
+    ///
+    /// The backend is selected with the "backend" key of the store configuration: "filesystem"
+    /// (the default) or "sqlite" (if compiled with the "sqlite" feature), which keeps all entries
+    /// in the database file `store.sqlite` in `location`.
+    ///
     /// # Return values
     ///
     /// - On success: Store object
     ///
     pub fn new(location: PathBuf, store_config: &Option<Value>) -> Result<Store> {
-        let backend = Box::new(FSFileAbstraction::default());
-        Store::new_with_backend(location, store_config, backend)
+        use configuration::config_backend;
+        use configuration::config_lock_timeout;
+
+        let backend : Box<FileAbstraction> = match config_backend(store_config)? {
+            None => Box::new(FSFileAbstraction::default()),
+            Some(ref name) if name == "filesystem" => Box::new(FSFileAbstraction::default()),
+
+            #[cfg(feature = "sqlite")]
+            Some(ref name) if name == "sqlite" => {
+                // The database lives in the store directory, so we need it on the filesystem
+                let _ = ensure_location(&location, store_config, &FSFileAbstraction::default())?;
+                let database = location.join(SQLITE_DATABASE);
+                Box::new(SqliteFileAbstraction::new(database, location.clone())?)
+            },
+
+            Some(name) => return Err(SE::from_kind(SEK::UnknownBackend(name))),
+        };
+
+        let locks = Locks::new(location.clone(), config_lock_timeout(store_config)?);
+        Store::open(location, store_config, backend, Some(locks))
     }
 
     /// Create a Store object as descripbed in `Store::new()` documentation, but with an alternative
     /// backend implementation.
     ///
-    /// Do not use directly, only for testing purposes.
+    /// Do not use directly, only for testing purposes. The store is not locked against other
+    /// processes.
     pub fn new_with_backend(location: PathBuf,
                             store_config: &Option<Value>,
                             backend: Box<FileAbstraction>) -> Result<Store> {
+        Store::open(location, store_config, backend, None)
+    }
+
+    fn open(location: PathBuf,
+            store_config: &Option<Value>,
+            backend: Box<FileAbstraction>,
+            locks: Option<Locks>) -> Result<Store> {
         use configuration::*;
 
         debug!("Building new Store object");
-        if !location.exists() {
-            if !config_implicit_store_create_allowed(store_config)? {
-                return Err(SE::from_kind(SEK::CreateStoreDirDenied))
-                    .chain_err(|| SEK::FileError)
-                    .chain_err(|| SEK::IoError);
-            }
-
-            backend
-                .create_dir_all(&location)
-                .chain_err(|| SEK::StorePathCreate(location.clone()))
-                .map_dbg_err_str("Failed")?;
-        } else if location.is_file() {
-            debug!("Store path exists as file");
-            return Err(SE::from_kind(SEK::StorePathExists(location)));
-        }
+        let _ = ensure_location(&location, store_config, &*backend)?;
 
         let store = Store {
             location: location.clone(),
             entries: Arc::new(RwLock::new(HashMap::new())),
             backend: backend,
+            index_enabled: config_index_enabled(store_config)?,
+            index: Mutex::new(None),
+            history_enabled: config_history_enabled(store_config)?,
+            history_limit: config_history_limit(store_config)?,
+            configured_schemas: config_schemas(store_config)?
+                .into_iter()
+                .map(|schema| (schema.collection().clone(), schema))
+                .collect(),
+            registered_schemas: RwLock::new(BTreeMap::new()),
+            hooks: RwLock::new(config_hooks(store_config)?
+                .into_iter()
+                .map(|(position, hook)| {
+                    // configured commands are executed in the store, so they can use git for example
+                    let hook = hook.with_working_dir(location.clone());
+                    (position, Arc::new(hook) as Arc<Hook>)
+                })
+                .collect()),
+            migrations: RwLock::new(vec![]),
+            locks: locks,
+            store_lock: Mutex::new(None),
         };
 
+        let _ = store.recover_journal()?;
+        let _ = store.load_schemas()?;
+
         debug!("Store building succeeded");
         debug!("------------------------");
         debug!("{:?}", store);
@@ -299,11 +413,17 @@ impl Store {
     /// to stdout, we need to be able to replace the in-memory backend with the real filesystem
     /// backend.
     ///
+    /// It is also used to migrate a store from one backend to another, for example from the
+    /// filesystem into a SQLite database and back. Only the entries and the header schemas are
+    /// migrated, the other internal data of the store (the full-text index and the history in the
+    /// `.store` collection) is not. The index is rebuilt when it is used next.
+    ///
     pub fn reset_backend(&mut self, mut backend: Box<FileAbstraction>) -> Result<()> {
         self.backend
-            .drain()
+            .drain(self.location.clone())
             .and_then(|drain| backend.fill(drain))
             .map(|_| self.backend = backend)
+            .and_then(|_| self.flush_cache()) // cached entries still refer to the old backend
     }
 
     /// Creates the Entry at the given location (inside the entry)
@@ -322,8 +442,11 @@ impl Store {
         let id = id.into_storeid()?.with_base(self.path().clone());
 
         debug!("Creating id: '{}'", id);
+        let _ = self
+            .run_hooks(HookPosition::PreCreate, &HookData::Id(&id))
+            .chain_err(|| SEK::CreateCallError(id.clone()))?;
 
-        let exists = id.exists()? || self.entries
+        let exists = self.backend.exists(&id.clone().into_pathbuf()?)? || self.entries
             .read()
             .map(|map| map.contains_key(&id))
             .map_err(|_| SE::from_kind(SEK::LockPoisoned))
@@ -334,6 +457,14 @@ impl Store {
             return Err(SEK::EntryAlreadyExists(id).into());
         }
 
+        let lock = self.lock_entry(&id).chain_err(|| SEK::CreateCallError(id.clone()))?;
+
+        // Another process might have created the entry while we waited for the lock
+        if lock.is_some() && self.backend.exists(&id.clone().into_pathbuf()?)? {
+            debug!("Entry was created by another process: {:?}", id);
+            return Err(SEK::EntryAlreadyExists(id).into());
+        }
+
         {
             let mut hsmap = self
                 .entries
@@ -350,13 +481,16 @@ impl Store {
                 debug!("Creating: '{}'", id);
                 let mut se = StoreEntry::new(id.clone(), &self.backend)?;
                 se.status = StoreEntryStatus::Borrowed;
+                se.lock   = lock;
                 se
             });
         }
 
         debug!("Constructing FileLockEntry: '{}'", id);
 
-        Ok(FileLockEntry::new(self, Entry::new(id)))
+        let entry = Entry::new(id);
+        let _     = self.run_hooks(HookPosition::PostCreate, &HookData::Entry(&entry))?;
+        Ok(FileLockEntry::new(self, entry))
     }
 
     /// Borrow a given Entry. When the `FileLockEntry` is either `update`d or
@@ -372,23 +506,54 @@ impl Store {
     /// On error:
     ///  - Errors StoreId::into_storeid() might return
     ///  - RetrieveCallError(LockPoisoned()) if the internal lock is poisened.
+    ///  - RetrieveCallError(EntryAlreadyBorrowed()) if the entry is already borrowed.
+    ///  - RetrieveCallError(EntryLocked()) if another process borrowed the entry.
     ///
     pub fn retrieve<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = id.into_storeid()?.with_base(self.path().clone());
         debug!("Retrieving id: '{}'", id);
-        let entry = self
+        self.borrow_entry(id, true)
+    }
+
+    /// Borrow the entry `id`, implementation of `Store::retrieve()` and `Store::get()`
+    ///
+    /// If `locked` is false, the entry is not locked against other processes while it is borrowed,
+    /// but only while it is written.
+    fn borrow_entry<'a>(&'a self, id: StoreId, locked: bool) -> Result<FileLockEntry<'a>> {
+        let _ = self
+            .run_hooks(HookPosition::PreRetrieve, &HookData::Id(&id))
+            .chain_err(|| SEK::RetrieveCallError(id.clone()))?;
+
+        if self.is_borrowed(&id)? {
+            return Err(SE::from_kind(SEK::EntryAlreadyBorrowed(id.clone())))
+                .chain_err(|| SEK::RetrieveCallError(id.clone()));
+        }
+
+        // The entry is read after it is locked, so it cannot be changed by another process in
+        // between
+        let lock = if locked {
+            self.lock_entry(&id).chain_err(|| SEK::RetrieveCallError(id.clone()))?
+        } else {
+            None
+        };
+        let keep_read = !locked && self.locks.is_some();
+        let entry     = self
             .entries
             .write()
             .map_err(|_| SE::from_kind(SEK::LockPoisoned))
             .and_then(|mut es| {
                 let new_se = StoreEntry::new(id.clone(), &self.backend)?;
                 let se = es.entry(id.clone()).or_insert(new_se);
-                let entry = se.get_entry();
+                let entry = se.get_entry()?;
                 se.status = StoreEntryStatus::Borrowed;
-                entry
+                se.lock   = lock;
+                se.read   = if keep_read { Some(entry.to_str()?) } else { None };
+                Ok(entry)
             })
             .chain_err(|| SEK::RetrieveCallError(id.clone()))?;
 
+        let _ = self.run_hooks(HookPosition::PostRetrieve, &HookData::Entry(&entry))?;
+
         debug!("Constructing FileLockEntry: '{}'", id);
         Ok(FileLockEntry::new(self, entry))
     }
@@ -403,12 +568,17 @@ impl Store {
     ///  - Errors StoreId::into_storeid() might return
     ///  - Errors Store::retrieve() might return
     ///
+    /// Other than `Store::retrieve()`, the entry is not locked against other processes while it is
+    /// borrowed. It is only locked while it is written, which only happens if it was changed. If
+    /// another process changed the entry since it was read, writing fails with `EntryChanged`. Use
+    /// `Store::retrieve()` to change an entry based on its current content.
+    ///
     pub fn get<'a, S: IntoStoreId + Clone>(&'a self, id: S) -> Result<Option<FileLockEntry<'a>>> {
         let id = id.into_storeid()?.with_base(self.path().clone());
 
         debug!("Getting id: '{}'", id);
 
-        let exists = id.exists()? || self.entries
+        let exists = self.backend.exists(&id.clone().into_pathbuf()?)? || self.entries
             .read()
             .map(|map| map.contains_key(&id))
             .map_err(|_| SE::from_kind(SEK::LockPoisoned))
@@ -419,7 +589,7 @@ impl Store {
             return Ok(None);
         }
 
-        self.retrieve(id.clone()).map(Some).chain_err(|| SEK::GetCallError(id))
+        self.borrow_entry(id.clone(), false).map(Some).chain_err(|| SEK::GetCallError(id))
     }
 
     /// Walk the store tree for the module
@@ -455,28 +625,82 @@ impl Store {
     /// This method assumes that entry is dropped _right after_ the call, hence
     /// it is not public.
     ///
-    fn _update<'a>(&'a self, entry: &mut FileLockEntry<'a>, modify_presence: bool) -> Result<()> {
-        let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+    /// The update hooks are only executed if the entry was mutably borrowed since it was retrieved
+    /// or last written.
+    pub(crate) fn _update<'a>(&'a self, entry: &mut FileLockEntry<'a>, modify_presence: bool) -> Result<()> {
+        let dirty = entry.dirty;
+        if dirty {
+            let _ = self.run_hooks(HookPosition::PreUpdate, &HookData::Entry(&entry.entry))?;
+        }
 
-        let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
-            SE::from_kind(SEK::IdNotFound(entry.location.clone()))
-        })?;
+        {
+            let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
 
-        assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
+            let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
+                SE::from_kind(SEK::IdNotFound(entry.location.clone()))
+            })?;
 
-        debug!("Verifying Entry");
-        entry.entry.verify()?;
+            assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
 
-        debug!("Writing Entry");
-        se.write_entry(&entry.entry)?;
-        if modify_presence {
-            debug!("Modifying presence of {} -> Present", entry.get_location());
-            se.status = StoreEntryStatus::Present;
+            let written = match se.read.clone() {
+                // Borrowed with `get()`, so it is only locked and written if it was changed
+                Some(read) => if dirty {
+                    self.write_unlocked_store_entry(se, &read, &entry.entry)
+                } else {
+                    Ok(())
+                },
+                None => self.write_store_entry(se, &entry.entry),
+            };
+
+            // The entry is not borrowed anymore, even if it could not be written
+            if modify_presence {
+                debug!("Modifying presence of {} -> Present", entry.get_location());
+                se.status = StoreEntryStatus::Present;
+                se.lock   = None;
+                se.read   = None;
+            }
+
+            let _ = written?;
+        }
+
+        entry.dirty = false;
+        if dirty {
+            self.run_hooks(HookPosition::PostUpdate, &HookData::Entry(&entry.entry))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a borrowed entry which is not locked, only called from `_update()`
+    ///
+    /// The entry is locked while it is written. If it was changed by another process since it was
+    /// read as `read`, it is not written.
+    fn write_unlocked_store_entry(&self, se: &mut StoreEntry, read: &str, entry: &Entry)
+        -> Result<()>
+    {
+        let _lock = self.lock_entry(&entry.location)?;
+        if se.read_entry()?.to_str()? != read {
+            return Err(SE::from_kind(SEK::EntryChanged(entry.location.clone())));
         }
 
+        let _   = self.write_store_entry(se, entry)?;
+        se.read = Some(entry.to_str()?);
         Ok(())
     }
 
+    /// Write a borrowed entry, only called from `_update()`
+    fn write_store_entry(&self, se: &mut StoreEntry, entry: &Entry) -> Result<()> {
+        debug!("Verifying Entry");
+        entry.verify()?;
+        let _ = self.verify_schema(entry)?;
+
+        self.record_revision(&entry.location, Some(entry))?;
+
+        debug!("Writing Entry");
+        se.write_entry(entry)?;
+        self.update_index(|index| index.insert(&entry.location, entry.get_content()))
+    }
+
     /// Flush the store internal cache
     ///
     /// This is helpful if a lot of entries are beeing read/written, because the store holds the
@@ -561,6 +785,23 @@ impl Store {
         let id = id.into_storeid()?.with_base(self.path().clone());
 
         debug!("Deleting id: '{}'", id);
+        let _ = self
+            .run_hooks(HookPosition::PreDelete, &HookData::Id(&id))
+            .chain_err(|| SEK::DeleteCallError(id.clone()))?;
+
+        let _ = self.delete_entry(id.clone())?;
+        self.run_hooks(HookPosition::PostDelete, &HookData::Id(&id))
+    }
+
+    /// Delete an entry without running hooks, used directly when rolling back transactions
+    pub(crate) fn delete_entry(&self, id: StoreId) -> Result<()> {
+        let _store_lock = self.lock_store().chain_err(|| SEK::DeleteCallError(id.clone()))?;
+
+        // if the entry is currently modified by the user, we cannot drop it
+        if self.is_borrowed(&id)? {
+            return Err(SE::from_kind(SEK::IdLocked)).chain_err(|| SEK::DeleteCallError(id))
+        }
+        let _entry_lock = self.lock_entry(&id).chain_err(|| SEK::DeleteCallError(id.clone()))?;
 
         {
             let mut entries = self
@@ -575,16 +816,16 @@ impl Store {
                     // The entry is not in the internal cache. But maybe on the filesystem?
                     debug!("Seems like {:?} is not in the internal cache", id);
 
-                    // Small optimization: We need the pathbuf for deleting, but when calling
-                    // StoreId::exists(), a PathBuf object gets allocated. So we simply get a
-                    // PathBuf here, check whether it is there and if it is, we can re-use it to
-                    // delete the filesystem file.
+                    // We need the pathbuf for deleting, so we simply get a PathBuf here, check
+                    // whether it is there and if it is, we can re-use it to delete the file.
                     let pb = id.clone().into_pathbuf()?;
 
-                    if pb.exists() {
+                    if self.backend.exists(&pb)? {
                         // looks like we're deleting a not-loaded file from the store.
                         debug!("Seems like {:?} is on the FS", pb);
-                        return self.backend.remove_file(&pb)
+                        let _ = self.record_revision(&id, None)?;
+                        let _ = self.backend.remove_file(&pb)?;
+                        return self.update_index(|index| index.remove(&id));
                     } else {
                         debug!("Seems like {:?} is not even on the FS", pb);
                         return Err(SE::from_kind(SEK::FileNotFound))
@@ -597,6 +838,8 @@ impl Store {
                 }
             }
 
+            let _ = self.record_revision(&id, None)?;
+
             // remove the entry first, then the file
             entries.remove(&id);
             let pb = id.clone().with_base(self.path().clone()).into_pathbuf()?;
@@ -604,7 +847,9 @@ impl Store {
                 .backend
                 .remove_file(&pb)
                 .chain_err(|| SEK::FileError)
-                .chain_err(|| SEK::DeleteCallError(id))?;
+                .chain_err(|| SEK::DeleteCallError(id.clone()))?;
+
+            self.update_index(|index| index.remove(&id))?;
         }
 
         debug!("Deleted");
@@ -628,6 +873,13 @@ impl Store {
         -> Result<()>
     {
         let new_id = new_id.with_base(self.path().clone());
+        let _store_lock = self
+            .lock_store()
+            .chain_err(|| SEK::MoveCallError(entry.get_location().clone(), new_id.clone()))?;
+        let _new_lock = self
+            .lock_entry(&new_id)
+            .chain_err(|| SEK::MoveCallError(entry.get_location().clone(), new_id.clone()))?;
+
         let hsmap = self
             .entries
             .write()
@@ -651,6 +903,16 @@ impl Store {
             } else {
                 Ok(())
             })
+            .and_then(|_| self.update_index(|index| if remove_old {
+                index.rename(&old_id, &new_id)
+            } else {
+                index.copy(&old_id, &new_id)
+            }))
+            .and_then(|_| if remove_old {
+                self.move_history(&old_id, &new_id)
+            } else {
+                Ok(())
+            })
             .chain_err(|| SEK::FileError)
             .chain_err(|| SEK::MoveCallError(old_id, new_id))
     }
@@ -688,11 +950,24 @@ impl Store {
     ///
     /// So the link is _partly dangling_, so to say.
     ///
+    /// The same is true for markdown links in the content of other entries. Use
+    /// `libimagentrymarkdown::rewrite::move_entry()` to move an entry and update the links to it.
+    ///
     pub fn move_by_id(&self, old_id: StoreId, new_id: StoreId) -> Result<()> {
         let new_id = new_id.with_base(self.path().clone());
         let old_id = old_id.with_base(self.path().clone());
 
         debug!("Moving '{}' to '{}'", old_id, new_id);
+        let _ = self
+            .run_hooks(HookPosition::PreMove, &HookData::Move { old: &old_id, new: &new_id })
+            .chain_err(|| SEK::MoveCallError(old_id.clone(), new_id.clone()))?;
+
+        let _store_lock = self.lock_store()?;
+        if self.is_borrowed(&old_id)? {
+            return Err(SE::from_kind(SEK::EntryAlreadyBorrowed(old_id.clone())));
+        }
+        let _old_lock = self.lock_entry(&old_id)?;
+        let _new_lock = self.lock_entry(&new_id)?;
 
         {
             let mut hsmap = self.entries.write().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
@@ -722,22 +997,28 @@ impl Store {
             let _ = self
                 .backend
                 .rename(&old_id_pb, &new_id_pb)
-                .chain_err(|| SEK::EntryRenameError(old_id_pb, new_id_pb))?;
+                .chain_err(|| SEK::EntryRenameError(old_id_pb, new_id_pb.clone()))?;
 
             debug!("Rename worked on filesystem");
 
+            self.update_index(|index| index.rename(&old_id, &new_id))?;
+            self.move_history(&old_id, &new_id)?;
+
             // assert enforced through check hsmap.contains_key(&new_id) above.
             // Should therefor never fail
             assert!(hsmap
                     .remove(&old_id)
                     .and_then(|mut entry| {
-                        entry.id = new_id.clone();
+                        // the file handle has to follow the entry, unless the backend is a file
+                        // system, the old handle still refers to the old path
+                        entry.file = self.backend.new_instance(new_id_pb.clone());
+                        entry.id   = new_id.clone();
                         hsmap.insert(new_id.clone(), entry)
                     }).is_none())
         }
 
         debug!("Moved");
-        Ok(())
+        self.run_hooks(HookPosition::PostMove, &HookData::Move { old: &old_id, new: &new_id })
     }
 
     /// Get _all_ entries in the store (by id as iterator)
@@ -755,7 +1036,9 @@ impl Store {
 
                     if is_file {
                         let sid = StoreId::from_full_path(self.path(), element)?;
-                        elems.push(sid);
+                        if !sid.is_in_collection(&[INTERNAL_COLLECTION]) {
+                            elems.push(sid);
+                        }
                     }
                 }
                 Ok(StoreIdIteratorWithStore::new(Box::new(elems.into_iter()), self))
@@ -767,137 +1050,886 @@ impl Store {
         &self.location
     }
 
-}
+    /// Whether the full-text index is enabled in the configuration ("store.index")
+    pub fn index_enabled(&self) -> bool {
+        self.index_enabled
+    }
 
-impl Debug for Store {
+    /// Look up entries in the full-text index
+    ///
+    /// Returns all entries which _might_ contain all of the `fragments`, see
+    /// `Index::candidates()` for details. The caller has to verify that the entries actually
+    /// match, as the index is case-insensitive and only knows about words.
+    ///
+    /// If there is no index in the store yet, it is built first.
+    ///
+    /// # Return value
+    ///
+    /// On success: Some(iterator over the candidates) or None if the index is not enabled
+    ///
+    /// On error:
+    ///  - LockPoisoned() if the index lock cannot be aquired
+    ///  - Errors Store::rebuild_index() might return
+    ///
+    pub fn search_index<'a, S>(&'a self, fragments: &[S])
+        -> Result<Option<StoreIdIteratorWithStore<'a>>>
+        where S: AsRef<str>
+    {
+        if !self.index_enabled {
+            debug!("Index disabled, not searching");
+            return Ok(None);
+        }
 
-    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
-        writeln!(fmt, "Store location = {:?}, entries = {:?}", self.location, self.entries)
-    }
+        let loaded = {
+            let mut index = self.index.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+            if index.is_none() {
+                *index = self.load_index()?;
+            }
+            index.is_some()
+        };
 
-}
+        if !loaded {
+            debug!("No index in store, building one");
+            let _ = self.rebuild_index()?;
+        }
 
-impl Drop for Store {
+        let index = self.index.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+        let ids = match *index {
+            Some(ref index) => index.candidates(fragments)?,
+            None            => vec![],
+        };
+
+        Ok(Some(StoreIdIteratorWithStore::new(Box::new(ids.into_iter()), self)))
+    }
 
+    /// Rebuild the full-text index from all entries in the store
     ///
-    /// Unlock all files on drop
-    //
-    /// TODO: Unlock them
+    /// This is necessary if entries were altered without using imag, for example with a text
+    /// editor or by a `git pull`.
     ///
-    fn drop(&mut self) {
-        debug!("Dropping store");
+    /// Entries which are currently borrowed are skipped, as they are indexed when they are
+    /// written back to the store.
+    pub fn rebuild_index(&self) -> Result<()> {
+        debug!("Rebuilding index");
+        let mut new_index = Index::new();
+
+        for id in self.entries()?.without_store() {
+            match self.get_copy(id.clone()) {
+                Ok(entry) => new_index.insert(&id, entry.get_content())?,
+                Err(ref e) if is_match!(e.kind(), &SEK::IdLocked) => {
+                    debug!("Not indexing borrowed entry {}", id);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+
+        // The rebuilt index replaces the persisted one, it is not merged with it
+        let _lock     = self.lock_store()?;
+        let mut index = self.index.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+        let _         = self.write_index(&mut new_index)?;
+        *index = Some(new_index);
+        Ok(())
     }
 
-}
+    /// Write the full-text index back to the store, if it was altered
+    ///
+    /// Other processes may have written the index since it was loaded, so the changes of this
+    /// process are applied to the persisted index, with the store locked meanwhile.
+    ///
+    /// This is done automatically when the store is dropped.
+    pub fn flush_index(&self) -> Result<()> {
+        let _lock     = self.lock_store()?;
+        let mut index = self.index.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
 
-/// A struct that allows you to borrow an Entry
-pub struct FileLockEntry<'a> {
-    store: &'a Store,
-    entry: Entry,
-}
+        if let Some(ref mut index) = *index {
+            if index.is_dirty() {
+                if let Some(persisted) = self.load_index()? {
+                    index.rebase(persisted);
+                }
 
-impl<'a> FileLockEntry<'a, > {
+                let _ = self.write_index(index)?;
+            }
+        }
 
-    /// Create a new FileLockEntry based on a `Entry` object.
-    ///
-    /// Only for internal use.
-    fn new(store: &'a Store, entry: Entry) -> FileLockEntry<'a> {
-        FileLockEntry { store, entry }
+        Ok(())
     }
-}
 
-impl<'a> Debug for FileLockEntry<'a> {
-    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
-        write!(fmt, "FileLockEntry(Store = {})", self.store.location.to_str()
-               .unwrap_or("Unknown Path"))
+    fn write_index(&self, index: &mut Index) -> Result<()> {
+        debug!("Writing index with {} entries", index.len());
+        let id = self.index_id()?;
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = index.to_json()?;
+
+        let _ = self.backend
+            .new_instance(id.into_pathbuf()?)
+            .write_file_content(&entry)?;
+        index.set_clean();
+        Ok(())
     }
-}
 
-impl<'a> Deref for FileLockEntry<'a> {
-    type Target = Entry;
+    /// Apply a modification to the full-text index
+    ///
+    /// Does nothing if the index is disabled or there is no index in the store yet.
+    fn update_index<F>(&self, f: F) -> Result<()>
+        where F: FnOnce(&mut Index) -> Result<()>
+    {
+        if !self.index_enabled {
+            return Ok(());
+        }
 
-    fn deref(&self) -> &Self::Target {
-        &self.entry
-    }
-}
+        let mut index = self.index.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+        if index.is_none() {
+            *index = self.load_index()?;
+        }
 
-impl<'a> DerefMut for FileLockEntry<'a> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.entry
+        match *index {
+            Some(ref mut index) => f(index),
+            None                => Ok(()),
+        }
     }
-}
 
-#[cfg(not(test))]
-impl<'a> Drop for FileLockEntry<'a> {
-
-    /// This will silently ignore errors, use `Store::update` if you want to catch the errors
+    /// Load the full-text index from the backend
     ///
-    /// This might panic if the store was compiled with the early-panic feature (which is not
-    /// intended for production use, though).
-    fn drop(&mut self) {
-        use libimagerror::trace::trace_error_dbg;
-        trace!("Dropping: {:?} - from FileLockEntry::drop()", self.get_location());
-        if let Err(e) = self.store._update(self, true) {
-            trace_error_dbg(&e);
-            if_cfg_panic!("ERROR WHILE DROPPING: {:?}", e);
+    /// Returns `None` if there is no (compatible) index in the store.
+    fn load_index(&self) -> Result<Option<Index>> {
+        let id = self.index_id()?;
+        let pb = id.clone().into_pathbuf()?;
+
+        if !self.backend.exists(&pb)? {
+            debug!("No index found at {:?}", pb);
+            return Ok(None);
         }
+
+        debug!("Loading index from {:?}", pb);
+        self.backend
+            .new_instance(pb)
+            .get_file_content(id)
+            .and_then(|entry| Index::from_json(entry.get_content()))
     }
-}
 
-#[cfg(test)]
-impl<'a> Drop for FileLockEntry<'a> {
+    fn index_id(&self) -> Result<StoreId> {
+        StoreId::new(Some(self.path().clone()), PathBuf::from(INDEX_ENTRY))
+    }
 
-    /// This will not silently ignore errors but prints the result of the _update() call for testing
-    fn drop(&mut self) {
-        trace!("Dropping: {:?} - from FileLockEntry::drop() (test impl)", self.get_location());
-        let _ = self.store._update(self, true).map_err(|e| trace_error(&e));
+    /// Start a new transaction
+    ///
+    /// See the documentation of `Transaction` for details.
+    pub fn transaction<'a>(&'a self) -> Transaction<'a> {
+        Transaction::new(self)
     }
 
-}
+    /// Read an entry directly from the backend, bypassing the internal cache
+    ///
+    /// Returns `None` if the entry does not exist in the backend. This does not check whether the
+    /// entry is borrowed, so the result might be outdated if it is.
+    pub(crate) fn get_raw<S: IntoStoreId>(&self, id: S) -> Result<Option<Entry>> {
+        let id = id.into_storeid()?.with_base(self.path().clone());
+        let pb = id.clone().into_pathbuf()?;
 
+        if !self.backend.exists(&pb)? {
+            return Ok(None);
+        }
 
-/// `EntryContent` type
-pub type EntryContent = String;
+        self.backend.new_instance(pb).get_file_content(id).map(Some)
+    }
 
-/// An Entry of the store
-//
-/// Contains location, header and content part.
-#[derive(Debug, Clone)]
-pub struct Entry {
-    location: StoreId,
-    header: Value,
-    content: EntryContent,
-}
+    pub(crate) fn write_journal(&self, journal: &Journal) -> Result<()> {
+        let id = self.journal_id()?;
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = journal.to_json()?;
 
-impl Entry {
+        debug!("Writing journal with {} operations", journal.operations().len());
+        let mut instance = self.backend.new_instance(id.into_pathbuf()?);
+        let _            = instance.write_file_content(&entry)?;
 
-    /// Create a new store entry with its location at `loc`.
-    ///
-    /// This creates the entry with the default header from `Entry::default_header()` and an empty
-    /// content.
-    pub fn new(loc: StoreId) -> Entry {
-        Entry {
-            location: loc,
-            header: Entry::default_header(),
-            content: EntryContent::new()
+        // The changes are only applied once the journal is safely on disk
+        instance.sync()
+    }
+
+    pub(crate) fn remove_journal(&self) -> Result<()> {
+        let pb = self.journal_id()?.into_pathbuf()?;
+
+        if self.backend.exists(&pb)? {
+            debug!("Removing journal");
+            self.backend.remove_file(&pb)
+        } else {
+            Ok(())
         }
     }
 
-    /// Get the default Header for an Entry.
+    /// Finish or discard a transaction which was interrupted
     ///
-    /// This function should be used to get a new Header, as the default header may change. Via
-    /// this function, compatibility is ensured.
-    pub fn default_header() -> Value { // BTreeMap<String, Value>
-        Value::default_header()
-    }
+    /// A complete journal is replayed, an incomplete one was written while the transaction was not
+    /// yet committed and is therefor discarded.
+    fn recover_journal(&self) -> Result<()> {
+        if self.get_raw(self.journal_id()?)?.is_none() {
+            return Ok(());
+        }
 
-    /// See `Entry::from_str()`, as this function is used internally. This is just a wrapper for
-    /// convenience.
-    pub fn from_reader<S: IntoStoreId>(loc: S, file: &mut Read) -> Result<Entry> {
-        let text = {
-            let mut s = String::new();
-            file.read_to_string(&mut s)?;
-            s
+        // Another process might be committing the transaction right now, so we wait for it
+        let _lock   = self.lock_store()?;
+        let journal = match self.get_raw(self.journal_id()?)? {
+            None        => return Ok(()),
+            Some(entry) => Journal::from_json(entry.get_content()),
+        };
+
+        match journal {
+            Err(e) => {
+                warn!("Discarding incomplete transaction journal");
+                trace_error(&e);
+            },
+            Ok(journal) => {
+                info!("Replaying transaction journal with {} operations",
+                      journal.operations().len());
+
+                for op in journal.operations() {
+                    let id = StoreId::new(Some(self.path().clone()), PathBuf::from(&op.id))?;
+                    let pb = id.clone().into_pathbuf()?;
+
+                    match entry_after(op, id.clone()).chain_err(|| SEK::JournalReplayError)? {
+                        Some(entry) => {
+                            let _ = self.backend
+                                .new_instance(pb)
+                                .write_file_content(&entry)
+                                .chain_err(|| SEK::JournalReplayError)?;
+                            let _ = self.update_index(|i| i.insert(&id, entry.get_content()))?;
+                        },
+                        None => {
+                            if self.backend.exists(&pb)? {
+                                let _ = self.backend
+                                    .remove_file(&pb)
+                                    .chain_err(|| SEK::JournalReplayError)?;
+                            }
+                            let _ = self.update_index(|i| i.remove(&id))?;
+                        },
+                    }
+                }
+            },
+        }
+
+        self.remove_journal()
+    }
+
+    pub(crate) fn journal_id(&self) -> Result<StoreId> {
+        StoreId::new(Some(self.path().clone()), PathBuf::from(JOURNAL_ENTRY))
+    }
+
+    /// Whether the store keeps prior versions of entries
+    pub fn history_enabled(&self) -> bool {
+        self.history_enabled
+    }
+
+    /// Get the revisions of an entry, oldest first
+    ///
+    /// The revisions are the prior versions of the entry, the current version is not included.
+    /// Revisions are kept even if the entry is deleted, so that it can be restored.
+    ///
+    /// Returns an empty list if the history is disabled or there are no revisions of the entry.
+    pub fn history<S: IntoStoreId>(&self, id: S) -> Result<Vec<Revision>> {
+        let id = id.into_storeid()?.with_base(self.path().clone());
+
+        self.revision_numbers(&id)?
+            .into_iter()
+            .map(|number| {
+                let rid = history::revision_id(self.path(), &id, number)?;
+                let rev = self.get_raw(rid)?.ok_or_else(|| history::revision_not_found(&id, number))?;
+                history::revision_from_entry(number, &rev)
+            })
+            .collect()
+    }
+
+    /// Get the version of an entry kept in revision `rev`
+    ///
+    /// Returns `None` if there is no such revision.
+    pub fn get_revision<S: IntoStoreId>(&self, id: S, rev: u64) -> Result<Option<Entry>> {
+        let id  = id.into_storeid()?.with_base(self.path().clone());
+        let rid = history::revision_id(self.path(), &id, rev)?;
+
+        match self.get_raw(rid)? {
+            Some(r) => history::entry_from_revision(id, &r).map(Some),
+            None    => Ok(None),
+        }
+    }
+
+    /// Restore an entry to the version kept in revision `rev`
+    ///
+    /// The entry is created if it does not exist anymore. The current version of the entry becomes
+    /// a new revision, so restoring can be undone.
+    ///
+    /// # Return value
+    ///
+    /// On success: ()
+    ///
+    /// On error:
+    ///  - RevisionNotFound() if there is no such revision
+    ///  - Errors Store::retrieve() might return
+    ///
+    pub fn restore<S: IntoStoreId>(&self, id: S, rev: u64) -> Result<()> {
+        let id  = id.into_storeid()?.with_base(self.path().clone());
+        debug!("Restoring {} to revision {}", id, rev);
+
+        let old = self
+            .get_revision(id.clone(), rev)?
+            .ok_or_else(|| history::revision_not_found(&id, rev))?;
+
+        let mut entry = self.retrieve(id)?;
+        *entry.get_header_mut()  = old.get_header().clone();
+        *entry.get_content_mut() = old.get_content().clone();
+        Ok(())
+    }
+
+    /// Keep the version of `id` which is in the backend as new revision, unless it equals `new`
+    ///
+    /// `new` is the version which is about to be written, `None` if the entry is about to be
+    /// deleted.
+    fn record_revision(&self, id: &StoreId, new: Option<&Entry>) -> Result<()> {
+        if !self.history_enabled || id.is_in_collection(&[INTERNAL_COLLECTION]) {
+            return Ok(());
+        }
+
+        let old = match self.get_raw(id.clone()) {
+            Ok(Some(old)) => old,
+            Ok(None)      => return Ok(()), // nothing written yet
+            Err(e)        => {
+                warn!("Cannot read current version of {}, not keeping a revision", id);
+                trace_error(&e);
+                return Ok(());
+            },
+        };
+
+        if new.map(|new| *new == old).unwrap_or(false) {
+            trace!("{} unchanged, not keeping a revision", id);
+            return Ok(());
+        }
+
+        let numbers = self.revision_numbers(id)?;
+        let number  = numbers.last().map(|n| n + 1).unwrap_or(1);
+        let rid     = history::revision_id(self.path(), id, number)?;
+
+        debug!("Keeping revision {} of {}", number, id);
+        let rev = history::revision_entry(rid.clone(), &old)?;
+        let _   = self.backend
+            .new_instance(rid.into_pathbuf()?)
+            .write_file_content(&rev)
+            .chain_err(|| SEK::HistoryError)?;
+
+        if let Some(limit) = self.history_limit {
+            let count = numbers.len() + 1;
+            if count > limit {
+                for number in numbers.into_iter().take(count - limit) {
+                    debug!("Dropping revision {} of {}", number, id);
+                    let pb = history::revision_id(self.path(), id, number)?.into_pathbuf()?;
+                    let _  = self.backend.remove_file(&pb).chain_err(|| SEK::HistoryError)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move the revisions of `old_id` to `new_id`
+    fn move_history(&self, old_id: &StoreId, new_id: &StoreId) -> Result<()> {
+        for number in self.revision_numbers(old_id)? {
+            let from = history::revision_id(self.path(), old_id, number)?.into_pathbuf()?;
+            let to   = history::revision_id(self.path(), new_id, number)?.into_pathbuf()?;
+
+            debug!("Moving revision {:?} -> {:?}", from, to);
+            let _ = self.backend.rename(&from, &to).chain_err(|| SEK::HistoryError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the numbers of all revisions of `id`, in ascending order
+    fn revision_numbers(&self, id: &StoreId) -> Result<Vec<u64>> {
+        let dir = history::history_dir(self.path(), id)?.into_pathbuf()?;
+
+        let pathes = match self.backend.pathes_recursively(dir.clone()) {
+            Ok(pathes) => pathes,
+            Err(_) if !self.backend.exists(&dir)? => return Ok(vec![]),
+            Err(e) => return Err(e).chain_err(|| SEK::HistoryError),
+        };
+
+        let mut numbers : Vec<u64> = pathes
+            .filter_map(|path| history::revision_number(&dir, &path))
+            .collect();
+        numbers.sort();
+        Ok(numbers)
+    }
+
+    /// Register the header schema of a collection
+    ///
+    /// From now on, the headers of the entries in the collection are checked against the schema
+    /// each time an entry is written, see `HeaderSchema`. The schema is persisted in the store, a
+    /// schema which was registered before for the same collection is replaced.
+    ///
+    /// A schema for the same collection from the configuration file takes precedence.
+    pub fn register_schema(&self, schema: HeaderSchema) -> Result<()> {
+        let mut schemas = self.registered_schemas
+            .write()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        if schemas.get(schema.collection()) == Some(&schema) {
+            trace!("Schema for {} already registered", schema.collection());
+            return Ok(());
+        }
+
+        debug!("Registering schema for {}", schema.collection());
+        let id    = schema::schema_id(self.path(), schema.collection())?;
+        let entry = schema::schema_entry(id.clone(), &schema)?;
+        let _     = self.backend
+            .new_instance(id.into_pathbuf()?)
+            .write_file_content(&entry)
+            .chain_err(|| SEK::SchemaError)?;
+
+        let _ = schemas.insert(schema.collection().clone(), schema);
+        Ok(())
+    }
+
+    /// Get the header schemas which are enforced, from the configuration and registered ones
+    pub fn header_schemas(&self) -> Result<Vec<HeaderSchema>> {
+        let registered = self.registered_schemas
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        let mut schemas = self.configured_schemas.clone();
+        for (collection, schema) in registered.iter() {
+            if !schemas.contains_key(collection) {
+                let _ = schemas.insert(collection.clone(), schema.clone());
+            }
+        }
+
+        Ok(schemas.into_iter().map(|(_, schema)| schema).collect())
+    }
+
+    /// Check the header of `entry` against all header schemas which apply to it
+    ///
+    /// Returns all violations, an empty list if the header is valid.
+    pub fn check_schema(&self, entry: &Entry) -> Result<Vec<SchemaViolation>> {
+        let id = entry.get_location();
+        if id.is_in_collection(&[INTERNAL_COLLECTION]) {
+            return Ok(vec![]);
+        }
+
+        Ok(self.header_schemas()?
+            .into_iter()
+            .filter(|schema| schema.applies_to(id))
+            .flat_map(|schema| schema.validate(entry.get_header()))
+            .collect())
+    }
+
+    /// Check the header of `entry` against the header schemas which apply to it
+    ///
+    /// Fails with `HeaderSchemaViolation` listing all violations if the header is not valid.
+    pub(crate) fn verify_schema(&self, entry: &Entry) -> Result<()> {
+        let violations = self.check_schema(entry)?;
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let violations = violations
+            .iter()
+            .map(|v| format!("{}", v))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        Err(SE::from_kind(SEK::HeaderSchemaViolation(entry.location.clone(), violations)))
+    }
+
+    /// Load the schemas persisted by `Store::register_schema()`
+    fn load_schemas(&self) -> Result<()> {
+        let dir = schema::schema_dir(self.path())?.into_pathbuf()?;
+
+        let pathes = match self.backend.pathes_recursively(dir.clone()) {
+            Ok(pathes) => pathes,
+            Err(_) if !self.backend.exists(&dir)? => return Ok(()),
+            Err(e) => return Err(e).chain_err(|| SEK::SchemaError),
+        };
+
+        let mut schemas = self.registered_schemas
+            .write()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+
+        for path in pathes {
+            if !self.backend.is_file(&path)? {
+                continue;
+            }
+
+            let collection = match schema::schema_collection(&dir, &path) {
+                Some(collection) => collection,
+                None             => continue,
+            };
+
+            let id = schema::schema_id(self.path(), &collection)?;
+            if let Some(entry) = self.get_raw(id)? {
+                debug!("Loading schema for {}", collection);
+                let schema = schema::schema_from_entry(collection.clone(), &entry)?;
+                let _ = schemas.insert(collection, schema);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Register a hook, which is executed at `position` from now on
+    ///
+    /// Hooks are executed in the order they were registered, after the hooks from the
+    /// configuration file. See the `hook` module for details.
+    pub fn register_hook(&self, position: HookPosition, hook: Box<Hook>) -> Result<()> {
+        debug!("Registering hook {} for {}", hook.name(), position.as_str());
+        self.hooks
+            .write()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|mut hooks| hooks.push((position, Arc::from(hook))))
+    }
+
+    /// Register a migration, which is applied by `Store::migrate()`
+    ///
+    /// See the `migration` module for details.
+    pub fn register_migration(&self, migration: Migration) -> Result<()> {
+        debug!("Registering {:?}", migration);
+        self.migrations
+            .write()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+            .map(|mut migrations| migrations.push(migration))
+    }
+
+    /// Upgrade all entries which were written by an older version of imag
+    ///
+    /// The registered migrations are applied and the version of the entries is set to the version
+    /// of the last applied migration, see the `migration` module. Entries which do not need any
+    /// migration are not touched. Each entry is backed up before it is changed.
+    ///
+    /// # Return value
+    ///
+    /// On success: What was done to the entries which were upgraded and the entries which could
+    /// not be upgraded, with the reason (for example MigrationError(), MigrationNotApplicable() or
+    /// MigrationBackupError()). Entries which fail do not stop the migration of the others.
+    ///
+    /// On error:
+    ///  - NoMigrations() if no migration is registered, so there is nothing to apply
+    ///  - Errors Store::entries() and locking the store might return
+    ///
+    pub fn migrate(&self) -> Result<MigrationReport> {
+        use std::time::SystemTime;
+        use std::time::UNIX_EPOCH;
+
+        let mut migrations = self.migrations
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?
+            .clone();
+        if migrations.is_empty() {
+            return Err(SE::from_kind(SEK::NoMigrations));
+        }
+        migrations.sort_by(|a, b| a.to().cmp(b.to())); // stable, keeps the order of registration
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let _lock      = self.lock_store()?;
+        let mut report = MigrationReport::default();
+
+        for id in self.entries()?.without_store() {
+            match self.migrate_entry(&migrations, timestamp, id.clone()) {
+                Ok(Some(migrated)) => report.migrated.push(migrated),
+                Ok(None)           => {},
+                Err(e)             => {
+                    debug!("Migrating {} failed: {:?}", id, e);
+                    report.failed.push((id.without_base(), e));
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Upgrade the entry `id` with the `migrations` it needs and back it up, for `Store::migrate()`
+    fn migrate_entry(&self, migrations: &[Migration], timestamp: i64, id: StoreId)
+        -> Result<Option<MigratedEntry>>
+    {
+        let mut entry = match self.get(id.clone())? {
+            Some(entry) => entry,
+            None        => return Ok(None),
+        };
+
+        // Migrate a copy, so a failing migration does not leave a half-migrated entry behind
+        let mut new = entry.deref().clone();
+        let (from, to, applied) = match migration::migrate_entry(migrations, &mut new)? {
+            Some(result) => result,
+            None         => return Ok(None),
+        };
+
+        let bid    = migration::backup_id(self.path(), timestamp, &id)?;
+        let backup = Entry::from_str(bid.clone(), &entry.to_str()?)?;
+        let _      = self.backend
+            .new_instance(bid.clone().into_pathbuf()?)
+            .write_file_content(&backup)
+            .chain_err(|| SEK::MigrationBackupError(id.clone()))?;
+
+        debug!("Migrated {} from version {} to {}, backup in {}", id, from, to, bid);
+        *entry.get_header_mut()  = new.get_header().clone();
+        *entry.get_content_mut() = new.get_content().clone();
+        let _ = self.update(&mut entry)?;
+
+        let bid = bid.without_base();
+        Ok(Some(migration::migrated_entry(id.without_base(), from, to, applied, bid)))
+    }
+
+    /// Execute the hooks registered for `position`
+    ///
+    /// The first failing hook aborts a "pre" operation, the errors of hooks at "post" positions
+    /// are only reported.
+    fn run_hooks(&self, position: HookPosition, data: &HookData) -> Result<()> {
+        // Do not hold the lock while executing, so hooks can use the store
+        let hooks : Vec<Arc<Hook>> = self.hooks
+            .read()
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))?
+            .iter()
+            .filter(|&&(pos, _)| pos == position)
+            .map(|&(_, ref hook)| hook.clone())
+            .collect();
+
+        for hook in hooks {
+            trace!("Executing hook {} for {}", hook.name(), position.as_str());
+            if let Err(e) = hook.execute(position, data) {
+                if position.is_pre() {
+                    return Err(e).chain_err(|| SEK::HookAborted(position.as_str()));
+                }
+
+                warn!("Hook {} failed after {}", hook.name(), data.id());
+                trace_error(&e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the entry `id` is currently borrowed from this store
+    fn is_borrowed(&self, id: &StoreId) -> Result<bool> {
+        self.entries
+            .read()
+            .map(|map| map.get(id).map(|se| se.is_borrowed()).unwrap_or(false))
+            .map_err(|_| SE::from_kind(SEK::LockPoisoned))
+    }
+
+    /// Lock the entry `id` against other processes
+    ///
+    /// Returns `None` if the store is not locked against other processes.
+    fn lock_entry(&self, id: &StoreId) -> Result<Option<LockFile>> {
+        match self.locks {
+            None            => Ok(None),
+            Some(ref locks) => locks.lock_entry(id).map(Some),
+        }
+    }
+
+    /// Lock the store against structural changes by other processes
+    ///
+    /// The store lock can be taken several times by this process, it is released when the last
+    /// handle is dropped. Returns `None` if the store is not locked against other processes.
+    pub(crate) fn lock_store(&self) -> Result<Option<Arc<LockFile>>> {
+        let locks = match self.locks {
+            None            => return Ok(None),
+            Some(ref locks) => locks,
+        };
+
+        let mut held = self.store_lock.lock().map_err(|_| SE::from_kind(SEK::LockPoisoned))?;
+        if let Some(lock) = held.as_ref().and_then(Weak::upgrade) {
+            return Ok(Some(lock));
+        }
+
+        let lock = Arc::new(locks.lock_store()?);
+        *held = Some(Arc::downgrade(&lock));
+        Ok(Some(lock))
+    }
+
+}
+
+/// Make sure the store directory `location` exists, create it if allowed by the configuration
+fn ensure_location(location: &PathBuf, store_config: &Option<Value>, backend: &FileAbstraction)
+    -> Result<()>
+{
+    use configuration::config_implicit_store_create_allowed;
+
+    if !location.exists() {
+        if !config_implicit_store_create_allowed(store_config)? {
+            return Err(SE::from_kind(SEK::CreateStoreDirDenied))
+                .chain_err(|| SEK::FileError)
+                .chain_err(|| SEK::IoError);
+        }
+
+        backend
+            .create_dir_all(location)
+            .chain_err(|| SEK::StorePathCreate(location.clone()))
+            .map_dbg_err_str("Failed")
+    } else if location.is_file() {
+        debug!("Store path exists as file");
+        Err(SE::from_kind(SEK::StorePathExists(location.clone())))
+    } else {
+        Ok(())
+    }
+}
+
+impl Debug for Store {
+
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        writeln!(fmt, "Store location = {:?}, entries = {:?}", self.location, self.entries)
+    }
+
+}
+
+impl Drop for Store {
+
+    ///
+    /// Flush the index and release the locks of all entries on drop
+    ///
+    fn drop(&mut self) {
+        debug!("Dropping store");
+        let _ = self.flush_index().map_err(|e| trace_error(&e));
+
+        match self.entries.write() {
+            Ok(mut entries) => for se in entries.values_mut() {
+                if se.lock.take().is_some() {
+                    debug!("Released lock of {}", se.id);
+                }
+            },
+            Err(_) => debug!("Cannot release locks, internal lock is poisoned"),
+        }
+    }
+
+}
+
+/// A struct that allows you to borrow an Entry
+pub struct FileLockEntry<'a> {
+    store: &'a Store,
+    entry: Entry,
+
+    /// Whether the entry was mutably borrowed since it was retrieved or last written
+    dirty: bool,
+}
+
+impl<'a> FileLockEntry<'a, > {
+
+    /// Create a new FileLockEntry based on a `Entry` object.
+    ///
+    /// Only for internal use.
+    fn new(store: &'a Store, entry: Entry) -> FileLockEntry<'a> {
+        FileLockEntry { store, entry, dirty: false }
+    }
+}
+
+impl<'a> Debug for FileLockEntry<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> RResult<(), FMTError> {
+        write!(fmt, "FileLockEntry(Store = {})", self.store.location.to_str()
+               .unwrap_or("Unknown Path"))
+    }
+}
+
+impl<'a> Deref for FileLockEntry<'a> {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<'a> DerefMut for FileLockEntry<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        &mut self.entry
+    }
+}
+
+#[cfg(not(test))]
+impl<'a> Drop for FileLockEntry<'a> {
+
+    /// This will silently ignore errors, use `Store::update` if you want to catch the errors
+    ///
+    /// Only violations of the header schema of the collection and changes of the entry by another
+    /// process since it was read are reported, as the entry is not written in these cases. The
+    /// entry which was not written is reported along, so the changes are not lost.
+    ///
+    /// This might panic if the store was compiled with the early-panic feature (which is not
+    /// intended for production use, though).
+    fn drop(&mut self) {
+        use libimagerror::trace::trace_error_dbg;
+        trace!("Dropping: {:?} - from FileLockEntry::drop()", self.get_location());
+        if let Err(e) = self.store._update(self, true) {
+            if is_match!(e.kind(), &SEK::HeaderSchemaViolation(..) | &SEK::EntryChanged(..)) {
+                trace_error(&e);
+                report_unwritten(&self.entry);
+            } else {
+                trace_error_dbg(&e);
+            }
+            if_cfg_panic!("ERROR WHILE DROPPING: {:?}", e);
+        }
+    }
+}
+
+/// Print an entry which could not be written when it was dropped, so its content can be recovered
+#[cfg(not(test))]
+fn report_unwritten(entry: &Entry) {
+    match entry.to_str() {
+        Ok(s)  => error!("The changes to {} were not written:\n{}", entry.get_location(), s),
+        Err(e) => trace_error(&e),
+    }
+}
+
+#[cfg(test)]
+impl<'a> Drop for FileLockEntry<'a> {
+
+    /// This will not silently ignore errors but prints the result of the _update() call for testing
+    fn drop(&mut self) {
+        trace!("Dropping: {:?} - from FileLockEntry::drop() (test impl)", self.get_location());
+        let _ = self.store._update(self, true).map_err(|e| trace_error(&e));
+    }
+
+}
+
+
+/// `EntryContent` type
+pub type EntryContent = String;
+
+/// An Entry of the store
+//
+/// Contains location, header and content part.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    location: StoreId,
+    header: Value,
+    content: EntryContent,
+}
+
+impl Entry {
+
+    /// Create a new store entry with its location at `loc`.
+    ///
+    /// This creates the entry with the default header from `Entry::default_header()` and an empty
+    /// content.
+    pub fn new(loc: StoreId) -> Entry {
+        Entry {
+            location: loc,
+            header: Entry::default_header(),
+            content: EntryContent::new()
+        }
+    }
+
+    /// Get the default Header for an Entry.
+    ///
+    /// This function should be used to get a new Header, as the default header may change. Via
+    /// this function, compatibility is ensured.
+    pub fn default_header() -> Value { // BTreeMap<String, Value>
+        Value::default_header()
+    }
+
+    /// See `Entry::from_str()`, as this function is used internally. This is just a wrapper for
+    /// convenience.
+    pub fn from_reader<S: IntoStoreId>(loc: S, file: &mut Read) -> Result<Entry> {
+        let text = {
+            let mut s = String::new();
+            file.read_to_string(&mut s)?;
+            s
         };
         Self::from_str(loc, &text[..])
     }
@@ -1215,8 +2247,21 @@ Hai
 #[cfg(test)]
 mod store_tests {
     use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use toml_query::delete::TomlValueDeleteExt;
 
     use super::Store;
+    use super::Entry;
+    use storeid::StoreId;
+    use schema::HeaderSchema;
+    use schema::FieldType;
+    use hook::Hook;
+    use hook::HookData;
+    use hook::HookPosition;
     use file_abstraction::InMemoryFileAbstraction;
 
     pub fn get_store() -> Store {
@@ -1400,6 +2445,543 @@ mod store_tests {
         }
     }
 
+    fn get_store_with_index() -> Store {
+        use toml::de::from_str as toml_from_str;
+
+        let config = toml_from_str(r#"
+        [store]
+            index = true
+        "#).unwrap();
+
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap()
+    }
+
+    fn search(store: &Store, fragments: &[&str]) -> Vec<String> {
+        let mut ids : Vec<String> = store
+            .search_index(fragments)
+            .unwrap()
+            .unwrap()
+            .without_store()
+            .map(|id| id.to_str().unwrap())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_index_disabled_by_default() {
+        let store = get_store();
+        assert!(!store.index_enabled());
+        assert!(store.search_index(&["foo"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_index_is_maintained() {
+        let store = get_store_with_index();
+
+        {
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("Hello World");
+        }
+        {
+            let mut entry = store.create(PathBuf::from("b")).unwrap();
+            *entry.get_content_mut() = String::from("Goodbye World");
+        }
+
+        // the index is built on the first search and maintained from then on
+        assert_eq!(search(&store, &["world"]), vec!["a", "b"]);
+
+        {
+            let mut entry = store.create(PathBuf::from("c")).unwrap();
+            *entry.get_content_mut() = String::from("Hello again");
+        }
+        assert_eq!(search(&store, &["hello"]), vec!["a", "c"]);
+
+        {
+            let mut entry = store.get(PathBuf::from("a")).unwrap().unwrap();
+            *entry.get_content_mut() = String::from("Nothing to see");
+        }
+        assert_eq!(search(&store, &["hello"]), vec!["c"]);
+
+        assert!(store.delete(PathBuf::from("c")).is_ok());
+        assert!(search(&store, &["hello"]).is_empty());
+
+        let old = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("d")).unwrap();
+        assert!(store.move_by_id(old, new).is_ok());
+        assert_eq!(search(&store, &["goodbye"]), vec!["d"]);
+    }
+
+    #[test]
+    fn test_index_is_persisted_but_not_listed() {
+        let store = get_store_with_index();
+
+        {
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("Hello World");
+        }
+
+        assert_eq!(search(&store, &["world"]), vec!["a"]);
+        assert!(store.flush_index().is_ok());
+
+        // Forget the index, so it is loaded from the backend again
+        *store.index.lock().unwrap() = None;
+        assert_eq!(search(&store, &["world"]), vec!["a"]);
+
+        let ids : Vec<String> = store
+            .entries()
+            .unwrap()
+            .without_store()
+            .map(|id| id.without_base().to_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_index_changes_of_several_stores_are_merged() {
+        use tempdir::TempDir;
+        use toml::de::from_str as toml_from_str;
+
+        let dir    = TempDir::new("imag-store-index-merge").unwrap();
+        let config = toml_from_str(r#"
+        [store]
+            index = true
+        "#).unwrap();
+        let config = Some(config);
+
+        let store1 = Store::new(dir.path().to_path_buf(), &config).unwrap();
+        write_content(&store1, "a", "Hello World");
+        assert_eq!(search(&store1, &["world"]), vec!["a"]);
+
+        // Both stores have loaded the index before the other one writes it
+        let store2 = Store::new(dir.path().to_path_buf(), &config).unwrap();
+        assert_eq!(search(&store2, &["world"]), vec!["a"]);
+
+        write_content(&store1, "b", "Goodbye World");
+        write_content(&store2, "c", "Hello again");
+        assert!(store1.flush_index().is_ok());
+        assert!(store2.flush_index().is_ok());
+        drop(store1);
+        drop(store2);
+
+        let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+        assert_eq!(search(&store, &["world"]), vec!["a", "b"]);
+        assert_eq!(search(&store, &["hello"]), vec!["a", "c"]);
+    }
+
+    fn get_store_with_history(limit: usize) -> Store {
+        use toml::de::from_str as toml_from_str;
+
+        let config = toml_from_str(&format!(r#"
+        [store]
+            history = true
+            history-limit = {}
+        "#, limit)).unwrap();
+
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap()
+    }
+
+    fn write_content(store: &Store, id: &str, content: &str) {
+        let mut entry = store.retrieve(PathBuf::from(id)).unwrap();
+        *entry.get_content_mut() = String::from(content);
+    }
+
+    fn revision_content(store: &Store, id: &str, rev: u64) -> Option<String> {
+        store.get_revision(PathBuf::from(id), rev)
+            .unwrap()
+            .map(|e| e.get_content().clone())
+    }
+
+    fn revision_numbers(store: &Store, id: &str) -> Vec<u64> {
+        store.history(PathBuf::from(id))
+            .unwrap()
+            .into_iter()
+            .map(|r| r.number())
+            .collect()
+    }
+
+    #[test]
+    fn test_history_disabled_by_default() {
+        let store = get_store();
+        write_content(&store, "a", "one");
+        write_content(&store, "a", "two");
+
+        assert!(!store.history_enabled());
+        assert!(store.history(PathBuf::from("a")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_keeps_prior_versions() {
+        let store = get_store_with_history(0);
+        write_content(&store, "a", "one");
+        write_content(&store, "a", "two");
+        write_content(&store, "a", "two"); // unchanged, no revision
+        write_content(&store, "a", "three");
+
+        assert_eq!(revision_numbers(&store, "a"), vec![1, 2]);
+        assert_eq!(revision_content(&store, "a", 1), Some(String::from("one")));
+        assert_eq!(revision_content(&store, "a", 2), Some(String::from("two")));
+        assert_eq!(revision_content(&store, "a", 3), None);
+
+        let ids : Vec<String> = store
+            .entries()
+            .unwrap()
+            .without_store()
+            .map(|id| id.without_base().to_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let store = get_store_with_history(2);
+        for content in &["one", "two", "three", "four"] {
+            write_content(&store, "a", content);
+        }
+
+        assert_eq!(revision_numbers(&store, "a"), vec![2, 3]);
+        assert_eq!(revision_content(&store, "a", 3), Some(String::from("three")));
+    }
+
+    #[test]
+    fn test_history_restore() {
+        let store = get_store_with_history(0);
+        write_content(&store, "a", "one");
+        write_content(&store, "a", "two");
+
+        assert!(store.restore(PathBuf::from("a"), 1).is_ok());
+        assert_eq!(store.get(PathBuf::from("a")).unwrap().unwrap().get_content(), "one");
+        assert_eq!(revision_content(&store, "a", 2), Some(String::from("two")));
+
+        assert!(store.restore(PathBuf::from("a"), 42).is_err());
+    }
+
+    #[test]
+    fn test_history_survives_delete_and_move() {
+        let store = get_store_with_history(0);
+        write_content(&store, "a", "one");
+        write_content(&store, "a", "two");
+
+        let a = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+        let b = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+        assert!(store.move_by_id(a, b).is_ok());
+        assert!(revision_numbers(&store, "a").is_empty());
+        assert_eq!(revision_numbers(&store, "b"), vec![1]);
+
+        assert!(store.delete(PathBuf::from("b")).is_ok());
+        assert_eq!(revision_content(&store, "b", 2), Some(String::from("two")));
+
+        assert!(store.restore(PathBuf::from("b"), 2).is_ok());
+        assert_eq!(store.get(PathBuf::from("b")).unwrap().unwrap().get_content(), "two");
+    }
+
+    fn todo_schema() -> HeaderSchema {
+        HeaderSchema::new("todo")
+            .strict(true)
+            .required("todo.uuid", FieldType::String)
+            .optional("todo.due", FieldType::Datetime)
+    }
+
+    #[test]
+    fn test_schema_is_enforced() {
+        let store = get_store();
+        assert!(store.register_schema(todo_schema()).is_ok());
+
+        {
+            let mut entry = store.create(PathBuf::from("todo/a")).unwrap();
+            assert!(store.update(&mut entry).is_err());
+
+            let _ = entry.get_header_mut().insert("todo.uuid", Value::String(String::from("a")));
+            assert!(store.update(&mut entry).is_ok());
+
+            let _ = entry.get_header_mut().insert("todo.due", Value::String(String::from("never")));
+            assert!(store.update(&mut entry).is_err());
+
+            let _ = entry.get_header_mut().delete("todo.due");
+        }
+
+        // Other collections are not affected
+        let mut entry = store.create(PathBuf::from("other/a")).unwrap();
+        assert!(store.update(&mut entry).is_ok());
+    }
+
+    #[test]
+    fn test_schema_is_enforced_when_dropping() {
+        use toml_query::read::TomlValueReadExt;
+
+        let store = get_store();
+        assert!(store.register_schema(todo_schema()).is_ok());
+
+        {
+            let mut entry = store.create(PathBuf::from("todo/a")).unwrap();
+            let _ = entry.get_header_mut().insert("todo.uuid", Value::String(String::from("a")));
+        }
+
+        {
+            // Invalid, so it is not written when dropped
+            let mut entry = store.get(PathBuf::from("todo/a")).unwrap().unwrap();
+            let _ = entry.get_header_mut().insert("todo.due", Value::String(String::from("never")));
+            *entry.get_content_mut() = String::from("changed");
+        }
+
+        let entry = store.get_raw(PathBuf::from("todo/a")).unwrap().unwrap();
+        assert!(entry.get_header().read("todo.due").unwrap().is_none());
+        assert_eq!(entry.get_content(), "");
+        assert!(store.check_schema(&entry).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_schema_registration_is_persisted() {
+        let store = get_store();
+        assert!(store.register_schema(todo_schema()).is_ok());
+
+        // Forget the schemas, so they are loaded from the backend again
+        store.registered_schemas.write().unwrap().clear();
+        assert!(store.header_schemas().unwrap().is_empty());
+
+        assert!(store.load_schemas().is_ok());
+        assert_eq!(store.header_schemas().unwrap(), vec![todo_schema()]);
+
+        // The schema entry is internal
+        assert_eq!(store.entries().unwrap().without_store().count(), 0);
+    }
+
+    #[test]
+    fn test_configured_schema_takes_precedence() {
+        use toml::de::from_str as toml_from_str;
+
+        let config = toml_from_str(r#"
+        [store.schema.todo.fields]
+            "todo.uuid" = { type = "integer", required = true }
+        "#).unwrap();
+
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        let store   = Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap();
+        assert!(store.register_schema(todo_schema()).is_ok());
+
+        let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from("todo/a")).unwrap());
+        let _ = entry.get_header_mut().insert("todo.uuid", Value::Integer(1));
+        assert!(store.check_schema(&entry).unwrap().is_empty());
+    }
+
+    /// A hook which records its executions and fails for the entry "fail"
+    #[derive(Debug)]
+    struct RecordingHook(Arc<Mutex<Vec<String>>>);
+
+    impl Hook for RecordingHook {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn execute(&self, position: HookPosition, data: &HookData) -> ::store::Result<()> {
+            let id = data.id().local().display().to_string();
+            self.0.lock().unwrap().push(format!("{} {}", position.as_str(), id));
+
+            if id == "fail" {
+                Err(::error::StoreError::from_kind(::error::StoreErrorKind::HookError(id)))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_store_with_recording_hook(positions: &[HookPosition]) -> (Store, Arc<Mutex<Vec<String>>>) {
+        let store = get_store();
+        let calls = Arc::new(Mutex::new(vec![]));
+
+        for position in positions {
+            let hook = Box::new(RecordingHook(calls.clone()));
+            assert!(store.register_hook(*position, hook).is_ok());
+        }
+
+        (store, calls)
+    }
+
+    #[test]
+    fn test_hooks_are_executed() {
+        let (store, calls) = get_store_with_recording_hook(&[
+            HookPosition::PreCreate,
+            HookPosition::PostCreate,
+            HookPosition::PreUpdate,
+            HookPosition::PostUpdate,
+            HookPosition::PreMove,
+            HookPosition::PostMove,
+            HookPosition::PreDelete,
+            HookPosition::PostDelete,
+        ]);
+
+        {
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("content");
+        }
+        let old = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+        assert!(store.move_by_id(old, new).is_ok());
+        assert!(store.delete(PathBuf::from("b")).is_ok());
+
+        assert_eq!(*calls.lock().unwrap(), vec![
+            "pre-create a",
+            "post-create a",
+            "pre-update a",
+            "post-update a",
+            "pre-move a",
+            "post-move a",
+            "pre-delete b",
+            "post-delete b",
+        ]);
+    }
+
+    #[test]
+    fn test_update_hooks_are_only_executed_for_changed_entries() {
+        let (store, calls) = get_store_with_recording_hook(&[HookPosition::PostUpdate]);
+
+        {
+            let _ = store.create(PathBuf::from("a")).unwrap();
+        }
+        {
+            let _ = store.get(PathBuf::from("a")).unwrap().unwrap().get_content().len();
+        }
+        assert!(calls.lock().unwrap().is_empty());
+
+        {
+            let mut entry = store.get(PathBuf::from("a")).unwrap().unwrap();
+            *entry.get_content_mut() = String::from("content");
+            assert!(store.update(&mut entry).is_ok());
+        }
+        assert_eq!(*calls.lock().unwrap(), vec!["post-update a"]);
+    }
+
+    #[test]
+    fn test_pre_hooks_abort() {
+        let (store, _) = get_store_with_recording_hook(&[
+            HookPosition::PreCreate,
+            HookPosition::PreDelete,
+        ]);
+
+        assert!(store.create(PathBuf::from("fail")).is_err());
+        assert!(store.get(PathBuf::from("fail")).unwrap().is_none());
+
+        {
+            let _ = store.create(PathBuf::from("ok")).unwrap();
+        }
+        assert!(store.delete(PathBuf::from("ok")).is_ok());
+    }
+
+    #[test]
+    fn test_post_hooks_do_not_abort() {
+        let (store, calls) = get_store_with_recording_hook(&[HookPosition::PostCreate]);
+
+        {
+            let _ = store.create(PathBuf::from("fail")).unwrap();
+        }
+        assert!(store.get(PathBuf::from("fail")).unwrap().is_some());
+        assert_eq!(*calls.lock().unwrap(), vec!["post-create fail"]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_migrate_to_sqlite_and_back() {
+        use file_abstraction::SqliteFileAbstraction;
+
+        let mut store = get_store_with_index();
+        write_content(&store, "a", "one");
+        write_content(&store, "b/c", "two");
+
+        let sqlite = SqliteFileAbstraction::in_memory(PathBuf::from("/")).unwrap();
+        assert!(store.reset_backend(Box::new(sqlite)).is_ok());
+
+        let mut ids : Vec<String> = store
+            .entries()
+            .unwrap()
+            .without_store()
+            .map(|id| id.without_base().to_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b/c"]);
+        assert_eq!(search(&store, &["two"]), vec!["b/c"]);
+
+        write_content(&store, "a", "three");
+        let a = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+        let d = StoreId::new_baseless(PathBuf::from("d")).unwrap();
+        assert!(store.move_by_id(a, d).is_ok());
+
+        assert!(store.reset_backend(Box::new(InMemoryFileAbstraction::default())).is_ok());
+        assert!(store.get(PathBuf::from("a")).unwrap().is_none());
+        assert_eq!(store.get(PathBuf::from("d")).unwrap().unwrap().get_content(), "three");
+        assert_eq!(store.get(PathBuf::from("b/c")).unwrap().unwrap().get_content(), "two");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_migrate_from_filesystem_to_sqlite() {
+        use std::fs;
+        use std::io::Write;
+        use tempdir::TempDir;
+        use toml::de::from_str as toml_from_str;
+        use file_abstraction::SqliteFileAbstraction;
+
+        let dir    = TempDir::new("imag-store-migrate-fs").unwrap();
+        let config = toml_from_str(r#"
+        [store]
+            index = true
+            history = true
+        "#).unwrap();
+
+        let mut store = Store::new(dir.path().to_path_buf(), &Some(config)).unwrap();
+        write_content(&store, "a", "one");
+        write_content(&store, "a", "one, changed");
+        write_content(&store, "b/c", "two");
+        assert_eq!(search(&store, &["two"]), vec!["b/c"]);
+
+        // Files next to the entries which are not entries
+        let write = |path: &str, content: &[u8]| {
+            fs::File::create(dir.path().join(path)).unwrap().write_all(content).unwrap()
+        };
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        write(".git/config", b"[core]\n");
+        write(super::SQLITE_DATABASE, b"not an entry");
+
+        let sqlite = SqliteFileAbstraction::in_memory(dir.path().to_path_buf()).unwrap();
+        assert!(store.reset_backend(Box::new(sqlite)).is_ok());
+
+        let mut ids : Vec<String> = store
+            .entries()
+            .unwrap()
+            .without_store()
+            .map(|id| id.without_base().to_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b/c"]);
+        assert_eq!(store.get(PathBuf::from("a")).unwrap().unwrap().get_content(), "one, changed");
+
+        // The index is rebuilt from the migrated entries
+        assert_eq!(search(&store, &["two"]), vec!["b/c"]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_migrate_schemas_to_sqlite_and_back() {
+        use tempdir::TempDir;
+        use file_abstraction::SqliteFileAbstraction;
+
+        let dir       = TempDir::new("imag-store-migrate-schemas").unwrap();
+        let mut store = Store::new(dir.path().to_path_buf(), &None).unwrap();
+        let schema    = HeaderSchema::new("a").required("a.b", FieldType::Integer);
+        assert!(store.register_schema(schema).is_ok());
+
+        let schema_path = ::schema::schema_id(store.path(), "a").unwrap().into_pathbuf().unwrap();
+        assert!(store.backend.is_file(&schema_path).unwrap());
+
+        let sqlite = SqliteFileAbstraction::in_memory(dir.path().to_path_buf()).unwrap();
+        assert!(store.reset_backend(Box::new(sqlite)).is_ok());
+        assert!(store.backend.is_file(&schema_path).unwrap());
+
+        assert!(store.reset_backend(Box::new(InMemoryFileAbstraction::default())).is_ok());
+        assert!(store.backend.is_file(&schema_path).unwrap());
+    }
+
     #[test]
     fn test_swap_backend_during_runtime() {
         use file_abstraction::InMemoryFileAbstraction;
@@ -1445,5 +3027,235 @@ mod store_tests {
         }
     }
 
-}
+    fn get_locked_store(path: &::std::path::Path) -> Store {
+        let config = ::toml::de::from_str(r#"
+        [store]
+            implicit-create = true
+            lock-timeout = 0
+        "#).unwrap();
+
+        Store::new(PathBuf::from(path), &Some(config)).unwrap()
+    }
+
+    fn is_locked_error(e: &::error::StoreError) -> bool {
+        e.iter().any(|cause| format!("{}", cause).contains("locked by another process"))
+    }
+
+    #[test]
+    fn test_entry_locked_by_other_store() {
+        use tempdir::TempDir;
+
+        let dir   = TempDir::new("imag-store-lock").unwrap();
+        let store = get_locked_store(dir.path());
+        let other = get_locked_store(dir.path());
+
+        {
+            let mut entry = store.retrieve(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("content");
+
+            assert!(is_locked_error(&other.retrieve(PathBuf::from("a")).unwrap_err()));
+            assert!(is_locked_error(&other.delete(PathBuf::from("a")).unwrap_err()));
+
+            let a = StoreId::new_baseless(PathBuf::from("a")).unwrap();
+            let b = StoreId::new_baseless(PathBuf::from("b")).unwrap();
+            assert!(is_locked_error(&other.move_by_id(a, b).unwrap_err()));
+
+            // Other entries are not locked
+            assert!(other.retrieve(PathBuf::from("c")).is_ok());
+        }
+
+        // The entry is read after it was unlocked, so the other store sees the new content
+        let entry = other.retrieve(PathBuf::from("a")).unwrap();
+        assert_eq!(entry.get_content(), "content");
+    }
+
+    #[test]
+    fn test_get_locks_only_when_writing() {
+        use tempdir::TempDir;
+        use lock::LOCK_DIRECTORY;
+
+        let dir   = TempDir::new("imag-store-lock").unwrap();
+        let store = get_locked_store(dir.path());
+        let other = get_locked_store(dir.path());
+        let _     = store.create(PathBuf::from("a")).unwrap();
+
+        {
+            let _entry = store.get(PathBuf::from("a")).unwrap().unwrap();
+
+            // Reading does not lock the entry
+            assert!(!dir.path().join(LOCK_DIRECTORY).join("entries/a").exists());
+            assert!(other.retrieve(PathBuf::from("a")).is_ok());
+        }
+
+        let mut entry = store.get(PathBuf::from("a")).unwrap().unwrap();
+        {
+            let mut changed = other.get(PathBuf::from("a")).unwrap().unwrap();
+            *changed.get_content_mut() = String::from("other");
+        }
+
+        // The entry was changed by the other store since it was read
+        *entry.get_content_mut() = String::from("this");
+        let e = store.update(&mut entry).unwrap_err();
+        assert!(e.iter().any(|cause| format!("{}", cause).contains("changed by another process")));
+    }
+
+    /// Increment the counter entry `rounds` times, as one of several concurrent writers
+    fn increment_counter(path: PathBuf, rounds: i64) {
+        use toml_query::read::TomlValueReadTypeExt;
+
+        // Each writer has its own store, just like separate imag processes
+        let config = ::toml::de::from_str(r#"
+        [store]
+            implicit-create = true
+            lock-timeout = 30
+        "#).unwrap();
+        let store = Store::new(path, &Some(config)).unwrap();
+
+        for _ in 0..rounds {
+            let mut entry = store.retrieve(PathBuf::from("counter")).unwrap();
+            let count     = entry.get_header().read_int("counter.value").unwrap();
+            let count     = Value::Integer(count.unwrap_or(0) + 1);
+            let _         = entry.get_header_mut().insert("counter.value", count);
+            store.update(&mut entry).unwrap();
+        }
+    }
+
+    fn counter(path: &::std::path::Path) -> Option<i64> {
+        use toml_query::read::TomlValueReadTypeExt;
+
+        let store = get_locked_store(path);
+        let entry = store.get(PathBuf::from("counter")).unwrap().unwrap();
+        entry.get_header().read_int("counter.value").unwrap()
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        use std::thread;
+        use tempdir::TempDir;
+
+        let dir     = TempDir::new("imag-store-concurrent").unwrap();
+        let writers = 4;
+        let rounds  = 25;
+
+        let threads = (0..writers)
+            .map(|_| {
+                let path = PathBuf::from(dir.path());
+                thread::spawn(move || increment_counter(path, rounds))
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(counter(dir.path()), Some(writers * rounds));
+    }
+
+    #[test]
+    fn test_concurrent_writer_processes() {
+        use std::env;
+        use std::process::Command;
+        use std::process::Stdio;
+        use tempdir::TempDir;
+
+        let test   = "store::store_tests::test_concurrent_writer_processes";
+        let var    = "IMAG_STORE_TEST_WRITER";
+        let rounds  = 25;
+
+        // The test binary runs this test again as writer process
+        if let Some(path) = env::var_os(var) {
+            return increment_counter(PathBuf::from(path), rounds);
+        }
+
+        let dir     = TempDir::new("imag-store-processes").unwrap();
+        let writers = 4;
+
+        let processes = (0..writers)
+            .map(|_| {
+                Command::new(env::current_exe().unwrap())
+                    .args(&[test, "--exact", "--quiet", "--test-threads", "1"])
+                    .env(var, dir.path())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for mut process in processes {
+            assert!(process.wait().unwrap().success());
+        }
+
+        assert_eq!(counter(dir.path()), Some(writers * rounds));
+    }
+
+    #[test]
+    fn test_migrate() {
+        use toml_query::read::TomlValueReadTypeExt;
+        use migration::Migration;
+        use error::Result;
+
+        fn rename(entry: &mut Entry) -> Result<()> {
+            let hdr = entry.get_header_mut();
+            if let Some(v) = hdr.delete("a.old")? {
+                let _ = hdr.insert("a.new", v)?;
+            }
+            Ok(())
+        }
+
+        let store = get_store();
+        {
+            let mut entry = store.create(PathBuf::from("old")).unwrap();
+            let hdr = entry.get_header_mut();
+            let _   = hdr.insert("imag.version", Value::String(String::from("0.7.0"))).unwrap();
+            let _   = hdr.insert("a.old", Value::Integer(1)).unwrap();
+        }
+        {
+            // There is no migration for entries this old
+            let mut entry = store.create(PathBuf::from("older")).unwrap();
+            let hdr = entry.get_header_mut();
+            let _   = hdr.insert("imag.version", Value::String(String::from("0.6.0"))).unwrap();
+            let _   = hdr.insert("a.old", Value::Integer(2)).unwrap();
+        }
+        let _ = store.create(PathBuf::from("new")).unwrap();
+
+        // Without migrations, migrating would only bump the versions, so it is refused
+        match store.migrate().unwrap_err().kind() {
+            &::error::StoreErrorKind::NoMigrations => {},
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+
+        let migration = Migration::new("rename", "0.7.0", "0.8.0", rename).unwrap();
+        assert!(store.register_migration(migration).is_ok());
+
+        let report   = store.migrate().unwrap();
+        let migrated = report.migrated();
+        assert_eq!(migrated.len(), 1);
+        assert_eq!(migrated[0].id(), &StoreId::new_baseless(PathBuf::from("old")).unwrap());
+        assert_eq!(migrated[0].applied(), &vec![String::from("rename")]);
+
+        // The entry which cannot be migrated does not stop the migration of the others
+        assert_eq!(report.failed().len(), 1);
+        assert_eq!(report.failed()[0].0, StoreId::new_baseless(PathBuf::from("older")).unwrap());
+        match report.failed()[0].1.kind() {
+            &::error::StoreErrorKind::MigrationNotApplicable(..) => {},
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+
+        {
+            let entry = store.get(PathBuf::from("old")).unwrap().unwrap();
+            let hdr   = entry.get_header();
+            assert_eq!(hdr.read_int("a.new").unwrap(), Some(1));
+            assert_eq!(hdr.read_string("imag.version").unwrap(), Some(String::from("0.8.0")));
+        }
 
+        let backup = store.get_raw(migrated[0].backup().clone()).unwrap().unwrap();
+        assert_eq!(backup.get_header().read_int("a.old").unwrap(), Some(1));
+        assert_eq!(backup.get_header().read_string("imag.version").unwrap(), Some(String::from("0.7.0")));
+
+        // Everything else is up to date now
+        let report = store.migrate().unwrap();
+        assert!(report.migrated().is_empty());
+        assert_eq!(report.failed().len(), 1);
+    }
+
+}
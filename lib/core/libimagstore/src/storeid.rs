@@ -187,6 +187,12 @@ impl IntoStoreId for StoreId {
     }
 }
 
+impl AsRef<StoreId> for StoreId {
+    fn as_ref(&self) -> &StoreId {
+        self
+    }
+}
+
 impl IntoStoreId for PathBuf {
     fn into_storeid(self) -> Result<StoreId> {
         StoreId::new_baseless(self)
@@ -0,0 +1,727 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Transactional writes of several entries
+//!
+//! A `Transaction` collects modified `FileLockEntry` objects and entries to delete and writes all
+//! of them at once when it is committed.
+//!
+//! Before anything is written, a journal which contains the old and the new version of each
+//! affected entry is written to the store. If the process dies while the changes are applied, the
+//! store finishes the transaction from the journal the next time it is opened. If the process
+//! dies while the journal is written, the incomplete journal is discarded, as nothing was applied
+//! yet.
+//!
+//! If applying the changes fails, the old versions of all entries are restored. If that fails as
+//! well, the journal is replaced by one which restores the old versions, so the transaction is
+//! rolled back the next time the store is opened.
+
+use std::mem;
+use std::path::PathBuf;
+
+use serde_json;
+
+use error::StoreError as SE;
+use error::StoreErrorKind as SEK;
+use error::ResultExt;
+use store::Entry;
+use store::FileLockEntry;
+use store::Result;
+use store::Store;
+use storeid::IntoStoreId;
+use storeid::StoreId;
+
+use libimagerror::trace::trace_error;
+
+/// The format version of the journal
+const JOURNAL_FORMAT_VERSION : u64 = 1;
+
+/// A single operation in the journal
+///
+/// `before` is the complete entry before the transaction, `None` if the entry did not exist.
+/// `after` is the complete entry after the transaction, `None` if the entry is deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalOperation {
+    pub id: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// The write-ahead journal of a transaction
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Journal {
+    operations: Vec<JournalOperation>,
+}
+
+impl Journal {
+
+    pub fn new() -> Journal {
+        Journal::default()
+    }
+
+    pub fn push(&mut self, op: JournalOperation) {
+        self.operations.push(op)
+    }
+
+    pub fn operations(&self) -> &Vec<JournalOperation> {
+        &self.operations
+    }
+
+    /// The journal which undoes this journal, replaying it restores the entries before the
+    /// transaction
+    pub fn inverse(&self) -> Journal {
+        Journal {
+            operations: self.operations
+                .iter()
+                .map(|op| JournalOperation {
+                    id: op.id.clone(),
+                    before: op.after.clone(),
+                    after: op.before.clone(),
+                })
+                .collect()
+        }
+    }
+
+    /// Parse a journal
+    ///
+    /// Fails if the journal is incomplete or of another format version.
+    pub fn from_json(s: &str) -> Result<Journal> {
+        let (version, operations) : (u64, Vec<(String, Option<String>, Option<String>)>) =
+            serde_json::from_str(s).chain_err(|| SEK::JournalDeserializationError)?;
+
+        if version != JOURNAL_FORMAT_VERSION {
+            return Err(SE::from_kind(SEK::JournalDeserializationError));
+        }
+
+        Ok(Journal {
+            operations: operations
+                .into_iter()
+                .map(|(id, before, after)| JournalOperation { id, before, after })
+                .collect()
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let operations : Vec<(&String, &Option<String>, &Option<String>)> = self.operations
+            .iter()
+            .map(|op| (&op.id, &op.before, &op.after))
+            .collect();
+
+        serde_json::to_string(&(JOURNAL_FORMAT_VERSION, operations))
+            .chain_err(|| SEK::JournalSerializationError)
+    }
+
+}
+
+/// A set of changes to the store which are written all at once or not at all
+///
+/// Created with `Store::transaction()`.
+///
+/// If the transaction is dropped without being committed, it is aborted (see
+/// `Transaction::abort()`).
+///
+/// # Example
+///
+/// ```ignore
+/// let mut transaction = store.transaction();
+///
+/// let mut a = store.retrieve(PathBuf::from("a"))?;
+/// let mut b = store.retrieve(PathBuf::from("b"))?;
+/// // modify a and b ...
+///
+/// transaction.update(a);
+/// transaction.update(b);
+/// transaction.delete(PathBuf::from("c"))?;
+/// transaction.commit()?;
+/// ```
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    store: &'a Store,
+    updates: Vec<FileLockEntry<'a>>,
+    deletes: Vec<StoreId>,
+}
+
+impl<'a> Transaction<'a> {
+
+    pub(crate) fn new(store: &'a Store) -> Transaction<'a> {
+        Transaction {
+            store,
+            updates: vec![],
+            deletes: vec![],
+        }
+    }
+
+    /// Stage an entry which should be written when the transaction is committed
+    pub fn update(&mut self, entry: FileLockEntry<'a>) {
+        debug!("Staging update of {}", entry.get_location());
+        self.updates.push(entry)
+    }
+
+    /// Stage an entry which should be deleted when the transaction is committed
+    pub fn delete<S: IntoStoreId>(&mut self, id: S) -> Result<()> {
+        let id = id.into_storeid()?.with_base(self.store.path().clone());
+        debug!("Staging deletion of {}", id);
+        self.deletes.push(id);
+        Ok(())
+    }
+
+    /// Whether nothing is staged in this transaction
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.deletes.is_empty()
+    }
+
+    /// Write all staged changes to the store
+    ///
+    /// # Return value
+    ///
+    /// On success: ()
+    ///
+    /// On error, nothing is changed in the store:
+    ///  - TransactionCommitError(Errors Entry::verify() might return) if a staged entry is invalid
+    ///  - TransactionCommitError(HeaderSchemaViolation()) if a staged entry violates the header
+    ///    schema of its collection
+    ///  - TransactionCommitError(FileNotFound()) if an entry which should be deleted does not exist
+    ///  - TransactionCommitError(_) if writing the journal or applying the changes failed
+    ///  - TransactionRollbackError(_) if restoring the old state after a failure failed. In this
+    ///    case the journal is replaced by one which restores the old state, so the transaction is
+    ///    rolled back the next time the store is opened. If even that cannot be written, the
+    ///    journal is removed, so the partially applied transaction is not finished either.
+    ///
+    pub fn commit(mut self) -> Result<()> {
+        let mut updates = mem::replace(&mut self.updates, vec![]);
+        let deletes     = mem::replace(&mut self.deletes, vec![]);
+        let store       = self.store;
+
+        debug!("Committing transaction with {} updates, {} deletes", updates.len(), deletes.len());
+
+        // Other processes must not commit or recover a transaction while we write the journal
+        let _lock = match store.lock_store() {
+            Ok(lock) => lock,
+            Err(e) => {
+                let _ = abort(store, updates).map_err(|e| trace_error(&e));
+                return Err(e).chain_err(|| SEK::TransactionCommitError);
+            }
+        };
+
+        let journal = match build_journal(store, &updates, &deletes) {
+            Ok(journal) => journal,
+            Err(e) => {
+                let _ = abort(store, updates).map_err(|e| trace_error(&e));
+                return Err(e).chain_err(|| SEK::TransactionCommitError);
+            }
+        };
+
+        if let Err(e) = store.write_journal(&journal) {
+            let _ = abort(store, updates).map_err(|e| trace_error(&e));
+            let _ = store.remove_journal().map_err(|e| trace_error(&e));
+            return Err(e).chain_err(|| SEK::TransactionCommitError);
+        }
+
+        // From here on, the transaction is committed: If we die, it is finished from the journal.
+
+        let applied = updates
+            .iter_mut()
+            .fold(Ok(()), |acc, entry| acc.and_then(|_| store._update(entry, false)))
+            .and_then(|_| {
+                deletes.iter().fold(Ok(()), |acc, id| acc.and_then(|_| store.delete(id.clone())))
+            });
+
+        match applied {
+            Ok(()) => {
+                drop(updates);
+                store.remove_journal().chain_err(|| SEK::TransactionCommitError)
+            },
+            Err(e) => {
+                warn!("Applying transaction failed, rolling back");
+                if let Err(rollback_error) = rollback(store, updates, &journal) {
+                    // Finishing the transaction from the journal would apply what just failed
+                    error!("Rolling back failed, the store is rolled back when it is opened next");
+                    let _ = store
+                        .write_journal(&journal.inverse())
+                        .or_else(|e| {
+                            trace_error(&e);
+                            store.remove_journal()
+                        })
+                        .map_err(|e| trace_error(&e));
+
+                    return Err(rollback_error).chain_err(|| SEK::TransactionRollbackError);
+                }
+
+                let _ = store.remove_journal().chain_err(|| SEK::TransactionRollbackError)?;
+                Err(e).chain_err(|| SEK::TransactionCommitError)
+            },
+        }
+    }
+
+    /// Discard all staged changes
+    ///
+    /// The staged entries are reset to what is in the store, entries which were created and staged
+    /// in this transaction are removed again.
+    pub fn abort(mut self) -> Result<()> {
+        let updates = mem::replace(&mut self.updates, vec![]);
+        self.deletes.clear();
+        abort(self.store, updates)
+    }
+
+}
+
+impl<'a> Drop for Transaction<'a> {
+
+    fn drop(&mut self) {
+        if !self.is_empty() {
+            debug!("Dropping uncommitted transaction, aborting");
+            let updates = mem::replace(&mut self.updates, vec![]);
+            let _ = abort(self.store, updates).map_err(|e| trace_error(&e));
+        }
+    }
+
+}
+
+fn build_journal<'a>(store: &'a Store, updates: &[FileLockEntry<'a>], deletes: &[StoreId])
+    -> Result<Journal>
+{
+    let mut journal = Journal::new();
+
+    for entry in updates {
+        let _ = entry.verify()?;
+        let _ = store.verify_schema(entry)?;
+        let before = store.get_raw(entry.get_location().clone())?;
+
+        journal.push(JournalOperation {
+            id: local_id_str(entry.get_location())?,
+            before: before.map(|e| e.to_str()).map_or(Ok(None), |r| r.map(Some))?,
+            after: Some(entry.to_str()?),
+        });
+    }
+
+    for id in deletes {
+        let before = store
+            .get_raw(id.clone())?
+            .ok_or_else(|| SE::from_kind(SEK::FileNotFound))?;
+
+        journal.push(JournalOperation {
+            id: local_id_str(id)?,
+            before: Some(before.to_str()?),
+            after: None,
+        });
+    }
+
+    Ok(journal)
+}
+
+/// Reset the staged entries to their state in the store and drop them
+fn abort<'a>(store: &'a Store, updates: Vec<FileLockEntry<'a>>) -> Result<()> {
+    let befores = updates
+        .iter()
+        .map(|entry| {
+            store.get_raw(entry.get_location().clone())
+                .and_then(|o| o.map(|e| e.to_str()).map_or(Ok(None), |r| r.map(Some)))
+        })
+        .collect::<Result<Vec<Option<String>>>>()?;
+
+    restore(store, updates, befores)
+}
+
+/// Restore the state before the transaction from the journal
+fn rollback<'a>(store: &'a Store, updates: Vec<FileLockEntry<'a>>, journal: &Journal) -> Result<()> {
+    let (update_ops, delete_ops) = journal.operations().split_at(updates.len());
+    let befores = update_ops.iter().map(|op| op.before.clone()).collect();
+
+    let _ = restore(store, updates, befores)?;
+
+    for op in delete_ops {
+        let id = StoreId::new_baseless(PathBuf::from(&op.id))?;
+        if store.get_raw(id.clone())?.is_none() {
+            if let Some(ref before) = op.before {
+                debug!("Re-creating deleted entry {}", id);
+                let mut entry = store.create(id)?;
+                let _ = entry.replace_from_buffer(before)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reset the `entries` to the `befores` and write them. Entries without a "before" are deleted.
+fn restore<'a>(store: &'a Store, entries: Vec<FileLockEntry<'a>>, befores: Vec<Option<String>>)
+    -> Result<()>
+{
+    let mut to_delete = vec![];
+
+    for (mut entry, before) in entries.into_iter().zip(befores.into_iter()) {
+        match before {
+            Some(before) => {
+                debug!("Restoring {}", entry.get_location());
+                let _ = entry.replace_from_buffer(&before)?;
+            },
+            None => {
+                debug!("Removing {} again", entry.get_location());
+                *entry.get_content_mut() = String::new();
+                to_delete.push(entry.get_location().clone());
+            },
+        }
+        // entry is written here, on drop
+    }
+
+    // Internal cleanup, hooks must not be able to prevent it
+    for id in to_delete {
+        let _ = store.delete_entry(id)?;
+    }
+
+    Ok(())
+}
+
+fn local_id_str(id: &StoreId) -> Result<String> {
+    id.local()
+        .to_str()
+        .map(String::from)
+        .ok_or_else(|| SE::from_kind(SEK::StoreIdHandlingError))
+}
+
+/// Helper for replaying a journal: Build the entry an operation results in
+pub(crate) fn entry_after(op: &JournalOperation, id: StoreId) -> Result<Option<Entry>> {
+    match op.after {
+        Some(ref after) => Entry::from_str(id, after).map(Some),
+        None            => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use store::Store;
+    use file_abstraction::InMemoryFileAbstraction;
+    use hook::Hook;
+    use hook::HookData;
+    use hook::HookPosition;
+
+    fn get_store() -> Store {
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn content_of(store: &Store, id: &str) -> Option<String> {
+        store.get(PathBuf::from(id))
+            .unwrap()
+            .map(|e| e.get_content().clone())
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let mut journal = Journal::new();
+        journal.push(JournalOperation {
+            id: String::from("a"),
+            before: None,
+            after: Some(String::from("after")),
+        });
+        journal.push(JournalOperation {
+            id: String::from("b"),
+            before: Some(String::from("before")),
+            after: None,
+        });
+
+        let parsed = Journal::from_json(&journal.to_json().unwrap()).unwrap();
+        assert_eq!(journal, parsed);
+    }
+
+    #[test]
+    fn test_journal_inverse() {
+        let mut journal = Journal::new();
+        journal.push(JournalOperation {
+            id: String::from("a"),
+            before: None,
+            after: Some(String::from("after")),
+        });
+
+        let inverse = journal.inverse();
+        assert_eq!(inverse.operations()[0].before, Some(String::from("after")));
+        assert_eq!(inverse.operations()[0].after, None);
+        assert_eq!(inverse.inverse(), journal);
+    }
+
+    #[test]
+    fn test_incomplete_journal_is_error() {
+        let mut journal = Journal::new();
+        journal.push(JournalOperation {
+            id: String::from("a"),
+            before: None,
+            after: Some(String::from("after")),
+        });
+
+        let json = journal.to_json().unwrap();
+        assert!(Journal::from_json(&json[..json.len() - 3]).is_err());
+    }
+
+    fn get_store_with_journal(journal: &str) -> Store {
+        use file_abstraction::FileAbstraction;
+
+        let backend = InMemoryFileAbstraction::default();
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from(".store/journal")).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from(journal);
+        backend.new_instance(id.into_pathbuf().unwrap()).write_file_content(&entry).unwrap();
+
+        Store::new_with_backend(PathBuf::from("/"), &None, Box::new(backend)).unwrap()
+    }
+
+    fn journal_creating_a() -> Journal {
+        let id = StoreId::new(Some(PathBuf::from("/")), PathBuf::from("a")).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from("replayed");
+
+        let mut journal = Journal::new();
+        journal.push(JournalOperation {
+            id: String::from("a"),
+            before: None,
+            after: Some(entry.to_str().unwrap()),
+        });
+        journal
+    }
+
+    #[test]
+    fn test_complete_journal_is_replayed_on_open() {
+        let store = get_store_with_journal(&journal_creating_a().to_json().unwrap());
+
+        assert!(store.get_raw(store.journal_id().unwrap()).unwrap().is_none());
+        let a = store.get_raw(PathBuf::from("a")).unwrap().unwrap();
+        assert_eq!(a.get_content(), "replayed");
+    }
+
+    #[test]
+    fn test_incomplete_journal_is_discarded_on_open() {
+        let json  = journal_creating_a().to_json().unwrap();
+        let store = get_store_with_journal(&json[..json.len() / 2]);
+
+        assert!(store.get_raw(store.journal_id().unwrap()).unwrap().is_none());
+        assert!(store.get_raw(PathBuf::from("a")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_commit_writes_all_entries() {
+        let store = get_store();
+        {
+            let mut c = store.create(PathBuf::from("c")).unwrap();
+            *c.get_content_mut() = String::from("to be deleted");
+        }
+
+        {
+            let mut transaction = store.transaction();
+
+            let mut a = store.create(PathBuf::from("a")).unwrap();
+            *a.get_content_mut() = String::from("content a");
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            *b.get_content_mut() = String::from("content b");
+
+            transaction.update(a);
+            transaction.update(b);
+            transaction.delete(PathBuf::from("c")).unwrap();
+            assert!(transaction.commit().is_ok());
+        }
+
+        assert_eq!(content_of(&store, "a"), Some(String::from("content a")));
+        assert_eq!(content_of(&store, "b"), Some(String::from("content b")));
+        assert_eq!(content_of(&store, "c"), None);
+        assert!(store.get_raw(store.journal_id().unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_abort_restores_entries() {
+        let store = get_store();
+        {
+            let mut a = store.create(PathBuf::from("a")).unwrap();
+            *a.get_content_mut() = String::from("old");
+        }
+
+        {
+            let mut transaction = store.transaction();
+
+            let mut a = store.get(PathBuf::from("a")).unwrap().unwrap();
+            *a.get_content_mut() = String::from("new");
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            *b.get_content_mut() = String::from("new");
+
+            transaction.update(a);
+            transaction.update(b);
+            // dropped without commit
+        }
+
+        assert_eq!(content_of(&store, "a"), Some(String::from("old")));
+        assert_eq!(content_of(&store, "b"), None);
+    }
+
+    #[test]
+    fn test_failing_commit_rolls_back() {
+        let store = get_store();
+        {
+            let mut a = store.create(PathBuf::from("a")).unwrap();
+            *a.get_content_mut() = String::from("old");
+        }
+
+        {
+            let mut transaction = store.transaction();
+
+            let mut a = store.get(PathBuf::from("a")).unwrap().unwrap();
+            *a.get_content_mut() = String::from("new");
+            transaction.update(a);
+
+            // deleting a non-existing entry fails
+            transaction.delete(PathBuf::from("does-not-exist")).unwrap();
+            assert!(transaction.commit().is_err());
+        }
+
+        assert_eq!(content_of(&store, "a"), Some(String::from("old")));
+    }
+
+    #[derive(Debug)]
+    struct FailingHook;
+
+    impl Hook for FailingHook {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn execute(&self, _: HookPosition, _: &HookData) -> Result<()> {
+            Err(SE::from_kind(SEK::HookError(String::from("failing"))))
+        }
+    }
+
+    #[test]
+    fn test_failing_commit_after_journal_write_rolls_back() {
+        let store = get_store();
+        {
+            let mut a = store.create(PathBuf::from("a")).unwrap();
+            *a.get_content_mut() = String::from("old");
+            let _ = store.create(PathBuf::from("c")).unwrap();
+        }
+
+        // The journal is written and the updates are applied, then deleting "c" fails
+        store.register_hook(HookPosition::PreDelete, Box::new(FailingHook)).unwrap();
+
+        {
+            let mut transaction = store.transaction();
+
+            let mut a = store.get(PathBuf::from("a")).unwrap().unwrap();
+            *a.get_content_mut() = String::from("new");
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            *b.get_content_mut() = String::from("new");
+
+            transaction.update(a);
+            transaction.update(b);
+            transaction.delete(PathBuf::from("c")).unwrap();
+
+            let err = transaction.commit().unwrap_err();
+            assert!(is_match!(err.kind(), &SEK::TransactionCommitError), "{:?}", err);
+        }
+
+        assert_eq!(content_of(&store, "a"), Some(String::from("old")));
+        assert_eq!(content_of(&store, "b"), None);
+        assert!(store.get_raw(PathBuf::from("c")).unwrap().is_some());
+        assert!(store.get_raw(store.journal_id().unwrap()).unwrap().is_none());
+    }
+
+    #[derive(Debug)]
+    struct FailingHookFor(&'static str);
+
+    impl Hook for FailingHookFor {
+        fn name(&self) -> &str {
+            "failing-for"
+        }
+
+        fn execute(&self, _: HookPosition, data: &HookData) -> Result<()> {
+            if data.id().local() == &PathBuf::from(self.0) {
+                Err(SE::from_kind(SEK::HookError(String::from("failing-for"))))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_failing_rollback_leaves_inverse_journal() {
+        let store = get_store();
+        {
+            let mut c = store.create(PathBuf::from("c")).unwrap();
+            *c.get_content_mut() = String::from("old");
+            let _ = store.create(PathBuf::from("d")).unwrap();
+        }
+
+        // Deleting "d" fails after "c" was deleted, then re-creating "c" fails
+        store.register_hook(HookPosition::PreDelete, Box::new(FailingHookFor("d"))).unwrap();
+        store.register_hook(HookPosition::PreCreate, Box::new(FailingHookFor("c"))).unwrap();
+
+        {
+            let mut transaction = store.transaction();
+            transaction.delete(PathBuf::from("c")).unwrap();
+            transaction.delete(PathBuf::from("d")).unwrap();
+
+            let err = transaction.commit().unwrap_err();
+            assert!(is_match!(err.kind(), &SEK::TransactionRollbackError), "{:?}", err);
+        }
+
+        // Replaying the journal re-creates "c" instead of deleting "d"
+        let journal = store.get_raw(store.journal_id().unwrap()).unwrap().unwrap();
+        let journal = Journal::from_json(journal.get_content()).unwrap();
+        let ops     = journal.operations();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].id, "c");
+        assert!(ops[0].before.is_none());
+        assert!(ops[0].after.as_ref().map(|a| a.contains("old")).unwrap_or(false));
+        assert_eq!(ops[1].id, "d");
+        assert!(ops[1].after.is_some());
+    }
+
+    #[test]
+    fn test_commit_checks_header_schemas() {
+        use toml::Value;
+        use toml_query::insert::TomlValueInsertExt;
+        use schema::FieldType;
+        use schema::HeaderSchema;
+
+        let store  = get_store();
+        let schema = HeaderSchema::new("coll").required("coll.value", FieldType::Integer);
+        store.register_schema(schema).unwrap();
+
+        {
+            let mut transaction = store.transaction();
+            let mut a = store.retrieve(PathBuf::from("coll/a")).unwrap();
+            let _ = a.get_header_mut().insert("coll.value", Value::Integer(1)).unwrap();
+            transaction.update(a);
+            assert!(transaction.commit().is_ok());
+        }
+
+        {
+            let mut transaction = store.transaction();
+            let mut a = store.get(PathBuf::from("coll/a")).unwrap().unwrap();
+            let _ = a.get_header_mut().insert("coll.value", Value::from("one")).unwrap();
+            *a.get_content_mut() = String::from("new");
+            transaction.update(a);
+
+            let err = transaction.commit().unwrap_err();
+            assert!(is_match!(err.kind(), &SEK::TransactionCommitError), "{:?}", err);
+        }
+
+        // Nothing was written, not even the journal
+        assert_eq!(content_of(&store, "coll/a"), Some(String::new()));
+        assert!(store.get_raw(store.journal_id().unwrap()).unwrap().is_none());
+    }
+
+}
@@ -0,0 +1,437 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Checking external links for link rot
+//!
+//! A `LinkChecker` decides what a URL currently points to. The `HttpLinkChecker` does this with
+//! HTTP `HEAD` requests and does not follow redirects, so a moved link is reported as such.
+//!
+//! `check_links()` runs a checker over `links/external` entries and records the outcome in the
+//! header of each link entry:
+//!
+//! ```toml
+//! [links.external.check]
+//! status = 301
+//! redirect = "https://imag-pim.org/new"
+//! checked = "2018-04-02 12:00:00"
+//! ```
+//!
+//! If the URL could not be fetched at all, `error` is set instead of `status`. Link entries which
+//! cannot be checked, for example because they have no URL, are reported in the `CheckReport`.
+
+use std::fmt::{Display, Formatter, Error as FmtError};
+use std::time::Duration;
+
+use chrono::Local;
+use curl::easy::Easy;
+use curl::Error as CurlError;
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use url::Url;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::external::Link;
+use libimagentrylink::external::is_external_link_storeid;
+use libimagutil::date::datetime_to_string;
+
+use error::Result;
+use error::BookmarkError as BE;
+
+/// The header path the result of the last check is stored at
+pub const CHECK_HEADER_PATH : &str = "links.external.check";
+
+/// What a link pointed to when it was checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The server answered with a HTTP status code and, for redirects, the target
+    Http {
+        code: u16,
+        redirect: Option<String>,
+    },
+
+    /// The URL could not be fetched, with the reason why
+    Unreachable(String),
+}
+
+impl LinkStatus {
+
+    /// A link is dead if the server answered with an error or did not answer at all
+    pub fn is_dead(&self) -> bool {
+        match *self {
+            LinkStatus::Http { code, .. } => code >= 400,
+            LinkStatus::Unreachable(_)    => true,
+        }
+    }
+
+    pub fn redirect(&self) -> Option<&String> {
+        match *self {
+            LinkStatus::Http { ref redirect, .. } => redirect.as_ref(),
+            LinkStatus::Unreachable(_)            => None,
+        }
+    }
+
+}
+
+impl Display for LinkStatus {
+
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            LinkStatus::Http { code, redirect: Some(ref to) } => write!(fmt, "{} -> {}", code, to),
+            LinkStatus::Http { code, redirect: None }         => write!(fmt, "{}", code),
+            LinkStatus::Unreachable(ref reason)               => write!(fmt, "unreachable: {}", reason),
+        }
+    }
+
+}
+
+pub trait LinkChecker {
+    fn check(&self, url: &Url) -> LinkStatus;
+}
+
+/// A `LinkChecker` which asks the server via HTTP
+pub struct HttpLinkChecker {
+    timeout: Duration,
+}
+
+impl HttpLinkChecker {
+
+    pub fn new(timeout: Duration) -> HttpLinkChecker {
+        HttpLinkChecker { timeout: timeout }
+    }
+
+    fn request(&self, url: &Url, head: bool) -> ::std::result::Result<LinkStatus, CurlError> {
+        let mut easy = Easy::new();
+        let _ = easy.url(url.as_str())?;
+        let _ = easy.follow_location(false)?;
+        let _ = easy.timeout(self.timeout)?;
+        let _ = easy.nobody(head)?;
+
+        // The body is not of interest, but would be written to stdout otherwise
+        let _ = easy.write_function(|data| Ok(data.len()))?;
+        let _ = easy.perform()?;
+
+        let code     = easy.response_code()?;
+        let redirect = if code >= 300 && code < 400 {
+            easy.redirect_url()?.map(String::from)
+        } else {
+            None
+        };
+
+        Ok(LinkStatus::Http {
+            code: code as u16,
+            redirect: redirect,
+        })
+    }
+
+}
+
+impl LinkChecker for HttpLinkChecker {
+
+    fn check(&self, url: &Url) -> LinkStatus {
+        debug!("Checking {}", url);
+        let status = self.request(url, true)
+            .and_then(|status| match status {
+                // Not every server implements HEAD, ask again with GET then
+                LinkStatus::Http { code: 405, .. } |
+                LinkStatus::Http { code: 501, .. } => self.request(url, false),
+                _ => Ok(status),
+            });
+
+        match status {
+            Ok(status) => status,
+            Err(e)     => LinkStatus::Unreachable(format!("{}", e)),
+        }
+    }
+
+}
+
+/// The outcome of checking one link entry
+#[derive(Debug, Clone)]
+pub struct CheckedLink {
+    id: StoreId,
+    url: Url,
+    status: LinkStatus,
+}
+
+impl CheckedLink {
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn status(&self) -> &LinkStatus {
+        &self.status
+    }
+
+}
+
+/// What `check_links()` did
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    checked: Vec<CheckedLink>,
+    failed: Vec<(StoreId, BE)>,
+}
+
+impl CheckReport {
+
+    /// The link entries which were checked
+    pub fn checked(&self) -> &Vec<CheckedLink> {
+        &self.checked
+    }
+
+    /// The link entries which could not be checked, with the reason why
+    pub fn failed(&self) -> &Vec<(StoreId, BE)> {
+        &self.failed
+    }
+
+    /// The checked links which are dead
+    pub fn dead(&self) -> Vec<&CheckedLink> {
+        self.checked.iter().filter(|c| c.status().is_dead()).collect()
+    }
+
+}
+
+/// Check the URLs of the passed link entries and record the results in their headers
+///
+/// Ids which do not point to an existing `links/external` entry are skipped. Link entries which
+/// cannot be checked do not abort the run, they are reported in `CheckReport::failed()`.
+pub fn check_links<C, I>(store: &Store, checker: &C, ids: I) -> Result<CheckReport>
+    where C: LinkChecker,
+          I: IntoIterator<Item = StoreId>
+{
+    let checked = datetime_to_string(&Local::now().naive_local());
+    let mut report = CheckReport::default();
+
+    for id in ids.into_iter().filter(|id| is_external_link_storeid(id)) {
+        let mut entry = match store.get(id.clone())? {
+            Some(entry) => entry,
+            None        => continue,
+        };
+
+        let url = match entry.get_url() {
+            Ok(Some(url)) => url,
+            Ok(None)      => {
+                report.failed.push((id, BE::from("Link entry has no URL")));
+                continue;
+            },
+            Err(e)        => {
+                report.failed.push((id, BE::from(e)));
+                continue;
+            },
+        };
+
+        let status = checker.check(&url);
+        let record = record_status(&mut entry, &status, &checked)
+            .and_then(|_| store.update(&mut entry).map_err(BE::from));
+        if let Err(e) = record {
+            report.failed.push((id, e));
+            continue;
+        }
+
+        report.checked.push(CheckedLink { id: id, url: url, status: status });
+    }
+
+    Ok(report)
+}
+
+/// Check all `links/external` entries of the store
+pub fn check_all_links<C: LinkChecker>(store: &Store, checker: &C) -> Result<CheckReport> {
+    let ids = store.entries()?.without_store().collect::<Vec<_>>();
+    check_links(store, checker, ids)
+}
+
+fn record_status(entry: &mut Entry, status: &LinkStatus, checked: &str) -> Result<()> {
+    let mut table = ::toml::value::Table::new();
+
+    match *status {
+        LinkStatus::Http { code, ref redirect } => {
+            table.insert(String::from("status"), Value::Integer(code as i64));
+            if let Some(ref to) = *redirect {
+                table.insert(String::from("redirect"), Value::String(to.clone()));
+            }
+        },
+        LinkStatus::Unreachable(ref reason) => {
+            table.insert(String::from("error"), Value::String(reason.clone()));
+        },
+    }
+    table.insert(String::from("checked"), Value::String(String::from(checked)));
+
+    entry.get_header_mut()
+        .insert(CHECK_HEADER_PATH, Value::Table(table))
+        .map(|_| ())
+        .map_err(From::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    use toml_query::read::TomlValueReadExt;
+    use url::Url;
+
+    use libimagstore::store::Store;
+    use libimagentrylink::external::ExternalLinker;
+
+    use super::*;
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    /// Start a local HTTP server which answers `/ok` with 200, `/moved` with a redirect to `/ok`
+    /// and everything else with 404. Returns the base URL of the server.
+    fn serve() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base     = format!("http://{}/", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream  = stream.unwrap();
+                let request_line = {
+                    let mut reader = BufReader::new(&mut stream);
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+
+                    // Skip the remaining request headers
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        line.clear();
+                    }
+                    request_line
+                };
+
+                let response = match request_line.split_whitespace().nth(1) {
+                    Some("/ok")    => "HTTP/1.1 200 OK\r\n",
+                    Some("/moved") => "HTTP/1.1 301 Moved Permanently\r\nLocation: /ok\r\n",
+                    _              => "HTTP/1.1 404 Not Found\r\n",
+                };
+                let _ = write!(stream, "{}Content-Length: 0\r\nConnection: close\r\n\r\n", response);
+            }
+        });
+
+        Url::parse(&base).unwrap()
+    }
+
+    /// An URL on a port nobody listens on
+    fn unreachable_url() -> Url {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap()
+    }
+
+    fn checker() -> HttpLinkChecker {
+        HttpLinkChecker::new(Duration::from_secs(5))
+    }
+
+    #[test]
+    fn test_http_link_checker() {
+        let base    = serve();
+        let checker = checker();
+
+        let ok = checker.check(&base.join("ok").unwrap());
+        assert_eq!(ok, LinkStatus::Http { code: 200, redirect: None });
+        assert!(!ok.is_dead());
+
+        let moved = checker.check(&base.join("moved").unwrap());
+        assert_eq!(moved.redirect(), Some(&base.join("ok").unwrap().into_string()));
+        assert!(!moved.is_dead());
+
+        let gone = checker.check(&base.join("gone").unwrap());
+        assert_eq!(gone, LinkStatus::Http { code: 404, redirect: None });
+        assert!(gone.is_dead());
+
+        assert!(checker.check(&unreachable_url()).is_dead());
+    }
+
+    #[test]
+    fn test_check_all_links_records_header() {
+        let base  = serve();
+        let store = get_store();
+
+        {
+            let mut e = store.retrieve(PathBuf::from("bookmarks")).unwrap();
+            for path in &["ok", "moved", "gone"] {
+                e.add_external_link(&store, base.join(path).unwrap()).unwrap();
+            }
+            e.add_external_link(&store, unreachable_url()).unwrap();
+        }
+
+        let report = check_all_links(&store, &checker()).unwrap();
+        assert_eq!(report.checked().len(), 4);
+        assert_eq!(report.dead().len(), 2);
+        assert!(report.failed().is_empty());
+
+        for result in report.checked() {
+            let entry  = store.get(result.id().clone()).unwrap().unwrap();
+            let header = entry.get_header();
+
+            assert!(header.read("links.external.check.checked").unwrap().is_some());
+
+            match *result.status() {
+                LinkStatus::Http { code, ref redirect } => {
+                    let status = header.read("links.external.check.status").unwrap().cloned();
+                    assert_eq!(status, Some(Value::Integer(code as i64)));
+
+                    let to = header.read("links.external.check.redirect").unwrap().cloned();
+                    assert_eq!(to, redirect.clone().map(Value::String));
+                },
+                LinkStatus::Unreachable(_) => {
+                    assert!(header.read("links.external.check.error").unwrap().is_some());
+                    assert!(header.read("links.external.check.status").unwrap().is_none());
+                },
+            }
+        }
+    }
+
+    struct AlwaysOk;
+
+    impl LinkChecker for AlwaysOk {
+        fn check(&self, _: &Url) -> LinkStatus {
+            LinkStatus::Http { code: 200, redirect: None }
+        }
+    }
+
+    #[test]
+    fn test_check_links_continues_after_entry_without_url() {
+        let store = get_store();
+
+        {
+            let mut e = store.retrieve(PathBuf::from("bookmarks")).unwrap();
+            e.add_external_link(&store, Url::parse("https://imag-pim.org").unwrap()).unwrap();
+        }
+        let _ = store.create(PathBuf::from("links/external/without-url")).unwrap();
+
+        let report = check_all_links(&store, &AlwaysOk).unwrap();
+        assert_eq!(report.checked().len(), 1);
+        assert_eq!(report.failed().len(), 1);
+        assert_eq!(report.failed()[0].0, StoreId::new_baseless(PathBuf::from("links/external/without-url")).unwrap());
+    }
+
+}
@@ -27,6 +27,11 @@ error_chain! {
         LinkError(::libimagentrylink::error::LinkError, ::libimagentrylink::error::LinkErrorKind);
     }
 
+    foreign_links {
+        TomlQueryError(::toml_query::error::Error);
+        HttpError(::curl::Error);
+    }
+
     errors {
         LinkParsingError   {
             description("Link parsing error")
@@ -43,6 +48,11 @@ error_chain! {
             display("Link-Collection not found")
         }
 
+        LinkCheckError {
+            description("Error while checking link")
+            display("Error while checking link")
+        }
+
     }
 }
 
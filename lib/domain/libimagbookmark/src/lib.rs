@@ -37,14 +37,21 @@
 
 extern crate url;
 extern crate regex;
+extern crate curl;
+extern crate chrono;
+extern crate toml;
+extern crate toml_query;
+#[macro_use] extern crate log;
 #[macro_use] extern crate error_chain;
 
 #[macro_use] extern crate libimagstore;
 extern crate libimagerror;
 extern crate libimagentrylink;
+extern crate libimagutil;
 
 module_entry_path_mod!("bookmark");
 
+pub mod check;
 pub mod collection;
 pub mod error;
 pub mod link;
@@ -30,6 +30,8 @@ use libimagstore::storeid::StoreId;
 use libimagstore::store::Store;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreIdIterator;
+use libimagstore::schema::HeaderSchema;
+use libimagstore::schema::FieldType;
 use libimagentryutil::isa::Is;
 
 use contact::IsContact;
@@ -53,6 +55,11 @@ pub trait ContactStore<'a> {
     // getting
 
     fn all_contacts(&'a self) -> Result<StoreIdIterator>;
+
+    // schema
+
+    /// Register the header schema of contacts in the store
+    fn register_contact_schema(&'a self) -> Result<()>;
 }
 
 /// The extension for the Store to work with contacts
@@ -70,11 +77,13 @@ impl<'a> ContactStore<'a> for Store {
     fn create_from_buf(&'a self, buf: &str) -> Result<FileLockEntry<'a>> {
         let (sid, value) = prepare_fetching_from_store(buf)?;
         postprocess_fetched_entry(self.create(sid)?, value)
+            .and_then(|mut entry| self.update(&mut entry).map(|_| entry).map_err(From::from))
     }
 
     fn retrieve_from_buf(&'a self, buf: &str) -> Result<FileLockEntry<'a>> {
         let (sid, value) = prepare_fetching_from_store(buf)?;
         postprocess_fetched_entry(self.retrieve(sid)?, value)
+            .and_then(|mut entry| self.update(&mut entry).map(|_| entry).map_err(From::from))
     }
 
     fn all_contacts(&'a self) -> Result<StoreIdIterator> {
@@ -86,6 +95,15 @@ impl<'a> ContactStore<'a> for Store {
         Ok(StoreIdIterator::new(Box::new(iter)))
     }
 
+    fn register_contact_schema(&'a self) -> Result<()> {
+        let schema = HeaderSchema::new("contact")
+            .strict(true)
+            .required("contact.is_contact", FieldType::Boolean)
+            .required("contact.data", FieldType::Table);
+
+        self.register_schema(schema).map_err(From::from)
+    }
+
 }
 
 /// Prepare the fetching from the store.
@@ -21,6 +21,8 @@ use std::cmp::Ordering;
 
 use libimagstore::store::FileLockEntry;
 use libimagstore::store::Store;
+use libimagstore::schema::HeaderSchema;
+use libimagstore::schema::FieldType;
 use libimagerror::trace::trace_error;
 use libimagentryutil::isa::Is;
 
@@ -55,6 +57,9 @@ pub trait Diary {
     /// Get all diary names
     fn diary_names(&self) -> Result<DiaryNameIterator>;
 
+    /// Register the header schema of diary entries in the store
+    fn register_diary_schema(&self) -> Result<()>;
+
 }
 
 impl Diary for Store {
@@ -67,6 +72,7 @@ impl Diary for Store {
 
         let mut entry = self.retrieve(id).chain_err(|| DEK::StoreReadError)?;
         let _         = entry.set_isflag::<IsDiaryEntry>()?;
+        let _         = self.update(&mut entry).chain_err(|| DEK::StoreWriteError)?;
         Ok(entry)
     }
 
@@ -83,6 +89,7 @@ impl Diary for Store {
 
         let mut entry = self.retrieve(id).chain_err(|| DEK::StoreReadError)?;
         let _         = entry.set_isflag::<IsDiaryEntry>()?;
+        let _         = self.update(&mut entry).chain_err(|| DEK::StoreWriteError)?;
         Ok(entry)
     }
 
@@ -139,5 +146,12 @@ impl Diary for Store {
             .map_err(::error::DiaryError::from)
     }
 
+    fn register_diary_schema(&self) -> Result<()> {
+        let schema = HeaderSchema::new("diary")
+            .required("diary.is_diary_entry", FieldType::Boolean);
+
+        self.register_schema(schema).chain_err(|| DEK::StoreWriteError)
+    }
+
 }
 
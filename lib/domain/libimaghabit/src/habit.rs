@@ -106,6 +106,7 @@ impl HabitTemplate for Entry {
         store.create(id)
             .map_err(From::from)
             .and_then(|entry| postprocess_instance(entry, name, date, comment, self))
+            .and_then(|mut entry| store.update(&mut entry).map(|_| entry).map_err(From::from))
     }
 
     fn create_instance_today<'a>(&mut self, store: &'a Store) -> Result<FileLockEntry<'a>> {
@@ -121,6 +122,7 @@ impl HabitTemplate for Entry {
         store.retrieve(id)
             .map_err(From::from)
             .and_then(|entry| postprocess_instance(entry, name, date, comment, self))
+            .and_then(|mut entry| store.update(&mut entry).map(|_| entry).map_err(From::from))
     }
 
     fn retrieve_instance_today<'a>(&mut self, store: &'a Store) -> Result<FileLockEntry<'a>> {
@@ -374,6 +376,9 @@ pub mod builder {
                 try!(entry.get_header_mut().insert("habit.template.until", Value::String(until)));
             }
 
+            // Write it now, so violations of the header schema are reported
+            let _ = try!(store.update(&mut entry));
+
             debug!("Success: Created entry in store and set headers");
             Ok(entry)
         }
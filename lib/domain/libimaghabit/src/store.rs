@@ -23,6 +23,8 @@ use iter::HabitTemplateStoreIdIterator;
 use iter::HabitInstanceStoreIdIterator;
 
 use libimagstore::store::Store;
+use libimagstore::schema::HeaderSchema;
+use libimagstore::schema::FieldType;
 
 /// Extension trait for libimagstore::store::Store which is basically our Habit-Store
 pub trait HabitStore {
@@ -38,6 +40,9 @@ pub trait HabitStore {
     /// Get instances
     fn all_habit_instances(&self) -> Result<HabitInstanceStoreIdIterator>;
 
+    /// Register the header schemas of habit templates and instances in the store
+    fn register_habit_schemas(&self) -> Result<()>;
+
     // /// Get instances of a certain date
     // fn all_habit_instances_on(&self, date: &NaiveDate) -> Result<HabitInstanceStoreIdIterator>;
 
@@ -54,5 +59,27 @@ impl HabitStore for Store {
     fn all_habit_instances(&self) -> Result<HabitInstanceStoreIdIterator> {
         self.entries().map(HabitInstanceStoreIdIterator::from).map_err(From::from)
     }
+
+    fn register_habit_schemas(&self) -> Result<()> {
+        let template = HeaderSchema::new("habit/template")
+            .strict(true)
+            .required("habit.template.is_habit_template", FieldType::Boolean)
+            .required("habit.template.name", FieldType::String)
+            .required("habit.template.basedate", FieldType::Date)
+            .required("habit.template.recurspec", FieldType::String)
+            .required("habit.template.comment", FieldType::String)
+            .optional("habit.template.until", FieldType::Date);
+
+        let instance = HeaderSchema::new("habit/instance")
+            .strict(true)
+            .required("habit.instance.is_habit_instance", FieldType::Boolean)
+            .required("habit.instance.name", FieldType::String)
+            .required("habit.instance.date", FieldType::Date)
+            .required("habit.instance.comment", FieldType::String);
+
+        self.register_schema(template)
+            .and_then(|_| self.register_schema(instance))
+            .map_err(From::from)
+    }
 }
 
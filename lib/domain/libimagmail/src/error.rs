@@ -24,10 +24,13 @@ error_chain! {
 
     links {
         RefError(::libimagentryref::error::RefError, ::libimagentryref::error::RefErrorKind);
+        StoreError(::libimagstore::error::StoreError, ::libimagstore::error::StoreErrorKind);
+        LinkError(::libimagentrylink::error::LinkError, ::libimagentrylink::error::LinkErrorKind);
     }
 
     foreign_links {
         IoError(::std::io::Error);
+        TomlQueryError(::toml_query::error::Error);
     }
 
 
@@ -59,6 +62,11 @@ error_chain! {
             description("IO Error")
             display("IO Error")
         }
+
+        NotAMaildir(path: ::std::path::PathBuf) {
+            description("Not a Maildir")
+            display("Not a Maildir: {}", path.display())
+        }
     }
 }
 
@@ -37,14 +37,22 @@
 
 #[macro_use] extern crate log;
 extern crate email;
-extern crate filters;
+extern crate crypto;
+extern crate toml;
+extern crate toml_query;
 #[macro_use] extern crate error_chain;
 
+#[cfg(test)]
+extern crate tempdir;
+
 extern crate libimagerror;
 extern crate libimagstore;
 extern crate libimagentryref;
+extern crate libimagentrylink;
 
 pub mod error;
 pub mod iter;
 pub mod mail;
+pub mod maildir;
+pub mod thread;
 
@@ -17,7 +17,9 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::fs::File;
 use std::io::Read;
 use std::fs::OpenOptions;
@@ -33,10 +35,11 @@ use libimagentryref::refstore::UniqueRefPathGenerator;
 use email::MimeMessage;
 use email::results::ParsingResult as EmailParsingResult;
 
+use maildir::MaildirFlags;
 use error::Result;
 use error::{ResultExt, MailError as ME, MailErrorKind as MEK};
 
-struct UniqueMailRefGenerator;
+pub(crate) struct UniqueMailRefGenerator;
 impl UniqueRefPathGenerator for UniqueMailRefGenerator {
     type Error = ME;
 
@@ -46,9 +49,12 @@ impl UniqueRefPathGenerator for UniqueMailRefGenerator {
     }
 
     /// A function which should generate a unique string for a Path
+    ///
+    /// This is the SHA1 of the `Message-ID` of the mail. Mails without `Message-ID` are identified
+    /// by the SHA1 of their content.
     fn unique_hash<A: AsRef<Path>>(path: A) -> RResult<String, Self::Error> {
-        use filters::filter::Filter;
-        use email::Header;
+        use crypto::sha1::Sha1;
+        use crypto::digest::Digest;
 
         let mut s = String::new();
         let _     = OpenOptions::new()
@@ -58,28 +64,25 @@ impl UniqueRefPathGenerator for UniqueMailRefGenerator {
             .open(path)?
             .read_to_string(&mut s)?;
 
-        MimeMessage::parse(&s)
-            .chain_err(|| MEK::RefCreationError)
-            .and_then(|mail| {
-                let has_key = |hdr: &Header, exp: &str| hdr.name == exp;
-
-                let subject_filter = |hdr: &Header| has_key(hdr, "Subject");
-                let from_filter    = |hdr: &Header| has_key(hdr, "From");
-                let to_filter      = |hdr: &Header| has_key(hdr, "To");
-
-                let filter = subject_filter.or(from_filter).or(to_filter);
-
-                let mut v : Vec<String> = vec![];
-                for hdr in mail.headers.iter().filter(|item| filter.filter(item)) {
-                    let s = hdr
-                        .get_value()
-                        .chain_err(|| MEK::RefCreationError)?;
-
-                    v.push(s);
-                }
-                let s : String = v.join("");
-                Ok(s)
-            })
+        let mail = MimeMessage::parse(&s).chain_err(|| MEK::RefCreationError)?;
+        if mail.headers.len() == 0 {
+            return Err(ME::from_kind(MEK::MailParsingError));
+        }
+
+        let message_id = mail.headers
+            .iter()
+            .filter(|hdr| hdr.name.eq_ignore_ascii_case("Message-ID"))
+            .filter_map(|hdr| hdr.get_value::<String>().ok())
+            .map(|value| String::from(value.trim()))
+            .find(|value| !value.is_empty());
+
+        let mut hasher = Sha1::new();
+        match message_id {
+            Some(ref message_id) => hasher.input_str(message_id),
+            None                 => hasher.input_str(&s),
+        }
+
+        Ok(hasher.result_str())
     }
 
     /// Postprocess the generated `StoreId` object
@@ -88,6 +91,28 @@ impl UniqueRefPathGenerator for UniqueMailRefGenerator {
     }
 }
 
+/// The ids of the mails in the store by the path of their file
+///
+/// Mails which were imported before their hash was calculated from the `Message-ID` can only be
+/// found by their path.
+pub(crate) fn mails_by_path(store: &Store) -> Result<BTreeMap<PathBuf, StoreId>> {
+    let mut mails = BTreeMap::new();
+
+    for id in store.entries()?.without_store() {
+        if !id.is_in_collection(&[UniqueMailRefGenerator::collection()]) {
+            continue;
+        }
+
+        if let Some(entry) = store.get(id.clone())? {
+            if entry.is_ref()? {
+                let _ = mails.insert(entry.get_path()?, id);
+            }
+        }
+    }
+
+    Ok(mails)
+}
+
 struct Buffer(String);
 
 impl Buffer {
@@ -107,23 +132,23 @@ pub struct Mail<'a>(FileLockEntry<'a>, Buffer);
 impl<'a> Mail<'a> {
 
     /// Imports a mail from the Path passed
+    ///
+    /// If the mail was imported before its hash was calculated from the `Message-ID`, the existing
+    /// entry is used.
     pub fn import_from_path<P: AsRef<Path>>(store: &Store, p: P) -> Result<Mail> {
         debug!("Importing Mail from path");
-        store.retrieve_ref::<UniqueMailRefGenerator, P>(p)
-            .and_then(|reference| {
-                debug!("Build reference file: {:?}", reference);
-                reference.get_path()
-                    .chain_err(|| MEK::RefHandlingError)
-                    .and_then(|path| File::open(path).chain_err(|| MEK::IOError))
-                    .and_then(|mut file| {
-                        let mut s = String::new();
-                        file.read_to_string(&mut s)
-                            .map(|_| s)
-                            .chain_err(|| MEK::IOError)
-                    })
-                    .map(Buffer::from)
-                    .map(|buffer| Mail(reference, buffer))
-            })
+        let hash = UniqueMailRefGenerator::unique_hash(p.as_ref())?;
+
+        let reference = match store.get_ref::<UniqueMailRefGenerator, _>(&hash)? {
+            Some(reference) => reference,
+            None            => match mails_by_path(store)?.remove(p.as_ref()) {
+                Some(id) => store.get(id)?.ok_or_else(|| ME::from_kind(MEK::FetchError))?,
+                None     => store.create_ref_with_hash::<UniqueMailRefGenerator, P>(p, hash)?,
+            },
+        };
+
+        debug!("Build reference file: {:?}", reference);
+        Mail::from_fle(reference)
     }
 
     /// Opens a mail by the passed hash
@@ -187,4 +212,9 @@ impl<'a> Mail<'a> {
         self.get_field("In-Reply-To")
     }
 
+    /// Get the Maildir flags of the mail, if it was synced from a Maildir
+    pub fn get_flags(&self) -> Result<Option<MaildirFlags>> {
+        MaildirFlags::from_entry(&self.0)
+    }
+
 }
@@ -0,0 +1,479 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Synchronizing Maildir directories with the store
+//!
+//! Mails are imported as refs (see `Mail::import_from_path()`). As the hash of a mail ref is
+//! calculated from the `Message-ID` of the mail, a mail keeps its ref when it is moved from `new/`
+//! to `cur/` or its flags (which are part of the filename) change. Syncing updates the path of the
+//! ref then and stores the flags in the header of the entry:
+//!
+//! ```toml
+//! [mail.flags]
+//! draft = false
+//! flagged = true
+//! passed = false
+//! replied = false
+//! seen = true
+//! trashed = false
+//! ```
+//!
+//! Mails which were imported before their hash was calculated from the `Message-ID` are found by
+//! the path of their file. Their entries keep their id, but get the new hash. If such a mail was
+//! moved since it was imported, it cannot be found and is imported again.
+
+use std::collections::BTreeMap;
+use std::fs::read_dir;
+use std::path::Path;
+use std::path::PathBuf;
+
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadExt;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentryref::reference::Ref;
+use libimagentryref::refstore::RefStore;
+use libimagentryref::refstore::UniqueRefPathGenerator;
+
+use mail::UniqueMailRefGenerator;
+use mail::mails_by_path;
+use error::Result;
+use error::MailError as ME;
+use error::MailErrorKind as MEK;
+
+/// The header path the Maildir flags are stored at
+pub const FLAGS_HEADER_PATH : &str = "mail.flags";
+
+/// The flags of a mail in a Maildir, encoded in the filename after `:2,`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaildirFlags {
+    pub passed: bool,
+    pub replied: bool,
+    pub seen: bool,
+    pub trashed: bool,
+    pub draft: bool,
+    pub flagged: bool,
+}
+
+impl MaildirFlags {
+
+    /// Parse the flags from the filename of a mail in a Maildir
+    ///
+    /// Mails without info part (for example the ones in `new/`) have no flags set.
+    pub fn from_filename(name: &str) -> MaildirFlags {
+        let mut flags = MaildirFlags::default();
+
+        if let Some(pos) = name.rfind(":2,") {
+            for c in name[(pos + 3)..].chars() {
+                match c {
+                    'P' => flags.passed  = true,
+                    'R' => flags.replied = true,
+                    'S' => flags.seen    = true,
+                    'T' => flags.trashed = true,
+                    'D' => flags.draft   = true,
+                    'F' => flags.flagged = true,
+                    _   => debug!("Ignoring unknown Maildir flag '{}' in {}", c, name),
+                }
+            }
+        }
+
+        flags
+    }
+
+    /// Read the flags from the header of an entry, `None` if there are none stored
+    pub fn from_entry(entry: &Entry) -> Result<Option<MaildirFlags>> {
+        let table = match entry.get_header().read(FLAGS_HEADER_PATH)? {
+            Some(&Value::Table(ref table)) => table,
+            _                              => return Ok(None),
+        };
+
+        let flag = |name: &str| table.get(name).and_then(Value::as_bool).unwrap_or(false);
+
+        Ok(Some(MaildirFlags {
+            passed: flag("passed"),
+            replied: flag("replied"),
+            seen: flag("seen"),
+            trashed: flag("trashed"),
+            draft: flag("draft"),
+            flagged: flag("flagged"),
+        }))
+    }
+
+    /// Write the flags to the header of an entry
+    pub fn write_to(&self, entry: &mut Entry) -> Result<()> {
+        let mut table = ::toml::value::Table::new();
+        table.insert(String::from("passed"), Value::Boolean(self.passed));
+        table.insert(String::from("replied"), Value::Boolean(self.replied));
+        table.insert(String::from("seen"), Value::Boolean(self.seen));
+        table.insert(String::from("trashed"), Value::Boolean(self.trashed));
+        table.insert(String::from("draft"), Value::Boolean(self.draft));
+        table.insert(String::from("flagged"), Value::Boolean(self.flagged));
+
+        entry.get_header_mut()
+            .insert(FLAGS_HEADER_PATH, Value::Table(table))
+            .map(|_| ())
+            .map_err(From::from)
+    }
+
+}
+
+/// What `sync_maildir()` did
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    imported: Vec<StoreId>,
+    moved: Vec<(StoreId, PathBuf, PathBuf)>,
+    flags_changed: Vec<StoreId>,
+    duplicates: Vec<(StoreId, PathBuf, PathBuf)>,
+    failed: Vec<(PathBuf, String)>,
+}
+
+impl SyncReport {
+
+    /// The mails which were not in the store before
+    pub fn imported(&self) -> &Vec<StoreId> {
+        &self.imported
+    }
+
+    /// The mails whose file was moved, with the old and the new path
+    pub fn moved(&self) -> &Vec<(StoreId, PathBuf, PathBuf)> {
+        &self.moved
+    }
+
+    /// The mails whose flags changed
+    pub fn flags_changed(&self) -> &Vec<StoreId> {
+        &self.flags_changed
+    }
+
+    /// The files which were not imported because another file has the same `Message-ID`, with the
+    /// path of the imported and of the duplicate file
+    pub fn duplicates(&self) -> &Vec<(StoreId, PathBuf, PathBuf)> {
+        &self.duplicates
+    }
+
+    /// The files which could not be imported, with the reason
+    pub fn failed(&self) -> &Vec<(PathBuf, String)> {
+        &self.failed
+    }
+
+}
+
+fn is_maildir(path: &Path) -> bool {
+    path.join("cur").is_dir() && path.join("new").is_dir()
+}
+
+/// Get the Maildir and its Maildir++ subfolders (the directories starting with a dot)
+fn maildir_folders(maildir: &Path) -> Result<Vec<PathBuf>> {
+    let mut folders = vec![maildir.to_path_buf()];
+
+    for dir in read_dir(maildir)? {
+        let path = dir?.path();
+        let is_subfolder = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with('.') && name != "." && name != "..")
+            .unwrap_or(false);
+
+        if is_subfolder && is_maildir(&path) {
+            folders.push(path);
+        }
+    }
+
+    Ok(folders)
+}
+
+/// Scan the `new/` and `cur/` directories of a Maildir (and its Maildir++ subfolders) and
+/// synchronize the mails in there with the store.
+///
+/// New mails are imported, the path of mails which were moved is updated and the flags of all
+/// mails are stored in the header of their entries. Files which cannot be parsed as mails and
+/// mails whose entries cannot be written are reported in `SyncReport::failed()`.
+pub fn sync_maildir<P: AsRef<Path>>(store: &Store, maildir: P) -> Result<SyncReport> {
+    let maildir = maildir.as_ref().canonicalize()?;
+    if !is_maildir(&maildir) {
+        return Err(ME::from_kind(MEK::NotAMaildir(maildir)));
+    }
+
+    let mut report = SyncReport::default();
+    let by_path    = mails_by_path(store)?;
+
+    for folder in maildir_folders(&maildir)? {
+        for sub in &["new", "cur"] {
+            for file in read_dir(folder.join(sub))? {
+                let path = file?.path();
+                if !path.is_file() {
+                    continue;
+                }
+
+                if let Err(e) = sync_mail(store, &path, &by_path, &mut report) {
+                    debug!("Failed to sync {}: {:?}", path.display(), e);
+                    report.failed.push((path, format!("{}", e)));
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn sync_mail(store: &Store, path: &Path, by_path: &BTreeMap<PathBuf, StoreId>, report: &mut SyncReport)
+    -> Result<()>
+{
+    let flags = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(MaildirFlags::from_filename)
+        .unwrap_or_default();
+
+    let hash = UniqueMailRefGenerator::unique_hash(path)?;
+
+    let existing = match store.get_ref::<UniqueMailRefGenerator, _>(&hash)? {
+        Some(entry) => Some(entry),
+
+        // Imported before the hash was calculated from the Message-ID
+        None => match by_path.get(path) {
+            Some(id) => store.get(id.clone())?,
+            None     => None,
+        },
+    };
+
+    match existing {
+        None => {
+            let mut entry = store.create_ref::<UniqueMailRefGenerator, _>(path)?;
+            let _ = flags.write_to(&mut entry)?;
+            let _ = store.update(&mut entry)?;
+            debug!("Imported {}", path.display());
+            report.imported.push(entry.get_location().clone());
+        },
+
+        Some(mut entry) => {
+            let old   = entry.get_path()?;
+            let moved = old != path;
+
+            if moved && old.exists() {
+                // Another file with the same Message-ID, most likely a copy of the mail
+                debug!("{} is a duplicate of {}", path.display(), old.display());
+                report.duplicates.push((entry.get_location().clone(), old, path.to_path_buf()));
+                return Ok(());
+            }
+
+            let rehashed = entry.get_hash()? != hash;
+            if moved || rehashed {
+                let _ = entry.make_ref(hash, path)?;
+            }
+
+            let flags_changed = MaildirFlags::from_entry(&entry)? != Some(flags);
+            if flags_changed {
+                let _ = flags.write_to(&mut entry)?;
+            }
+
+            if moved || rehashed || flags_changed {
+                let _ = store.update(&mut entry)?;
+            }
+
+            if moved {
+                debug!("Mail moved: {} -> {}", old.display(), path.display());
+                report.moved.push((entry.get_location().clone(), old, path.to_path_buf()));
+            }
+
+            if flags_changed {
+                report.flags_changed.push(entry.get_location().clone());
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::fs::create_dir;
+    use std::fs::rename;
+    use std::io::Write;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+    use libimagentryref::reference::Ref;
+
+    use super::*;
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn make_maildir(path: &Path) {
+        for sub in &["cur", "new", "tmp"] {
+            create_dir(path.join(sub)).unwrap();
+        }
+    }
+
+    fn write_mail(path: &Path, subject: &str) {
+        let mut file = File::create(path).unwrap();
+        let _ = write!(file,
+                       "From: sender@example.com\r\nTo: rcpt@example.com\r\nSubject: {}\r\nMessage-ID: <{}@example.com>\r\n\r\nBody\r\n",
+                       subject, subject).unwrap();
+    }
+
+    #[test]
+    fn test_flags_from_filename() {
+        assert_eq!(MaildirFlags::from_filename("1234.host"), MaildirFlags::default());
+
+        let flags = MaildirFlags::from_filename("1234.host:2,FRS");
+        assert!(flags.flagged && flags.replied && flags.seen);
+        assert!(!flags.passed && !flags.trashed && !flags.draft);
+    }
+
+    #[test]
+    fn test_sync_maildir() {
+        let store   = get_store();
+        let dir     = TempDir::new("imag-maildir").unwrap();
+        let maildir = dir.path().canonicalize().unwrap();
+        make_maildir(&maildir);
+
+        let subfolder = maildir.join(".Archive");
+        create_dir(&subfolder).unwrap();
+        make_maildir(&subfolder);
+
+        let new_path = maildir.join("new").join("1.host");
+        write_mail(&new_path, "first");
+        write_mail(&subfolder.join("cur").join("2.host:2,S"), "second");
+        write_mail(&maildir.join("new").join("3.host"), "");
+        File::create(maildir.join("cur").join("broken")).unwrap();
+
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert_eq!(report.imported().len(), 3);
+        assert_eq!(report.failed().len(), 1);
+        assert!(report.moved().is_empty());
+        assert!(report.flags_changed().is_empty());
+
+        // Nothing changed
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert!(report.imported().is_empty());
+        assert!(report.moved().is_empty());
+        assert!(report.flags_changed().is_empty());
+
+        // Read by the mail client
+        let cur_path = maildir.join("cur").join("1.host:2,S");
+        rename(&new_path, &cur_path).unwrap();
+
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert!(report.imported().is_empty());
+        assert_eq!(report.moved().len(), 1);
+        assert_eq!(report.moved()[0].1, new_path);
+        assert_eq!(report.moved()[0].2, cur_path);
+        assert_eq!(report.flags_changed().len(), 1);
+
+        let id = report.moved()[0].0.clone();
+        {
+            let entry = store.get(id.clone()).unwrap().unwrap();
+            assert_eq!(entry.get_path().unwrap(), cur_path);
+
+            let flags = MaildirFlags::from_entry(&entry).unwrap().unwrap();
+            assert!(flags.seen);
+            assert!(!flags.replied);
+        }
+
+        // Replied to
+        let replied_path = maildir.join("cur").join("1.host:2,RS");
+        rename(&cur_path, &replied_path).unwrap();
+
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert_eq!(report.moved().len(), 1);
+        assert_eq!(report.flags_changed(), &vec![id.clone()]);
+
+        let entry = store.get(id).unwrap().unwrap();
+        let flags = MaildirFlags::from_entry(&entry).unwrap().unwrap();
+        assert!(flags.seen && flags.replied);
+    }
+
+    #[test]
+    fn test_sync_same_headers_and_duplicates() {
+        let store   = get_store();
+        let dir     = TempDir::new("imag-maildir").unwrap();
+        let maildir = dir.path().canonicalize().unwrap();
+        make_maildir(&maildir);
+
+        // Same Subject, From and To, but different mails
+        let write = |name: &str, message_id: &str| {
+            let mut file = File::create(maildir.join("cur").join(name)).unwrap();
+            let _ = write!(file,
+                           "From: sender@example.com\r\nTo: rcpt@example.com\r\nSubject: Hi\r\nMessage-ID: <{}@example.com>\r\n\r\nBody\r\n",
+                           message_id).unwrap();
+        };
+        write("1.host:2,S", "one");
+        write("2.host:2,S", "two");
+        write("3.host:2,S", "one");
+
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert_eq!(report.imported().len(), 2);
+        assert_eq!(report.duplicates().len(), 1);
+        assert!(report.failed().is_empty());
+    }
+
+    #[test]
+    fn test_sync_mails_imported_with_old_hash() {
+        let store   = get_store();
+        let dir     = TempDir::new("imag-maildir").unwrap();
+        let maildir = dir.path().canonicalize().unwrap();
+        make_maildir(&maildir);
+
+        let path = maildir.join("cur").join("1.host:2,S");
+        write_mail(&path, "old");
+
+        // Imported when the hash was not calculated from the Message-ID
+        let id = {
+            let mut entry = store.create(PathBuf::from("mail/oldhash")).unwrap();
+            let _ = entry.make_ref(String::from("oldhash"), &path).unwrap();
+            entry.get_location().clone()
+        };
+
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert!(report.imported().is_empty());
+        assert!(report.moved().is_empty());
+        assert_eq!(report.flags_changed(), &vec![id.clone()]);
+
+        {
+            let entry = store.get(id.clone()).unwrap().unwrap();
+            let hash  = UniqueMailRefGenerator::unique_hash(&path).unwrap();
+            assert_eq!(entry.get_hash().unwrap(), hash);
+        }
+
+        // Nothing changed
+        let report = sync_maildir(&store, &maildir).unwrap();
+        assert!(report.imported().is_empty());
+        assert!(report.flags_changed().is_empty());
+    }
+
+    #[test]
+    fn test_sync_not_a_maildir() {
+        let store = get_store();
+        let dir   = TempDir::new("imag-maildir").unwrap();
+        assert!(sync_maildir(&store, dir.path()).is_err());
+    }
+
+}
+
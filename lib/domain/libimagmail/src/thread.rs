@@ -0,0 +1,293 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Threading mails into conversations
+//!
+//! `link_threads()` links each mail with the mail it replies to (found via the `In-Reply-To` and
+//! `Message-ID` headers) with a directed "reply-to" link, which is outgoing in the reply.
+//! `Thread` rebuilds the conversation tree from these links.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::internal::InternalLinker;
+use libimagentrylink::internal::LinkDirection;
+
+use mail::Mail;
+use error::Result;
+use error::MailError as ME;
+use error::MailErrorKind as MEK;
+
+/// The relation type of the links between a reply and the mail it replies to
+pub const REPLY_TO_LINK : &str = "reply-to";
+
+fn is_mail(id: &StoreId) -> bool {
+    id.is_in_collection(&["mail"])
+}
+
+/// Get the first message id (`<...>`) from a header value
+fn message_id(s: &str) -> Option<String> {
+    let s = s.trim();
+    match s.find('<').and_then(|start| s[start..].find('>').map(|len| (start, start + len))) {
+        Some((start, end)) => Some(String::from(&s[start..(end + 1)])),
+        None               => if s.is_empty() { None } else { Some(String::from(s)) },
+    }
+}
+
+/// Link all mails in the store with the mails they reply to
+///
+/// Mails which are already linked are not linked again. Returns the pairs of reply and replied-to
+/// mail which were linked.
+pub fn link_threads(store: &Store) -> Result<Vec<(StoreId, StoreId)>> {
+    let mut by_message_id = BTreeMap::new();
+    let mut replies       = vec![];
+
+    for id in store.entries()?.without_store().filter(is_mail) {
+        let mail = match store.get(id.clone())?.map(Mail::from_fle) {
+            Some(Ok(mail)) => mail,
+            Some(Err(e))   => {
+                debug!("Cannot read mail {}, ignoring: {:?}", id, e);
+                continue
+            },
+            None           => continue,
+        };
+
+        let header = |h: Result<Option<String>>| h.ok().and_then(|o| o).and_then(|s| message_id(&s));
+
+        if let Some(mid) = header(mail.get_message_id()) {
+            by_message_id.insert(mid, id.clone());
+        }
+
+        if let Some(parent) = header(mail.get_in_reply_to()) {
+            replies.push((id, parent));
+        }
+    }
+
+    let mut linked = vec![];
+
+    for (reply, parent_mid) in replies {
+        let parent = match by_message_id.get(&parent_mid) {
+            Some(parent) if *parent != reply => parent.clone(),
+            _                                => continue,
+        };
+
+        let mut reply_entry = store.get(reply.clone())?.ok_or_else(|| ME::from_kind(MEK::FetchError))?;
+
+        let already_linked = reply_entry
+            .get_internal_links_of_type(REPLY_TO_LINK)?
+            .any(|l| *l.get_store_id() == parent && l.direction() == Some(LinkDirection::Outgoing));
+        if already_linked {
+            continue;
+        }
+
+        let mut parent_entry = store.get(parent.clone())?.ok_or_else(|| ME::from_kind(MEK::FetchError))?;
+
+        debug!("Linking {} as reply to {}", reply, parent);
+        let _ = reply_entry.add_internal_typed_link(&mut parent_entry, String::from(REPLY_TO_LINK), true)?;
+        let _ = store.update(&mut reply_entry)?;
+        let _ = store.update(&mut parent_entry)?;
+        linked.push((reply, parent));
+    }
+
+    Ok(linked)
+}
+
+/// A mail with the replies to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    id: StoreId,
+    replies: Vec<Thread>,
+}
+
+impl Thread {
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    pub fn replies(&self) -> &Vec<Thread> {
+        &self.replies
+    }
+
+    /// Get the whole conversation the mail `id` is part of
+    pub fn of_mail(store: &Store, id: StoreId) -> Result<Thread> {
+        let mut root = id;
+        let mut seen = BTreeSet::new();
+
+        while let Some(parent) = linked_mails(store, &root, LinkDirection::Outgoing)?.into_iter().next() {
+            if !seen.insert(root.clone()) {
+                break; // reply-to links form a cycle
+            }
+            root = parent;
+        }
+
+        Thread::build(store, root, &mut BTreeSet::new())
+    }
+
+    /// Get the conversations of all mails the entry `id` is linked with
+    ///
+    /// If `id` is a mail itself, this is the conversation of the mail.
+    pub fn of_entry(store: &Store, id: StoreId) -> Result<Vec<Thread>> {
+        if is_mail(&id) {
+            return Thread::of_mail(store, id).map(|thread| vec![thread]);
+        }
+
+        let mails = store
+            .get(id)?
+            .ok_or_else(|| ME::from_kind(MEK::FetchError))?
+            .get_internal_links()?
+            .map(|link| link.get_store_id().clone())
+            .filter(is_mail)
+            .collect::<Vec<_>>();
+
+        let mut threads : Vec<Thread> = vec![];
+        for mail in mails {
+            let thread = Thread::of_mail(store, mail)?;
+            if !threads.iter().any(|t| t.id == thread.id) {
+                threads.push(thread);
+            }
+        }
+
+        Ok(threads)
+    }
+
+    fn build(store: &Store, id: StoreId, seen: &mut BTreeSet<StoreId>) -> Result<Thread> {
+        let _ = seen.insert(id.clone());
+
+        let mut replies = vec![];
+        for reply in linked_mails(store, &id, LinkDirection::Incoming)? {
+            if !seen.contains(&reply) {
+                replies.push(Thread::build(store, reply, seen)?);
+            }
+        }
+
+        Ok(Thread { id: id, replies: replies })
+    }
+
+}
+
+/// Get the mails linked with `id` by "reply-to" links in `direction`
+fn linked_mails(store: &Store, id: &StoreId, direction: LinkDirection) -> Result<Vec<StoreId>> {
+    let entry = store.get(id.clone())?.ok_or_else(|| ME::from_kind(MEK::FetchError))?;
+    let mails = entry
+        .get_internal_links_of_type(REPLY_TO_LINK)?
+        .filter(|link| link.direction() == Some(direction))
+        .map(|link| link.get_store_id().clone())
+        .collect();
+    Ok(mails)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+    use libimagentrylink::internal::InternalLinker;
+
+    use libimagentryref::refstore::UniqueRefPathGenerator;
+
+    use mail::Mail;
+    use mail::UniqueMailRefGenerator;
+    use super::*;
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn import(store: &Store, dir: &Path, subject: &str, in_reply_to: Option<&str>) -> StoreId {
+        let path     = dir.join(subject);
+        let mut file = File::create(&path).unwrap();
+        let _ = write!(file, "From: a@example.com\r\nTo: b@example.com\r\nSubject: {}\r\nMessage-ID: <{}@example.com>\r\n",
+                       subject, subject).unwrap();
+        if let Some(parent) = in_reply_to {
+            let _ = write!(file, "In-Reply-To: <{}@example.com>\r\n", parent).unwrap();
+        }
+        let _ = write!(file, "\r\nBody\r\n").unwrap();
+
+        let mail = Mail::import_from_path(store, &path).unwrap();
+        assert!(mail.get_message_id().unwrap().is_some());
+        drop(mail);
+
+        let hash = UniqueMailRefGenerator::unique_hash(&path).unwrap();
+        StoreId::new_baseless(PathBuf::from(format!("mail/{}", hash))).unwrap()
+    }
+
+    #[test]
+    fn test_message_id() {
+        assert_eq!(message_id(" <a@b> "), Some(String::from("<a@b>")));
+        assert_eq!(message_id("Your mail <a@b> of today"), Some(String::from("<a@b>")));
+        assert_eq!(message_id("a@b"), Some(String::from("a@b")));
+        assert_eq!(message_id(" "), None);
+    }
+
+    #[test]
+    fn test_link_threads() {
+        let store = get_store();
+        let dir   = TempDir::new("imag-mail-thread").unwrap();
+
+        let root   = import(&store, dir.path(), "root", None);
+        let first  = import(&store, dir.path(), "first", Some("root"));
+        let second = import(&store, dir.path(), "second", Some("root"));
+        let nested = import(&store, dir.path(), "nested", Some("first"));
+        let orphan = import(&store, dir.path(), "orphan", Some("unknown"));
+
+        let linked = link_threads(&store).unwrap();
+        assert_eq!(linked.len(), 3);
+
+        // Linking again does nothing
+        assert!(link_threads(&store).unwrap().is_empty());
+
+        let thread = Thread::of_mail(&store, nested.clone()).unwrap();
+        assert_eq!(thread.id(), &root);
+        assert_eq!(thread.replies().len(), 2);
+
+        let first_thread = thread.replies().iter().find(|t| t.id() == &first).unwrap();
+        assert_eq!(first_thread.replies().len(), 1);
+        assert_eq!(first_thread.replies()[0].id(), &nested);
+
+        let second_thread = thread.replies().iter().find(|t| t.id() == &second).unwrap();
+        assert!(second_thread.replies().is_empty());
+
+        let orphan_thread = Thread::of_mail(&store, orphan.clone()).unwrap();
+        assert_eq!(orphan_thread.id(), &orphan);
+        assert!(orphan_thread.replies().is_empty());
+
+        // A note linked with a mail gets to the whole conversation
+        {
+            let mut note = store.create(PathBuf::from("notes/todo")).unwrap();
+            let mut mail = store.get(second.clone()).unwrap().unwrap();
+            let _ = note.add_internal_link(&mut mail).unwrap();
+        }
+
+        let threads = Thread::of_entry(&store, StoreId::new_baseless(PathBuf::from("notes/todo")).unwrap()).unwrap();
+        assert_eq!(threads, vec![thread]);
+    }
+
+}
+
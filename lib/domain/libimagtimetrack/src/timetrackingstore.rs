@@ -28,6 +28,8 @@ use toml_query::insert::TomlValueInsertExt;
 
 use libimagstore::store::Store;
 use libimagstore::store::FileLockEntry;
+use libimagstore::schema::HeaderSchema;
+use libimagstore::schema::FieldType;
 use libimagentrydatetime::datepath::compiler::DatePathCompiler;
 
 use error::Result;
@@ -43,6 +45,9 @@ pub trait TimeTrackStore<'a> {
     fn create_timetracking(&'a self, start: &NDT, end: &NDT, ts: &TTT) -> Result<FileLockEntry<'a>>;
 
     fn get_timetrackings(&'a self) -> Result<TimeTrackingsGetIterator<'a>>;
+
+    /// Register the header schema of timetrackings in the store
+    fn register_timetrack_schema(&'a self) -> Result<()>;
 }
 
 fn now() -> NDT {
@@ -89,6 +94,7 @@ impl<'a> TimeTrackStore<'a> for Store {
                     .map_err(From::from)
                     .map(|_| fle)
             })
+            .and_then(|mut fle| self.update(&mut fle).map(|_| fle).map_err(From::from))
     }
 
     fn create_timetracking(&'a self, start: &NDT, end: &NDT, ts: &TTT) -> Result<FileLockEntry<'a>> {
@@ -100,11 +106,22 @@ impl<'a> TimeTrackStore<'a> for Store {
                     .map_err(From::from)
                     .map(|_| fle)
             })
+            .and_then(|mut fle| self.update(&mut fle).map(|_| fle).map_err(From::from))
     }
 
     fn get_timetrackings(&'a self) -> Result<TimeTrackingsGetIterator<'a>> {
         Ok(TimeTrackingsGetIterator::new(self.entries()?, self))
     }
 
+    fn register_timetrack_schema(&'a self) -> Result<()> {
+        let schema = HeaderSchema::new(CRATE_NAME)
+            .strict(true)
+            .required(DATE_TIME_TAG_HEADER_PATH, FieldType::String)
+            .required(DATE_TIME_START_HEADER_PATH, FieldType::Datetime)
+            .optional(DATE_TIME_END_HEADER_PATH, FieldType::Datetime);
+
+        self.register_schema(schema).map_err(From::from)
+    }
+
 }
 
@@ -28,6 +28,7 @@ error_chain! {
 
     foreign_links {
         TomlQueryError(::toml_query::error::Error);
+        IO(::std::io::Error);
     }
 
     errors {
@@ -46,6 +47,11 @@ error_chain! {
             display("Error importing")
         }
 
+        ExportError         {
+            description("Error exporting")
+            display("Error exporting")
+        }
+
         UTF8Error           {
             description("Encountered non-UTF8 characters while reading input")
             display("Encountered non-UTF8 characters while reading input")
@@ -65,6 +71,16 @@ error_chain! {
             description("Uuid parser error")
             display("Uuid parser error")
         }
+
+        TaskNotFound(id: String) {
+            description("Task not found")
+            display("Task not found: {}", id)
+        }
+
+        AmbiguousTaskId(id: String) {
+            description("Ambiguous task id")
+            display("Ambiguous task id: {}", id)
+        }
     }
 }
 
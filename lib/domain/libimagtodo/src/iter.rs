@@ -37,7 +37,7 @@ impl Iterator for TaskIdIterator {
         loop {
             match self.0.next() {
                 None    => return None,
-                Some(n) => if n.is_in_collection(&["todo", "taskwarrior"]) {
+                Some(n) => if n.is_in_collection(&["todo"]) {
                     return Some(n)
                 }, // else continue
             }
@@ -36,6 +36,7 @@
 )]
 
 extern crate uuid;
+extern crate chrono;
 extern crate toml;
 extern crate toml_query;
 #[macro_use] extern crate log;
@@ -44,6 +45,7 @@ extern crate serde_json;
 
 #[macro_use] extern crate libimagstore;
 extern crate libimagerror;
+extern crate libimagutil;
 extern crate task_hookrs;
 
 module_entry_path_mod!("todo");
@@ -51,5 +53,6 @@ module_entry_path_mod!("todo");
 pub mod error;
 pub mod task;
 pub mod taskstore;
+pub mod sync;
 pub mod iter;
 
@@ -0,0 +1,549 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Synchronizing the `todo/taskwarrior` entries with a taskwarrior export
+//!
+//! The input is the JSON array which `task export` prints. The description, status, project,
+//! tags and due date of every task are mirrored into the `todo` header of its entry. To be able to
+//! tell which side changed a task, the values of the last synchronization are remembered in the
+//! header as well:
+//!
+//! ```toml
+//! [todo.taskwarrior]
+//! description = "Write documentation"
+//! status = "pending"
+//! tags = ["imag"]
+//! ```
+//!
+//! A task which only changed in taskwarrior is updated in the store. A task which only changed in
+//! imag is reported as locally modified and its new representation is part of the report, so it
+//! can be fed back to taskwarrior with `task import`. A task which changed on both sides is a
+//! conflict and stays untouched.
+//!
+//! Taskwarrior stores the due date as UTC time, imag as date. The due date of an entry is the
+//! local date of the taskwarrior time, and is passed back as the beginning of that local day.
+
+use std::collections::BTreeSet;
+use std::io::Read;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+use toml::Value;
+use toml::value::Table;
+use toml_query::read::TomlValueReadTypeExt;
+use uuid::Uuid;
+
+use task_hookrs::import::import;
+use task_hookrs::status::TaskStatus;
+use task_hookrs::task::Task as TTask;
+
+use libimagstore::store::Entry;
+use libimagstore::store::Store;
+use libimagutil::date::date_from_string;
+use libimagutil::date::date_to_string;
+
+use error::TodoError as TE;
+use error::TodoErrorKind as TEK;
+use error::Result;
+use error::ResultExt;
+use task::Status;
+use task::Task;
+use task::read_string_array;
+use task::set_field;
+use taskstore::TaskStore;
+
+/// The values of a task which are synchronized with taskwarrior
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SyncedFields {
+    description: Option<String>,
+    status: Status,
+    project: Option<String>,
+    tags: Vec<String>,
+    due: Option<NaiveDate>,
+}
+
+impl SyncedFields {
+
+    fn from_twtask(task: &TTask) -> SyncedFields {
+        let status = match *task.status() {
+            TaskStatus::Completed => Status::Done,
+            _                     => Status::Pending,
+        };
+
+        let mut tags = task.tags().cloned().unwrap_or_else(Vec::new);
+        tags.sort();
+
+        SyncedFields {
+            description: Some(task.description().clone()),
+            status: status,
+            project: task.project().cloned(),
+            tags: tags,
+            due: task.due().map(|d| local_date(d)),
+        }
+    }
+
+    fn from_entry(entry: &Entry) -> Result<SyncedFields> {
+        let mut tags = entry.get_tags()?;
+        tags.sort();
+
+        Ok(SyncedFields {
+            description: entry.get_description()?,
+            status: entry.get_status()?,
+            project: entry.get_project()?,
+            tags: tags,
+            due: entry.get_due()?,
+        })
+    }
+
+    /// Read the values of the last synchronization, if the entry was synchronized before
+    fn from_snapshot(entry: &Entry) -> Result<Option<SyncedFields>> {
+        let header = entry.get_header();
+        let status = match header.read_string("todo.taskwarrior.status")? {
+            None    => return Ok(None),
+            Some(s) => Status::from_str(&s).ok_or_else(|| TE::from_kind(TEK::HeaderTypeError))?,
+        };
+
+        let due = match header.read_string("todo.taskwarrior.due")? {
+            None    => None,
+            Some(s) => Some(date_from_string(&s).chain_err(|| TEK::HeaderTypeError)?),
+        };
+
+        Ok(Some(SyncedFields {
+            description: header.read_string("todo.taskwarrior.description")?,
+            status: status,
+            project: header.read_string("todo.taskwarrior.project")?,
+            tags: read_string_array(entry, "todo.taskwarrior.tags")?,
+            due: due,
+        }))
+    }
+
+    fn write_to(&self, entry: &mut Entry) -> Result<()> {
+        if let Some(ref description) = self.description {
+            entry.set_description(description.clone())?;
+        }
+        entry.set_status(self.status)?;
+        entry.set_project(self.project.clone())?;
+        entry.set_due(self.due)?;
+
+        for tag in entry.get_tags()? {
+            entry.remove_tag(&tag)?;
+        }
+        for tag in self.tags.iter() {
+            entry.add_tag(tag.clone())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_snapshot(&self, entry: &mut Entry) -> Result<()> {
+        let mut table = Table::new();
+        let _ = table.insert(String::from("status"), Value::String(String::from(self.status.as_str())));
+
+        if let Some(ref description) = self.description {
+            let _ = table.insert(String::from("description"), Value::String(description.clone()));
+        }
+        if let Some(ref project) = self.project {
+            let _ = table.insert(String::from("project"), Value::String(project.clone()));
+        }
+        if !self.tags.is_empty() {
+            let tags = self.tags.iter().cloned().map(Value::String).collect();
+            let _ = table.insert(String::from("tags"), Value::Array(tags));
+        }
+        if let Some(ref due) = self.due {
+            let _ = table.insert(String::from("due"), Value::String(date_to_string(due)));
+        }
+
+        set_field(entry, "taskwarrior", Some(Value::Table(table)))
+    }
+
+    /// The names of the fields which differ between `self` and `other`
+    fn differing_fields(&self, other: &SyncedFields) -> Vec<&'static str> {
+        let mut fields = vec![];
+        if self.description != other.description { fields.push("description"); }
+        if self.status      != other.status      { fields.push("status"); }
+        if self.project     != other.project     { fields.push("project"); }
+        if self.tags        != other.tags        { fields.push("tags"); }
+        if self.due         != other.due         { fields.push("due"); }
+        fields
+    }
+
+    /// Apply these values to the JSON representation of `task`, so that taskwarrior can import it
+    fn to_taskwarrior_json(&self, task: &TTask) -> Result<JsonValue> {
+        let remote   = SyncedFields::from_twtask(task);
+        let mut json = ::serde_json::to_value(task).chain_err(|| TEK::ExportError)?;
+
+        {
+            let object = json.as_object_mut().ok_or_else(|| TE::from_kind(TEK::ExportError))?;
+
+            for field in self.differing_fields(&remote) {
+                let value = match field {
+                    "description" => self.description.clone().map(JsonValue::String),
+                    "status"      => Some(JsonValue::String(String::from(match self.status {
+                        Status::Pending => "pending",
+                        Status::Done    => "completed",
+                    }))),
+                    "project"     => self.project.clone().map(JsonValue::String),
+                    "tags"        => if self.tags.is_empty() {
+                        None
+                    } else {
+                        Some(JsonValue::Array(self.tags.iter().cloned().map(JsonValue::String).collect()))
+                    },
+                    "due"         => self.due.map(|d| JsonValue::String(taskwarrior_date(&d))),
+                    _             => continue,
+                };
+
+                match value {
+                    Some(value) => { let _ = object.insert(String::from(field), value); },
+                    None        => { let _ = object.remove(field); },
+                }
+            }
+        }
+
+        Ok(json)
+    }
+
+}
+
+/// The local date of the UTC time `utc`, taskwarrior stores its times in UTC
+fn local_date(utc: &NaiveDateTime) -> NaiveDate {
+    DateTime::<Utc>::from_utc(*utc, Utc).with_timezone(&Local).naive_local().date()
+}
+
+/// The beginning of the local day `date` in UTC, in the format taskwarrior uses
+fn taskwarrior_date(date: &NaiveDate) -> String {
+    // In some time zones, midnight does not exist on the day the daylight saving time starts
+    let start = Local.from_local_datetime(&date.and_hms(0, 0, 0))
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&date.and_hms(1, 0, 0)).earliest())
+        .map(|local| local.with_timezone(&Utc).naive_utc())
+        .unwrap_or_else(|| date.and_hms(0, 0, 0));
+
+    format!("{}", start.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// A task which was modified in imag and in taskwarrior since the last synchronization
+#[derive(Debug)]
+pub struct Conflict {
+    uuid: Uuid,
+    fields: Vec<&'static str>,
+}
+
+impl Conflict {
+
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+
+    /// The fields which differ between imag and taskwarrior
+    pub fn fields(&self) -> &Vec<&'static str> {
+        &self.fields
+    }
+
+}
+
+/// What `sync_taskwarrior_export()` did
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    imported: Vec<Uuid>,
+    updated: Vec<Uuid>,
+    unchanged: Vec<Uuid>,
+    locally_modified: Vec<Uuid>,
+    conflicts: Vec<Conflict>,
+    deleted: Vec<Uuid>,
+    missing: Vec<Uuid>,
+    taskwarrior_updates: Vec<JsonValue>,
+}
+
+impl SyncReport {
+
+    /// The tasks which were not in the store before
+    pub fn imported(&self) -> &Vec<Uuid> {
+        &self.imported
+    }
+
+    /// The tasks which were updated with the values from taskwarrior
+    pub fn updated(&self) -> &Vec<Uuid> {
+        &self.updated
+    }
+
+    /// The tasks which are the same on both sides
+    pub fn unchanged(&self) -> &Vec<Uuid> {
+        &self.unchanged
+    }
+
+    /// The tasks which were only modified in imag
+    ///
+    /// See `SyncReport::taskwarrior_updates()`.
+    pub fn locally_modified(&self) -> &Vec<Uuid> {
+        &self.locally_modified
+    }
+
+    /// The tasks which were modified on both sides and were not touched
+    pub fn conflicts(&self) -> &Vec<Conflict> {
+        &self.conflicts
+    }
+
+    /// The tasks which were deleted in taskwarrior and therefore removed from the store
+    pub fn deleted(&self) -> &Vec<Uuid> {
+        &self.deleted
+    }
+
+    /// The tasks which are in the store but not in the export
+    pub fn missing(&self) -> &Vec<Uuid> {
+        &self.missing
+    }
+
+    /// The locally modified tasks in the JSON format of taskwarrior
+    ///
+    /// Serialized as JSON array, these can be imported into taskwarrior with `task import`.
+    pub fn taskwarrior_updates(&self) -> &Vec<JsonValue> {
+        &self.taskwarrior_updates
+    }
+
+}
+
+/// Synchronize the `todo/taskwarrior` entries with the output of `task export`
+pub fn sync_taskwarrior_export<R: Read>(store: &Store, export: R) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let mut seen   = BTreeSet::new();
+
+    for task in import(export).map_err(|_| TE::from_kind(TEK::ImportError))? {
+        let uuid   = *task.uuid();
+        let remote = SyncedFields::from_twtask(&task);
+        let _      = seen.insert(uuid);
+
+        let mut entry = match store.get_task_from_uuid(uuid)? {
+            Some(entry) => entry,
+            None        => {
+                if *task.status() != TaskStatus::Deleted {
+                    let mut entry = store.new_from_twtask(task)?;
+                    remote.write_to(&mut entry)?;
+                    remote.write_snapshot(&mut entry)?;
+                    report.imported.push(uuid);
+                }
+                continue;
+            },
+        };
+
+        let local    = SyncedFields::from_entry(&entry)?;
+        let snapshot = SyncedFields::from_snapshot(&entry)?;
+
+        // An entry which was never synchronized (e.g. created by the taskwarrior hook) has no
+        // local modifications
+        let local_changed  = snapshot.as_ref().map(|s| *s != local).unwrap_or(false);
+        let remote_changed = snapshot.as_ref().map(|s| *s != remote).unwrap_or(true);
+
+        if *task.status() == TaskStatus::Deleted {
+            if local_changed {
+                report.conflicts.push(Conflict { uuid: uuid, fields: vec!["status"] });
+            } else {
+                drop(entry);
+                store.delete_task_by_uuid(uuid)?;
+                report.deleted.push(uuid);
+            }
+            continue;
+        }
+
+        match (local_changed, remote_changed) {
+            (false, false) => report.unchanged.push(uuid),
+            (false, true)  => {
+                remote.write_to(&mut entry)?;
+                remote.write_snapshot(&mut entry)?;
+                report.updated.push(uuid);
+            },
+            (true, false)  => {
+                report.taskwarrior_updates.push(local.to_taskwarrior_json(&task)?);
+                report.locally_modified.push(uuid);
+            },
+            (true, true)   => if local == remote {
+                local.write_snapshot(&mut entry)?;
+                report.unchanged.push(uuid);
+            } else {
+                let fields = local.differing_fields(&remote);
+                report.conflicts.push(Conflict { uuid: uuid, fields: fields });
+            },
+        }
+    }
+
+    for id in store.all_tasks()? {
+        if !id.is_in_collection(&["todo", "taskwarrior"]) {
+            continue;
+        }
+
+        let uuid = id.local()
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| Uuid::parse_str(n).ok());
+
+        if let Some(uuid) = uuid {
+            if !seen.contains(&uuid) {
+                report.missing.push(uuid);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use libimagstore::store::Store;
+
+    use task::Status;
+    use task::Task;
+    use taskstore::TaskStore;
+    use super::*;
+
+    const FIRST: &'static str  = "9f5c8b3e-6c1a-4f0e-9d2b-2f0d1c6b7a01";
+    const SECOND: &'static str = "0c3f2a1b-7d4e-4b6a-8c5d-9e8f7a6b5c02";
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn export(first_description: &str, second_status: &str) -> String {
+        format!(r#"[
+            {{"id":1,"description":"{}","entry":"20180401T100000Z","modified":"20180401T100000Z",
+              "project":"imag","status":"pending","tags":["rust","code"],"uuid":"{}",
+              "due":"20180501T220000Z","urgency":5.2}},
+            {{"id":0,"description":"Second","entry":"20180402T100000Z","modified":"20180403T100000Z",
+              "end":"20180403T100000Z","status":"{}","uuid":"{}","urgency":0}}
+        ]"#, first_description, FIRST, second_status, SECOND)
+    }
+
+    fn uuid(s: &str) -> Uuid {
+        Uuid::parse_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_import_and_update() {
+        let store  = get_store();
+        let report = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+
+        assert_eq!(report.imported().len(), 2);
+        {
+            let first = store.get_task(uuid(FIRST)).unwrap().unwrap();
+            assert_eq!(first.get_description().unwrap(), Some(String::from("First")));
+            assert_eq!(first.get_status().unwrap(), Status::Pending);
+            assert_eq!(first.get_project().unwrap(), Some(String::from("imag")));
+            assert_eq!(first.get_tags().unwrap(), vec![String::from("code"), String::from("rust")]);
+
+            // The due date is the local date of the UTC time taskwarrior exports
+            let due = Utc.ymd(2018, 5, 1).and_hms(22, 0, 0).with_timezone(&Local).naive_local().date();
+            assert_eq!(first.get_due().unwrap(), Some(due));
+
+            let second = store.get_task(uuid(SECOND)).unwrap().unwrap();
+            assert_eq!(second.get_status().unwrap(), Status::Done);
+            assert_eq!(second.get_project().unwrap(), None);
+        }
+
+        let report = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+        assert!(report.imported().is_empty());
+        assert_eq!(report.unchanged().len(), 2);
+
+        let report = sync_taskwarrior_export(&store, export("Changed", "completed").as_bytes()).unwrap();
+        assert_eq!(report.updated(), &vec![uuid(FIRST)]);
+        let first = store.get_task(uuid(FIRST)).unwrap().unwrap();
+        assert_eq!(first.get_description().unwrap(), Some(String::from("Changed")));
+    }
+
+    #[test]
+    fn test_local_modifications_and_conflicts() {
+        let store = get_store();
+        let _     = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+
+        {
+            let mut first = store.get_task(uuid(FIRST)).unwrap().unwrap();
+            first.set_status(Status::Done).unwrap();
+            first.set_project(None).unwrap();
+        }
+
+        let report = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+        assert_eq!(report.locally_modified(), &vec![uuid(FIRST)]);
+        assert_eq!(report.taskwarrior_updates().len(), 1);
+
+        let update = &report.taskwarrior_updates()[0];
+        assert_eq!(update["uuid"], FIRST);
+        assert_eq!(update["status"], "completed");
+        assert_eq!(update["description"], "First");
+        assert!(update.get("project").is_none());
+
+        let report = sync_taskwarrior_export(&store, export("Changed", "completed").as_bytes()).unwrap();
+        assert_eq!(report.conflicts().len(), 1);
+        assert_eq!(report.conflicts()[0].uuid(), &uuid(FIRST));
+        assert_eq!(report.conflicts()[0].fields(), &vec!["description", "status", "project"]);
+
+        let first = store.get_task(uuid(FIRST)).unwrap().unwrap();
+        assert_eq!(first.get_description().unwrap(), Some(String::from("First")));
+    }
+
+    #[test]
+    fn test_taskwarrior_date_roundtrip() {
+        for &(y, m, d) in &[(2018, 1, 1), (2018, 3, 25), (2018, 6, 30), (2018, 10, 28), (2018, 12, 31)] {
+            let date = NaiveDate::from_ymd(y, m, d);
+            let utc  = NaiveDateTime::parse_from_str(&taskwarrior_date(&date), "%Y%m%dT%H%M%SZ").unwrap();
+            assert_eq!(local_date(&utc), date);
+        }
+    }
+
+    #[test]
+    fn test_local_due_date_is_exported() {
+        let store = get_store();
+        let _     = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+
+        let due = NaiveDate::from_ymd(2018, 6, 1);
+        {
+            let mut first = store.get_task(uuid(FIRST)).unwrap().unwrap();
+            first.set_due(Some(due)).unwrap();
+        }
+
+        let report = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+        assert_eq!(report.taskwarrior_updates().len(), 1);
+
+        let exported = report.taskwarrior_updates()[0]["due"].as_str().unwrap().to_owned();
+        let utc      = NaiveDateTime::parse_from_str(&exported, "%Y%m%dT%H%M%SZ").unwrap();
+        assert_eq!(local_date(&utc), due);
+    }
+
+    #[test]
+    fn test_deleted_and_missing() {
+        let store = get_store();
+        let _     = sync_taskwarrior_export(&store, export("First", "completed").as_bytes()).unwrap();
+
+        let report = sync_taskwarrior_export(&store, export("First", "deleted").as_bytes()).unwrap();
+        assert_eq!(report.deleted(), &vec![uuid(SECOND)]);
+        assert!(store.get_task(uuid(SECOND)).unwrap().is_none());
+
+        let report = sync_taskwarrior_export(&store, "[]".as_bytes()).unwrap();
+        assert_eq!(report.missing(), &vec![uuid(FIRST)]);
+    }
+
+}
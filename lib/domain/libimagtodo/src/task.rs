@@ -17,18 +17,126 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::fmt::{Display, Formatter, Error as FmtError};
+
 use error::TodoError as TE;
 use error::TodoErrorKind as TEK;
 use error::ResultExt;
 use error::Result;
 
 use libimagstore::store::Entry;
+use libimagutil::date::date_from_string;
+use libimagutil::date::date_to_string;
 
+use chrono::NaiveDate;
+use toml::Value;
+use toml::value::Table;
 use uuid::Uuid;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadExt;
 use toml_query::read::TomlValueReadTypeExt;
 
+/// The status of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Done,
+}
+
+impl Status {
+
+    pub fn from_str(s: &str) -> Option<Status> {
+        match s {
+            "pending" => Some(Status::Pending),
+            "done"    => Some(Status::Done),
+            _         => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Status::Pending => "pending",
+            Status::Done    => "done",
+        }
+    }
+
+}
+
+impl Display for Status {
+
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        write!(fmt, "{}", self.as_str())
+    }
+
+}
+
+/// The priority of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+
+    pub fn from_str(s: &str) -> Option<Priority> {
+        match s {
+            "high"   | "h" | "H" => Some(Priority::High),
+            "medium" | "m" | "M" => Some(Priority::Medium),
+            "low"    | "l" | "L" => Some(Priority::Low),
+            _                    => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Priority::High   => "high",
+            Priority::Medium => "medium",
+            Priority::Low    => "low",
+        }
+    }
+
+}
+
+impl Display for Priority {
+
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        write!(fmt, "{}", self.as_str())
+    }
+
+}
+
+/// Accessors for the `todo` header of a task
+///
+/// Tasks which are mirrored from taskwarrior only have a uuid, all other fields are optional. A
+/// task without status is pending.
 pub trait Task {
     fn get_uuid(&self) -> Result<Uuid>;
+
+    fn get_description(&self) -> Result<Option<String>>;
+    fn set_description(&mut self, description: String) -> Result<()>;
+
+    fn get_status(&self) -> Result<Status>;
+    fn set_status(&mut self, status: Status) -> Result<()>;
+
+    fn get_priority(&self) -> Result<Option<Priority>>;
+    fn set_priority(&mut self, priority: Option<Priority>) -> Result<()>;
+
+    fn get_project(&self) -> Result<Option<String>>;
+    fn set_project(&mut self, project: Option<String>) -> Result<()>;
+
+    fn get_due(&self) -> Result<Option<NaiveDate>>;
+    fn set_due(&mut self, due: Option<NaiveDate>) -> Result<()>;
+
+    fn get_tags(&self) -> Result<Vec<String>>;
+    fn add_tag(&mut self, tag: String) -> Result<()>;
+    fn remove_tag(&mut self, tag: &str) -> Result<()>;
+
+    /// Get the uuids of the tasks this task depends on
+    fn get_dependencies(&self) -> Result<Vec<Uuid>>;
+    fn add_dependency(&mut self, uuid: Uuid) -> Result<()>;
+    fn remove_dependency(&mut self, uuid: &Uuid) -> Result<()>;
 }
 
 impl Task for Entry {
@@ -38,5 +146,134 @@ impl Task for Entry {
             .ok_or(TE::from_kind(TEK::HeaderFieldMissing))
             .and_then(|u| Uuid::parse_str(&u).chain_err(|| TEK::UuidParserError))
     }
+
+    fn get_description(&self) -> Result<Option<String>> {
+        self.get_header().read_string("todo.description").map_err(From::from)
+    }
+
+    fn set_description(&mut self, description: String) -> Result<()> {
+        set_field(self, "description", Some(Value::String(description)))
+    }
+
+    fn get_status(&self) -> Result<Status> {
+        match self.get_header().read_string("todo.status")? {
+            None        => Ok(Status::Pending),
+            Some(ref s) => Status::from_str(s).ok_or_else(|| TE::from_kind(TEK::HeaderTypeError)),
+        }
+    }
+
+    fn set_status(&mut self, status: Status) -> Result<()> {
+        set_field(self, "status", Some(Value::String(String::from(status.as_str()))))
+    }
+
+    fn get_priority(&self) -> Result<Option<Priority>> {
+        match self.get_header().read_string("todo.priority")? {
+            None        => Ok(None),
+            Some(ref s) => Priority::from_str(s).map(Some).ok_or_else(|| TE::from_kind(TEK::HeaderTypeError)),
+        }
+    }
+
+    fn set_priority(&mut self, priority: Option<Priority>) -> Result<()> {
+        set_field(self, "priority", priority.map(|p| Value::String(String::from(p.as_str()))))
+    }
+
+    fn get_project(&self) -> Result<Option<String>> {
+        self.get_header().read_string("todo.project").map_err(From::from)
+    }
+
+    fn set_project(&mut self, project: Option<String>) -> Result<()> {
+        set_field(self, "project", project.map(Value::String))
+    }
+
+    fn get_due(&self) -> Result<Option<NaiveDate>> {
+        match self.get_header().read_string("todo.due")? {
+            None        => Ok(None),
+            Some(ref s) => date_from_string(s).map(Some).chain_err(|| TEK::HeaderTypeError),
+        }
+    }
+
+    fn set_due(&mut self, due: Option<NaiveDate>) -> Result<()> {
+        set_field(self, "due", due.map(|d| Value::String(date_to_string(&d))))
+    }
+
+    fn get_tags(&self) -> Result<Vec<String>> {
+        read_string_array(self, "todo.tags")
+    }
+
+    fn add_tag(&mut self, tag: String) -> Result<()> {
+        let mut tags = self.get_tags()?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+        set_string_array(self, "tags", tags)
+    }
+
+    fn remove_tag(&mut self, tag: &str) -> Result<()> {
+        let tags = self.get_tags()?.into_iter().filter(|t| t != tag).collect();
+        set_string_array(self, "tags", tags)
+    }
+
+    fn get_dependencies(&self) -> Result<Vec<Uuid>> {
+        read_string_array(self, "todo.depends")?
+            .into_iter()
+            .map(|u| Uuid::parse_str(&u).chain_err(|| TEK::UuidParserError))
+            .collect()
+    }
+
+    fn add_dependency(&mut self, uuid: Uuid) -> Result<()> {
+        let mut deps = self.get_dependencies()?;
+        if !deps.contains(&uuid) {
+            deps.push(uuid);
+        }
+        set_string_array(self, "depends", deps.into_iter().map(|u| format!("{}", u)).collect())
+    }
+
+    fn remove_dependency(&mut self, uuid: &Uuid) -> Result<()> {
+        let deps = self.get_dependencies()?
+            .into_iter()
+            .filter(|u| u != uuid)
+            .map(|u| format!("{}", u))
+            .collect();
+        set_string_array(self, "depends", deps)
+    }
 }
 
+/// Set (or remove, if `value` is `None`) the field `key` in the `todo` header table
+pub(crate) fn set_field(entry: &mut Entry, key: &str, value: Option<Value>) -> Result<()> {
+    if entry.get_header().read("todo")?.is_none() {
+        let _ = entry.get_header_mut().insert("todo", Value::Table(Table::new()))?;
+    }
+
+    match entry.get_header_mut().read_mut("todo")? {
+        Some(&mut Value::Table(ref mut table)) => {
+            match value {
+                Some(value) => { let _ = table.insert(String::from(key), value); },
+                None        => { let _ = table.remove(key); },
+            }
+            Ok(())
+        },
+        _ => Err(TE::from_kind(TEK::HeaderTypeError)),
+    }
+}
+
+pub(crate) fn read_string_array(entry: &Entry, path: &str) -> Result<Vec<String>> {
+    match entry.get_header().read(path)? {
+        None                         => Ok(vec![]),
+        Some(&Value::Array(ref arr)) => arr
+            .iter()
+            .map(|v| v.as_str().map(String::from).ok_or_else(|| TE::from_kind(TEK::HeaderTypeError)))
+            .collect(),
+        Some(_)                      => Err(TE::from_kind(TEK::HeaderTypeError)),
+    }
+}
+
+/// Set the array `key` in the `todo` header table, removing it if `values` is empty
+pub(crate) fn set_string_array(entry: &mut Entry, key: &str, values: Vec<String>) -> Result<()> {
+    let value = if values.is_empty() {
+        None
+    } else {
+        Some(Value::Array(values.into_iter().map(Value::String).collect()))
+    };
+
+    set_field(entry, key, value)
+}
@@ -49,6 +49,10 @@ pub trait TaskStore<'a> {
     fn delete_task_by_uuid(&self, uuid: Uuid) -> Result<()>;
     fn all_tasks(&self) -> Result<TaskIdIterator>;
     fn new_from_twtask(&'a self, task: TTask) -> Result<FileLockEntry<'a>>;
+    fn create_task(&'a self, description: String) -> Result<FileLockEntry<'a>>;
+    fn get_task(&'a self, uuid: Uuid) -> Result<Option<FileLockEntry<'a>>>;
+    fn find_task_uuid(&self, prefix: &str) -> Result<Uuid>;
+    fn delete_task(&self, uuid: Uuid) -> Result<()>;
 }
 
 impl<'a> TaskStore<'a> for Store {
@@ -176,6 +180,7 @@ impl<'a> TaskStore<'a> for Store {
 
     fn new_from_twtask(&'a self, task: TTask) -> Result<FileLockEntry<'a>> {
         use toml_query::read::TomlValueReadExt;
+        use toml_query::insert::TomlValueInsertExt;
         use toml_query::set::TomlValueSetExt;
 
         let uuid     = task.uuid();
@@ -189,7 +194,7 @@ impl<'a> TaskStore<'a> for Store {
                         {
                             let hdr = fle.get_header_mut();
                             if hdr.read("todo")?.is_none() {
-                                hdr.set("todo", Value::Table(BTreeMap::new()))?;
+                                let _ = hdr.insert("todo", Value::Table(BTreeMap::new()))?;
                             }
 
                             hdr.set("todo.uuid", Value::String(format!("{}",uuid)))?;
@@ -202,5 +207,154 @@ impl<'a> TaskStore<'a> for Store {
 
     }
 
+    /// Create a new task which is managed by imag (and not mirrored from taskwarrior)
+    fn create_task(&'a self, description: String) -> Result<FileLockEntry<'a>> {
+        use toml_query::insert::TomlValueInsertExt;
+        use task::Task;
+        use task::Status;
+
+        let uuid = Uuid::new_v4();
+        let mut fle = ModuleEntryPath::new(format!("{}", uuid))
+            .into_storeid()
+            .and_then(|id| self.create(id))
+            .map_err(TE::from)?;
+
+        let _ = fle.get_header_mut().insert("todo.uuid", Value::String(format!("{}", uuid)))?;
+        fle.set_status(Status::Pending)?;
+        fle.set_description(description)?;
+        Ok(fle)
+    }
+
+    /// Get a task by its UUID, no matter whether it is managed by imag or mirrored from
+    /// taskwarrior
+    fn get_task(&'a self, uuid: Uuid) -> Result<Option<FileLockEntry<'a>>> {
+        let native = ModuleEntryPath::new(format!("{}", uuid))
+            .into_storeid()
+            .and_then(|id| self.get(id))
+            .map_err(TE::from)?;
+
+        match native {
+            Some(fle) => Ok(Some(fle)),
+            None      => self.get_task_from_uuid(uuid),
+        }
+    }
+
+    /// Find the UUID of the task whose UUID starts with `prefix`
+    ///
+    /// Fails if there is no such task or if the prefix is ambiguous.
+    fn find_task_uuid(&self, prefix: &str) -> Result<Uuid> {
+        let mut uuids = self.all_tasks()?
+            .filter_map(|id| id.local().file_name().and_then(|n| n.to_str()).map(String::from))
+            .filter(|name| name.starts_with(prefix))
+            .collect::<Vec<_>>();
+
+        match uuids.len() {
+            0 => Err(TE::from_kind(TEK::TaskNotFound(String::from(prefix)))),
+            1 => Uuid::parse_str(&uuids.pop().unwrap()).chain_err(|| TEK::UuidParserError),
+            _ => Err(TE::from_kind(TEK::AmbiguousTaskId(String::from(prefix)))),
+        }
+    }
+
+    /// Delete a task and remove it from the dependencies of all other tasks
+    fn delete_task(&self, uuid: Uuid) -> Result<()> {
+        use task::Task;
+
+        let id = match self.get_task(uuid)? {
+            Some(fle) => fle.get_location().clone(),
+            None      => return Err(TE::from_kind(TEK::TaskNotFound(format!("{}", uuid)))),
+        };
+
+        for other in self.all_tasks()? {
+            if let Some(mut fle) = self.get(other)? {
+                if fle.get_dependencies()?.contains(&uuid) {
+                    fle.remove_dependency(&uuid)?;
+                }
+            }
+        }
+
+        self.delete(id).map_err(TE::from)
+    }
+
+}
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+    use toml_query::read::TomlValueReadExt;
+
+    use libimagstore::store::Store;
+
+    use task::Task;
+    use task::Status;
+    use task::Priority;
+    use super::*;
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    #[test]
+    fn test_task_fields() {
+        let store    = get_store();
+        let mut task = store.create_task(String::from("Write tests")).unwrap();
+
+        assert_eq!(task.get_description().unwrap(), Some(String::from("Write tests")));
+        assert_eq!(task.get_status().unwrap(), Status::Pending);
+        assert_eq!(task.get_priority().unwrap(), None);
+        assert_eq!(task.get_due().unwrap(), None);
+        assert!(task.get_tags().unwrap().is_empty());
+
+        let due = NaiveDate::from_ymd(2018, 5, 1);
+        task.set_status(Status::Done).unwrap();
+        task.set_priority(Some(Priority::High)).unwrap();
+        task.set_due(Some(due)).unwrap();
+        task.add_tag(String::from("imag")).unwrap();
+        task.add_tag(String::from("rust")).unwrap();
+        task.add_tag(String::from("imag")).unwrap();
+        task.remove_tag("rust").unwrap();
+
+        assert_eq!(task.get_status().unwrap(), Status::Done);
+        assert_eq!(task.get_priority().unwrap(), Some(Priority::High));
+        assert_eq!(task.get_due().unwrap(), Some(due));
+        assert_eq!(task.get_tags().unwrap(), vec![String::from("imag")]);
+
+        task.set_priority(None).unwrap();
+        task.set_due(None).unwrap();
+        task.remove_tag("imag").unwrap();
+        assert_eq!(task.get_priority().unwrap(), None);
+        assert_eq!(task.get_due().unwrap(), None);
+        assert!(task.get_header().read("todo.tags").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_and_delete_task() {
+        let store = get_store();
+
+        let (first, second) = {
+            let first      = store.create_task(String::from("first")).unwrap();
+            let mut second = store.create_task(String::from("second")).unwrap();
+            let first      = first.get_uuid().unwrap();
+
+            second.add_dependency(first).unwrap();
+            (first, second.get_uuid().unwrap())
+        };
+
+        let first_str = format!("{}", first);
+        assert_eq!(store.find_task_uuid(&first_str[..8]).unwrap(), first);
+        assert!(store.find_task_uuid("").is_err());
+        assert!(store.find_task_uuid("xyz").is_err());
+        assert_eq!(store.all_tasks().unwrap().count(), 2);
+
+        store.delete_task(first).unwrap();
+        assert!(store.get_task(first).unwrap().is_none());
+        assert!(store.delete_task(first).is_err());
+
+        let second = store.get_task(second).unwrap().unwrap();
+        assert!(second.get_dependencies().unwrap().is_empty());
+    }
+
 }
 
@@ -18,7 +18,10 @@
 //
 
 use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::internal::store_check::linked_entries;
 
+use annotateable::Annotateable;
 use error::Result;
 use iter::*;
 
@@ -26,6 +29,9 @@ pub trait AnnotationFetcher<'a> {
 
     fn all_annotations(&'a self) -> Result<AnnotationIter<'a>>;
 
+    /// Get the ids of all annotations which do not annotate any entry in the store (anymore)
+    fn orphaned_annotations(&'a self) -> Result<Vec<StoreId>>;
+
 }
 
 impl<'a> AnnotationFetcher<'a> for Store {
@@ -34,5 +40,21 @@ impl<'a> AnnotationFetcher<'a> for Store {
         Ok(AnnotationIter::new(self.entries()?.without_store(), self))
     }
 
+    fn orphaned_annotations(&'a self) -> Result<Vec<StoreId>> {
+        let connected = linked_entries(self)?;
+        let mut orphaned = vec![];
+
+        for id in self.entries()?.without_store().map(StoreId::without_base) {
+            if id.is_in_collection(&["annotations"])
+                && !connected.contains(&id)
+                && self.get_copy(id.clone())?.is_annotation()?
+            {
+                orphaned.push(id);
+            }
+        }
+
+        Ok(orphaned)
+    }
+
 }
 
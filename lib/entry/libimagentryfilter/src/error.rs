@@ -28,6 +28,10 @@ error_chain! {
     }
 
     errors {
+        QueryParseError(msg: String) {
+            description("Cannot parse query")
+            display("Cannot parse query: {}", msg)
+        }
     }
 
 }
@@ -33,6 +33,7 @@
     while_true,
 )]
 
+extern crate chrono;
 extern crate filters;
 extern crate regex;
 extern crate semver;
@@ -54,3 +55,4 @@ pub mod error;
 // their functionality
 
 pub mod tags;
+pub mod query;
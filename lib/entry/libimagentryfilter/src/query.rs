@@ -0,0 +1,542 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A small query language for filtering entries by their headers
+//!
+//! A query is parsed with `Query::parse()` and can then be used as `Filter<Entry>`:
+//!
+//! ```ignore
+//! todo.status == "pending" and datetime.value > 2018-01-01 and tag:work
+//! ```
+//!
+//! The language knows the following expressions:
+//!
+//! * `<field> <op> <value>` compares the header field `<field>` (a path like `todo.status`) with
+//!   `<value>`, where `<op>` is one of `==`, `!=`, `<`, `<=`, `>`, `>=`. Values are strings in
+//!   double quotes, integers, floats, `true`, `false`, dates (`2018-01-31`) and datetimes
+//!   (`2018-01-31T12:00:00`). Other words are taken as strings. Datetime header fields can be
+//!   strings in the formats imag uses. Dates are compared to the date part of a header field.
+//!   If the header field is an array, the comparison matches if it matches for one element.
+//! * `<field> ~ "<regex>"` matches the header field with a regular expression
+//! * `<field>` matches if the header field exists
+//! * `tag:<tag>` matches if the entry has the tag `<tag>`
+//! * `not <expr>`, `<expr> and <expr>`, `<expr> or <expr>` and parentheses combine expressions,
+//!   `not` binds strongest, `or` weakest.
+//!
+//! A comparison with a missing header field never matches.
+
+use std::cmp::Ordering;
+
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use filters::filter::Filter;
+use regex::Regex;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+use libimagstore::store::Entry;
+
+use builtin::header::field_path::FieldPath;
+use error::FilterError as FE;
+use error::FilterErrorKind as FEK;
+use error::Result;
+use tags::HasTag;
+
+/// Formats of datetime header fields
+const DATETIME_FORMATS : &'static [&'static str] = &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+
+/// Format of date header fields and literals
+const DATE_FORMAT : &'static str = "%Y-%m-%d";
+
+/// A comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+
+    fn from_str(s: &str) -> Option<Operator> {
+        match s {
+            "==" => Some(Operator::Eq),
+            "!=" => Some(Operator::Ne),
+            "<"  => Some(Operator::Lt),
+            "<=" => Some(Operator::Le),
+            ">"  => Some(Operator::Gt),
+            ">=" => Some(Operator::Ge),
+            _    => None,
+        }
+    }
+
+    /// Check whether `ord`, the ordering of a header value compared to a literal, satisfies the
+    /// operator. `None` means the values are not comparable.
+    fn holds_for(&self, ord: Option<Ordering>) -> bool {
+        match (*self, ord) {
+            (_, None)                    => *self == Operator::Ne,
+            (Operator::Eq, Some(o))      => o == Ordering::Equal,
+            (Operator::Ne, Some(o))      => o != Ordering::Equal,
+            (Operator::Lt, Some(o))      => o == Ordering::Less,
+            (Operator::Le, Some(o))      => o != Ordering::Greater,
+            (Operator::Gt, Some(o))      => o == Ordering::Greater,
+            (Operator::Ge, Some(o))      => o != Ordering::Less,
+        }
+    }
+
+}
+
+/// A value in a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Date(NaiveDate),
+    Datetime(NaiveDateTime),
+}
+
+impl Literal {
+
+    /// Parse a word which is not in quotes
+    fn from_word(w: &str) -> Literal {
+        if w == "true" {
+            return Literal::Boolean(true);
+        }
+        if w == "false" {
+            return Literal::Boolean(false);
+        }
+        if let Ok(i) = w.parse::<i64>() {
+            return Literal::Integer(i);
+        }
+        if let Ok(f) = w.parse::<f64>() {
+            return Literal::Float(f);
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(w, DATE_FORMAT) {
+            return Literal::Date(d);
+        }
+        if let Some(dt) = parse_datetime(w) {
+            return Literal::Datetime(dt);
+        }
+
+        Literal::String(String::from(w))
+    }
+
+    /// Compare the header value `v` to this literal
+    fn compare(&self, v: &Value) -> Option<Ordering> {
+        match (self, v) {
+            (&Literal::String(ref s),   &Value::String(ref t)) => Some(t.as_str().cmp(s.as_str())),
+            (&Literal::Integer(i),      &Value::Integer(j))    => Some(j.cmp(&i)),
+            (&Literal::Integer(i),      &Value::Float(f))      => f.partial_cmp(&(i as f64)),
+            (&Literal::Float(f),        &Value::Integer(i))    => (i as f64).partial_cmp(&f),
+            (&Literal::Float(f),        &Value::Float(g))      => g.partial_cmp(&f),
+            (&Literal::Boolean(b),      &Value::Boolean(c))    => Some(c.cmp(&b)),
+            (&Literal::Date(d),         v)                     => {
+                header_datetime(v).map(|dt| dt.date().cmp(&d))
+            },
+            (&Literal::Datetime(dt),    v)                     => {
+                header_datetime(v).map(|hdt| hdt.cmp(&dt))
+            },
+            _ => None,
+        }
+    }
+
+}
+
+/// A parsed query
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Tag(HasTag),
+    Exists(FieldPath),
+    Compare(FieldPath, Operator, Literal),
+    Matches(FieldPath, Regex),
+}
+
+impl Query {
+
+    /// Parse a query, see the module documentation for the syntax
+    pub fn parse(s: &str) -> Result<Query> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens: tokens, pos: 0 };
+        let query = parser.parse_or()?;
+
+        match parser.peek() {
+            None        => Ok(query),
+            Some(token) => Err(parse_error(format!("Unexpected {}", token))),
+        }
+    }
+
+    fn read_field<'a>(e: &'a Entry, field: &FieldPath) -> Option<&'a Value> {
+        e.get_header().read(&field[..]).ok().and_then(|v| v)
+    }
+
+}
+
+impl Filter<Entry> for Query {
+
+    fn filter(&self, e: &Entry) -> bool {
+        match *self {
+            Query::And(ref a, ref b) => a.filter(e) && b.filter(e),
+            Query::Or(ref a, ref b)  => a.filter(e) || b.filter(e),
+            Query::Not(ref a)        => !a.filter(e),
+            Query::Tag(ref t)        => t.filter(e),
+            Query::Exists(ref field) => Query::read_field(e, field).is_some(),
+            Query::Compare(ref field, ref op, ref literal) => {
+                match Query::read_field(e, field) {
+                    None                         => false,
+                    Some(&Value::Array(ref vs))  => {
+                        vs.iter().any(|v| op.holds_for(literal.compare(v)))
+                    },
+                    Some(v)                      => op.holds_for(literal.compare(v)),
+                }
+            },
+            Query::Matches(ref field, ref regex) => {
+                match Query::read_field(e, field) {
+                    Some(&Value::String(ref s))  => regex.is_match(s),
+                    Some(&Value::Array(ref vs))  => vs.iter().any(|v| match *v {
+                        Value::String(ref s) => regex.is_match(s),
+                        _                    => false,
+                    }),
+                    _                            => false,
+                }
+            },
+        }
+    }
+
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .filter_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .next()
+}
+
+/// Get a header value as datetime, dates are taken as midnight
+fn header_datetime(v: &Value) -> Option<NaiveDateTime> {
+    let s = match *v {
+        Value::String(ref s)   => s.clone(),
+        Value::Datetime(ref d) => {
+            // Ignore fractional seconds and offsets, we compare local times
+            let s = d.to_string();
+            s.chars().take(19).collect()
+        },
+        _ => return None,
+    };
+
+    parse_datetime(&s).or_else(|| {
+        NaiveDate::parse_from_str(&s, DATE_FORMAT).ok().map(|d| d.and_hms(0, 0, 0))
+    })
+}
+
+fn parse_error(msg: String) -> FE {
+    FE::from_kind(FEK::QueryParseError(msg))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(String),
+    Str(String),
+    Word(String),
+}
+
+impl ::std::fmt::Display for Token {
+
+    fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Token::LParen      => write!(fmt, "'('"),
+            Token::RParen      => write!(fmt, "')'"),
+            Token::Op(ref s)   => write!(fmt, "'{}'", s),
+            Token::Str(ref s)  => write!(fmt, "\"{}\"", s),
+            Token::Word(ref s) => write!(fmt, "'{}'", s),
+        }
+    }
+
+}
+
+fn is_op_char(c: char) -> bool {
+    c == '=' || c == '!' || c == '<' || c == '>' || c == '~'
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars  = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            let _ = chars.next();
+        } else if c == '(' {
+            let _ = chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            let _ = chars.next();
+            tokens.push(Token::RParen);
+        } else if c == '"' {
+            let _ = chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    None       => return Err(parse_error(String::from("Unterminated string"))),
+                    Some('"')  => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => string.push(c),
+                        None    => return Err(parse_error(String::from("Unterminated string"))),
+                    },
+                    Some(c)    => string.push(c),
+                }
+            }
+            tokens.push(Token::Str(string));
+        } else if is_op_char(c) {
+            let mut op = String::new();
+            while let Some(&c) = chars.peek() {
+                if !is_op_char(c) {
+                    break;
+                }
+                op.push(c);
+                let _ = chars.next();
+            }
+            tokens.push(Token::Op(op));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' || c == '"' || is_op_char(c) {
+                    break;
+                }
+                word.push(c);
+                let _ = chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn next_is_word(&self, w: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Word(ref s)) => s == w,
+            _                         => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut query = self.parse_and()?;
+        while self.next_is_word("or") {
+            let _ = self.next();
+            query = Query::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut query = self.parse_not()?;
+        while self.next_is_word("and") {
+            let _ = self.next();
+            query = Query::And(Box::new(query), Box::new(self.parse_not()?));
+        }
+        Ok(query)
+    }
+
+    fn parse_not(&mut self) -> Result<Query> {
+        if self.next_is_word("not") {
+            let _ = self.next();
+            Ok(Query::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Query> {
+        match self.next() {
+            None => Err(parse_error(String::from("Unexpected end of query"))),
+            Some(Token::LParen) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(query),
+                    _                   => Err(parse_error(String::from("Missing ')'"))),
+                }
+            },
+            Some(Token::Word(ref w)) if w.starts_with("tag:") => {
+                let tag = String::from(&w["tag:".len()..]);
+                if tag.is_empty() {
+                    return Err(parse_error(String::from("Missing tag after 'tag:'")));
+                }
+                Ok(Query::Tag(HasTag::new(tag)))
+            },
+            Some(Token::Word(field)) => match self.peek().cloned() {
+                Some(Token::Op(op)) => {
+                    let _ = self.next();
+                    self.parse_comparison(field, op)
+                },
+                _ => Ok(Query::Exists(field)),
+            },
+            Some(token) => Err(parse_error(format!("Unexpected {}", token))),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: FieldPath, op: String) -> Result<Query> {
+        let value = self.next();
+
+        if op == "~" {
+            return match value {
+                Some(Token::Str(s)) | Some(Token::Word(s)) => Regex::new(&s)
+                    .map(|re| Query::Matches(field, re))
+                    .map_err(|e| parse_error(format!("Invalid regex '{}': {}", s, e))),
+                _ => Err(parse_error(String::from("Missing regex after '~'"))),
+            };
+        }
+
+        let op = Operator::from_str(&op)
+            .ok_or_else(|| parse_error(format!("Unknown operator '{}'", op)))?;
+
+        let literal = match value {
+            Some(Token::Str(s))  => Literal::String(s),
+            Some(Token::Word(w)) => Literal::from_word(&w),
+            Some(token)          => return Err(parse_error(format!("Unexpected {}", token))),
+            None                 => return Err(parse_error(String::from("Missing value"))),
+        };
+
+        Ok(Query::Compare(field, op, literal))
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use filters::filter::Filter;
+    use toml::de::from_str as toml_from_str;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::*;
+
+    fn entry(header: &str) -> Entry {
+        let mut entry = Entry::new(StoreId::new_baseless(PathBuf::from("test")).unwrap());
+        *entry.get_header_mut() = toml_from_str(header).unwrap();
+        entry
+    }
+
+    fn matches(query: &str, e: &Entry) -> bool {
+        Query::parse(query).unwrap().filter(e)
+    }
+
+    fn todo() -> Entry {
+        entry(r#"
+        [imag]
+        version = "0.8.0"
+
+        [todo]
+        status = "pending"
+        priority = 2
+
+        [datetime]
+        value = "2018-03-01T12:00:00"
+
+        [tag]
+        values = [ "work", "urgent" ]
+        "#)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let e = todo();
+
+        assert!(matches(r#"todo.status == "pending""#, &e));
+        assert!(matches("todo.status == pending", &e));
+        assert!(!matches(r#"todo.status != "pending""#, &e));
+        assert!(matches("todo.priority > 1", &e));
+        assert!(matches("todo.priority <= 2.0", &e));
+        assert!(!matches("todo.priority < 2", &e));
+        assert!(matches("todo.status ~ \"^pend\"", &e));
+    }
+
+    #[test]
+    fn test_datetimes() {
+        let e = todo();
+
+        assert!(matches("datetime.value > 2018-01-01", &e));
+        assert!(matches("datetime.value == 2018-03-01", &e));
+        assert!(matches("datetime.value < 2018-03-01T13:00:00", &e));
+        assert!(!matches("datetime.value >= 2018-03-02", &e));
+    }
+
+    #[test]
+    fn test_missing_field_and_wrong_type() {
+        let e = todo();
+
+        assert!(!matches("todo.due < 2018-01-01", &e));
+        assert!(!matches("todo.due == 1", &e));
+        assert!(!matches("todo.status > 1", &e));
+        assert!(matches("todo.status", &e));
+        assert!(!matches("todo.due", &e));
+    }
+
+    #[test]
+    fn test_combinations() {
+        let e = todo();
+
+        assert!(matches(r#"todo.status == "pending" and datetime.value > 2018-01-01 and tag:work"#, &e));
+        assert!(!matches("tag:home", &e));
+        assert!(matches("tag:home or tag:urgent", &e));
+        assert!(matches("not tag:home", &e));
+        assert!(!matches("not (tag:home or tag:work)", &e));
+        assert!(matches("tag.values == urgent", &e));
+
+        // "and" binds stronger than "or"
+        assert!(matches("tag:work or tag:home and tag:none", &e));
+        assert!(!matches("(tag:work or tag:home) and tag:none", &e));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for q in &["", "(tag:work", "todo.status ==", "todo.status === 1", "\"pending\"",
+                   "tag:", "a b", "todo.status ~ \"(\"", "todo.status == \"x"] {
+            assert!(Query::parse(q).is_err(), "Expected '{}' to fail", q);
+        }
+    }
+
+}
@@ -88,6 +88,11 @@ error_chain! {
             description("Error in link handling")
             display("Error in link handling")
         }
+
+        EntryNotFound(id: StoreId) {
+            description("Entry not found")
+            display("Entry not found: {}", id)
+        }
     }
 }
 
@@ -47,6 +47,7 @@ use toml_query::insert::TomlValueInsertExt;
 use error::LinkErrorKind as LEK;
 use error::Result;
 use internal::InternalLinker;
+use internal::store_check::linked_entries;
 use module_path::ModuleEntryPath;
 use error::ResultExt;
 
@@ -81,7 +82,7 @@ impl Link for Entry {
     }
 
     fn get_url(&self) -> Result<Option<Url>> {
-        match self.get_header().read_string("links.external.url")? {
+        match self.get_header().read_string("links.external.content.url")? {
             None        => Ok(None),
             Some(ref s) => Url::parse(&s[..])
                 .map(Some)
@@ -402,6 +403,21 @@ impl ExternalLinker for Entry {
 
 }
 
+/// Get the ids of all external link entries which are not linked with any entry in the store
+///
+/// An external link entry belongs to the entries which link to it, so such an entry is not needed
+/// anymore and can be deleted.
+pub fn orphaned_external_links(store: &Store) -> Result<Vec<StoreId>> {
+    let connected = linked_entries(store)?;
+
+    Ok(store
+        .entries()?
+        .without_store()
+        .map(StoreId::without_base)
+        .filter(|id| is_external_link_storeid(id) && !connected.contains(id))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -434,5 +450,23 @@ mod tests {
         assert_eq!(url, e.get_external_links(&store).unwrap().next().unwrap().unwrap());
     }
 
+    #[test]
+    fn test_orphaned_external_links() {
+        setup_logging();
+        let store = get_store();
+
+        {
+            let mut e = store.retrieve(PathBuf::from("base-test_orphaned")).unwrap();
+            let url   = Url::parse("http://imag-pim.org").unwrap();
+            assert!(e.add_external_link(&store, url).is_ok());
+        }
+        assert_eq!(orphaned_external_links(&store).unwrap(), vec![]);
+
+        assert!(store.delete(PathBuf::from("base-test_orphaned")).is_ok());
+        let orphaned = orphaned_external_links(&store).unwrap();
+        assert_eq!(orphaned.len(), 1);
+        assert!(is_external_link_storeid(&orphaned[0]));
+    }
+
 }
 
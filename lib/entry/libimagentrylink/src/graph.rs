@@ -0,0 +1,426 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Export of the link network as graph
+//!
+//! A `LinkGraph` is built by walking the internal links of the entries in the store, starting from
+//! a set of entries or from all entries in the store. It can be exported as Graphviz DOT, as
+//! GraphML or as JSON list of nodes and edges.
+//!
+//! Links without direction (plain, annotated and undirected typed links) are exported as
+//! undirected edges, directed typed links as edges from the "outgoing" to the "incoming" side.
+//! Nodes of external link entries are labeled with their URL.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use serde_json::Value as JsonValue;
+use serde_json::Map as JsonMap;
+use url::Url;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use external::Link as ExternalLink;
+use external::is_external_link_storeid;
+use internal::InternalLinker;
+use internal::LinkDirection;
+use error::Result;
+
+/// A link between two nodes of the graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    from: StoreId,
+    to: StoreId,
+    kind: Option<String>,
+    directed: bool,
+}
+
+impl Edge {
+
+    /// The entry the link comes from, for undirected links the lower of the two ids
+    pub fn from(&self) -> &StoreId {
+        &self.from
+    }
+
+    /// The entry the link goes to, for undirected links the higher of the two ids
+    pub fn to(&self) -> &StoreId {
+        &self.to
+    }
+
+    /// The relation type of the link, if it is a typed link
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_ref().map(String::as_str)
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+}
+
+/// The link network of (a part of) the store
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    nodes: BTreeMap<StoreId, Option<Url>>,
+    edges: BTreeSet<Edge>,
+}
+
+impl LinkGraph {
+
+    /// Build the graph of the entries reachable from `starts` in at most `depth` links
+    ///
+    /// If `starts` is empty, the graph contains all entries of the store. If `collections` is not
+    /// empty, only entries in one of these collections (for example "wiki" or "links/external")
+    /// are part of the graph, and links are not followed through other entries.
+    pub fn walk(store: &Store, starts: Vec<StoreId>, depth: Option<usize>, collections: &[String])
+        -> Result<LinkGraph>
+    {
+        let collections = collections
+            .iter()
+            .map(|c| c.split('/').filter(|s| !s.is_empty()).map(String::from).collect())
+            .collect::<Vec<Vec<String>>>();
+
+        let included = |id: &StoreId| -> bool {
+            collections.is_empty() || collections.iter().any(|c| id.is_in_collection(c))
+        };
+
+        let existing = store
+            .entries()?
+            .without_store()
+            .map(StoreId::without_base)
+            .collect::<BTreeSet<StoreId>>();
+
+        let starts = if starts.is_empty() {
+            existing.iter().cloned().collect()
+        } else {
+            starts.into_iter().map(StoreId::without_base).collect::<Vec<_>>()
+        };
+
+        let mut graph   = LinkGraph::default();
+        let mut links   = BTreeMap::new();
+        let mut visited = BTreeSet::new();
+        let mut queue   = starts
+            .into_iter()
+            .filter(|id| existing.contains(id) && included(id))
+            .map(|id| (id, 0))
+            .collect::<VecDeque<(StoreId, usize)>>();
+
+        while let Some((id, distance)) = queue.pop_front() {
+            if !visited.insert(id.clone()) {
+                continue;
+            }
+
+            debug!("Adding node {} to the graph", id);
+            let entry = store.get_copy(id.clone())?;
+            let url   = if is_external_link_storeid(&id) {
+                entry.get_url()?
+            } else {
+                None
+            };
+            let _ = graph.nodes.insert(id.clone(), url);
+
+            let mut targets = vec![];
+            for link in entry.get_internal_links()? {
+                let target = link.get_store_id().clone().without_base();
+                if !existing.contains(&target) || !included(&target) {
+                    continue;
+                }
+
+                if depth.map(|d| distance < d).unwrap_or(true) {
+                    queue.push_back((target.clone(), distance + 1));
+                }
+
+                targets.push((target, link.kind().map(String::from), link.direction()));
+            }
+            let _ = links.insert(id, targets);
+        }
+
+        // Only links between nodes of the graph are edges, links to entries beyond the depth limit
+        // are dropped
+        for (id, targets) in links {
+            for (target, kind, direction) in targets {
+                if !graph.nodes.contains_key(&target) {
+                    continue;
+                }
+
+                let edge = match direction {
+                    Some(LinkDirection::Outgoing) => Edge { from: id.clone(), to: target, kind, directed: true },
+                    Some(LinkDirection::Incoming) => Edge { from: target, to: id.clone(), kind, directed: true },
+                    None => {
+                        let (from, to) = if id <= target {
+                            (id.clone(), target)
+                        } else {
+                            (target, id.clone())
+                        };
+                        Edge { from, to, kind, directed: false }
+                    },
+                };
+                let _ = graph.edges.insert(edge);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// The nodes of the graph with the URL of external link nodes
+    pub fn nodes(&self) -> &BTreeMap<StoreId, Option<Url>> {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &BTreeSet<Edge> {
+        &self.edges
+    }
+
+    /// Export the graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph imag {\n");
+
+        for (id, url) in self.nodes.iter() {
+            out.push_str(&format!("    \"{}\" [label=\"{}\"];\n",
+                                  dot_escape(&node_name(id)),
+                                  dot_escape(&node_label(id, url))));
+        }
+
+        for edge in self.edges.iter() {
+            let mut attrs = vec![];
+            if let Some(ref kind) = edge.kind {
+                attrs.push(format!("label=\"{}\"", dot_escape(kind)));
+            }
+            if !edge.directed {
+                attrs.push(String::from("dir=none"));
+            }
+
+            out.push_str(&format!("    \"{}\" -> \"{}\"", dot_escape(&node_name(&edge.from)), dot_escape(&node_name(&edge.to))));
+            if !attrs.is_empty() {
+                out.push_str(&format!(" [{}]", attrs.join(", ")));
+            }
+            out.push_str(";\n");
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Export the graph in the GraphML format
+    ///
+    /// Nodes have the attributes "label" and "url", edges the attribute "type".
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"url\" for=\"node\" attr.name=\"url\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"type\" for=\"edge\" attr.name=\"type\" attr.type=\"string\"/>\n");
+        out.push_str("  <graph id=\"imag\" edgedefault=\"undirected\">\n");
+
+        for (id, url) in self.nodes.iter() {
+            out.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&node_name(id))));
+            out.push_str(&format!("      <data key=\"label\">{}</data>\n", xml_escape(&node_label(id, url))));
+            if let Some(ref url) = *url {
+                out.push_str(&format!("      <data key=\"url\">{}</data>\n", xml_escape(url.as_str())));
+            }
+            out.push_str("    </node>\n");
+        }
+
+        for edge in self.edges.iter() {
+            out.push_str(&format!("    <edge source=\"{}\" target=\"{}\" directed=\"{}\"",
+                                  xml_escape(&node_name(&edge.from)),
+                                  xml_escape(&node_name(&edge.to)),
+                                  edge.directed));
+            match edge.kind {
+                Some(ref kind) => {
+                    out.push_str(">\n");
+                    out.push_str(&format!("      <data key=\"type\">{}</data>\n", xml_escape(kind)));
+                    out.push_str("    </edge>\n");
+                },
+                None => out.push_str("/>\n"),
+            }
+        }
+
+        out.push_str("  </graph>\n");
+        out.push_str("</graphml>\n");
+        out
+    }
+
+    /// Export the graph as JSON object with the lists "nodes" and "edges"
+    ///
+    /// Nodes are objects with the keys "id", "label" and "url" (null for entries which are not
+    /// external links), edges objects with the keys "from", "to", "type" (null for untyped links)
+    /// and "directed".
+    pub fn to_json(&self) -> JsonValue {
+        let nodes = self.nodes
+            .iter()
+            .map(|(id, url)| {
+                let mut node = JsonMap::new();
+                let _ = node.insert(String::from("id"), JsonValue::String(node_name(id)));
+                let _ = node.insert(String::from("label"), JsonValue::String(node_label(id, url)));
+                let _ = node.insert(String::from("url"), url
+                                    .as_ref()
+                                    .map(|u| JsonValue::String(String::from(u.as_str())))
+                                    .unwrap_or(JsonValue::Null));
+                JsonValue::Object(node)
+            })
+            .collect();
+
+        let edges = self.edges
+            .iter()
+            .map(|edge| {
+                let mut e = JsonMap::new();
+                let _ = e.insert(String::from("from"), JsonValue::String(node_name(&edge.from)));
+                let _ = e.insert(String::from("to"), JsonValue::String(node_name(&edge.to)));
+                let _ = e.insert(String::from("type"), edge.kind
+                                 .as_ref()
+                                 .map(|k| JsonValue::String(k.clone()))
+                                 .unwrap_or(JsonValue::Null));
+                let _ = e.insert(String::from("directed"), JsonValue::Bool(edge.directed));
+                JsonValue::Object(e)
+            })
+            .collect();
+
+        let mut graph = JsonMap::new();
+        let _ = graph.insert(String::from("nodes"), JsonValue::Array(nodes));
+        let _ = graph.insert(String::from("edges"), JsonValue::Array(edges));
+        JsonValue::Object(graph)
+    }
+
+}
+
+fn node_name(id: &StoreId) -> String {
+    PathBuf::from(id.local()).to_string_lossy().into_owned()
+}
+
+fn node_label(id: &StoreId, url: &Option<Url>) -> String {
+    url.as_ref().map(|u| String::from(u.as_str())).unwrap_or_else(|| node_name(id))
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use url::Url;
+
+    use libimagstore::store::Store;
+
+    use super::*;
+    use external::ExternalLinker;
+
+    fn setup_logging() {
+        use env_logger;
+        let _ = env_logger::try_init();
+    }
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    // wiki/a -- wiki/b -blocks-> todo/c -- wiki/d, wiki/a -- external link
+    fn build_store(store: &Store) {
+        let mut a = store.create(PathBuf::from("wiki/a")).unwrap();
+        let mut b = store.create(PathBuf::from("wiki/b")).unwrap();
+        let mut c = store.create(PathBuf::from("todo/c")).unwrap();
+        let mut d = store.create(PathBuf::from("wiki/d")).unwrap();
+
+        assert!(a.add_internal_link(&mut b).is_ok());
+        assert!(b.add_internal_typed_link(&mut c, String::from("blocks"), true).is_ok());
+        assert!(c.add_internal_link(&mut d).is_ok());
+        assert!(a.add_external_link(store, Url::parse("https://imag-pim.org").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_whole_store() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        let graph = LinkGraph::walk(&store, vec![], None, &[]).unwrap();
+        assert_eq!(graph.nodes().len(), 5);
+        assert_eq!(graph.edges().len(), 4);
+
+        let blocks = graph.edges().iter().find(|e| e.kind() == Some("blocks")).unwrap();
+        assert_eq!(blocks.from(), &id("wiki/b"));
+        assert_eq!(blocks.to(), &id("todo/c"));
+        assert!(blocks.is_directed());
+
+        let urls = graph.nodes().values().filter_map(|u| u.clone()).collect::<Vec<_>>();
+        assert_eq!(urls, vec![Url::parse("https://imag-pim.org").unwrap()]);
+    }
+
+    #[test]
+    fn test_depth_and_collections() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        let graph = LinkGraph::walk(&store, vec![id("wiki/b")], Some(1), &[]).unwrap();
+        let nodes = graph.nodes().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(nodes, vec![id("todo/c"), id("wiki/a"), id("wiki/b")]);
+        assert_eq!(graph.edges().len(), 2);
+
+        // Links are not followed through entries of other collections
+        let graph = LinkGraph::walk(&store, vec![id("wiki/a")], None, &[String::from("wiki")]).unwrap();
+        let nodes = graph.nodes().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(nodes, vec![id("wiki/a"), id("wiki/b")]);
+    }
+
+    #[test]
+    fn test_export() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        let graph = LinkGraph::walk(&store, vec![id("wiki/b")], Some(1), &[]).unwrap();
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph imag {\n"));
+        assert!(dot.contains("    \"wiki/b\" -> \"todo/c\" [label=\"blocks\"];\n"));
+        assert!(dot.contains("    \"wiki/a\" -> \"wiki/b\" [dir=none];\n"));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<edge source=\"wiki/b\" target=\"todo/c\" directed=\"true\">"));
+        assert!(graphml.contains("<node id=\"wiki/a\">"));
+
+        let json = graph.to_json();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(json["edges"][0]["from"], "wiki/a");
+        assert_eq!(json["edges"][0]["type"], JsonValue::Null);
+        assert_eq!(json["edges"][1]["type"], "blocks");
+        assert_eq!(json["edges"][1]["directed"], true);
+    }
+
+}
@@ -43,6 +43,47 @@ use toml::Value;
 pub enum Link {
     Id          { link: StoreId },
     Annotated   { link: StoreId, annotation: String },
+
+    /// A link with a relation type, for example "blocks" or "parent-of"
+    ///
+    /// If `direction` is `None`, the relation is symmetric (for example "relates-to").
+    Typed       { link: StoreId, kind: String, direction: Option<LinkDirection> },
+}
+
+/// The direction of a typed link, as seen from the entry which holds the link
+///
+/// If A "blocks" B, the link in A is `Outgoing` and the link in B is `Incoming`.
+#[derive(Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub enum LinkDirection {
+    Outgoing,
+    Incoming,
+}
+
+impl LinkDirection {
+
+    pub fn from_str(s: &str) -> Option<LinkDirection> {
+        match s {
+            "outgoing" => Some(LinkDirection::Outgoing),
+            "incoming" => Some(LinkDirection::Incoming),
+            _          => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LinkDirection::Outgoing => "outgoing",
+            LinkDirection::Incoming => "incoming",
+        }
+    }
+
+    /// The direction of the link in the linked entry
+    pub fn reverse(&self) -> LinkDirection {
+        match *self {
+            LinkDirection::Outgoing => LinkDirection::Incoming,
+            LinkDirection::Incoming => LinkDirection::Outgoing,
+        }
+    }
+
 }
 
 impl Link {
@@ -51,6 +92,7 @@ impl Link {
         match *self {
             Link::Id { ref link }             => link.exists(),
             Link::Annotated { ref link, .. }  => link.exists(),
+            Link::Typed { ref link, .. }      => link.exists(),
         }
         .map_err(From::from)
     }
@@ -59,6 +101,7 @@ impl Link {
         match *self {
             Link::Id { ref link }             => link.to_str(),
             Link::Annotated { ref link, .. }  => link.to_str(),
+            Link::Typed { ref link, .. }      => link.to_str(),
         }
         .map_err(From::from)
     }
@@ -68,6 +111,7 @@ impl Link {
         match self {
             &Link::Id { link: ref s }             => s.eq(id),
             &Link::Annotated { link: ref s, .. }  => s.eq(id),
+            &Link::Typed { link: ref s, .. }      => s.eq(id),
         }
     }
 
@@ -76,6 +120,23 @@ impl Link {
         match self {
             &Link::Id { link: ref s }             => s,
             &Link::Annotated { link: ref s, .. }  => s,
+            &Link::Typed { link: ref s, .. }      => s,
+        }
+    }
+
+    /// Get the relation type of the link, if it is a typed link
+    pub fn kind(&self) -> Option<&str> {
+        match self {
+            &Link::Typed { ref kind, .. } => Some(kind),
+            _                             => None,
+        }
+    }
+
+    /// Get the direction of the link, if it is a directed typed link
+    pub fn direction(&self) -> Option<LinkDirection> {
+        match self {
+            &Link::Typed { direction, .. } => direction,
+            _                              => None,
         }
     }
 
@@ -85,6 +146,17 @@ impl Link {
             Link::Id { link: s } => Link::Id { link: s.without_base() },
             Link::Annotated { link: s, annotation: ann } =>
                 Link::Annotated { link: s.without_base(), annotation: ann },
+            Link::Typed { link: s, kind, direction } =>
+                Link::Typed { link: s.without_base(), kind, direction },
+        }
+    }
+
+    /// The same link, but to the entry `id`
+    fn with_store_id(self, id: StoreId) -> Link {
+        match self {
+            Link::Id { .. } => Link::Id { link: id },
+            Link::Annotated { annotation, .. } => Link::Annotated { link: id, annotation },
+            Link::Typed { kind, direction, .. } => Link::Typed { link: id, kind, direction },
         }
     }
 
@@ -95,6 +167,8 @@ impl Link {
             Link::Id { link: s } => Link::Id { link: s.with_base(pb) },
             Link::Annotated { link: s, annotation: ann } =>
                 Link::Annotated { link: s.with_base(pb), annotation: ann },
+            Link::Typed { link: s, kind, direction } =>
+                Link::Typed { link: s.with_base(pb), kind, direction },
         }
     }
 
@@ -113,7 +187,22 @@ impl Link {
                         tab.insert("annotation".to_owned(), Value::String(anno.clone()));
                         Value::Table(tab)
                     })
-            }
+            },
+            &Link::Typed { ref link, ref kind, direction } => {
+                link.to_str()
+                    .map(Value::String)
+                    .chain_err(|| LEK::InternalConversionError)
+                    .map(|link| {
+                        let mut tab = BTreeMap::new();
+
+                        tab.insert("link".to_owned(), link);
+                        tab.insert("type".to_owned(), Value::String(kind.clone()));
+                        if let Some(direction) = direction {
+                            tab.insert("direction".to_owned(), Value::String(direction.as_str().to_owned()));
+                        }
+                        Value::Table(tab)
+                    })
+            },
         }
     }
 
@@ -126,6 +215,9 @@ impl ::std::cmp::PartialEq for Link {
             (&Link::Annotated { link: ref a, annotation: ref ann1 },
              &Link::Annotated { link: ref b, annotation: ref ann2 }) =>
                 (a, ann1).eq(&(b, ann2)),
+            (&Link::Typed { link: ref a, kind: ref kind1, direction: dir1 },
+             &Link::Typed { link: ref b, kind: ref kind2, direction: dir2 }) =>
+                (a, kind1, dir1).eq(&(b, kind2, dir2)),
             _ => false,
         }
     }
@@ -143,6 +235,7 @@ impl Into<StoreId> for Link {
         match self {
             Link::Id { link }            => link,
             Link::Annotated { link, .. } => link,
+            Link::Typed { link, .. }     => link,
         }
     }
 }
@@ -152,6 +245,7 @@ impl IntoStoreId for Link {
         match self {
             Link::Id { link }            => Ok(link),
             Link::Annotated { link, .. } => Ok(link),
+            Link::Typed { link, .. }     => Ok(link),
         }
     }
 }
@@ -161,6 +255,7 @@ impl AsRef<StoreId> for Link {
         match self {
             &Link::Id { ref link }            => &link,
             &Link::Annotated { ref link, .. } => &link,
+            &Link::Typed { ref link, .. }     => &link,
         }
     }
 }
@@ -184,9 +279,24 @@ pub trait InternalLinker {
 
     /// Add internal annotated link
     fn add_internal_annotated_link(&mut self, link: &mut Entry, annotation: String) -> Result<()>;
+
+    /// Add an internal link with the relation type `kind`
+    ///
+    /// If `directed` is true, the relation points from the implementor object to `link` (the
+    /// implementor object "blocks" `link`, for example), otherwise it is symmetric. A plain link
+    /// between the two entries is replaced by the typed link.
+    fn add_internal_typed_link(&mut self, link: &mut Entry, kind: String, directed: bool) -> Result<()>;
+
+    /// Get the internal links with the relation type `kind`
+    fn get_internal_links_of_type(&self, kind: &str) -> Result<LinkIter>;
+
+    /// Remove the internal links with the relation type `kind` between the implementor object and
+    /// `link`, other links between them are kept
+    fn remove_internal_typed_link(&mut self, link: &mut Entry, kind: &str) -> Result<()>;
 }
 
 pub mod iter {
+    use std::collections::BTreeMap;
     use std::vec::IntoIter;
     use super::Link;
 
@@ -228,11 +338,29 @@ pub mod iter {
 
     impl<I: Iterator<Item = Link>> IntoValues for I {
         fn into_values(self) -> Vec<Result<Value>> {
-            self.map(|s| s.without_base())
+            let values : Vec<Result<Value>> = self.map(|s| s.without_base())
                 .unique()
                 .sorted()
                 .into_iter() // Cannot sort toml::Value, hence uglyness here
                 .map(|link| link.to_value().chain_err(|| LEK::InternalConversionError))
+                .collect();
+
+            // TOML arrays cannot mix strings and tables, so if there is an annotated or typed
+            // link, plain links are written as `{ link = "..." }` as well
+            if !values.iter().any(|v| is_match!(*v, Ok(Value::Table(_)))) {
+                return values;
+            }
+
+            values
+                .into_iter()
+                .map(|v| v.map(|v| match v {
+                    Value::String(link) => {
+                        let mut tab = BTreeMap::new();
+                        tab.insert("link".to_owned(), Value::String(link));
+                        Value::Table(tab)
+                    },
+                    other => other,
+                }))
                 .collect()
         }
     }
@@ -292,11 +420,16 @@ pub mod iter {
         type Item = Result<FileLockEntry<'a>>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            self.0.next().and_then(|id| match self.1.get(id) {
-                Ok(None)    => None,
-                Ok(Some(x)) => Some(Ok(x)),
-                Err(e)      => Some(Err(e).map_err(From::from)),
-            })
+            // Links to entries which do not exist are skipped
+            while let Some(id) = self.0.next() {
+                match self.1.get(id) {
+                    Ok(None)    => continue,
+                    Ok(Some(x)) => return Some(Ok(x)),
+                    Err(e)      => return Some(Err(e).map_err(From::from)),
+                }
+            }
+
+            None
         }
 
     }
@@ -430,6 +563,14 @@ impl InternalLinker for Entry {
     }
 
     fn add_internal_link(&mut self, link: &mut Entry) -> Result<()> {
+        let target = link.get_location().clone().without_base();
+
+        // A typed link already links the entries, a plain link would only duplicate it
+        if self.get_internal_links()?.any(|l| l.eq_store_id(&target) && l.kind().is_some()) {
+            debug!("{:?} already has a typed link to {:?}", self.get_location(), target);
+            return Ok(());
+        }
+
         let location = link.get_location().clone().into();
         add_internal_link_with_instance(self, link, location)
     }
@@ -476,6 +617,93 @@ impl InternalLinker for Entry {
         add_internal_link_with_instance(self, link, new_link)
     }
 
+    fn add_internal_typed_link(&mut self, link: &mut Entry, kind: String, directed: bool) -> Result<()> {
+        let direction = if directed { Some(LinkDirection::Outgoing) } else { None };
+        let own = Link::Typed {
+            link: link.get_location().clone(),
+            kind: kind.clone(),
+            direction: direction,
+        };
+        let foreign = Link::Typed {
+            link: self.get_location().clone(),
+            kind: kind,
+            direction: direction.map(|d| d.reverse()),
+        };
+
+        debug!("Adding typed link from {:?} to {:?}", self.get_location(), own);
+        add_typed_link_instance(link, foreign)
+            .and_then(|_| add_typed_link_instance(self, own))
+    }
+
+    fn get_internal_links_of_type(&self, kind: &str) -> Result<LinkIter> {
+        self.get_internal_links()
+            .map(|links| LinkIter::new(links.filter(|l| l.kind() == Some(kind)).collect()))
+    }
+
+    fn remove_internal_typed_link(&mut self, link: &mut Entry, kind: &str) -> Result<()> {
+        let own_loc   = self.get_location().clone().without_base();
+        let other_loc = link.get_location().clone().without_base();
+
+        debug!("Removing '{}' links between {:?} and {:?}", kind, own_loc, other_loc);
+        let _ = link.get_internal_links()
+            .and_then(|links| {
+                let links = links.filter(|l| !(l.eq_store_id(&own_loc) && l.kind() == Some(kind)));
+                rewrite_links(link.get_header_mut(), links)
+            })?;
+
+        self.get_internal_links()
+            .and_then(|links| {
+                let links = links.filter(|l| !(l.eq_store_id(&other_loc) && l.kind() == Some(kind)));
+                rewrite_links(self.get_header_mut(), links)
+            })
+    }
+
+}
+
+/// Let the entries which are linked with the entry moved from `old` to `new` link to `new`
+///
+/// `Store::move_by_id()` only moves the entry itself, so afterwards the links in the linked entries
+/// still point to `old`. The annotations and types of the links are kept. Links to entries which
+/// do not exist are left alone.
+pub fn move_links(store: &Store, old: &StoreId, new: &StoreId) -> Result<()> {
+    let old_id = old.clone().without_base();
+    let new_id = new.clone().without_base();
+
+    let linked = store
+        .get(new.clone())?
+        .ok_or_else(|| LE::from_kind(LEK::LinkTargetDoesNotExist))?
+        .get_internal_links()?
+        .map(|link| link.get_store_id().clone())
+        .collect::<Vec<_>>();
+
+    for id in linked {
+        let mut entry = match store.get(id)? {
+            Some(entry) => entry,
+            None        => continue,
+        };
+
+        debug!("Moving links of {} from {} to {}", entry.get_location(), old_id, new_id);
+        let links = entry
+            .get_internal_links()?
+            .map(|l| if l.eq_store_id(&old_id) { l.with_store_id(new_id.clone()) } else { l });
+        let _ = rewrite_links(entry.get_header_mut(), links)?;
+        let _ = store.update(&mut entry)?;
+    }
+
+    Ok(())
+}
+
+/// Add the typed link `instance` to `this`, replacing a plain link to the same entry
+fn add_typed_link_instance(this: &mut Entry, instance: Link) -> Result<()> {
+    let target = instance.get_store_id().clone().without_base();
+
+    this.get_internal_links()
+        .and_then(|links| {
+            let links = links
+                .filter(|l| !(l.eq_store_id(&target) && is_match!(*l, Link::Id { .. })))
+                .chain(LinkIter::new(vec![instance]));
+            rewrite_links(this.get_header_mut(), links)
+        })
 }
 
 fn add_internal_link_with_instance(this: &mut Entry, link: &mut Entry, instance: Link) -> Result<()> {
@@ -576,6 +804,20 @@ fn process_rw_result(links: Result<Option<Value>>) -> Result<LinkIter> {
                     ,
                 Value::Table(mut tab) => {
                     debug!("Destructuring table");
+                    if tab.contains_key("link") && tab.contains_key("type") {
+                        return parse_typed_link(tab);
+                    }
+
+                    if tab.len() == 1 {
+                        return match tab.remove("link") {
+                            Some(Value::String(link)) => StoreId::new_baseless(PathBuf::from(link))
+                                .map(|s| Link::Id { link: s })
+                                .map_err(From::from),
+                            Some(_) => Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+                            None    => Err(LE::from_kind(LEK::LinkParserError)),
+                        };
+                    }
+
                     if !tab.contains_key("link")
                     || !tab.contains_key("annotation") {
                         debug!("Things missing... returning Error instance");
@@ -612,25 +854,163 @@ fn process_rw_result(links: Result<Option<Value>>) -> Result<LinkIter> {
     Ok(LinkIter::new(links))
 }
 
+/// Parse a typed link from its table `{ link = "...", type = "...", direction = "..." }`
+fn parse_typed_link(mut tab: BTreeMap<String, Value>) -> Result<Link> {
+    use std::path::PathBuf;
+
+    debug!("Building a Link::Typed");
+    let direction = match tab.remove("direction") {
+        None                       => None,
+        Some(Value::String(ref d)) => Some(LinkDirection::from_str(d)
+            .ok_or_else(|| LE::from_kind(LEK::LinkParserFieldTypeError))?),
+        Some(_)                    => return Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+    };
+
+    match (tab.remove("link"), tab.remove("type")) {
+        (Some(Value::String(link)), Some(Value::String(kind))) => {
+            StoreId::new_baseless(PathBuf::from(link))
+                .map_err(From::from)
+                .map(|link| Link::Typed { link, kind, direction })
+        },
+        (Some(_), Some(_)) => Err(LE::from_kind(LEK::LinkParserFieldTypeError)),
+        _                  => Err(LE::from_kind(LEK::LinkParserFieldMissingError)),
+    }
+}
+
 pub mod store_check {
+    use std::collections::BTreeSet;
+    use std::fmt::{Display, Formatter, Result as FmtResult};
+
     use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+    use error::LinkErrorKind as LEK;
+    use error::LinkError as LE;
     use error::Result;
     use error::ResultExt;
+    use external::is_external_link_storeid;
+    use internal::InternalLinker;
+    use internal::Link;
+
+    /// An inconsistency in the internal links of the store
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum LinkInconsistency {
+        /// `from` links to `to`, which does not exist
+        Dangling { from: StoreId, to: StoreId },
+
+        /// `from` links to `to`, but `to` does not link back to `from`
+        OneDirectional { from: StoreId, to: StoreId },
+    }
+
+    impl LinkInconsistency {
+
+        /// Repair the inconsistency
+        ///
+        /// Dangling links are removed. One-directional links are completed by linking back, typed
+        /// links with the same type and the reversed direction, except for links of external link entries: these entries belong to the entries which
+        /// link to them, so if such an entry does not link to the external link entry anymore, the
+        /// link is removed from the external link entry as well.
+        pub fn repair(&self, store: &Store) -> Result<()> {
+            match *self {
+                LinkInconsistency::Dangling { ref from, ref to } => remove_link(store, from, to),
+                LinkInconsistency::OneDirectional { ref from, ref to } => {
+                    if is_external_link_storeid(from) {
+                        return remove_link(store, from, to);
+                    }
+
+                    // Typed links are mirrored with their type and the reversed direction
+                    let typed = store
+                        .get_copy(from.clone())?
+                        .get_internal_links()?
+                        .filter_map(|l| match l {
+                            Link::Typed { ref link, ref kind, direction } if link == to => {
+                                Some(Link::Typed {
+                                    link: from.clone(),
+                                    kind: kind.clone(),
+                                    direction: direction.map(|d| d.reverse()),
+                                })
+                            },
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+
+                    let mut target = store
+                        .get(to.clone())?
+                        .ok_or_else(|| LE::from_kind(LEK::LinkTargetDoesNotExist))?;
+                    if typed.is_empty() {
+                        let _ = super::add_foreign_link(&mut target, from.clone())?;
+                    }
+                    for link in typed {
+                        let _ = super::add_typed_link_instance(&mut target, link)?;
+                    }
+                    store.update(&mut target).map_err(From::from)
+                },
+            }
+        }
+
+    }
+
+    impl Display for LinkInconsistency {
+
+        fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+            match *self {
+                LinkInconsistency::Dangling { ref from, ref to } =>
+                    write!(fmt, "{} links to {}, which does not exist", from, to),
+                LinkInconsistency::OneDirectional { ref from, ref to } =>
+                    write!(fmt, "{} links to {}, but {} does not link back", from, to, to),
+            }
+        }
+
+    }
+
+    /// Remove the link to `to` from the entry `from`, without touching `to`
+    fn remove_link(store: &Store, from: &StoreId, to: &StoreId) -> Result<()> {
+        let mut entry = store
+            .get(from.clone())?
+            .ok_or_else(|| LE::from_kind(LEK::LinkTargetDoesNotExist))?;
+
+        let links = entry.get_internal_links()?.filter(|l| !l.eq_store_id(to));
+        let _     = super::rewrite_links(entry.get_header_mut(), links)?;
+        store.update(&mut entry).map_err(From::from)
+    }
+
+    /// Get the ids of all entries which are linked with at least one existing entry, in either
+    /// direction
+    pub fn linked_entries(store: &Store) -> Result<BTreeSet<StoreId>> {
+        let ids = store
+            .entries()?
+            .without_store()
+            .map(StoreId::without_base)
+            .collect::<BTreeSet<StoreId>>();
+
+        let mut connected = BTreeSet::new();
+        for id in ids.iter() {
+            for link in store.get_copy(id.clone())?.get_internal_links()? {
+                if ids.contains(link.get_store_id()) {
+                    connected.insert(id.clone());
+                    connected.insert(link.get_store_id().clone().without_base());
+                }
+            }
+        }
+
+        Ok(connected)
+    }
 
     pub trait StoreLinkConsistentExt {
         fn check_link_consistency(&self) -> Result<()>;
+
+        /// Find all inconsistencies in the internal links of the store
+        ///
+        /// In contrast to `check_link_consistency()`, this does not stop at the first problem
+        /// and does not write the entries back to the store.
+        fn link_inconsistencies(&self) -> Result<Vec<LinkInconsistency>>;
     }
 
     impl StoreLinkConsistentExt for Store {
         fn check_link_consistency(&self) -> Result<()> {
             use std::collections::HashMap;
 
-            use error::LinkErrorKind as LEK;
-            use error::LinkError as LE;
             use error::Result as LResult;
-            use internal::InternalLinker;
 
-            use libimagstore::storeid::StoreId;
             use libimagutil::debug_result::DebugResult;
 
             // Helper data structure to collect incoming and outgoing links for each StoreId
@@ -765,6 +1145,43 @@ pub mod store_check {
                 })
                 .map(|_| ())
         }
+
+        fn link_inconsistencies(&self) -> Result<Vec<LinkInconsistency>> {
+            use std::collections::BTreeMap;
+            use std::collections::BTreeSet;
+
+            let mut network : BTreeMap<StoreId, Vec<StoreId>> = BTreeMap::new();
+            for id in self.entries()?.without_store() {
+                let links = self
+                    .get_copy(id.clone())?
+                    .get_internal_links()?
+                    .map(|link| link.get_store_id().clone().without_base())
+                    .collect();
+
+                network.insert(id.without_base(), links);
+            }
+
+            let mut found = BTreeSet::new();
+            for (from, links) in network.iter() {
+                for to in links {
+                    let inconsistency = match network.get(to) {
+                        None => LinkInconsistency::Dangling {
+                            from: from.clone(),
+                            to: to.clone(),
+                        },
+                        Some(back) if !back.contains(from) => LinkInconsistency::OneDirectional {
+                            from: from.clone(),
+                            to: to.clone(),
+                        },
+                        Some(_) => continue,
+                    };
+
+                    found.insert(inconsistency);
+                }
+            }
+
+            Ok(found.into_iter().collect())
+        }
     }
 
 }
@@ -1007,10 +1424,213 @@ mod test {
                 match link  {
                     Link::Id {..}        => {},
                     Link::Annotated {..} => assert!(false, "Annotated link found"),
+                    Link::Typed {..}     => assert!(false, "Typed link found"),
                 }
             }
         }
     }
 
+    #[test]
+    fn test_typed_links() {
+        use super::LinkDirection;
+
+        setup_logging();
+        let store      = get_store();
+        let mut entry1 = store.create(PathBuf::from("test_typed_links-1")).unwrap();
+        let mut entry2 = store.create(PathBuf::from("test_typed_links-2")).unwrap();
+
+        // The plain link is replaced by the typed ones
+        assert!(entry1.add_internal_link(&mut entry2).is_ok());
+        assert!(entry1.add_internal_typed_link(&mut entry2, String::from("blocks"), true).is_ok());
+        assert!(entry1.add_internal_typed_link(&mut entry2, String::from("relates-to"), false).is_ok());
+
+        let links1 = entry1.get_internal_links().unwrap().collect::<Vec<_>>();
+        let links2 = entry2.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links1.len(), 2);
+        assert_eq!(links2.len(), 2);
+
+        let blocks1 = entry1.get_internal_links_of_type("blocks").unwrap().collect::<Vec<_>>();
+        let blocks2 = entry2.get_internal_links_of_type("blocks").unwrap().collect::<Vec<_>>();
+        assert_eq!(blocks1.len(), 1);
+        assert_eq!(blocks1[0].get_store_id(), entry2.get_location());
+        assert_eq!(blocks1[0].direction(), Some(LinkDirection::Outgoing));
+        assert_eq!(blocks2[0].get_store_id(), entry1.get_location());
+        assert_eq!(blocks2[0].direction(), Some(LinkDirection::Incoming));
+
+        let related = entry2.get_internal_links_of_type("relates-to").unwrap().collect::<Vec<_>>();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].direction(), None);
+
+        assert!(entry2.remove_internal_typed_link(&mut entry1, "blocks").is_ok());
+        assert_eq!(entry1.get_internal_links_of_type("blocks").unwrap().count(), 0);
+        assert_eq!(entry2.get_internal_links_of_type("blocks").unwrap().count(), 0);
+        assert_eq!(entry1.get_internal_links().unwrap().count(), 1);
+        assert_eq!(entry2.get_internal_links().unwrap().count(), 1);
+
+        // The typed link is not duplicated by a plain link
+        assert!(entry1.add_internal_link(&mut entry2).is_ok());
+        assert!(entry2.add_internal_link(&mut entry1).is_ok());
+        assert_eq!(entry1.get_internal_links().unwrap().count(), 1);
+        assert_eq!(entry2.get_internal_links().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_move_links() {
+        use libimagstore::storeid::StoreId;
+        use super::LinkDirection;
+        use super::move_links;
+
+        setup_logging();
+        let store = get_store();
+        {
+            let mut entry1 = store.create(PathBuf::from("test_move_links-1")).unwrap();
+            let mut entry2 = store.create(PathBuf::from("test_move_links-2")).unwrap();
+            let mut entry3 = store.create(PathBuf::from("test_move_links-3")).unwrap();
+            assert!(entry1.add_internal_typed_link(&mut entry2, String::from("blocks"), true).is_ok());
+            assert!(entry3.add_internal_link(&mut entry2).is_ok());
+        }
+
+        let old = StoreId::new_baseless(PathBuf::from("test_move_links-2")).unwrap();
+        let new = StoreId::new_baseless(PathBuf::from("test_move_links-moved")).unwrap();
+        assert!(store.move_by_id(old.clone(), new.clone()).is_ok());
+        assert!(move_links(&store, &old, &new).is_ok());
+
+        let entry1 = store.get(PathBuf::from("test_move_links-1")).unwrap().unwrap();
+        let links  = entry1.get_internal_links_of_type("blocks").unwrap().collect::<Vec<_>>();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].get_store_id(), &new);
+        assert_eq!(links[0].direction(), Some(LinkDirection::Outgoing));
+
+        let entry3 = store.get(PathBuf::from("test_move_links-3")).unwrap().unwrap();
+        let links  = entry3.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].get_store_id(), &new);
+    }
+
+    #[test]
+    fn test_typed_link_header_format() {
+        use toml::Value;
+        use toml_query::read::TomlValueReadExt;
+
+        setup_logging();
+        let store      = get_store();
+        let mut entry1 = store.create(PathBuf::from("test_typed_link_format-1")).unwrap();
+        let mut entry2 = store.create(PathBuf::from("test_typed_link_format-2")).unwrap();
+        assert!(entry1.add_internal_typed_link(&mut entry2, String::from("parent-of"), true).is_ok());
+
+        let links = entry2.get_header().read("links.internal").unwrap().unwrap();
+        match *links {
+            Value::Array(ref links) => {
+                assert_eq!(links.len(), 1);
+                assert_eq!(links[0].get("link"), Some(&Value::String(String::from("test_typed_link_format-1"))));
+                assert_eq!(links[0].get("type"), Some(&Value::String(String::from("parent-of"))));
+                assert_eq!(links[0].get("direction"), Some(&Value::String(String::from("incoming"))));
+            },
+            _ => panic!("Links are not an array"),
+        }
+
+        // Plain links next to typed ones are written as tables, too
+        let mut entry3 = store.create(PathBuf::from("test_typed_link_format-3")).unwrap();
+        assert!(entry2.add_internal_link(&mut entry3).is_ok());
+
+        let links = entry2.get_header().read("links.internal").unwrap().unwrap();
+        match *links {
+            Value::Array(ref links) => {
+                assert_eq!(links.len(), 2);
+                assert!(links.iter().all(|l| is_match!(*l, Value::Table(_))));
+            },
+            _ => panic!("Links are not an array"),
+        }
+
+        let links = entry2.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links.iter().filter(|l| l.kind().is_none()).count(), 1);
+        assert!(entry2.to_str().is_ok());
+    }
+
+    #[test]
+    fn test_link_inconsistencies_repair() {
+        use toml::Value;
+        use toml_query::insert::TomlValueInsertExt;
+        use libimagstore::storeid::StoreId;
+        use super::store_check::LinkInconsistency;
+        use super::store_check::StoreLinkConsistentExt;
+
+        setup_logging();
+        let store = get_store();
+        let id    = |s: &str| StoreId::new_baseless(PathBuf::from(s)).unwrap();
+
+        {
+            let mut entry1 = store.create(PathBuf::from("test_repair-1")).unwrap();
+            let mut entry2 = store.create(PathBuf::from("test_repair-2")).unwrap();
+            assert!(entry1.add_internal_link(&mut entry2).is_ok());
+        }
+        assert_eq!(store.link_inconsistencies().unwrap(), vec![]);
+
+        {
+            // Break the link in one direction and add a link to an entry which does not exist
+            let mut entry1 = store.get(PathBuf::from("test_repair-1")).unwrap().unwrap();
+            let mut entry2 = store.get(PathBuf::from("test_repair-2")).unwrap().unwrap();
+            let links      = vec![Value::String(String::from("test_repair-2")),
+                                  Value::String(String::from("test_repair-3"))];
+            let _ = entry1.get_header_mut().insert("links.internal", Value::Array(links)).unwrap();
+            let _ = entry2.get_header_mut().insert("links.internal", Value::Array(vec![])).unwrap();
+        }
+
+        let found = store.link_inconsistencies().unwrap();
+        assert_eq!(found, vec![
+            LinkInconsistency::Dangling {
+                from: id("test_repair-1"),
+                to: id("test_repair-3"),
+            },
+            LinkInconsistency::OneDirectional {
+                from: id("test_repair-1"),
+                to: id("test_repair-2"),
+            },
+        ]);
+
+        for inconsistency in found {
+            assert!(inconsistency.repair(&store).is_ok());
+        }
+        assert_eq!(store.link_inconsistencies().unwrap(), vec![]);
+
+        let entry1 = store.get(PathBuf::from("test_repair-1")).unwrap().unwrap();
+        let links  = entry1.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links, vec![Link::from(id("test_repair-2"))]);
+    }
+
+    #[test]
+    fn test_link_inconsistencies_repair_typed() {
+        use toml::Value;
+        use toml_query::insert::TomlValueInsertExt;
+        use libimagstore::storeid::StoreId;
+        use super::LinkDirection;
+        use super::store_check::StoreLinkConsistentExt;
+
+        setup_logging();
+        let store = get_store();
+        let id    = |s: &str| StoreId::new_baseless(PathBuf::from(s)).unwrap();
+
+        {
+            let mut entry1 = store.create(PathBuf::from("test_repair_typed-1")).unwrap();
+            let mut entry2 = store.create(PathBuf::from("test_repair_typed-2")).unwrap();
+            assert!(entry1.add_internal_typed_link(&mut entry2, String::from("blocks"), true).is_ok());
+            let _ = entry2.get_header_mut().insert("links.internal", Value::Array(vec![])).unwrap();
+        }
+
+        for inconsistency in store.link_inconsistencies().unwrap() {
+            assert!(inconsistency.repair(&store).is_ok());
+        }
+        assert_eq!(store.link_inconsistencies().unwrap(), vec![]);
+
+        let entry2 = store.get(PathBuf::from("test_repair_typed-2")).unwrap().unwrap();
+        let links  = entry2.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links, vec![Link::Typed {
+            link: id("test_repair_typed-1"),
+            kind: String::from("blocks"),
+            direction: Some(LinkDirection::Incoming),
+        }]);
+    }
+
 }
 
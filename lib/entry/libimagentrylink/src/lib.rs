@@ -40,6 +40,7 @@ extern crate itertools;
 extern crate toml;
 extern crate toml_query;
 extern crate url;
+extern crate serde_json;
 extern crate crypto;
 #[macro_use] extern crate is_match;
 #[macro_use] extern crate error_chain;
@@ -55,5 +56,7 @@ module_entry_path_mod!("links");
 
 pub mod error;
 pub mod external;
+pub mod graph;
 pub mod internal;
+pub mod traversal;
 
@@ -0,0 +1,254 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Queries over the link network
+//!
+//! The traversals follow the internal links of the entries regardless of their direction, as
+//! links are stored on both sides. Links to entries which do not exist are ignored.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use internal::InternalLinker;
+use error::LinkError as LE;
+use error::LinkErrorKind as LEK;
+use error::Result;
+
+pub trait LinkTraversal {
+
+    /// Get all entries within `depth` links of the entry `id`, with their distance to it
+    ///
+    /// The entries are ordered by distance. The entry `id` itself is not part of the result.
+    fn neighborhood(&self, id: StoreId, depth: usize) -> Result<Vec<(StoreId, usize)>>;
+
+    /// Get the shortest chain of links from the entry `from` to the entry `to`
+    ///
+    /// The path contains both `from` and `to`. If the entries are not connected, `None` is
+    /// returned.
+    fn shortest_path(&self, from: StoreId, to: StoreId) -> Result<Option<Vec<StoreId>>>;
+
+    /// Get the groups of entries which are connected by links
+    ///
+    /// Entries without links form a group of their own.
+    fn connected_components(&self) -> Result<Vec<Vec<StoreId>>>;
+
+}
+
+impl LinkTraversal for Store {
+
+    fn neighborhood(&self, id: StoreId, depth: usize) -> Result<Vec<(StoreId, usize)>> {
+        let start         = id.without_base();
+        let mut distances = BTreeMap::new();
+        let mut queue     = VecDeque::new();
+
+        let _ = distances.insert(start.clone(), 0);
+        queue.push_back(start.clone());
+
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[&current];
+            if distance >= depth {
+                continue;
+            }
+
+            for neighbor in linked_entries_of(self, &current)? {
+                if !distances.contains_key(&neighbor) {
+                    let _ = distances.insert(neighbor.clone(), distance + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        let _ = distances.remove(&start);
+        let mut neighborhood = distances.into_iter().collect::<Vec<_>>();
+        neighborhood.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(neighborhood)
+    }
+
+    fn shortest_path(&self, from: StoreId, to: StoreId) -> Result<Option<Vec<StoreId>>> {
+        let from = from.without_base();
+        let to   = to.without_base();
+
+        if self.get(to.clone())?.is_none() {
+            return Err(LE::from_kind(LEK::EntryNotFound(to)));
+        }
+
+        let mut predecessors : BTreeMap<StoreId, Option<StoreId>> = BTreeMap::new();
+        let mut queue        = VecDeque::new();
+        let _ = predecessors.insert(from.clone(), None);
+        queue.push_back(from.clone());
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![current];
+                while let Some(&Some(ref prev)) = predecessors.get(&path[path.len() - 1]) {
+                    path.push(prev.clone());
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+
+            for neighbor in linked_entries_of(self, &current)? {
+                if !predecessors.contains_key(&neighbor) {
+                    let _ = predecessors.insert(neighbor.clone(), Some(current.clone()));
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn connected_components(&self) -> Result<Vec<Vec<StoreId>>> {
+        let ids = self
+            .entries()?
+            .without_store()
+            .map(StoreId::without_base)
+            .collect::<BTreeSet<StoreId>>();
+
+        let mut visited    = BTreeSet::new();
+        let mut components = vec![];
+
+        for id in ids {
+            if visited.contains(&id) {
+                continue;
+            }
+
+            let mut component = BTreeSet::new();
+            let mut queue     = VecDeque::new();
+            let _ = visited.insert(id.clone());
+            queue.push_back(id);
+
+            while let Some(current) = queue.pop_front() {
+                for neighbor in linked_entries_of(self, &current)? {
+                    if visited.insert(neighbor.clone()) {
+                        queue.push_back(neighbor);
+                    }
+                }
+                let _ = component.insert(current);
+            }
+
+            components.push(component.into_iter().collect());
+        }
+
+        Ok(components)
+    }
+
+}
+
+/// Get the ids of the existing entries the entry `id` links to
+fn linked_entries_of(store: &Store, id: &StoreId) -> Result<Vec<StoreId>> {
+    let links = store
+        .get(id.clone())?
+        .ok_or_else(|| LE::from_kind(LEK::EntryNotFound(id.clone())))?
+        .get_internal_links()?;
+
+    links
+        .into_getter(store)
+        .map(|entry| entry.map(|e| e.get_location().clone().without_base()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use libimagstore::store::Store;
+
+    use super::*;
+
+    fn setup_logging() {
+        use env_logger;
+        let _ = env_logger::try_init();
+    }
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    // a -- b -- c -- d, a -- c, e -- f, g
+    fn build_store(store: &Store) {
+        for name in &["a", "b", "c", "d", "e", "f", "g"] {
+            let _ = store.create(PathBuf::from(name)).unwrap();
+        }
+
+        for &(from, to) in &[("a", "b"), ("b", "c"), ("c", "d"), ("a", "c"), ("e", "f")] {
+            let mut from = store.get(PathBuf::from(from)).unwrap().unwrap();
+            let mut to   = store.get(PathBuf::from(to)).unwrap().unwrap();
+            assert!(from.add_internal_link(&mut to).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_neighborhood() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        assert_eq!(store.neighborhood(id("a"), 1).unwrap(), vec![(id("b"), 1), (id("c"), 1)]);
+        assert_eq!(store.neighborhood(id("a"), 2).unwrap(),
+                   vec![(id("b"), 1), (id("c"), 1), (id("d"), 2)]);
+        assert_eq!(store.neighborhood(id("g"), 3).unwrap(), vec![]);
+        assert!(store.neighborhood(id("nonexistent"), 1).is_err());
+
+        // Links to deleted entries are ignored
+        {
+            let mut a    = store.get(PathBuf::from("a")).unwrap().unwrap();
+            let mut gone = store.create(PathBuf::from("0")).unwrap();
+            assert!(a.add_internal_link(&mut gone).is_ok());
+        }
+        assert!(store.delete(PathBuf::from("0")).is_ok());
+        assert_eq!(store.neighborhood(id("a"), 1).unwrap(), vec![(id("b"), 1), (id("c"), 1)]);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        assert_eq!(store.shortest_path(id("a"), id("d")).unwrap(), Some(vec![id("a"), id("c"), id("d")]));
+        assert_eq!(store.shortest_path(id("b"), id("b")).unwrap(), Some(vec![id("b")]));
+        assert_eq!(store.shortest_path(id("a"), id("f")).unwrap(), None);
+        assert!(store.shortest_path(id("a"), id("nonexistent")).is_err());
+    }
+
+    #[test]
+    fn test_connected_components() {
+        setup_logging();
+        let store = get_store();
+        build_store(&store);
+
+        assert_eq!(store.connected_components().unwrap(), vec![
+            vec![id("a"), id("b"), id("c"), id("d")],
+            vec![id("e"), id("f")],
+            vec![id("g")],
+        ]);
+    }
+
+}
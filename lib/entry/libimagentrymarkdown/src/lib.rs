@@ -53,4 +53,5 @@ pub mod error;
 pub mod html;
 pub mod link;
 pub mod processor;
+pub mod rewrite;
 
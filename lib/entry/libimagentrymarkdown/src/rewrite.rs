@@ -0,0 +1,397 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Rewriting markdown links to an entry which was moved
+//!
+//! When an entry is moved, the markdown links in the content of the entries which link to it still
+//! point to the old id. `rewrite_links_to_moved_entry()` rewrites them in all entries which are
+//! linked with the moved entry. `move_entry()` moves an entry and updates both the internal links
+//! and the markdown links to it.
+//!
+//! Inline links (`[text](target)`) and link reference definitions (`[label]: target`) are
+//! rewritten, links in code blocks and code spans are left alone. Links to the old id which are
+//! still found in the content afterwards are reported as not rewritten.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use libimagentrylink::external::is_external_link_storeid;
+use libimagentrylink::internal::InternalLinker;
+use libimagentrylink::internal::move_links;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use error::MarkdownError as ME;
+use error::MarkdownErrorKind as MEK;
+use error::Result;
+use link::extract_links;
+
+/// A link to the moved entry which could not be rewritten
+#[derive(Debug)]
+pub struct UnrewrittenLink {
+    entry: StoreId,
+    link: Option<String>,
+    reason: String,
+}
+
+impl UnrewrittenLink {
+
+    /// The entry containing the link
+    pub fn entry(&self) -> &StoreId {
+        &self.entry
+    }
+
+    /// The target of the link, `None` if the whole entry could not be processed
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_ref().map(String::as_str)
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+}
+
+impl Display for UnrewrittenLink {
+
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self.link {
+            Some(ref link) => write!(fmt, "{}: link to '{}' not rewritten: {}", self.entry, link, self.reason),
+            None           => write!(fmt, "{}: not rewritten: {}", self.entry, self.reason),
+        }
+    }
+
+}
+
+/// What `rewrite_links_to_moved_entry()` did
+#[derive(Debug, Default)]
+pub struct RewriteReport {
+    rewritten: Vec<(StoreId, usize)>,
+    unrewritten: Vec<UnrewrittenLink>,
+}
+
+impl RewriteReport {
+
+    /// The entries whose content was changed, with the number of rewritten links
+    pub fn rewritten(&self) -> &Vec<(StoreId, usize)> {
+        &self.rewritten
+    }
+
+    /// The links which could not be rewritten
+    pub fn unrewritten(&self) -> &Vec<UnrewrittenLink> {
+        &self.unrewritten
+    }
+
+}
+
+/// Move the entry `old` to `new` and let all links to it point to `new`
+///
+/// The internal links of the entries linked with the moved entry are updated, then the markdown
+/// links in their content are rewritten (see `rewrite_links_to_moved_entry()`).
+pub fn move_entry(store: &Store, old: &StoreId, new: &StoreId) -> Result<RewriteReport> {
+    let _ = store.move_by_id(old.clone(), new.clone())?;
+    let _ = move_links(store, old, new)?;
+    rewrite_links_to_moved_entry(store, old, new)
+}
+
+/// Rewrite the markdown links to `old` in the content of all entries linked with the entry `new`
+///
+/// This has to be called after the entry was moved from `old` to `new` and the internal links
+/// were moved with it, as the linked entries are found through the internal links of `new`.
+pub fn rewrite_links_to_moved_entry(store: &Store, old: &StoreId, new: &StoreId) -> Result<RewriteReport> {
+    let old_target = old.clone().without_base().to_str()?;
+    let new_target = new.clone().without_base().to_str()?;
+
+    let linked = store
+        .get(new.clone())?
+        .ok_or_else(|| ME::from_kind(MEK::StoreGetError(new.clone())))?
+        .get_internal_links()?
+        .map(|link| link.get_store_id().clone().without_base())
+        .filter(|id| !is_external_link_storeid(id))
+        .collect::<Vec<StoreId>>();
+
+    let mut report = RewriteReport::default();
+
+    for id in linked {
+        let mut entry = match store.get(id.clone()) {
+            Ok(Some(entry)) => entry,
+            Ok(None)        => continue,
+            Err(e)          => {
+                report.unrewritten.push(UnrewrittenLink { entry: id, link: None, reason: e.to_string() });
+                continue;
+            },
+        };
+
+        let (content, count) = rewrite_link_targets(entry.get_content(), &old_target, &new_target);
+        if count > 0 {
+            debug!("Rewriting {} links in {}", count, id);
+            *entry.get_content_mut() = content;
+
+            if let Err(e) = store.update(&mut entry) {
+                report.unrewritten.push(UnrewrittenLink { entry: id, link: None, reason: e.to_string() });
+                continue;
+            }
+
+            report.rewritten.push((id.clone(), count));
+        }
+
+        for link in extract_links(entry.get_content()) {
+            if link_target_base(&link.link) == old_target {
+                report.unrewritten.push(UnrewrittenLink {
+                    entry: id.clone(),
+                    link: Some(link.link),
+                    reason: String::from("link syntax not supported"),
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Replace the markdown link targets `old` by `new` in `text`
+///
+/// Fragments (`old#section`) are kept. Returns the new text and the number of replaced targets.
+pub fn rewrite_link_targets(text: &str, old: &str, new: &str) -> (String, usize) {
+    let mut count    = 0;
+    let mut in_fence = false;
+
+    let lines = text
+        .split('\n')
+        .map(|line| {
+            let trimmed = line.trim_left();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                return String::from(line);
+            }
+
+            if in_fence || line.starts_with("    ") || line.starts_with('\t') {
+                return String::from(line);
+            }
+
+            if let Some(rewritten) = rewrite_reference_definition(line, old, new) {
+                count += 1;
+                return rewritten;
+            }
+
+            // Every other segment is inside a code span
+            line.split('`')
+                .enumerate()
+                .map(|(i, segment)| if i % 2 == 0 {
+                    rewrite_inline_links(segment, old, new, &mut count)
+                } else {
+                    String::from(segment)
+                })
+                .collect::<Vec<_>>()
+                .join("`")
+        })
+        .collect::<Vec<_>>();
+
+    (lines.join("\n"), count)
+}
+
+/// Rewrite the target of a link reference definition `[label]: target`, if it is `old`
+fn rewrite_reference_definition(line: &str, old: &str, new: &str) -> Option<String> {
+    let trimmed = line.trim_left();
+    if line.len() - trimmed.len() > 3 || !trimmed.starts_with('[') {
+        return None;
+    }
+
+    let close = trimmed.find(']')?;
+    if !trimmed[close..].starts_with("]:") {
+        return None;
+    }
+
+    let start = line.len() - trimmed.len() + close + 2;
+    let (begin, end) = destination(&line[start..])?;
+    let replacement  = rewrite_target(&line[start + begin..start + end], old, new)?;
+
+    Some(format!("{}{}{}", &line[..start + begin], replacement, &line[start + end..]))
+}
+
+/// Rewrite the targets of the inline links `[text](target "title")` in `text` which are `old`
+fn rewrite_inline_links(text: &str, old: &str, new: &str, count: &mut usize) -> String {
+    let mut out  = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find("](") {
+        let (before, after) = rest.split_at(pos + 2);
+        out.push_str(before);
+
+        match destination(after) {
+            Some((begin, end)) => {
+                out.push_str(&after[..begin]);
+                match rewrite_target(&after[begin..end], old, new) {
+                    Some(replacement) => {
+                        *count += 1;
+                        out.push_str(&replacement);
+                    },
+                    None => out.push_str(&after[begin..end]),
+                }
+                rest = &after[end..];
+            },
+            None => rest = after,
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Find the link destination at the beginning of `s`, returns its start and end
+fn destination(s: &str) -> Option<(usize, usize)> {
+    let begin = s.len() - s.trim_left_matches(' ').len();
+    let rest  = &s[begin..];
+
+    if rest.starts_with('<') {
+        rest.find('>').map(|end| (begin + 1, begin + end))
+    } else {
+        let end = rest.find(|c: char| c.is_whitespace() || c == ')').unwrap_or(rest.len());
+        if end == 0 {
+            None
+        } else {
+            Some((begin, begin + end))
+        }
+    }
+}
+
+/// The link target without fragment
+fn link_target_base(target: &str) -> &str {
+    target.split('#').next().unwrap_or(target)
+}
+
+/// Get the rewritten target, if `target` points to `old`
+fn rewrite_target(target: &str, old: &str, new: &str) -> Option<String> {
+    if link_target_base(target) == old {
+        Some(format!("{}{}", new, &target[old.len()..]))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use libimagstore::store::Store;
+    use libimagentrylink::internal::InternalLinker;
+
+    use super::*;
+
+    fn setup_logging() {
+        let _ = ::env_logger::try_init();
+    }
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let fs = InMemoryFileAbstraction::default();
+        Store::new_with_backend(PathBuf::from("/"), &None, Box::new(fs)).unwrap()
+    }
+
+    #[test]
+    fn test_rewrite_link_targets() {
+        let text = r#"A [link](notes/old) and [another](notes/old#section "Title").
+[Not this](notes/older), `[code](notes/old)` and <notes/old>
+
+[ref]: notes/old
+
+```
+[fenced](notes/old)
+```"#;
+
+        let expected = r#"A [link](wiki/new) and [another](wiki/new#section "Title").
+[Not this](notes/older), `[code](notes/old)` and <notes/old>
+
+[ref]: wiki/new
+
+```
+[fenced](notes/old)
+```"#;
+
+        let (rewritten, count) = rewrite_link_targets(text, "notes/old", "wiki/new");
+        assert_eq!(count, 3);
+        assert_eq!(rewritten, expected);
+    }
+
+    #[test]
+    fn test_rewrite_angle_bracket_destination() {
+        let (rewritten, count) = rewrite_link_targets("[a](<notes/old>)", "notes/old", "new");
+        assert_eq!(count, 1);
+        assert_eq!(rewritten, "[a](<new>)");
+    }
+
+    #[test]
+    fn test_rewrite_links_to_moved_entry() {
+        setup_logging();
+        let store = get_store();
+
+        {
+            let mut linking = store.create(PathBuf::from("linking")).unwrap();
+            let mut moved   = store.create(PathBuf::from("old")).unwrap();
+            *linking.get_content_mut() = String::from("See [the entry](old)");
+            assert!(linking.add_internal_link(&mut moved).is_ok());
+        }
+
+        assert!(store.move_by_id(StoreId::new_baseless(PathBuf::from("old")).unwrap(),
+                                 StoreId::new_baseless(PathBuf::from("new")).unwrap()).is_ok());
+
+        {
+            let mut linking = store.get(PathBuf::from("linking")).unwrap().unwrap();
+            let mut moved   = store.get(PathBuf::from("new")).unwrap().unwrap();
+            assert!(moved.add_internal_link(&mut linking).is_ok());
+        }
+
+        let old    = StoreId::new_baseless(PathBuf::from("old")).unwrap();
+        let new    = StoreId::new_baseless(PathBuf::from("new")).unwrap();
+        let report = rewrite_links_to_moved_entry(&store, &old, &new).unwrap();
+
+        assert_eq!(report.rewritten().len(), 1);
+        assert_eq!(report.rewritten()[0].1, 1);
+        assert!(report.unrewritten().is_empty());
+
+        let linking = store.get(PathBuf::from("linking")).unwrap().unwrap();
+        assert_eq!(linking.get_content(), "See [the entry](new)");
+    }
+
+    #[test]
+    fn test_move_entry() {
+        setup_logging();
+        let store = get_store();
+
+        {
+            let mut linking = store.create(PathBuf::from("linking")).unwrap();
+            let mut moved   = store.create(PathBuf::from("old")).unwrap();
+            *linking.get_content_mut() = String::from("See [the entry](old#top)");
+            assert!(linking.add_internal_link(&mut moved).is_ok());
+        }
+
+        let old    = StoreId::new_baseless(PathBuf::from("old")).unwrap();
+        let new    = StoreId::new_baseless(PathBuf::from("new")).unwrap();
+        let report = move_entry(&store, &old, &new).unwrap();
+        assert_eq!(report.rewritten().len(), 1);
+        assert!(report.unrewritten().is_empty());
+
+        let linking = store.get(PathBuf::from("linking")).unwrap().unwrap();
+        assert_eq!(linking.get_content(), "See [the entry](new#top)");
+
+        let links = linking.get_internal_links().unwrap().collect::<Vec<_>>();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].get_store_id(), &new);
+    }
+
+}
@@ -66,6 +66,11 @@ error_chain! {
             display("Path cannot be converted because of UTF8 Error")
         }
 
+        PartialHashUnsupported {
+            description("Generator cannot hash parts of files")
+            display("Generator cannot hash parts of files")
+        }
+
     }
 }
 
@@ -44,6 +44,11 @@
 /// If passed, the new implementation is used (defaults to the implementation from the underlying
 /// generator).
 /// If passed, the new postprocessing is used (defaults to not changing the StoreId)
+/// If passed, the generator name is stored in the header of the refs created with the new
+/// generator (defaults to the name of the underlying generator if no new implementation is passed,
+/// and to no name otherwise)
+/// If passed, the partial hashing implementation is used for
+/// `UniqueRefPathGenerator::unique_hash_n_bytes()` (defaults to failing)
 ///
 #[macro_export]
 macro_rules! make_unique_ref_path_generator {
@@ -66,6 +71,10 @@ macro_rules! make_unique_ref_path_generator {
                 $underlying::unique_hash(path)
             }
 
+            fn name() -> Option<&'static str> {
+                <$underlying as $crate::refstore::UniqueRefPathGenerator>::name()
+            }
+
             fn postprocess_storeid(sid: ::libimagstore::storeid::StoreId)
                 -> Result<::libimagstore::storeid::StoreId, Self::Error>
             {
@@ -103,6 +112,71 @@ macro_rules! make_unique_ref_path_generator {
         }
     };
 
+    (
+        pub $name:ident
+        over $underlying:ty
+        => with error $errtype:ty
+        => with collection name $collectionname:expr
+        => with generator name $generatorname:expr
+        => $impl:expr
+        => with partial hashing $partialimpl:expr
+    ) => {
+        make_unique_ref_path_generator!(
+            @define pub $name,
+            $errtype,
+            $collectionname,
+            Some($generatorname),
+            $impl,
+            |sid| { Ok(sid) },
+            {
+                fn unique_hash_n_bytes<A: AsRef<Path>>(path: A, n: usize)
+                    -> ::std::result::Result<String, Self::Error>
+                {
+                    $partialimpl(path, n)
+                }
+            }
+            );
+    };
+
+    (
+        pub $name:ident
+        over $underlying:ty
+        => with error $errtype:ty
+        => with collection name $collectionname:expr
+        => with generator name $generatorname:expr
+        => $impl:expr
+        => $postproc:expr
+    ) => {
+        make_unique_ref_path_generator!(
+            @define pub $name,
+            $errtype,
+            $collectionname,
+            Some($generatorname),
+            $impl,
+            $postproc,
+            {}
+            );
+    };
+
+    (
+        pub $name:ident
+        over $underlying:ty
+        => with error $errtype:ty
+        => with collection name $collectionname:expr
+        => with generator name $generatorname:expr
+        => $impl:expr
+    ) => {
+        make_unique_ref_path_generator!(
+            @define pub $name,
+            $errtype,
+            $collectionname,
+            Some($generatorname),
+            $impl,
+            |sid| { Ok(sid) },
+            {}
+            );
+    };
+
     (
         pub $name:ident
         over $underlying:ty
@@ -111,11 +185,13 @@ macro_rules! make_unique_ref_path_generator {
         => $impl:expr
     ) => {
         make_unique_ref_path_generator!(
-            pub $name
-            over $underlying
-            => with error $errtype
-            => with collection name $collectionname
-            => $impl => |sid| { Ok(sid) }
+            @define pub $name,
+            $errtype,
+            $collectionname,
+            None,
+            $impl,
+            |sid| { Ok(sid) },
+            {}
             );
     };
 
@@ -126,6 +202,26 @@ macro_rules! make_unique_ref_path_generator {
         => with collection name $collectionname:expr
         => $impl:expr
         => $postproc:expr
+    ) => {
+        make_unique_ref_path_generator!(
+            @define pub $name,
+            $errtype,
+            $collectionname,
+            None,
+            $impl,
+            $postproc,
+            {}
+            );
+    };
+
+    (
+        @define pub $name:ident,
+        $errtype:ty,
+        $collectionname:expr,
+        $generatorname:expr,
+        $impl:expr,
+        $postproc:expr,
+        { $($items:tt)* }
     ) => {
         pub struct $name;
 
@@ -136,6 +232,10 @@ macro_rules! make_unique_ref_path_generator {
                 $collectionname
             }
 
+            fn name() -> Option<&'static str> {
+                $generatorname
+            }
+
             fn unique_hash<A: AsRef<Path>>(path: A) -> ::std::result::Result<String, Self::Error> {
                 debug!("Making unique hash for path: {:?}", path.as_ref());
                 $impl(path)
@@ -146,6 +246,8 @@ macro_rules! make_unique_ref_path_generator {
             {
                 $postproc(sid)
             }
+
+            $($items)*
         }
     };
 }
@@ -181,6 +283,7 @@ macro_rules! make_sha_mod {
                 over generators::base::Base
                 => with error RE
                 => with collection name "ref"
+                => with generator name stringify!($modname)
                 => |path| {
                     OpenOptions::new()
                         .read(true)
@@ -194,6 +297,7 @@ macro_rules! make_sha_mod {
                             $hashingimpl(buffer)
                         })
                 }
+                => with partial hashing |path, n| $hashname::hash_n_bytes(path, n)
             );
 
             impl $hashname {
@@ -39,17 +39,22 @@
 extern crate itertools;
 extern crate toml;
 extern crate toml_query;
+extern crate walkdir;
 
 #[macro_use] extern crate libimagstore;
 extern crate libimagerror;
 #[macro_use] extern crate libimagentryutil;
 #[macro_use] extern crate error_chain;
 
+#[cfg(test)]
+extern crate tempdir;
+
 module_entry_path_mod!("ref");
 
 pub mod error;
 pub mod reference;
 pub mod refstore;
+pub mod relocate;
 
 #[cfg(any(
     feature = "generators-sha1",
@@ -51,12 +51,38 @@ pub trait Ref {
     /// Make this object a ref
     fn make_ref<P: AsRef<Path>>(&mut self, hash: String, path: P) -> Result<()>;
 
+    /// Get the name of the `UniqueRefPathGenerator` the ref was created with
+    ///
+    /// Refs created with a generator without name (or before the name was stored) have none.
+    fn get_generator(&self) -> Result<Option<&str>>;
+
+    /// Store the name of the `UniqueRefPathGenerator` the ref was created with
+    fn set_generator(&mut self, name: &str) -> Result<()>;
+
+    /// Get the number of bytes of the referenced file the hash was calculated over
+    ///
+    /// Refs with a hash over the whole file have none.
+    fn get_partial(&self) -> Result<Option<usize>>;
+
+    /// Store the number of bytes of the referenced file the hash was calculated over
+    fn set_partial(&mut self, n: usize) -> Result<()>;
+
+    /// Check whether the ref was created with the `UniqueRefPathGenerator` `RPG`
+    ///
+    /// Refs which do not know their generator are assumed to be created with `RPG`.
+    fn is_created_with<RPG: UniqueRefPathGenerator>(&self) -> Result<bool> {
+        self.get_generator().map(|g| g.map(|g| Some(g) == RPG::name()).unwrap_or(true))
+    }
+
     /// Get the referenced path.
     ///
     /// Does not need a `UniqueRefPathGenerator` as it reads the path stored in the header.
     fn get_path(&self) -> Result<PathBuf>;
 
     /// Check whether the referenced file still matches its hash
+    ///
+    /// If the hash was calculated over a part of the file only (see `Ref::get_partial()`), the
+    /// same part is hashed.
     fn hash_valid<RPG: UniqueRefPathGenerator>(&self) -> RResult<bool, RPG::Error>;
 
     fn remove_ref(&mut self) -> Result<()>;
@@ -110,6 +136,36 @@ impl Ref for Entry {
         Ok(())
     }
 
+    fn get_generator(&self) -> Result<Option<&str>> {
+        match self.get_header().read("ref.generator").map_err(RE::from)? {
+            Some(&Value::String(ref name)) => Ok(Some(name)),
+            Some(_) => Err(REK::HeaderTypeError("ref.generator", "string").into()),
+            None    => Ok(None),
+        }
+    }
+
+    fn set_generator(&mut self, name: &str) -> Result<()> {
+        self.get_header_mut()
+            .insert("ref.generator", Value::String(String::from(name)))
+            .map(|_| ())
+            .map_err(From::from)
+    }
+
+    fn get_partial(&self) -> Result<Option<usize>> {
+        match self.get_header().read("ref.partial").map_err(RE::from)? {
+            Some(&Value::Integer(n)) if n >= 0 => Ok(Some(n as usize)),
+            Some(_) => Err(REK::HeaderTypeError("ref.partial", "positive integer").into()),
+            None    => Ok(None),
+        }
+    }
+
+    fn set_partial(&mut self, n: usize) -> Result<()> {
+        self.get_header_mut()
+            .insert("ref.partial", Value::Integer(n as i64))
+            .map(|_| ())
+            .map_err(From::from)
+    }
+
     fn get_path(&self) -> Result<PathBuf> {
         self.get_header()
             .read("ref.path")
@@ -120,11 +176,16 @@ impl Ref for Entry {
     }
 
     fn hash_valid<RPG: UniqueRefPathGenerator>(&self) -> RResult<bool, RPG::Error> {
+        let partial = self.get_partial().map_err(RPG::Error::from)?;
+
         self.get_path()
             .map(PathBuf::from)
             .map_err(RE::from)
             .map_err(RPG::Error::from)
-            .and_then(|pb| RPG::unique_hash(pb))
+            .and_then(|pb| match partial {
+                None    => RPG::unique_hash(pb),
+                Some(n) => RPG::unique_hash_n_bytes(pb, n),
+            })
             .and_then(|h| Ok(h == self.get_hash()?))
     }
 
@@ -132,6 +193,9 @@ impl Ref for Entry {
         let hdr = self.get_header_mut();
         let _   = hdr.delete("ref.hash")?;
         let _   = hdr.delete("ref.path")?;
+        let _   = hdr.delete("ref.generator")?;
+        let _   = hdr.delete("ref.partial")?;
+        let _   = hdr.delete("ref.is_ref")?;
         let _   = hdr.delete("ref")?;
         Ok(())
     }
@@ -25,6 +25,7 @@ use libimagstore::store::Store;
 use libimagstore::storeid::StoreId;
 
 use error::RefError as RE;
+use error::RefErrorKind as REK;
 use reference::Ref;
 
 /// A UniqueRefPathGenerator generates unique Pathes
@@ -42,6 +43,21 @@ pub trait UniqueRefPathGenerator {
     /// A function which should generate a unique string for a Path
     fn unique_hash<A: AsRef<Path>>(path: A) -> Result<String, Self::Error>;
 
+    /// Like `unique_hash()`, but only for the first `n` bytes of the file
+    ///
+    /// The default implementation fails, as not every generator can hash parts of files.
+    fn unique_hash_n_bytes<A: AsRef<Path>>(_path: A, _n: usize) -> Result<String, Self::Error> {
+        Err(Self::Error::from(RE::from_kind(REK::PartialHashUnsupported)))
+    }
+
+    /// The name of the generator, which is stored in the header of the refs created with it
+    ///
+    /// A ref can only be verified with the generator it was created with, so the name should be
+    /// set for every generator which can be chosen at runtime.
+    fn name() -> Option<&'static str> {
+        None
+    }
+
     /// Postprocess the generated `StoreId` object
     fn postprocess_storeid(sid: StoreId) -> Result<StoreId, Self::Error> {
         Ok(sid)
@@ -67,7 +83,7 @@ pub trait UniqueRefPathGenerator {
 /// The StoreId which is generated is then used to carry out the actual action (reading, creating
 /// ...).
 /// If a entry is created, header information is set (that it is a ref, the hash which was just
-/// generated and the path of the referenced file)
+/// generated, the path of the referenced file and the name of the generator, if it has one)
 ///
 /// # Details
 ///
@@ -82,6 +98,13 @@ pub trait RefStore<'a> {
 
     fn get_ref<RPG: UniqueRefPathGenerator, H: AsRef<str>>(&'a self, hash: H) -> Result<Option<FileLockEntry<'a>>, RPG::Error>;
     fn create_ref<RPG: UniqueRefPathGenerator, A: AsRef<Path>>(&'a self, path: A) -> Result<FileLockEntry<'a>, RPG::Error>;
+
+    /// Create a ref with a hash which was already calculated with `RPG`
+    ///
+    /// This can be used for hashes over parts of the file, like the `hash_n_bytes()` functions of
+    /// the default generators calculate them. The number of hashed bytes should be stored with
+    /// `Ref::set_partial()` then, so the ref can be verified and relocated later.
+    fn create_ref_with_hash<RPG: UniqueRefPathGenerator, A: AsRef<Path>>(&'a self, path: A, hash: String) -> Result<FileLockEntry<'a>, RPG::Error>;
     fn retrieve_ref<RPG: UniqueRefPathGenerator, A: AsRef<Path>>(&'a self, path: A) -> Result<FileLockEntry<'a>, RPG::Error>;
 
 }
@@ -103,7 +126,13 @@ impl<'a> RefStore<'a> for Store {
     fn create_ref<RPG: UniqueRefPathGenerator, A: AsRef<Path>>(&'a self, path: A)
         -> Result<FileLockEntry<'a>, RPG::Error>
     {
-        let hash     = RPG::unique_hash(&path)?;
+        let hash = RPG::unique_hash(&path)?;
+        self.create_ref_with_hash::<RPG, A>(path, hash)
+    }
+
+    fn create_ref_with_hash<RPG: UniqueRefPathGenerator, A: AsRef<Path>>(&'a self, path: A, hash: String)
+        -> Result<FileLockEntry<'a>, RPG::Error>
+    {
         let pathbuf  = PathBuf::from(format!("{}/{}", RPG::collection(), hash));
         let sid      = StoreId::new_baseless(pathbuf.clone()).map_err(RE::from)?;
 
@@ -112,6 +141,9 @@ impl<'a> RefStore<'a> for Store {
             .map_err(RE::from)
             .and_then(|mut fle| {
                 fle.make_ref(hash, path)?;
+                if let Some(name) = RPG::name() {
+                    fle.set_generator(name)?;
+                }
                 Ok(fle)
             })
             .map_err(RPG::Error::from)
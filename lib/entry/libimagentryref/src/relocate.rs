@@ -0,0 +1,329 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Finding the files of dangling refs again
+//!
+//! A ref stores the hash of the referenced file, so if the file was moved, it can be found again
+//! by hashing candidate files with the same `UniqueRefPathGenerator` the ref was created with.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use walkdir::WalkDir;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagerror::trace::trace_error;
+
+use error::RefError as RE;
+use reference::Ref;
+use refstore::UniqueRefPathGenerator;
+
+/// The outcome of `relocate_dangling_refs()`
+#[derive(Debug, Default)]
+pub struct RelocationReport {
+    relocated: Vec<(StoreId, PathBuf, PathBuf)>,
+    unresolved: Vec<(StoreId, PathBuf)>,
+}
+
+impl RelocationReport {
+
+    /// The refs which were updated, with their old and their new path
+    pub fn relocated(&self) -> &Vec<(StoreId, PathBuf, PathBuf)> {
+        &self.relocated
+    }
+
+    /// The refs which are still dangling, with their (old) path
+    pub fn unresolved(&self) -> &Vec<(StoreId, PathBuf)> {
+        &self.unresolved
+    }
+
+}
+
+/// Search the `roots` for the files of all dangling refs in the collection of the
+/// `UniqueRefPathGenerator` and update the path of the refs whose hash matches a file.
+///
+/// Refs with a hash over a part of the file only (see `Ref::get_partial()`) are compared with the
+/// hash over the same part of the candidate files.
+/// The roots are searched recursively, symlinks are not followed. Files which cannot be hashed
+/// (for example because they cannot be read) are skipped. Refs which were created with another
+/// generator are skipped as well (see `Ref::is_created_with()`). Refs which cannot be written
+/// after their file was found are reported as unresolved.
+pub fn relocate_dangling_refs<RPG, P>(store: &Store, roots: &[P]) -> Result<RelocationReport, RPG::Error>
+    where RPG: UniqueRefPathGenerator,
+          P: AsRef<Path>
+{
+    // (hashed bytes, hash) -> (id, old path) of all dangling refs
+    let mut dangling : BTreeMap<(Option<usize>, String), Vec<(StoreId, PathBuf)>> = BTreeMap::new();
+
+    for id in store.entries().map_err(RE::from)?.without_store() {
+        if !id.is_in_collection(&[RPG::collection()]) {
+            continue;
+        }
+
+        let entry = match store.get(id.clone()).map_err(RE::from)? {
+            Some(entry) => entry,
+            None        => continue,
+        };
+
+        if !entry.is_ref()? || !entry.is_created_with::<RPG>()? || !entry.is_dangling()? {
+            continue;
+        }
+
+        debug!("Dangling ref: {}", id);
+        dangling
+            .entry((entry.get_partial()?, String::from(entry.get_hash()?)))
+            .or_insert_with(Vec::new)
+            .push((id, entry.get_path()?));
+    }
+
+    let mut report = RelocationReport::default();
+
+    for root in roots {
+        if dangling.is_empty() {
+            break;
+        }
+
+        let root = root.as_ref().canonicalize().map_err(RE::from)?;
+        let files = WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file());
+
+        for file in files {
+            let partials : BTreeSet<Option<usize>> = dangling.keys().map(|&(p, _)| p).collect();
+
+            for partial in partials {
+                let hash = match partial {
+                    None    => RPG::unique_hash(file.path()),
+                    Some(n) => RPG::unique_hash_n_bytes(file.path(), n),
+                };
+
+                let hash = match hash {
+                    Ok(hash) => hash,
+                    Err(_)   => {
+                        debug!("Cannot hash {}, skipping", file.path().display());
+                        continue
+                    },
+                };
+
+                if let Some(refs) = dangling.remove(&(partial, hash.clone())) {
+                    for (id, old) in refs {
+                        let mut entry = match store.get(id.clone()).map_err(RE::from)? {
+                            Some(entry) => entry,
+                            None        => continue,
+                        };
+
+                        debug!("Relocating {}: {} -> {}", id, old.display(), file.path().display());
+                        let written = entry
+                            .make_ref(hash.clone(), file.path())
+                            .and_then(|_| store.update(&mut entry).map_err(RE::from));
+
+                        match written {
+                            Ok(()) => report.relocated.push((id, old, file.path().to_path_buf())),
+                            Err(e) => {
+                                trace_error(&e);
+                                report.unresolved.push((id, old));
+                            },
+                        }
+                    }
+                }
+            }
+
+            if dangling.is_empty() {
+                break;
+            }
+        }
+    }
+
+    report.unresolved.extend(dangling.into_iter().flat_map(|(_, refs)| refs));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::fs::OpenOptions;
+    use std::fs::create_dir;
+    use std::io::Read;
+    use std::io::Write;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+    use toml_query::read::TomlValueReadExt;
+
+    use libimagstore::store::Store;
+
+    use error::RefError as RE;
+    use reference::Ref;
+    use refstore::RefStore;
+    use refstore::UniqueRefPathGenerator;
+
+    use super::*;
+
+    /// Uses the content of the file as hash
+    struct ContentGenerator;
+
+    impl UniqueRefPathGenerator for ContentGenerator {
+        type Error = RE;
+
+        fn unique_hash<A: AsRef<Path>>(path: A) -> Result<String, Self::Error> {
+            let mut s = String::new();
+            let _ = OpenOptions::new().read(true).open(path)?.read_to_string(&mut s)?;
+            Ok(s)
+        }
+
+        fn unique_hash_n_bytes<A: AsRef<Path>>(path: A, n: usize) -> Result<String, Self::Error> {
+            ContentGenerator::unique_hash(path).map(|s| s.chars().take(n).collect())
+        }
+    }
+
+    /// Like `ContentGenerator`, but with a name
+    struct NamedContentGenerator;
+
+    impl UniqueRefPathGenerator for NamedContentGenerator {
+        type Error = RE;
+
+        fn unique_hash<A: AsRef<Path>>(path: A) -> Result<String, Self::Error> {
+            ContentGenerator::unique_hash(path)
+        }
+
+        fn name() -> Option<&'static str> {
+            Some("content")
+        }
+    }
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn write_file(path: &Path, content: &str) {
+        let _ = File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_relocate_dangling_refs() {
+        let store = get_store();
+        let dir   = TempDir::new("imag-relocate").unwrap();
+        let root  = dir.path().canonicalize().unwrap();
+
+        let old_moved = root.join("moved");
+        let old_gone  = root.join("gone");
+        let old_kept  = root.join("kept");
+        write_file(&old_moved, "moved");
+        write_file(&old_gone, "gone");
+        write_file(&old_kept, "kept");
+
+        let moved_id = store.create_ref::<ContentGenerator, _>(&old_moved).unwrap().get_location().clone();
+        let gone_id  = store.create_ref::<ContentGenerator, _>(&old_gone).unwrap().get_location().clone();
+        let kept_id  = store.create_ref::<ContentGenerator, _>(&old_kept).unwrap().get_location().clone();
+
+        let new_moved = root.join("sub").join("moved-here");
+        create_dir(root.join("sub")).unwrap();
+        ::std::fs::rename(&old_moved, &new_moved).unwrap();
+        ::std::fs::remove_file(&old_gone).unwrap();
+
+        let report = relocate_dangling_refs::<ContentGenerator, _>(&store, &[&root]).unwrap();
+
+        assert_eq!(report.relocated(), &vec![(moved_id.clone(), old_moved, new_moved.clone())]);
+        assert_eq!(report.unresolved(), &vec![(gone_id.clone(), old_gone.clone())]);
+
+        let moved = store.get(moved_id).unwrap().unwrap();
+        assert_eq!(moved.get_path().unwrap(), new_moved);
+        assert!(!moved.is_dangling().unwrap());
+
+        assert_eq!(store.get(gone_id).unwrap().unwrap().get_path().unwrap(), old_gone);
+        assert_eq!(store.get(kept_id).unwrap().unwrap().get_path().unwrap(), old_kept);
+    }
+
+    #[test]
+    fn test_relocate_partial_refs() {
+        let store = get_store();
+        let dir   = TempDir::new("imag-relocate").unwrap();
+        let root  = dir.path().canonicalize().unwrap();
+
+        let old = root.join("old");
+        let new = root.join("new");
+        write_file(&old, "head-old-tail");
+
+        let id = {
+            let hash      = ContentGenerator::unique_hash_n_bytes(&old, 4).unwrap();
+            let mut entry = store.create_ref_with_hash::<ContentGenerator, _>(&old, hash).unwrap();
+            assert!(entry.set_partial(4).is_ok());
+            assert!(store.update(&mut entry).is_ok());
+            assert!(entry.hash_valid::<ContentGenerator>().unwrap());
+            entry.get_location().clone()
+        };
+
+        ::std::fs::remove_file(&old).unwrap();
+        write_file(&new, "head-new-tail");
+
+        let report = relocate_dangling_refs::<ContentGenerator, _>(&store, &[&root]).unwrap();
+        assert_eq!(report.relocated(), &vec![(id.clone(), old, new.clone())]);
+
+        let mut entry = store.get(id).unwrap().unwrap();
+        assert_eq!(entry.get_path().unwrap(), new);
+        assert_eq!(entry.get_partial().unwrap(), Some(4));
+        assert!(entry.hash_valid::<ContentGenerator>().unwrap());
+
+        assert!(entry.remove_ref().is_ok());
+        assert!(entry.get_header().read("ref").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_relocate_skips_refs_of_other_generators() {
+        let store = get_store();
+        let dir   = TempDir::new("imag-relocate").unwrap();
+        let root  = dir.path().canonicalize().unwrap();
+
+        let old = root.join("old");
+        let new = root.join("new");
+        write_file(&old, "named");
+
+        let id = {
+            let entry = store.create_ref::<NamedContentGenerator, _>(&old).unwrap();
+            assert_eq!(entry.get_generator().unwrap(), Some("content"));
+            assert!(entry.is_created_with::<NamedContentGenerator>().unwrap());
+            assert!(!entry.is_created_with::<ContentGenerator>().unwrap());
+            entry.get_location().clone()
+        };
+        ::std::fs::rename(&old, &new).unwrap();
+
+        let report = relocate_dangling_refs::<ContentGenerator, _>(&store, &[&root]).unwrap();
+        assert!(report.relocated().is_empty());
+        assert!(report.unresolved().is_empty());
+
+        let report = relocate_dangling_refs::<NamedContentGenerator, _>(&store, &[&root]).unwrap();
+        assert_eq!(report.relocated(), &vec![(id.clone(), old, new.clone())]);
+
+        let mut entry = store.get(id).unwrap().unwrap();
+        assert_eq!(entry.get_path().unwrap(), new);
+        assert_eq!(entry.get_generator().unwrap(), Some("content"));
+
+        assert!(entry.remove_ref().is_ok());
+        assert!(entry.get_header().read("ref").unwrap().is_none());
+    }
+
+}
@@ -127,9 +127,13 @@ impl Tagable for Value {
     }
 
     fn has_tag(&self, t: TagSlice) -> Result<bool> {
-        let tags = self.read("tag.values").chain_err(|| TagErrorKind::HeaderReadError)?;
+        let tags = match self.read("tag.values").chain_err(|| TagErrorKind::HeaderReadError)? {
+            None                          => return Ok(false),
+            Some(&Value::Array(ref tags)) => tags,
+            Some(_)                       => return Err(TagErrorKind::TagTypeError.into()),
+        };
 
-        if !tags.iter().all(|t| is_match!(*t, &Value::String(_))) {
+        if !tags.iter().all(|t| is_match!(*t, Value::String(_))) {
             return Err(TagErrorKind::TagTypeError.into());
         }
 
@@ -137,7 +141,7 @@ impl Tagable for Value {
            .iter()
            .any(|tag| {
                match *tag {
-                   &Value::String(ref s) => { s == t },
+                   Value::String(ref s) => { s == t },
                    _ => unreachable!()
                }
            }))
@@ -182,3 +186,26 @@ impl Tagable for Entry {
 
 }
 
+#[cfg(test)]
+mod tests {
+    use toml::de::from_str as toml_from_str;
+    use toml::Value;
+
+    use super::Tagable;
+
+    #[test]
+    fn test_has_tag() {
+        let header : Value = toml_from_str(r#"
+        [tag]
+        values = [ "work", "home" ]
+        "#).unwrap();
+
+        assert!(header.has_tag(&String::from("work")).unwrap());
+        assert!(!header.has_tag(&String::from("garden")).unwrap());
+        assert!(header.has_tags(&[String::from("work"), String::from("home")]).unwrap());
+
+        let empty : Value = toml_from_str("[imag]").unwrap();
+        assert!(!empty.has_tag(&String::from("work")).unwrap());
+    }
+
+}
@@ -0,0 +1,209 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::PathBuf;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::external::is_external_link_storeid;
+use libimagentrylink::external::orphaned_external_links;
+use libimagentrylink::internal::store_check::LinkInconsistency;
+use libimagentrylink::internal::store_check::StoreLinkConsistentExt;
+use libimagentryannotation::annotation_fetcher::AnnotationFetcher;
+use libimagentryref::reference::Ref;
+
+use error::RepairError as RE;
+use error::RepairErrorKind as REK;
+use error::Result;
+
+/// An inconsistency in the store
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Link(LinkInconsistency),
+    OrphanedExternalLink(StoreId),
+    OrphanedAnnotation(StoreId),
+    DanglingRef(StoreId, PathBuf),
+}
+
+impl Problem {
+
+    /// Describe what `repair()` does about the problem
+    pub fn action(&self) -> &'static str {
+        match *self {
+            Problem::Link(LinkInconsistency::Dangling { .. })          => "remove the link",
+            Problem::Link(LinkInconsistency::OneDirectional { ref from, .. }) => {
+                if is_external_link_storeid(from) {
+                    "remove the link"
+                } else {
+                    "link back"
+                }
+            },
+            Problem::OrphanedExternalLink(_) |
+            Problem::OrphanedAnnotation(_)                             => "delete the entry",
+            Problem::DanglingRef(..)                                   => "remove the ref from the entry",
+        }
+    }
+
+    /// Whether repairing the problem drops information which cannot be found again
+    ///
+    /// This is the case for dangling refs: the file may only be on a disk which is not mounted,
+    /// or a mail may have been moved by the mail client. Such problems should only be repaired
+    /// on explicit request.
+    pub fn is_lossy(&self) -> bool {
+        match *self {
+            Problem::DanglingRef(..) => true,
+            _                        => false,
+        }
+    }
+
+    /// Repair the problem
+    ///
+    /// Dangling refs are removed from their entries, the entries themselves are kept. Files which
+    /// were only moved can be found again with `imag ref relocate` before.
+    pub fn repair(&self, store: &Store) -> Result<()> {
+        match *self {
+            Problem::Link(ref inconsistency)    => inconsistency.repair(store).map_err(RE::from),
+            Problem::OrphanedExternalLink(ref id) |
+            Problem::OrphanedAnnotation(ref id) => store.delete(id.clone()).map_err(RE::from),
+            Problem::DanglingRef(ref id, _)     => {
+                let mut entry = store
+                    .get(id.clone())?
+                    .ok_or_else(|| RE::from_kind(REK::EntryNotFound(id.clone())))?;
+
+                entry.remove_ref().map_err(RE::from)
+            },
+        }
+    }
+
+}
+
+impl Display for Problem {
+
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match *self {
+            Problem::Link(ref inconsistency)      => write!(fmt, "{}", inconsistency),
+            Problem::OrphanedExternalLink(ref id) =>
+                write!(fmt, "{} is an external link which is not linked with any entry", id),
+            Problem::OrphanedAnnotation(ref id)   =>
+                write!(fmt, "{} is an annotation which does not annotate any entry", id),
+            Problem::DanglingRef(ref id, ref path) =>
+                write!(fmt, "{} refers to {}, which does not exist", id, path.display()),
+        }
+    }
+
+}
+
+/// Find all inconsistencies in the store, in the order in which they should be repaired
+///
+/// Orphaned entries are not linked with any existing entry, so deleting them does not introduce
+/// new inconsistencies. The dangling links of orphaned entries are not reported, as they are
+/// removed together with the entries.
+pub fn find_problems(store: &Store) -> Result<Vec<Problem>> {
+    let external    = orphaned_external_links(store)?;
+    let annotations = store.orphaned_annotations()?;
+    let orphaned    = external.iter().chain(annotations.iter()).collect::<BTreeSet<_>>();
+
+    let links = store
+        .link_inconsistencies()?
+        .into_iter()
+        .filter(|inconsistency| match *inconsistency {
+            LinkInconsistency::Dangling { ref from, .. } => !orphaned.contains(from),
+            LinkInconsistency::OneDirectional { .. }     => true,
+        })
+        .map(Problem::Link);
+
+    let mut problems = links
+        .chain(external.iter().cloned().map(Problem::OrphanedExternalLink))
+        .chain(annotations.iter().cloned().map(Problem::OrphanedAnnotation))
+        .collect::<Vec<_>>();
+
+    for id in store.entries()?.without_store() {
+        let entry = store.get_copy(id.clone())?;
+
+        // Entries without the "ref.is_ref" flag are no refs
+        if entry.is_ref().unwrap_or(false) && entry.is_dangling()? {
+            let path = entry.get_path()?;
+            problems.push(Problem::DanglingRef(id.without_base(), path));
+        }
+    }
+
+    debug!("Found {} inconsistencies", problems.len());
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use toml_query::read::TomlValueReadExt;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+    use super::*;
+
+    fn get_store() -> Store {
+        use libimagstore::file_abstraction::InMemoryFileAbstraction;
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new_baseless(PathBuf::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_find_and_repair_problems() {
+        let store = get_store();
+
+        {
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            let links     = Value::Array(vec![Value::String(String::from("b"))]);
+            let _         = entry.get_header_mut().insert("links.internal", links).unwrap();
+
+            let mut entry = store.create(PathBuf::from("ref")).unwrap();
+            let _         = entry.make_ref(String::from("hash"), "/nonexistent/file").unwrap();
+        }
+
+        let expected = vec![
+            Problem::Link(LinkInconsistency::Dangling { from: id("a"), to: id("b") }),
+            Problem::DanglingRef(id("ref"), PathBuf::from("/nonexistent/file")),
+        ];
+
+        // Only removing the ref loses information
+        assert!(!expected[0].is_lossy());
+        assert!(expected[1].is_lossy());
+
+        // Finding the problems does not change anything, so it can be used for a dry run
+        assert_eq!(find_problems(&store).unwrap(), expected);
+        assert_eq!(find_problems(&store).unwrap(), expected);
+
+        for problem in find_problems(&store).unwrap() {
+            assert!(problem.repair(&store).is_ok());
+        }
+
+        assert_eq!(find_problems(&store).unwrap(), vec![]);
+        let entry = store.get(PathBuf::from("ref")).unwrap().unwrap();
+        assert!(entry.get_header().read("ref").unwrap().is_none());
+    }
+
+}
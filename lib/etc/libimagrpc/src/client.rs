@@ -0,0 +1,294 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The requesting side of the protocol.
+//!
+//! Modules which want to use a running `imagd` instead of opening the store themselves use
+//! `DaemonClient::from_runtime()`, which only connects if the daemon is enabled in the
+//! configuration:
+//!
+//! ```toml
+//! [imagd]
+//! enabled = true
+//! socket  = "/path/to/imagd.sock" # optional, defaults to <rtp>/imagd.sock
+//! ```
+
+use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[cfg(unix)] use std::io::BufReader;
+#[cfg(unix)] use std::os::unix::net::UnixStream;
+#[cfg(unix)] use std::path::Path;
+
+use serde_json;
+use serde_json::Value;
+use toml;
+use toml_query::read::TomlValueReadExt;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::storeid::StoreId;
+
+use error::RpcErrorKind as REK;
+use error::Result;
+use protocol::*;
+
+/// Get the path of the socket `imagd` listens on
+///
+/// This is `imagd.socket` from the configuration or `imagd.sock` in the runtime path.
+pub fn socket_path(rt: &Runtime) -> Result<PathBuf> {
+    match rt.config() {
+        None      => Ok(None),
+        Some(cfg) => match cfg.read("imagd.socket")? {
+            None                              => Ok(None),
+            Some(&toml::Value::String(ref s)) => Ok(Some(PathBuf::from(s))),
+            Some(_)                           => Err(REK::ConfigTypeError("imagd.socket", "String").into()),
+        },
+    }
+    .map(|path| path.unwrap_or_else(|| rt.rtp().join("imagd.sock")))
+}
+
+/// Check whether the modules should talk to `imagd`, which is `imagd.enabled` in the configuration
+pub fn daemon_enabled(rt: &Runtime) -> Result<bool> {
+    match rt.config() {
+        None      => Ok(false),
+        Some(cfg) => match cfg.read("imagd.enabled")? {
+            None                          => Ok(false),
+            Some(&toml::Value::Boolean(b)) => Ok(b),
+            Some(_)                       => Err(REK::ConfigTypeError("imagd.enabled", "Boolean").into()),
+        },
+    }
+}
+
+/// A client connected to `imagd` via its socket
+#[cfg(unix)]
+pub type DaemonClient = Client<BufReader<UnixStream>, UnixStream>;
+
+/// `imagd` is not available without unix domain sockets, so this client is never connected
+#[cfg(not(unix))]
+pub type DaemonClient = Client<::std::io::Empty, ::std::io::Sink>;
+
+pub struct Client<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    next_id: u64,
+}
+
+#[cfg(unix)]
+impl DaemonClient {
+
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Client::new(reader, stream))
+    }
+
+    /// Connect to `imagd` if it is enabled in the configuration
+    ///
+    /// If the daemon is enabled but cannot be reached, a warning is logged and `None` is returned,
+    /// so the caller falls back to its own store. A caller which set up its runtime without the
+    /// store (`Runtime::new_without_store()`) has to open it then.
+    pub fn from_runtime(rt: &Runtime) -> Result<Option<Self>> {
+        if !daemon_enabled(rt)? {
+            return Ok(None);
+        }
+
+        let path = socket_path(rt)?;
+        match Client::connect(&path) {
+            Ok(client) => {
+                debug!("Connected to imagd at {}", path.display());
+                Ok(Some(client))
+            },
+            Err(e) => {
+                warn!("Cannot connect to imagd at {}: {}", path.display(), e);
+                warn!("Using the store directly");
+                Ok(None)
+            },
+        }
+    }
+
+}
+
+#[cfg(not(unix))]
+impl DaemonClient {
+
+    /// `imagd` is not available on this platform, so this always returns `None`
+    pub fn from_runtime(rt: &Runtime) -> Result<Option<Self>> {
+        if daemon_enabled(rt)? {
+            warn!("imagd is only available on unix, using the store directly");
+        }
+
+        Ok(None)
+    }
+
+}
+
+impl<R: BufRead, W: Write> Client<R, W> {
+
+    pub fn new(reader: R, writer: W) -> Client<R, W> {
+        Client {
+            reader: reader,
+            writer: writer,
+            next_id: 0,
+        }
+    }
+
+    /// Send a request and wait for its response
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let request = Request::new(self.next_id, method, params);
+        let _ = writeln!(self.writer, "{}", serde_json::to_string(&request)?)?;
+        let _ = self.writer.flush()?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(REK::ConnectionClosed.into());
+        }
+
+        let response: Response = serde_json::from_str(&line)?;
+        if response.id != Value::from(self.next_id) {
+            return Err(REK::UnexpectedResponse.into());
+        }
+
+        match response.error {
+            Some(e) => Err(REK::RemoteError(e.code, e.message).into()),
+            None    => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    pub fn get(&mut self, id: &StoreId) -> Result<Option<RemoteEntry>> {
+        let params = json!({ "id": id_str(id)? });
+        self.call("get", params)
+            .and_then(|v| serde_json::from_value(v).map_err(From::from))
+    }
+
+    pub fn create(&mut self, id: &StoreId, header: Option<toml::Value>, content: Option<String>)
+        -> Result<RemoteEntry>
+    {
+        let params = json!({ "id": id_str(id)?, "header": header, "content": content });
+        self.call("create", params)
+            .and_then(|v| serde_json::from_value(v).map_err(From::from))
+    }
+
+    pub fn update(&mut self, id: &StoreId, header: Option<toml::Value>, content: Option<String>)
+        -> Result<RemoteEntry>
+    {
+        let params = json!({ "id": id_str(id)?, "header": header, "content": content });
+        self.call("update", params)
+            .and_then(|v| serde_json::from_value(v).map_err(From::from))
+    }
+
+    pub fn delete(&mut self, id: &StoreId) -> Result<()> {
+        let params = json!({ "id": id_str(id)? });
+        self.call("delete", params).map(|_| ())
+    }
+
+    /// Get the ids of all entries, optionally only the ones in `collection`
+    ///
+    /// The returned ids have no store path attached.
+    pub fn entries(&mut self, collection: Option<&str>) -> Result<Vec<StoreId>> {
+        let params = json!({ "collection": collection });
+        self.call("entries", params)
+            .and_then(|v| serde_json::from_value::<Vec<String>>(v).map_err(From::from))?
+            .into_iter()
+            .map(|id| StoreId::new_baseless(PathBuf::from(id)).map_err(From::from))
+            .collect()
+    }
+
+    pub fn link(&mut self, from: &StoreId, to: &StoreId) -> Result<()> {
+        let params = json!({ "from": id_str(from)?, "to": id_str(to)? });
+        self.call("link", params).map(|_| ())
+    }
+
+    /// Add and remove tags, returns the tags the entry has afterwards
+    pub fn tag(&mut self, id: &StoreId, add: &[String], remove: &[String]) -> Result<Vec<String>> {
+        let params = json!({ "id": id_str(id)?, "add": add, "remove": remove });
+        self.call("tag", params)
+            .and_then(|v| serde_json::from_value(v).map_err(From::from))
+    }
+
+}
+
+fn id_str(id: &StoreId) -> Result<String> {
+    id.clone().without_base().to_str().map_err(From::from)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::io::BufReader;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::thread;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+    use libimagstore::file_abstraction::InMemoryFileAbstraction;
+
+    use error::RpcErrorKind as REK;
+    use server::Server;
+    use super::Client;
+
+    #[test]
+    fn test_client_roundtrip() {
+        let (client_side, server_side) = UnixStream::pair().unwrap();
+
+        let server = thread::spawn(move || {
+            let backend = Box::new(InMemoryFileAbstraction::default());
+            let store   = Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap();
+            let reader  = BufReader::new(server_side.try_clone().unwrap());
+            Server::new(&store).serve(reader, server_side).unwrap();
+        });
+
+        {
+            let reader     = BufReader::new(client_side.try_clone().unwrap());
+            let mut client = Client::new(reader, client_side);
+
+            let a = StoreId::new_baseless(PathBuf::from("test/a")).unwrap();
+            let b = StoreId::new_baseless(PathBuf::from("test/b")).unwrap();
+
+            let entry = client.create(&a, None, Some(String::from("content"))).unwrap();
+            assert_eq!(entry.id, "test/a");
+            let _ = client.create(&b, None, None).unwrap();
+
+            let entry = client.get(&a).unwrap().unwrap().into_entry().unwrap();
+            assert_eq!(entry.get_content(), "content");
+
+            client.link(&a, &b).unwrap();
+            let tags = client.tag(&a, &[String::from("foo")], &[]).unwrap();
+            assert_eq!(tags, vec![String::from("foo")]);
+
+            let mut ids = client.entries(Some("test")).unwrap();
+            ids.sort();
+            assert_eq!(ids, vec![a.clone(), b.clone()]);
+
+            client.delete(&b).unwrap();
+            assert!(client.get(&b).unwrap().is_none());
+
+            match client.link(&a, &b).unwrap_err().kind() {
+                &REK::RemoteError(code, _) => assert_eq!(code, ::protocol::ENTRY_NOT_FOUND),
+                other => panic!("Unexpected error: {:?}", other),
+            }
+        }
+
+        server.join().unwrap();
+    }
+
+}
+
@@ -0,0 +1,59 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+error_chain! {
+    types {
+        RpcError, RpcErrorKind, ResultExt, Result;
+    }
+
+    links {
+        StoreError(::libimagstore::error::StoreError, ::libimagstore::error::StoreErrorKind);
+    }
+
+    foreign_links {
+        IO(::std::io::Error);
+        JsonError(::serde_json::Error);
+        TomlQueryError(::toml_query::error::Error);
+    }
+
+    errors {
+        ConnectionClosed {
+            description("Connection closed by imagd")
+            display("Connection closed by imagd")
+        }
+
+        UnexpectedResponse {
+            description("Unexpected response from imagd")
+            display("Unexpected response from imagd")
+        }
+
+        RemoteError(code: i64, message: String) {
+            description("imagd returned an error")
+            display("imagd returned an error ({}): {}", code, message)
+        }
+
+        ConfigTypeError(path: &'static str, should_be_type: &'static str) {
+            description("Type error in configuration")
+            display("Type error at '{}', should be '{}'", path, should_be_type)
+        }
+
+    }
+}
+
@@ -0,0 +1,66 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The protocol `imagd` speaks on its socket.
+//!
+//! `imagd` keeps one `Store` open and answers requests from other processes. Requests and
+//! responses are JSON-RPC 2.0 objects, one per line. The `server` module implements the
+//! answering side on top of a `Store`, the `client` module the requesting side.
+
+#![recursion_limit="256"]
+
+#![deny(
+    dead_code,
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+extern crate toml;
+extern crate toml_query;
+#[macro_use] extern crate error_chain;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+#[macro_use] extern crate serde_json;
+
+extern crate libimagstore;
+extern crate libimagerror;
+extern crate libimagrt;
+extern crate libimagentrylink;
+extern crate libimagentrytag;
+
+#[cfg(test)]
+extern crate tempdir;
+
+pub mod client;
+pub mod error;
+pub mod protocol;
+pub mod server;
+
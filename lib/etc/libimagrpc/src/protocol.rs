@@ -0,0 +1,167 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Types which are sent over the `imagd` socket.
+//!
+//! Each message is a JSON-RPC 2.0 object on a single line. The methods `imagd` understands are:
+//!
+//! | Method    | Parameters                          | Result                   |
+//! | --------- | ----------------------------------- | ------------------------ |
+//! | `get`     | `{"id"}`                            | the entry or `null`      |
+//! | `create`  | `{"id", "header"?, "content"?}`     | the created entry        |
+//! | `update`  | `{"id", "header"?, "content"?}`     | the updated entry        |
+//! | `delete`  | `{"id"}`                            | `null`                   |
+//! | `entries` | `{"collection"?}`                   | list of ids              |
+//! | `link`    | `{"from", "to"}`                    | `null`                   |
+//! | `tag`     | `{"id", "add"?, "remove"?}`         | the tags of the entry    |
+//!
+//! Ids are store ids relative to the store root. A `header` is merged into the header of the
+//! entry section by section.
+
+use serde_json::Value;
+use toml;
+
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+
+use error::Result;
+
+pub const JSONRPC_VERSION : &'static str = "2.0";
+
+pub const PARSE_ERROR      : i64 = -32700;
+pub const INVALID_REQUEST  : i64 = -32600;
+pub const METHOD_NOT_FOUND : i64 = -32601;
+pub const INVALID_PARAMS   : i64 = -32602;
+pub const INTERNAL_ERROR   : i64 = -32603;
+
+/// Error while executing the request on the store
+pub const STORE_ERROR      : i64 = -32000;
+
+/// The entry the request refers to does not exist
+pub const ENTRY_NOT_FOUND  : i64 = -32001;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    pub jsonrpc: String,
+
+    /// Requests without id are notifications and do not get a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+
+    pub method: String,
+
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl Request {
+
+    pub fn new<S: Into<String>>(id: u64, method: S, params: Value) -> Request {
+        Request {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id: Some(Value::from(id)),
+            method: method.into(),
+            params: params,
+        }
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub jsonrpc: String,
+
+    /// `null` if the id of the request could not be determined
+    pub id: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+}
+
+impl Response {
+
+    pub fn result(id: Value, result: Value) -> Response {
+        Response {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id: id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Value, error: ErrorObject) -> Response {
+        Response {
+            jsonrpc: String::from(JSONRPC_VERSION),
+            id: id,
+            result: None,
+            error: Some(error),
+        }
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorObject {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ErrorObject {
+
+    pub fn new<S: Into<String>>(code: i64, message: S) -> ErrorObject {
+        ErrorObject {
+            code: code,
+            message: message.into(),
+        }
+    }
+
+}
+
+/// An entry as it is transferred over the socket
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RemoteEntry {
+    pub id: String,
+    pub header: toml::Value,
+    pub content: String,
+}
+
+impl RemoteEntry {
+
+    pub fn from_entry(entry: &Entry) -> Result<RemoteEntry> {
+        Ok(RemoteEntry {
+            id: entry.get_location().clone().without_base().to_str()?,
+            header: entry.get_header().clone(),
+            content: entry.get_content().clone(),
+        })
+    }
+
+    /// Build an `Entry` from the transferred data, without a store backing it
+    pub fn into_entry(self) -> Result<Entry> {
+        let mut entry = Entry::new(StoreId::new_baseless(::std::path::PathBuf::from(self.id))?);
+        *entry.get_header_mut()  = self.header;
+        *entry.get_content_mut() = self.content;
+        Ok(entry)
+    }
+
+}
+
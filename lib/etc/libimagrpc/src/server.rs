@@ -0,0 +1,508 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2018 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! The answering side of the protocol, as used by `imagd`.
+
+use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+use std::result::Result as RResult;
+
+use error_chain::ChainedError;
+use serde::de::DeserializeOwned;
+use serde_json;
+use serde_json::Value;
+use toml;
+
+use libimagerror::trace::trace_error;
+use libimagstore::store::Entry;
+use libimagstore::store::FileLockEntry;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::internal::InternalLinker;
+use libimagentrytag::tag::is_tag_str;
+use libimagentrytag::tagable::Tagable;
+
+use error::Result;
+use protocol::*;
+
+type RpcResult = RResult<Value, ErrorObject>;
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct EntryParams {
+    id: String,
+
+    #[serde(default)]
+    header: Option<toml::Value>,
+
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EntriesParams {
+    #[serde(default)]
+    collection: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LinkParams {
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct TagParams {
+    id: String,
+
+    #[serde(default)]
+    add: Vec<String>,
+
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+/// Answers requests using a `Store`
+///
+/// The cache of the store is flushed after each request, so changes other processes make to the
+/// store in the meantime are seen by the next request. The full-text index is written after each
+/// request which changes the store, so it is not lost if the server is killed.
+pub struct Server<'a> {
+    store: &'a Store,
+}
+
+impl<'a> Server<'a> {
+
+    pub fn new(store: &'a Store) -> Server<'a> {
+        Server { store: store }
+    }
+
+    /// Handle a request, returns `None` if the request was a notification
+    pub fn handle(&self, request: Request) -> Option<Response> {
+        debug!("Handling request: {:?}", request);
+        let writes = is_write(&request.method);
+        let result = if request.jsonrpc != JSONRPC_VERSION {
+            let msg = format!("Unsupported JSON-RPC version '{}'", request.jsonrpc);
+            Err(ErrorObject::new(INVALID_REQUEST, msg))
+        } else {
+            self.dispatch(&request.method, request.params)
+        };
+
+        if let Err(e) = self.store.flush_cache() {
+            trace_error(&e);
+        }
+
+        if writes {
+            if let Err(e) = self.store.flush_index() {
+                trace_error(&e);
+            }
+        }
+
+        request.id.map(|id| match result {
+            Ok(value) => Response::result(id, value),
+            Err(error) => Response::error(id, error),
+        })
+    }
+
+    /// Handle one line of the protocol
+    pub fn handle_line(&self, line: &str) -> Option<Response> {
+        match serde_json::from_str::<Value>(line) {
+            Err(e) => Some(Response::error(Value::Null, ErrorObject::new(PARSE_ERROR, e.to_string()))),
+            Ok(value) => {
+                let id = value.get("id").cloned().unwrap_or(Value::Null);
+                match serde_json::from_value::<Request>(value) {
+                    Ok(request) => self.handle(request),
+                    Err(e) => Some(Response::error(id, ErrorObject::new(INVALID_REQUEST, e.to_string()))),
+                }
+            },
+        }
+    }
+
+    /// Answer the requests read from `input` until it is closed
+    pub fn serve<R: BufRead, W: Write>(&self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(response) = self.handle_line(&line) {
+                let _ = writeln!(output, "{}", serde_json::to_string(&response)?)?;
+                let _ = output.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dispatch(&self, method: &str, params: Value) -> RpcResult {
+        match method {
+            "get"     => self.get(from_params(params)?),
+            "create"  => self.create(from_params(params)?),
+            "update"  => self.update(from_params(params)?),
+            "delete"  => self.delete(from_params(params)?),
+            "entries" => self.entries(from_params(params)?),
+            "link"    => self.link(from_params(params)?),
+            "tag"     => self.tag(from_params(params)?),
+            other     => Err(ErrorObject::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", other))),
+        }
+    }
+
+    fn get(&self, p: IdParams) -> RpcResult {
+        match self.store.get(store_id(&p.id)?).map_err(store_error)? {
+            Some(entry) => remote(&entry),
+            None        => Ok(Value::Null),
+        }
+    }
+
+    fn create(&self, p: EntryParams) -> RpcResult {
+        let id         = store_id(&p.id)?;
+        let header     = merged_header(&id, Entry::default_header(), p.header)?;
+        let mut entry  = self.store.create(id).map_err(store_error)?;
+
+        *entry.get_header_mut() = header;
+        if let Some(content) = p.content {
+            *entry.get_content_mut() = content;
+        }
+
+        let _ = self.store.update(&mut entry).map_err(store_error)?;
+        remote(&entry)
+    }
+
+    fn update(&self, p: EntryParams) -> RpcResult {
+        let mut entry = self.existing(store_id(&p.id)?)?;
+        let header    = merged_header(entry.get_location(), entry.get_header().clone(), p.header)?;
+
+        *entry.get_header_mut() = header;
+        if let Some(content) = p.content {
+            *entry.get_content_mut() = content;
+        }
+
+        let _ = self.store.update(&mut entry).map_err(store_error)?;
+        remote(&entry)
+    }
+
+    fn delete(&self, p: IdParams) -> RpcResult {
+        let _ = self.store.delete(store_id(&p.id)?).map_err(store_error)?;
+        Ok(Value::Null)
+    }
+
+    fn entries(&self, p: EntriesParams) -> RpcResult {
+        let collection = p.collection
+            .as_ref()
+            .map(|c| c.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>());
+
+        let mut ids = vec![];
+        for id in self.store.entries().map_err(store_error)? {
+            if let Some(ref c) = collection {
+                if !id.is_in_collection(c) {
+                    continue;
+                }
+            }
+
+            ids.push(Value::String(id.without_base().to_str().map_err(store_error)?));
+        }
+
+        Ok(Value::Array(ids))
+    }
+
+    fn link(&self, p: LinkParams) -> RpcResult {
+        let from = store_id(&p.from)?;
+        let to   = store_id(&p.to)?;
+
+        if from == to {
+            return Err(ErrorObject::new(INVALID_PARAMS, "Cannot link entry to itself"));
+        }
+
+        let mut from = self.existing(from)?;
+        let mut to   = self.existing(to)?;
+
+        let _ = from.add_internal_link(&mut to).map_err(store_error)?;
+        let _ = self.store.update(&mut from).map_err(store_error)?;
+        let _ = self.store.update(&mut to).map_err(store_error)?;
+        Ok(Value::Null)
+    }
+
+    fn tag(&self, p: TagParams) -> RpcResult {
+        // Check all tags before touching the entry, so a bad tag does not leave it half-modified
+        for tag in p.add.iter().chain(p.remove.iter()) {
+            if let Err(e) = is_tag_str(tag) {
+                return Err(ErrorObject::new(INVALID_PARAMS, format!("Not a tag: {}", e)));
+            }
+        }
+
+        let mut entry = self.existing(store_id(&p.id)?)?;
+        let changed   = !p.add.is_empty() || !p.remove.is_empty();
+
+        for tag in p.add {
+            let _ = entry.add_tag(tag).map_err(store_error)?;
+        }
+
+        for tag in p.remove {
+            let _ = entry.remove_tag(tag).map_err(store_error)?;
+        }
+
+        if changed {
+            let _ = self.store.update(&mut entry).map_err(store_error)?;
+        }
+
+        entry.get_tags().map(|tags| json!(tags)).map_err(store_error)
+    }
+
+    fn existing(&self, id: StoreId) -> RResult<FileLockEntry<'a>, ErrorObject> {
+        let msg = format!("Entry does not exist: {}", id);
+        self.store
+            .get(id)
+            .map_err(store_error)?
+            .ok_or_else(|| ErrorObject::new(ENTRY_NOT_FOUND, msg))
+    }
+
+}
+
+/// Whether the method changes the store
+fn is_write(method: &str) -> bool {
+    match method {
+        "create" | "update" | "delete" | "link" | "tag" => true,
+        _                                              => false,
+    }
+}
+
+fn from_params<T: DeserializeOwned>(params: Value) -> RResult<T, ErrorObject> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| ErrorObject::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn store_id(id: &str) -> RResult<StoreId, ErrorObject> {
+    StoreId::new_baseless(PathBuf::from(id))
+        .map_err(|e| ErrorObject::new(INVALID_PARAMS, chain_message(&e)))
+}
+
+/// Merge the sections of `header` into `base` and verify the result
+fn merged_header(id: &StoreId, mut base: toml::Value, header: Option<toml::Value>)
+    -> RResult<toml::Value, ErrorObject>
+{
+    let sections = match header {
+        None                            => return Ok(base),
+        Some(toml::Value::Table(table)) => table,
+        Some(_)                         => {
+            return Err(ErrorObject::new(INVALID_PARAMS, "Header must be a table"));
+        },
+    };
+
+    {
+        let table = base
+            .as_table_mut()
+            .ok_or_else(|| ErrorObject::new(INTERNAL_ERROR, "Header of entry is not a table"))?;
+
+        for (section, value) in sections {
+            let _ = table.insert(section, value);
+        }
+    }
+
+    let mut probe = Entry::new(id.clone());
+    *probe.get_header_mut() = base.clone();
+    probe.verify()
+        .map(|_| base)
+        .map_err(|e| ErrorObject::new(INVALID_PARAMS, chain_message(&e)))
+}
+
+fn remote(entry: &Entry) -> RpcResult {
+    let entry = RemoteEntry::from_entry(entry).map_err(store_error)?;
+    serde_json::to_value(entry).map_err(|e| ErrorObject::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn store_error<E: ChainedError>(e: E) -> ErrorObject {
+    ErrorObject::new(STORE_ERROR, chain_message(&e))
+}
+
+fn chain_message<E: ChainedError>(e: &E) -> String {
+    e.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use serde_json::Value;
+
+    use libimagstore::store::Store;
+    use libimagstore::file_abstraction::InMemoryFileAbstraction;
+
+    use protocol::*;
+    use super::Server;
+
+    fn get_store() -> Store {
+        let backend = Box::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &None, backend).unwrap()
+    }
+
+    fn call(server: &Server, line: &str) -> Response {
+        server.handle_line(line).unwrap()
+    }
+
+    fn result(server: &Server, line: &str) -> Value {
+        let response = call(server, line);
+        assert!(response.error.is_none(), "Unexpected error: {:?}", response.error);
+        response.result.unwrap()
+    }
+
+    fn error_code(server: &Server, line: &str) -> i64 {
+        call(server, line).error.unwrap().code
+    }
+
+    #[test]
+    fn test_create_get_update_delete() {
+        let store  = get_store();
+        let server = Server::new(&store);
+
+        let created = result(&server, r#"{"jsonrpc":"2.0","id":1,"method":"create",
+            "params":{"id":"test/a","header":{"foo":{"bar":1}},"content":"hello"}}"#.replace('\n', "").as_str());
+        assert_eq!(created["id"], "test/a");
+        assert_eq!(created["header"]["foo"]["bar"], 1);
+        assert_eq!(created["content"], "hello");
+
+        let got = result(&server, r#"{"jsonrpc":"2.0","id":2,"method":"get","params":{"id":"test/a"}}"#);
+        assert_eq!(got, created);
+
+        let updated = result(&server, r#"{"jsonrpc":"2.0","id":3,"method":"update","params":{"id":"test/a","content":"world"}}"#);
+        assert_eq!(updated["header"]["foo"]["bar"], 1);
+        assert_eq!(updated["content"], "world");
+
+        let deleted = result(&server, r#"{"jsonrpc":"2.0","id":4,"method":"delete","params":{"id":"test/a"}}"#);
+        assert_eq!(deleted, Value::Null);
+
+        let got = result(&server, r#"{"jsonrpc":"2.0","id":5,"method":"get","params":{"id":"test/a"}}"#);
+        assert_eq!(got, Value::Null);
+    }
+
+    #[test]
+    fn test_invalid_header_is_rejected() {
+        let store  = get_store();
+        let server = Server::new(&store);
+
+        let code = error_code(&server, r#"{"jsonrpc":"2.0","id":1,"method":"create","params":{"id":"a","header":{"imag":1}}}"#);
+        assert_eq!(code, INVALID_PARAMS);
+        assert!(store.get(PathBuf::from("a")).unwrap().is_none());
+
+        let code = error_code(&server, r#"{"jsonrpc":"2.0","id":2,"method":"update","params":{"id":"a","content":"x"}}"#);
+        assert_eq!(code, ENTRY_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_entries_link_tag() {
+        let store  = get_store();
+        let server = Server::new(&store);
+
+        for id in &["coll/a", "coll/b", "other/c"] {
+            let _ = result(&server, &format!(r#"{{"jsonrpc":"2.0","id":1,"method":"create","params":{{"id":"{}"}}}}"#, id));
+        }
+
+        let mut all = result(&server, r#"{"jsonrpc":"2.0","id":2,"method":"entries"}"#)
+            .as_array()
+            .unwrap()
+            .clone();
+        all.sort_by_key(|v| v.as_str().unwrap().to_owned());
+        assert_eq!(all, vec![json!("coll/a"), json!("coll/b"), json!("other/c")]);
+
+        let coll = result(&server, r#"{"jsonrpc":"2.0","id":3,"method":"entries","params":{"collection":"other"}}"#);
+        assert_eq!(coll, json!(["other/c"]));
+
+        let _ = result(&server, r#"{"jsonrpc":"2.0","id":4,"method":"link","params":{"from":"coll/a","to":"coll/b"}}"#);
+        let a = result(&server, r#"{"jsonrpc":"2.0","id":5,"method":"get","params":{"id":"coll/a"}}"#);
+        assert_eq!(a["header"]["links"]["internal"], json!(["coll/b"]));
+
+        let tags = result(&server, r#"{"jsonrpc":"2.0","id":6,"method":"tag","params":{"id":"coll/a","add":["foo","bar"]}}"#);
+        assert_eq!(tags, json!(["foo", "bar"]));
+
+        let tags = result(&server, r#"{"jsonrpc":"2.0","id":7,"method":"tag","params":{"id":"coll/a","remove":["foo"]}}"#);
+        assert_eq!(tags, json!(["bar"]));
+
+        let code = error_code(&server, r#"{"jsonrpc":"2.0","id":8,"method":"tag","params":{"id":"coll/a","add":["Not a tag"]}}"#);
+        assert_eq!(code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let store  = get_store();
+        let server = Server::new(&store);
+
+        assert_eq!(error_code(&server, "{"), PARSE_ERROR);
+        assert_eq!(error_code(&server, r#"{"id":1}"#), INVALID_REQUEST);
+        assert_eq!(error_code(&server, r#"{"jsonrpc":"1.0","id":1,"method":"get"}"#), INVALID_REQUEST);
+        assert_eq!(error_code(&server, r#"{"jsonrpc":"2.0","id":1,"method":"frobnicate"}"#), METHOD_NOT_FOUND);
+        assert_eq!(error_code(&server, r#"{"jsonrpc":"2.0","id":1,"method":"get","params":{}}"#), INVALID_PARAMS);
+
+        // Notifications are executed, but not answered
+        assert!(server.handle_line(r#"{"jsonrpc":"2.0","method":"create","params":{"id":"n"}}"#).is_none());
+        assert!(store.get(PathBuf::from("n")).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_index_is_written_after_changes() {
+        use tempdir::TempDir;
+        use toml::de::from_str as toml_from_str;
+
+        let dir    = TempDir::new("imag-rpc-index").unwrap();
+        let config = Some(toml_from_str("[store]\nindex = true").unwrap());
+        let store  = Store::new(dir.path().to_path_buf(), &config).unwrap();
+        assert!(store.rebuild_index().is_ok());
+        let server = Server::new(&store);
+
+        let _ = result(&server, r#"{"jsonrpc":"2.0","id":1,"method":"create","params":{"id":"a","content":"Hello"}}"#);
+
+        // Another process sees the change in the index while the server is still running
+        let other = Store::new(dir.path().to_path_buf(), &config).unwrap();
+        let found = other
+            .search_index(&["hello"])
+            .unwrap()
+            .unwrap()
+            .without_store()
+            .map(|id| id.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["a"]);
+    }
+
+    #[test]
+    fn test_serve() {
+        let store  = get_store();
+        let server = Server::new(&store);
+
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"create","params":{"id":"a"}}"#, "\n",
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"delete","params":{"id":"a"}}"#, "\n",
+            r#"{"jsonrpc":"2.0","id":"x","method":"entries"}"#, "\n",
+        );
+        let mut output = vec![];
+        server.serve(input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines  = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1], r#"{"jsonrpc":"2.0","id":"x","result":[]}"#);
+    }
+
+}
+